@@ -0,0 +1,27 @@
+//! Times exporting a 4 hour, two controller SNES movie to r16m. Run with `cargo bench --bench replay`.
+
+use std::hint::black_box;
+use std::time::Instant;
+use tasd::export::replay::ReplayOptions;
+use tasd::spec::packets::{ConsoleType, InputChunk, PortController};
+use tasd::spec::TasdFile;
+
+const FRAMES: usize = 60 * 60 * 60 * 4;
+const RUNS: u32 = 10;
+
+fn main() {
+    let mut file = TasdFile::default();
+    file.packets.push(ConsoleType { kind: 0x02, custom: None }.into());
+    for port in 1..=2 {
+        file.packets.push(PortController { port, kind: 0x0201 }.into());
+        let inputs = (0..FRAMES).flat_map(|frame| [!(frame as u8), !((frame >> 8) as u8) | 0x0F]).collect();
+        file.packets.push(InputChunk { port, inputs }.into());
+    }
+    
+    let options = ReplayOptions::default();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(file.export_r16m(black_box(&options)));
+    }
+    println!("export_r16m: {:?} per {FRAMES} frames", start.elapsed() / RUNS);
+}
\ No newline at end of file
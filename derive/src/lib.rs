@@ -0,0 +1,176 @@
+//! Derive macro for the packet types of the [tasd](https://crates.io/crates/tasd) crate.
+//!
+//! This crate is re-exported by `tasd`, and shouldn't need to be depended on directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span};
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Field, Fields, Type};
+
+/// Wire layout of a single field.
+enum Layout {
+    /// Fixed size big-endian integer, or a bool stored as one byte.
+    Fixed { name: &'static str, size: usize },
+    /// String prefixed with a u8 length.
+    LenPrefixedStr,
+    /// String made of all remaining bytes of the payload.
+    RemainingStr,
+    /// Bytes making up the rest of the payload.
+    RemainingBytes,
+}
+
+const FIXED: [(&str, usize); 9] = [
+    ("u8", 1), ("u16", 2), ("u32", 4), ("u64", 8),
+    ("i8", 1), ("i16", 2), ("i32", 4), ("i64", 8),
+    ("bool", 1),
+];
+
+/// Implements `Decode` and `EncodePayload` (including `payload_len`) for a packet struct, based on the wire layout of its
+/// fields.
+/// 
+/// Fields are read and written in the order they're declared. Integer and `bool` fields are inferred from their type
+/// (or can be spelled out, e.g. `#[tasd(u16)]`), while `String` and `Vec<u8>` fields need one of:
+/// 
+/// - `#[tasd(len_prefixed_str)]`: a string prefixed with a u8 length
+/// - `#[tasd(remaining)]`: the rest of the payload (must be the last field)
+/// 
+/// Decoding fails with `PacketError::InvalidPayload` if the payload is too short for the fixed size fields, or if
+/// there are bytes left over and no field takes the remaining payload.
+/// 
+/// The key and kind of the packet come from its entry in the packet list of `tasd::spec::packets`.
+#[proc_macro_derive(TasdPacket, attributes(tasd))]
+pub fn derive_tasd_packet(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    
+    expand(input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unit => vec![],
+            Fields::Unnamed(_) => return Err(Error::new_spanned(&input, "TasdPacket requires named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input, "TasdPacket can only be derived for structs")),
+    };
+    
+    let mut reads = vec![];
+    let mut writes = vec![];
+    let mut lens = vec![];
+    let mut idents = vec![];
+    let mut takes_remaining = false;
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        if takes_remaining {
+            return Err(Error::new_spanned(field, "only the last field may use #[tasd(remaining)]"));
+        }
+        
+        let invalid = quote! {
+            return Err(::tasd::spec::packets::PacketError::InvalidPayload { key: key.to_vec(), payload: payload.to_vec() })
+        };
+        match layout(field)? {
+            Layout::Fixed { name, size } => {
+                let read = Ident::new(&format!("read_{name}"), Span::call_site());
+                let write = Ident::new(&format!("write_{name}"), Span::call_site());
+                reads.push(quote! {
+                    if payload.remaining() < #size {
+                        #invalid;
+                    }
+                    let #ident = payload.#read();
+                });
+                writes.push(quote! { w.#write(self.#ident); });
+                lens.push(quote! { #size });
+            },
+            Layout::LenPrefixedStr => {
+                reads.push(quote! {
+                    if payload.remaining() < 1 {
+                        #invalid;
+                    }
+                    let len = payload.read_u8() as usize;
+                    if payload.remaining() < len {
+                        #invalid;
+                    }
+                    let #ident = payload.read_string(len);
+                });
+                writes.push(quote! { w.write_u8_str(&self.#ident); });
+                lens.push(quote! { 1 + ::tasd::util::truncate_str(&self.#ident, u8::MAX as usize).len() });
+            },
+            Layout::RemainingStr => {
+                takes_remaining = true;
+                reads.push(quote! { let #ident = payload.read_string(payload.remaining()); });
+                writes.push(quote! { w.write_str(&self.#ident); });
+                lens.push(quote! { self.#ident.len() });
+            },
+            Layout::RemainingBytes => {
+                takes_remaining = true;
+                reads.push(quote! { let #ident = payload.read_remaining().to_vec(); });
+                writes.push(quote! { w.write_slice(&self.#ident); });
+                lens.push(quote! { self.#ident.len() });
+            },
+        }
+        idents.push(ident);
+    }
+    if !takes_remaining {
+        reads.push(quote! {
+            if payload.remaining() != 0 {
+                return Err(::tasd::spec::packets::PacketError::InvalidPayload { key: key.to_vec(), payload: payload.to_vec() });
+            }
+        });
+    }
+    
+    Ok(quote! {
+        impl ::tasd::spec::packets::Decode for #name {
+            #[allow(unused_mut, unused_variables)]
+            fn decode(key: &[u8], mut payload: ::tasd::spec::reader::Reader) -> Result<Self, ::tasd::spec::packets::PacketError> {
+                #(#reads)*
+                
+                Ok(Self {
+                    #(#idents,)*
+                })
+            }
+        }
+        impl ::tasd::spec::packets::EncodePayload for #name {
+            #[allow(unused_variables)]
+            fn encode_payload(&self, w: &mut ::tasd::spec::writer::Writer, _keylen: u8) {
+                #(#writes)*
+            }
+            
+            fn payload_len(&self, _keylen: u8) -> usize {
+                0 #(+ #lens)*
+            }
+        }
+    })
+}
+
+fn layout(field: &Field) -> Result<Layout, Error> {
+    let mut explicit: Option<Ident> = None;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("tasd")) {
+        attr.parse_nested_meta(|meta| {
+            match meta.path.get_ident() {
+                Some(ident) if explicit.is_none() => {
+                    explicit = Some(ident.clone());
+                    Ok(())
+                },
+                Some(_) => Err(meta.error("a field can only have one layout")),
+                None => Err(meta.error("expected a field layout")),
+            }
+        })?;
+    }
+    
+    let ty = type_name(&field.ty);
+    let fixed = |name: &str| FIXED.iter().find(|(fixed, _)| *fixed == name).map(|(name, size)| Layout::Fixed { name, size: *size });
+    match explicit {
+        Some(ident) if ident == "len_prefixed_str" && ty == "String" => Ok(Layout::LenPrefixedStr),
+        Some(ident) if ident == "remaining" && ty == "String" => Ok(Layout::RemainingStr),
+        Some(ident) if ident == "remaining" && ty == "Vec<u8>" => Ok(Layout::RemainingBytes),
+        Some(ident) if ident == ty.as_str() => fixed(&ty).ok_or_else(|| Error::new_spanned(&ident, "unknown field layout")),
+        Some(ident) => Err(Error::new_spanned(&ident, format!("layout `{ident}` can't be used for a field of type `{ty}`"))),
+        None => fixed(&ty).ok_or_else(|| Error::new_spanned(&field.ty, "this type needs a #[tasd(len_prefixed_str)] or #[tasd(remaining)] layout")),
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    quote!(#ty).to_string().replace(' ', "")
+}
\ No newline at end of file
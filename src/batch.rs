@@ -0,0 +1,242 @@
+//! Running the same operation over many files at once, e.g. validating or normalizing a whole archive.
+//!
+//! Files are parsed and processed on a pool of threads. A file which fails to parse, or which the operation fails on,
+//! doesn't stop the rest; every outcome is collected into a [BatchReport].
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::spec::{TasdError, TasdFile};
+
+/// Why a file of a batch failed.
+#[derive(Debug)]
+pub enum BatchError<E> {
+    /// The file couldn't be read or parsed.
+    Parse(TasdError),
+    /// The operation returned an error.
+    Process(E),
+}
+
+/// Outcome of processing one file of a batch.
+#[derive(Debug)]
+pub struct FileResult<T, E> {
+    pub path: PathBuf,
+    pub result: Result<T, BatchError<E>>,
+}
+
+/// Outcome of every file of a batch, in the order the files were matched.
+#[derive(Debug)]
+pub struct BatchReport<T, E> {
+    pub files: Vec<FileResult<T, E>>,
+}
+impl<T, E> BatchReport<T, E> {
+    /// Files which were processed successfully, with their results.
+    pub fn succeeded(&self) -> impl Iterator<Item = (&Path, &T)> {
+        self.files.iter().filter_map(|file| Some((file.path.as_path(), file.result.as_ref().ok()?)))
+    }
+    
+    /// Files which failed to parse or process, with their errors.
+    pub fn failed(&self) -> impl Iterator<Item = (&Path, &BatchError<E>)> {
+        self.files.iter().filter_map(|file| Some((file.path.as_path(), file.result.as_ref().err()?)))
+    }
+    
+    pub fn summary(&self) -> BatchSummary {
+        let parse_errors = self.failed().filter(|(_, err)| matches!(err, BatchError::Parse(_))).count();
+        let failed = self.failed().count();
+        
+        BatchSummary {
+            files: self.files.len(),
+            succeeded: self.files.len() - failed,
+            parse_errors,
+            process_errors: failed - parse_errors,
+        }
+    }
+}
+
+/// Counts of how a batch went, from [`BatchReport::summary`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub files: usize,
+    pub succeeded: usize,
+    pub parse_errors: usize,
+    pub process_errors: usize,
+}
+impl Display for BatchSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files: {} succeeded, {} failed to parse, {} failed", self.files, self.succeeded, self.parse_errors, self.process_errors)
+    }
+}
+
+/// Parses every file matching `pattern` (see [glob]) and runs `f` on it, using one thread per available CPU.
+/// 
+/// ```no_run
+/// let report = tasd::batch::process("archive/**/*.tasd", |_path, file| {
+///     Ok::<_, ()>(file.validate().len())
+/// });
+/// println!("{}", report.summary());
+/// ```
+pub fn process<T, E, F>(pattern: &str, f: F) -> BatchReport<T, E>
+where
+    T: Send,
+    E: Send,
+    F: Fn(&Path, TasdFile) -> Result<T, E> + Sync,
+{
+    process_paths(glob(pattern), f)
+}
+
+/// Parses each of `paths` and runs `f` on it, using one thread per available CPU.
+pub fn process_paths<T, E, F>(paths: Vec<PathBuf>, f: F) -> BatchReport<T, E>
+where
+    T: Send,
+    E: Send,
+    F: Fn(&Path, TasdFile) -> Result<T, E> + Sync,
+{
+    let results = map_paths(&paths, |path| match TasdFile::parse_file(path) {
+        Ok(file) => f(path, file).map_err(BatchError::Process),
+        Err(err) => Err(BatchError::Parse(err)),
+    });
+    
+    BatchReport {
+        files: paths.into_iter().zip(results).map(|(path, result)| FileResult { path, result }).collect(),
+    }
+}
+
+/// Runs `f` on each of `paths`, using one thread per available CPU. Results are in the same order as `paths`.
+pub(crate) fn map_paths<T, F>(paths: &[PathBuf], f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1).min(paths.len());
+    let next = AtomicUsize::new(0);
+    
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+            let mut results = vec![];
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else { break };
+                results.push((i, f(path)));
+            }
+            
+            results
+        })).collect();
+        
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Paths of the files matching a glob pattern, sorted.
+/// 
+/// `*` matches any part of a file or directory name, `?` matches any single character, and a `**` component matches
+/// any number of directories. A pattern without wildcards matches the file it names, if it exists.
+pub fn glob(pattern: &str) -> Vec<PathBuf> {
+    let pattern = Path::new(pattern);
+    let mut base = PathBuf::new();
+    let mut components = vec![];
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if components.is_empty() && !text.contains(['*', '?']) {
+            base.push(component);
+        } else {
+            components.push(text.into_owned());
+        }
+    }
+    
+    let mut matches = vec![];
+    if components.is_empty() {
+        if base.is_file() {
+            matches.push(base);
+        }
+    } else {
+        let root = if base.as_os_str().is_empty() { PathBuf::from(".") } else { base.clone() };
+        glob_dir(&root, &base, &components, &mut matches);
+    }
+    matches.sort();
+    matches.dedup();
+    
+    matches
+}
+
+fn glob_dir(dir: &Path, prefix: &Path, components: &[String], matches: &mut Vec<PathBuf>) {
+    let Some((component, rest)) = components.split_first() else { return };
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    
+    if component == "**" {
+        // matching zero directories
+        glob_dir(dir, prefix, rest, matches);
+    }
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let prefixed = prefix.join(&name);
+        let is_dir = path.is_dir();
+        
+        if component == "**" {
+            if is_dir {
+                glob_dir(&path, &prefixed, components, matches);
+            }
+        } else if matches_name(&component.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>()) {
+            if rest.is_empty() {
+                if !is_dir {
+                    matches.push(prefixed);
+                }
+            } else if is_dir {
+                glob_dir(&path, &prefixed, rest, matches);
+            }
+        }
+    }
+}
+
+/// Whether a file name matches a pattern of literal characters, `*`, and `?`.
+fn matches_name(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches_name(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_name(rest, &name[1..]),
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::batch::{matches_name, process};
+    use crate::spec::packets::GameTitle;
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn batch() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert!(matches_name(&chars("*.tasd"), &chars("run.tasd")));
+        assert!(matches_name(&chars("run-??.tasd"), &chars("run-é1.tasd")));
+        assert!(!matches_name(&chars("*.tasd"), &chars("run.tasd.bak")));
+        
+        let dir = std::env::temp_dir().join(format!("tasd-batch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        std::fs::write(dir.join("one.tasd"), file.encode()).unwrap();
+        std::fs::write(dir.join("a/b/two.tasd"), TasdFile::default().encode()).unwrap();
+        std::fs::write(dir.join("a/broken.tasd"), b"not a tasd file").unwrap();
+        std::fs::write(dir.join("a/other.txt"), b"").unwrap();
+        
+        let report = process(&format!("{}/**/*.tasd", dir.display()), |_, file| {
+            file.first::<GameTitle>().map(|title| title.title.clone()).ok_or("no title")
+        });
+        let paths: Vec<_> = report.files.iter().map(|file| file.path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+        assert_eq!(paths, ["a/b/two.tasd", "a/broken.tasd", "one.tasd"].map(PathBuf::from));
+        assert_eq!(report.succeeded().map(|(_, title)| title.as_str()).collect::<Vec<_>>(), ["title"]);
+        assert_eq!(report.summary().to_string(), "3 files: 1 succeeded, 1 failed to parse, 1 failed");
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
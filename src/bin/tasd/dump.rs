@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use tasd::libretro::{dump, LagHeuristic, LibretroOptions, MovieInputs};
+use tasd::spec::packets::{ConsoleType, PortController};
+use crate::{load, save};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Movie whose inputs are played back. Its CONSOLE_TYPE and PORT_CONTROLLER packets describe the console and
+    /// controllers.
+    movie: PathBuf,
+    
+    /// Libretro core to run (e.g. `mesen_libretro.so`).
+    #[arg(long)]
+    core: PathBuf,
+    
+    /// ROM to load into the core.
+    #[arg(long)]
+    rom: PathBuf,
+    
+    /// Where to write the dump.
+    #[arg(short, long)]
+    output: PathBuf,
+    
+    /// Also count frames where the core repeated the previous video frame as lag, for cores which poll input every
+    /// frame.
+    #[arg(long)]
+    duped_video_lag: bool,
+    
+    /// Stop after this many frames.
+    #[arg(long)]
+    max_frames: Option<u64>,
+}
+
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let movie = load(&args.movie)?;
+    let options = LibretroOptions {
+        console: movie.first::<ConsoleType>().ok_or("movie has no CONSOLE_TYPE")?.kind,
+        controllers: movie.all::<PortController>().map(|controller| (controller.port, controller.kind)).collect(),
+        lag: if args.duped_video_lag { LagHeuristic::NoInputReadOrDupedVideo } else { LagHeuristic::NoInputRead },
+        max_frames: args.max_frames,
+    };
+    
+    let file = dump(&args.core, &args.rom, &options, &mut MovieInputs::new(&movie))
+        .map_err(|err| format!("failed to run {}: {err:?}", args.core.display()))?;
+    
+    save(file, args.output, json)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use tasd::spec::TasdFile;
+    use crate::dump::run;
+    use crate::tests::{movie, parse};
+    use crate::Command;
+    
+    #[test]
+    fn dump() {
+        let path = movie("dump", vec![0; 10]);
+        let core = path.with_extension("so");
+        let args = || {
+            let Command::Dump(args) = parse(&["dump", path.to_str().unwrap(), "--core", core.to_str().unwrap(), "--rom", "rom.nes", "-o", "out.tasd", "--max-frames", "5"]) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        assert!(run(args(), false).unwrap_err().starts_with("failed to run"));
+        
+        std::fs::write(&path, TasdFile::default().encode()).unwrap();
+        assert_eq!(run(args(), false), Err("movie has no CONSOLE_TYPE".into()));
+        
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+use serde_json::json;
+use crate::{load, save};
+
+#[derive(Debug, clap::Args)]
+pub struct TrimArgs {
+    file: PathBuf,
+    
+    /// First frame to keep.
+    #[arg(long, default_value_t = 0)]
+    start: u64,
+    
+    /// Frame after the last frame to keep. Defaults to the end of the movie.
+    #[arg(long)]
+    end: Option<u64>,
+    
+    /// Where to write the trimmed file.
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SpliceArgs {
+    file: PathBuf,
+    
+    /// File whose inputs are spliced in.
+    #[arg(long)]
+    from: PathBuf,
+    
+    /// Frame at which the inputs are spliced in.
+    #[arg(long)]
+    at: u64,
+    
+    /// Where to write the spliced file.
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+pub fn trim(args: TrimArgs, json: bool) -> Result<(), String> {
+    let mut file = load(&args.file)?;
+    file.trim(args.start..args.end.unwrap_or(u64::MAX)).map_err(|err| format!("failed to trim: {err:?}"))?;
+    
+    save(file, args.output, json)
+}
+
+pub fn splice(args: SpliceArgs, json: bool) -> Result<(), String> {
+    let mut file = load(&args.file)?;
+    let other = load(&args.from)?;
+    for warning in file.merge_warnings(&other) {
+        match json {
+            true => eprintln!("{}", json!({ "warning": warning.to_string() })),
+            false => eprintln!("warning: {warning}"),
+        }
+    }
+    file.splice(&other, args.at).map_err(|err| format!("failed to splice: {err:?}"))?;
+    
+    save(file, args.output, json)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::edit::{splice, trim};
+    use crate::tests::{movie, parse};
+    use crate::{load, Command};
+    
+    #[test]
+    fn trim_movie() {
+        let path = movie("trim", (0..10).collect());
+        let output = path.with_extension("trimmed");
+        let args = |start: &str, end: &str| {
+            let Command::Trim(args) = parse(&["trim", path.to_str().unwrap(), "--start", start, "--end", end, "-o", output.to_str().unwrap()]) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        trim(args("2", "5"), false).unwrap();
+        assert_eq!(load(&output).unwrap().port_inputs(1), [2, 3, 4]);
+        assert!(trim(args("5", "2"), true).unwrap_err().starts_with("failed to trim"));
+        
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert!(trim(args("0", "1"), false).unwrap_err().starts_with("failed to parse"));
+    }
+    
+    #[test]
+    fn splice_movie() {
+        let path = movie("splice", vec![0; 6]);
+        let from = movie("splice-from", vec![0xAA; 3]);
+        let output = path.with_extension("spliced");
+        let Command::Splice(args) = parse(&["splice", path.to_str().unwrap(), "--from", from.to_str().unwrap(), "--at", "4", "-o", output.to_str().unwrap()]) else {
+            unreachable!("parsed as another command")
+        };
+        
+        splice(args, false).unwrap();
+        assert_eq!(load(&output).unwrap().port_inputs(1), [0, 0, 0, 0, 0xAA, 0xAA, 0xAA]);
+        
+        for path in [path, from, output] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
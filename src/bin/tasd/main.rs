@@ -0,0 +1,201 @@
+#[cfg(feature = "libretro")]
+mod dump;
+mod edit;
+mod normalize;
+mod play;
+mod stats;
+mod validate;
+mod watch;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use serde_json::json;
+use tasd::spec::packets::PacketChecksum;
+use tasd::spec::TasdFile;
+
+/// Inspect and edit TASD files.
+#[derive(Debug, Parser)]
+#[command(name = "tasd", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    
+    /// Print results (and errors) as JSON, for use in scripts.
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print input statistics, lag, duration, and size information.
+    Stats(stats::Args),
+    /// Cut a movie down to a range of frames.
+    Trim(edit::TrimArgs),
+    /// Overwrite a movie's inputs with those of another movie, starting at a given frame.
+    Splice(edit::SpliceArgs),
+    /// Canonicalize a movie, consolidate its input chunks, and optionally change its keylen.
+    Normalize(normalize::Args),
+    /// Play a movie's inputs back on a replay device, or print them with `--dry-run`.
+    Play(play::Args),
+    /// Validate any number of files, in parallel, and summarize the results.
+    Validate(validate::Args),
+    /// Follow a dump as it is being written, printing and validating new packets as they arrive.
+    Watch(watch::Args),
+    /// Dump a movie by playing it back on a libretro core.
+    #[cfg(feature = "libretro")]
+    Dump(dump::Args),
+    /// Print a shell completion script.
+    Completions {
+        shell: Shell,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+    let result = match cli.command {
+        Command::Stats(args) => stats::run(args, json),
+        Command::Trim(args) => edit::trim(args, json),
+        Command::Splice(args) => edit::splice(args, json),
+        Command::Normalize(args) => normalize::run(args, json),
+        Command::Play(args) => play::run(args, json),
+        Command::Validate(args) => validate::run(args, json),
+        Command::Watch(args) => watch::run(args, json),
+        #[cfg(feature = "libretro")]
+        Command::Dump(args) => dump::run(args, json),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "tasd", &mut std::io::stdout());
+            Ok(())
+        },
+    };
+    
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if json => {
+            eprintln!("{}", json!({ "error": err }));
+            ExitCode::FAILURE
+        },
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// Parses a file, converting any error into a message for the user.
+fn load(path: &Path) -> Result<TasdFile, String> {
+    TasdFile::parse_file(path).map_err(|err| format!("failed to parse {}: {err:?}", path.display()))
+}
+
+/// Marks the file as modified, saves it to `path`, and prints a summary of what was written.
+/// 
+/// If the file had packet checksums, they are regenerated to match its final contents.
+fn save(mut file: TasdFile, path: PathBuf, json: bool) -> Result<(), String> {
+    let checksummed = file.first::<PacketChecksum>().is_some();
+    file.remove_checksums();
+    
+    file.set_dump_last_modified_now();
+    if checksummed {
+        file.add_checksums();
+    }
+    
+    file.path = Some(path.clone());
+    file.save().map_err(|err| format!("failed to write {}: {err:?}", path.display()))?;
+    
+    let bytes = file.estimated_encoded_size();
+    let frames = file.computed_total_frames();
+    if json {
+        println!("{:#}", json!({
+            "output": path,
+            "encoded_bytes": bytes,
+            "packets": file.packets.len(),
+            "frames": frames,
+        }));
+    } else {
+        println!("Wrote {} ({bytes} bytes, {} packets)", path.display(), file.packets.len());
+    }
+    
+    Ok(())
+}
+
+/// Formats a duration as `[H:]MM:SS.mmm`.
+fn format_duration(duration: std::time::Duration) -> String {
+    let millis = duration.as_millis();
+    let (hours, minutes, seconds) = (millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}.{:03}", millis % 1000)
+    } else {
+        format!("{minutes:02}:{seconds:02}.{:03}", millis % 1000)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use clap::{CommandFactory, Parser};
+    use tasd::spec::packets::{ConsoleType, InputChunk, PortController};
+    use tasd::spec::TasdFile;
+    use crate::{format_duration, Cli, Command};
+    
+    /// Parses the arguments after `tasd`, panicking if they're invalid.
+    pub fn parse(args: &[&str]) -> Command {
+        Cli::try_parse_from(["tasd"].iter().chain(args)).unwrap().command
+    }
+    
+    /// Writes an NES movie with one byte of input per frame to a temporary file.
+    pub fn movie(name: &str, inputs: Vec<u8>) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tasd-cli-{name}-{}.tasd", std::process::id()));
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs }.into());
+        std::fs::write(&path, file.encode()).unwrap();
+        
+        path
+    }
+    
+    #[test]
+    fn arguments() {
+        Cli::command().debug_assert();
+        
+        // `--json` is accepted before or after the subcommand
+        assert!(Cli::try_parse_from(["tasd", "--json", "stats", "run.tasd"]).unwrap().json);
+        assert!(Cli::try_parse_from(["tasd", "stats", "run.tasd", "--json"]).unwrap().json);
+        assert!(!Cli::try_parse_from(["tasd", "stats", "run.tasd"]).unwrap().json);
+        
+        assert!(matches!(parse(&["trim", "run.tasd", "-o", "out.tasd"]), Command::Trim(_)));
+        assert!(matches!(parse(&["splice", "run.tasd", "--from", "other.tasd", "--at", "10", "-o", "out.tasd"]), Command::Splice(_)));
+        assert!(matches!(parse(&["normalize", "run.tasd", "-o", "out.tasd"]), Command::Normalize(_)));
+        assert!(matches!(parse(&["play", "run.tasd", "--dry-run"]), Command::Play(_)));
+        assert!(matches!(parse(&["validate", "a.tasd", "b.tasd"]), Command::Validate(_)));
+        assert!(matches!(parse(&["watch", "run.tasd"]), Command::Watch(_)));
+        assert!(matches!(parse(&["completions", "bash"]), Command::Completions { .. }));
+        #[cfg(feature = "libretro")]
+        assert!(matches!(parse(&["dump", "run.tasd", "--core", "core.so", "--rom", "rom.nes", "-o", "out.tasd"]), Command::Dump(_)));
+        
+        for args in [
+            &["trim", "run.tasd"][..],
+            &["splice", "run.tasd", "--from", "other.tasd", "-o", "out.tasd"],
+            &["normalize", "run.tasd", "-o", "out.tasd", "--max-chunk", "lots"],
+            &["play", "run.tasd"],
+            &["validate"],
+            &["completions", "cmd.exe"],
+            &["unknown"],
+        ] {
+            assert!(Cli::try_parse_from(["tasd"].iter().chain(args)).is_err(), "{args:?}");
+        }
+    }
+    
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(0)), "00:00.000");
+        assert_eq!(format_duration(Duration::from_millis(61_005)), "01:01.005");
+        assert_eq!(format_duration(Duration::from_millis(3_723_456)), "1:02:03.456");
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use tasd::spec::packets::Encode;
+use crate::{load, save};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    file: PathBuf,
+    
+    /// Where to write the normalized file.
+    #[arg(short, long)]
+    output: PathBuf,
+    
+    /// Key length to encode the file with. Defaults to the file's current keylen.
+    #[arg(long)]
+    keylen: Option<u8>,
+    
+    /// Maximum encoded size of each INPUT_CHUNK packet, in bytes. Accepts `k` and `m` suffixes (e.g. `64k`).
+    #[arg(long, value_parser = parse_size)]
+    max_chunk: Option<usize>,
+}
+
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let mut file = load(&args.file)?;
+    
+    if let Some(keylen) = args.keylen {
+        let required = file.packets.iter().map(|packet| packet.key().len()).max().unwrap_or(0);
+        if (keylen as usize) < required {
+            return Err(format!("keylen {keylen} is too short for the keys in this file (need at least {required})"));
+        }
+        file.keylen = keylen;
+    }
+    
+    file.canonicalize();
+    if let Some(max) = args.max_chunk {
+        file.rechunk_inputs_for_device(max, 1).map_err(|err| format!("failed to consolidate input chunks: {err:?}"))?;
+    }
+    
+    save(file, args.output, json)
+}
+
+/// Parses a byte count with an optional `k` (KiB) or `m` (MiB) suffix.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let lower = value.to_ascii_lowercase();
+    let (digits, multiplier) = match lower.strip_suffix('k') {
+        Some(digits) => (digits, 1024),
+        None => match lower.strip_suffix('m') {
+            Some(digits) => (digits, 1024 * 1024),
+            None => (lower.as_str(), 1),
+        },
+    };
+    
+    digits.parse::<usize>().ok()
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size: {value}"))
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use tasd::spec::packets::InputChunk;
+    use crate::normalize::{parse_size, run};
+    use crate::tests::{movie, parse};
+    use crate::{load, Command};
+    
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64k"), Ok(64 * 1024));
+        assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("k").is_err());
+        assert!(parse_size("1.5k").is_err());
+        assert!(parse_size(&format!("{}m", usize::MAX)).is_err());
+    }
+    
+    #[test]
+    fn normalize() {
+        let path = movie("normalize", vec![0; 300]);
+        let output = path.with_extension("normalized");
+        let args = |extra: &[&str]| {
+            let args: Vec<_> = ["normalize", path.to_str().unwrap(), "-o", output.to_str().unwrap()].into_iter().chain(extra.iter().copied()).collect();
+            let Command::Normalize(args) = parse(&args) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        run(args(&["--keylen", "3", "--max-chunk", "64"]), false).unwrap();
+        let normalized = load(&output).unwrap();
+        assert_eq!(normalized.keylen, 3);
+        assert!(normalized.all::<InputChunk>().count() > 1);
+        assert_eq!(normalized.port_inputs(1), vec![0; 300]);
+        
+        assert!(run(args(&["--keylen", "1"]), false).unwrap_err().contains("too short"));
+        
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use serde_json::json;
+use tasd::lookup::console_type_lut;
+use tasd::playback::{Playback, PlaybackDriver};
+use tasd::spec::packets::ConsoleType;
+use crate::load;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    file: PathBuf,
+    
+    /// Replay device to send inputs to (e.g. `/dev/ttyACM0`). The device must already be configured, as no baud rate or
+    /// other line settings are applied.
+    #[arg(long, required_unless_present = "dry_run")]
+    device: Option<PathBuf>,
+    
+    /// Console the device is connected to (e.g. `nes`). Used for timing if the file has no CONSOLE_TYPE, and checked
+    /// against it otherwise.
+    #[arg(long)]
+    console: Option<String>,
+    
+    /// Print the latch stream instead of sending it to a device.
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// Send frames as fast as possible, instead of at the console's frame rate.
+    #[arg(long)]
+    unthrottled: bool,
+}
+
+/// Sends each frame's input data, for every port in ascending order, to a device or stdout.
+struct LatchDriver {
+    device: Option<Box<dyn Write>>,
+    json: bool,
+    error: Option<std::io::Error>,
+}
+impl PlaybackDriver for LatchDriver {
+    fn on_frame(&mut self, frame: u64, inputs: &[(u8, &[u8])]) {
+        match &mut self.device {
+            Some(device) => {
+                let data: Vec<u8> = inputs.iter().flat_map(|(_, data)| data.iter().copied()).collect();
+                if let Err(err) = device.write_all(&data).and_then(|_| device.flush()) {
+                    self.error.get_or_insert(err);
+                }
+            },
+            None if self.json => {
+                let ports: Vec<_> = inputs.iter().map(|(port, data)| json!({ "port": port, "data": hex(data) })).collect();
+                println!("{}", json!({ "frame": frame, "ports": ports }));
+            },
+            None => {
+                let ports: Vec<_> = inputs.iter().map(|(port, data)| format!("port {port}: {}", hex(data))).collect();
+                println!("{frame:>8}  {}", ports.join("  "));
+            },
+        }
+    }
+    
+    fn on_reset(&mut self, frame: u64, transition_type: u8) {
+        if self.device.is_none() && !self.json {
+            let kind = if transition_type == 0x01 { "soft" } else { "power" };
+            println!("{frame:>8}  {kind} reset");
+        }
+    }
+}
+
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let mut file = load(&args.file)?;
+    
+    if let Some(console) = &args.console {
+        let kind = (0..=u8::MAX)
+            .find(|kind| console_type_lut(*kind).is_some_and(|name| name.eq_ignore_ascii_case(console)))
+            .ok_or_else(|| format!("unknown console: {console}"))?;
+        match file.first::<ConsoleType>() {
+            Some(existing) if existing.kind != kind => {
+                let name = console_type_lut(existing.kind).unwrap_or_else(|| format!("{:#04X}", existing.kind));
+                return Err(format!("file is for {name}, not {console}"));
+            },
+            Some(_) => (),
+            None => file.packets.push(ConsoleType { kind, custom: None }.into()),
+        }
+    }
+    
+    let device: Option<Box<dyn Write>> = match (&args.device, args.dry_run) {
+        (Some(path), false) => Some(Box::new(OpenOptions::new().write(true).open(path)
+            .map_err(|err| format!("failed to open {}: {err}", path.display()))?)),
+        _ => None,
+    };
+    
+    let mut playback = Playback::new(&file);
+    if args.unthrottled {
+        playback = playback.unthrottled();
+    }
+    let mut driver = LatchDriver { device, json, error: None };
+    while playback.step(&mut driver) {
+        if let Some(err) = driver.error.take() {
+            return Err(format!("failed to write to device on frame {}: {err}", playback.frame() - 1));
+        }
+    }
+    
+    if driver.device.is_some() {
+        if json {
+            println!("{:#}", json!({ "frames": playback.total_frames() }));
+        } else {
+            println!("Sent {} frames", playback.total_frames());
+        }
+    }
+    
+    Ok(())
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::play::run;
+    use crate::tests::{movie, parse};
+    use crate::Command;
+    
+    #[test]
+    fn play() {
+        let path = movie("play", (0..10).collect());
+        let device = path.with_extension("device");
+        let args = |extra: &[&str]| {
+            let args: Vec<_> = ["play", path.to_str().unwrap(), "--unthrottled"].into_iter().chain(extra.iter().copied()).collect();
+            let Command::Play(args) = parse(&args) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        run(args(&["--dry-run"]), false).unwrap();
+        run(args(&["--dry-run", "--json", "--console", "nes"]), true).unwrap();
+        assert_eq!(run(args(&["--dry-run", "--console", "snes"]), false), Err("file is for NES, not snes".into()));
+        assert_eq!(run(args(&["--dry-run", "--console", "toaster"]), false), Err("unknown console: toaster".into()));
+        
+        // the device isn't created, only written to
+        assert!(run(args(&["--device", device.to_str().unwrap()]), false).unwrap_err().starts_with("failed to open"));
+        std::fs::write(&device, b"").unwrap();
+        run(args(&["--device", device.to_str().unwrap()]), false).unwrap();
+        assert_eq!(std::fs::read(&device).unwrap(), (0..10).collect::<Vec<u8>>());
+        
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&device).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use serde_json::json;
+use tasd::spec::packets::{GameTitle, LagFrameChunk};
+use tasd::util::elide;
+use crate::{format_duration, load};
+
+/// Columns the game title is elided to, so it fits on one line alongside the label.
+const TITLE_WIDTH: usize = 68;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    file: PathBuf,
+}
+
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let file = load(&args.file)?;
+    let health = file.health();
+    let lag: Vec<_> = file.all::<LagFrameChunk>().collect();
+    let lag_frames: u64 = lag.iter().map(|lag| lag.count as u64).sum();
+    let frames = file.input_frames();
+    let bytes = file.input_bytes();
+    let created_by = file.provenance_counts();
+    
+    if json {
+        let value = json!({
+            "file": args.file,
+            "encoded_bytes": health.size.encoded_bytes,
+            "packets": health.size.packets,
+            "frame_rate": health.timing.frame_rate,
+            "declared_frames": health.timing.declared_frames,
+            "computed_frames": health.timing.computed_frames,
+            "duration_ms": health.timing.duration.map(|duration| duration.as_millis() as u64),
+            "lag": {
+                "chunks": lag.len(),
+                "frames": lag_frames,
+            },
+            "ports": bytes.iter().map(|(port, bytes)| json!({
+                "port": port,
+                "controller": file.controller_kind(*port),
+                "bytes": bytes,
+                "frames": frames.get(port),
+            })).collect::<Vec<_>>(),
+            "packet_kinds": health.size.stats.iter().map(|stats| json!({
+                "kind": stats.kind.to_string(),
+                "count": stats.count,
+                "payload_bytes": stats.payload_bytes,
+                "entropy": stats.entropy,
+                "duplicates": stats.duplicates,
+                "compressibility": stats.compressibility(),
+            })).collect::<Vec<_>>(),
+            "created_by": created_by.iter().map(|(provenance, packets)| json!({
+                "tool": provenance.tool,
+                "version": provenance.version,
+                "packets": packets,
+            })).collect::<Vec<_>>(),
+        });
+        println!("{value:#}");
+        return Ok(());
+    }
+    
+    println!("File:       {} ({} bytes, {} packets)", args.file.display(), health.size.encoded_bytes, health.size.packets);
+    if let Some(title) = file.first::<GameTitle>() {
+        println!("Title:      {}", elide(&title.title, TITLE_WIDTH));
+    }
+    if let Some(fps) = health.timing.frame_rate {
+        println!("Frame rate: {fps:.4} fps");
+    }
+    match (health.timing.computed_frames, health.timing.declared_frames) {
+        (Some(computed), Some(declared)) => println!("Frames:     {computed} (TOTAL_FRAMES: {declared})"),
+        (Some(frames), None) | (None, Some(frames)) => println!("Frames:     {frames}"),
+        (None, None) => (),
+    }
+    if let Some(duration) = health.timing.duration {
+        println!("Duration:   {}", format_duration(duration));
+    }
+    println!("Lag:        {lag_frames} frames in {} chunks", lag.len());
+    
+    if !bytes.is_empty() {
+        println!();
+        println!("Inputs:");
+        for (port, bytes) in &bytes {
+            match frames.get(port) {
+                Some(frames) => println!("  port {port}: {frames} frames ({bytes} bytes)"),
+                None => println!("  port {port}: {bytes} bytes (unknown controller)"),
+            }
+        }
+    }
+    
+    if !created_by.is_empty() {
+        println!();
+        println!("Created by:");
+        for (provenance, packets) in &created_by {
+            println!("  {} {}: {packets} packets", provenance.tool, provenance.version);
+        }
+    }
+    
+    println!();
+    println!("{:<24} {:>7} {:>10} {:>8} {:>6} {:>9}", "Packet", "Count", "Bytes", "Entropy", "Dups", "Savings");
+    for stats in &health.size.stats {
+        println!("{:<24} {:>7} {:>10} {:>8.3} {:>6} {:>8.1}%",
+            stats.kind.to_string(), stats.count, stats.payload_bytes, stats.entropy, stats.duplicates, stats.compressibility() * 100.0);
+    }
+    
+    Ok(())
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::run;
+    use crate::tests::{movie, parse};
+    use crate::Command;
+    
+    #[test]
+    fn stats() {
+        let path = movie("stats", vec![0; 120]);
+        let args = || {
+            let Command::Stats(args) = parse(&["stats", path.to_str().unwrap()]) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        run(args(), false).unwrap();
+        run(args(), true).unwrap();
+        
+        std::fs::remove_file(&path).unwrap();
+        assert!(run(args(), false).unwrap_err().starts_with("failed to parse"));
+    }
+}
@@ -0,0 +1,98 @@
+use serde_json::json;
+use tasd::batch::{self, BatchError};
+use tasd::validate::{Issue, Severity};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Files to validate. Glob patterns (e.g. `archive/**/*.tasd`) are expanded, for shells which don't.
+    #[arg(required = true)]
+    patterns: Vec<String>,
+}
+
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let mut paths: Vec<_> = args.patterns.iter().flat_map(|pattern| batch::glob(pattern)).collect();
+    paths.dedup();
+    if paths.is_empty() {
+        return Err("no files matched".into());
+    }
+    
+    let report = batch::process_paths(paths, |_, file| Ok::<Vec<Issue>, ()>(file.validate()));
+    let summary = report.summary();
+    let mut invalid = 0;
+    for file in &report.files {
+        let issues = match &file.result {
+            Ok(issues) => issues,
+            Err(BatchError::Parse(err)) => {
+                if json {
+                    println!("{}", json!({ "file": file.path, "error": format!("{err:?}") }));
+                } else {
+                    println!("{}: failed to parse: {err:?}", file.path.display());
+                }
+                continue;
+            },
+            Err(BatchError::Process(())) => continue,
+        };
+        if issues.iter().any(|issue| issue.severity == Severity::Error) {
+            invalid += 1;
+        }
+        
+        if json {
+            println!("{}", json!({
+                "file": file.path,
+                "issues": issues.iter().map(|issue| json!({
+                    "severity": format!("{:?}", issue.severity).to_lowercase(),
+                    "index": issue.index,
+                    "kind": issue.kind.map(|kind| kind.to_string()),
+                    "message": issue.message,
+                })).collect::<Vec<_>>(),
+            }));
+        } else {
+            for issue in issues {
+                println!("{}: {issue}", file.path.display());
+            }
+        }
+    }
+    
+    if json {
+        println!("{}", json!({ "files": summary.files, "parse_errors": summary.parse_errors, "invalid": invalid }));
+    } else {
+        println!("{summary}, {invalid} with errors");
+    }
+    
+    match summary.parse_errors + invalid {
+        0 => Ok(()),
+        failed => Err(format!("{failed} of {} files are invalid", summary.files)),
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{movie, parse};
+    use crate::validate::run;
+    use crate::Command;
+    
+    #[test]
+    fn validate() {
+        let path = movie("validate", vec![0; 10]);
+        let broken = path.with_extension("broken");
+        std::fs::write(&broken, b"not a tasd file").unwrap();
+        let args = |patterns: &[&str]| {
+            let args: Vec<_> = ["validate"].into_iter().chain(patterns.iter().copied()).collect();
+            let Command::Validate(args) = parse(&args) else {
+                unreachable!("parsed as another command")
+            };
+            args
+        };
+        
+        run(args(&[path.to_str().unwrap()]), false).unwrap();
+        run(args(&[path.to_str().unwrap()]), true).unwrap();
+        assert_eq!(run(args(&[path.to_str().unwrap(), broken.to_str().unwrap()]), false), Err("1 of 2 files are invalid".into()));
+        
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&broken).unwrap();
+        assert_eq!(run(args(&[path.to_str().unwrap()]), false), Err("no files matched".into()));
+    }
+}
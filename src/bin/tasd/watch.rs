@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+use serde_json::json;
+use tasd::spec::packets::{Encode, Packet, PacketError, Provenance};
+use tasd::spec::stream::StreamParser;
+use tasd::spec::{MAGIC_NUMBER, TasdError, TasdFile};
+use tasd::validate::{Issue, Severity};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    file: PathBuf,
+    
+    /// How often to check the file for new data, in milliseconds.
+    #[arg(long, default_value_t = 500)]
+    interval: u64,
+}
+
+/// Polls a file which is still being written, printing each packet once it has been fully written, and any new
+/// validation issues it causes. Runs until interrupted.
+pub fn run(args: Args, json: bool) -> Result<(), String> {
+    let mut file = TasdFile::default();
+    let mut reported: Vec<Issue> = vec![];
+    // position just after the last complete packet, or `None` until the header has been read
+    let mut offset: Option<u64> = None;
+    
+    loop {
+        let data = read_from(&args.file, offset.unwrap_or(0))?;
+        let start = match offset {
+            Some(_) => 0,
+            None if data.len() < 7 => {
+                sleep(Duration::from_millis(args.interval));
+                continue;
+            },
+            None => {
+                if data[..4] != MAGIC_NUMBER {
+                    return Err(format!("{} is not a TASD file", args.file.display()));
+                }
+                file.version = u16::from_be_bytes([data[4], data[5]]);
+                file.keylen = data[6];
+                7
+            },
+        };
+        
+        let first_new = file.packets.len();
+        let mut parser = StreamParser::with_header(&data[start..], file.version, file.keylen);
+        let mut consumed = start as u64;
+        loop {
+            match parser.next_packet() {
+                Ok(Some(packet)) => {
+                    print_packet(file.packets.len(), &packet, file.provenance(file.packets.len()), file.keylen, json);
+                    file.packets.push(packet);
+                },
+                Ok(None) => break,
+                Err(TasdError::Packet(err @ PacketError::InvalidPayload { .. })) => print_error(&format!("{err:?}"), json),
+                // the rest of the packet hasn't been written yet
+                Err(_) if start as u64 + parser.bytes_read() == data.len() as u64 => break,
+                Err(err) => return Err(format!("failed to read {}: {err:?}", args.file.display())),
+            }
+            consumed = start as u64 + parser.bytes_read();
+        }
+        offset = Some(offset.unwrap_or(0) + consumed);
+        
+        if file.packets.len() > first_new {
+            for issue in file.validate() {
+                if !reported.contains(&issue) {
+                    print_issue(&issue, json);
+                    reported.push(issue);
+                }
+            }
+        }
+        
+        sleep(Duration::from_millis(args.interval));
+    }
+}
+
+/// Reads everything in the file after `offset`.
+fn read_from(path: &PathBuf, offset: u64) -> Result<Vec<u8>, String> {
+    let mut data = vec![];
+    File::open(path)
+        .and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_to_end(&mut data)
+        })
+        .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+    
+    Ok(data)
+}
+
+fn print_packet(index: usize, packet: &Packet, created_by: Option<&Provenance>, keylen: u8, json: bool) {
+    let bytes = packet.encode(keylen).len();
+    let created_by = created_by.map(|provenance| format!("{} {}", provenance.tool, provenance.version));
+    if json {
+        println!("{}", json!({ "event": "packet", "index": index, "kind": packet.kind().to_string(), "encoded_bytes": bytes, "created_by": created_by }));
+    } else {
+        match created_by {
+            Some(created_by) => println!("#{index:<6} {:<24} {bytes:>8} bytes  ({created_by})", packet.kind().to_string()),
+            None => println!("#{index:<6} {:<24} {bytes:>8} bytes", packet.kind().to_string()),
+        }
+    }
+}
+
+fn print_issue(issue: &Issue, json: bool) {
+    if json {
+        let severity = match issue.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        println!("{}", json!({
+            "event": "issue",
+            "severity": severity,
+            "index": issue.index,
+            "kind": issue.kind.map(|kind| kind.to_string()),
+            "message": issue.message,
+        }));
+    } else {
+        println!("        {issue}");
+    }
+}
+
+fn print_error(message: &str, json: bool) {
+    if json {
+        println!("{}", json!({ "event": "error", "message": message }));
+    } else {
+        println!("        failed to decode packet: {message}");
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{movie, parse};
+    use crate::watch::{read_from, run};
+    use crate::Command;
+    
+    #[test]
+    fn watch() {
+        let path = movie("watch", vec![0; 10]);
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(read_from(&path, 0).unwrap(), data);
+        assert_eq!(read_from(&path, 7).unwrap(), data[7..]);
+        assert!(read_from(&path, data.len() as u64 + 1).unwrap().is_empty());
+        
+        std::fs::write(&path, b"not a tasd file").unwrap();
+        let Command::Watch(args) = parse(&["watch", path.to_str().unwrap(), "--interval", "1"]) else {
+            unreachable!("parsed as another command")
+        };
+        assert!(run(args, false).unwrap_err().ends_with("is not a TASD file"));
+        
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_from(&path, 0).unwrap_err().starts_with("failed to read"));
+    }
+}
@@ -0,0 +1,34 @@
+use crate::spec::packets::{Bookmark, Packet};
+use crate::spec::TasdFile;
+
+impl TasdFile {
+    /// All bookmarks in this file, ordered by frame.
+    pub fn bookmarks(&self) -> Vec<&Bookmark> {
+        let mut bookmarks: Vec<_> = self.all::<Bookmark>().collect();
+        bookmarks.sort_by_key(|bookmark| bookmark.frame);
+        
+        bookmarks
+    }
+    
+    /// First bookmark with the given name.
+    pub fn bookmark(&self, name: &str) -> Option<&Bookmark> {
+        self.all::<Bookmark>().find(|bookmark| bookmark.name == name)
+    }
+    
+    /// Adds a bookmark after any existing bookmarks (or at the end of the file if there are none).
+    pub fn add_bookmark<S: Into<String>>(&mut self, frame: u64, name: S) {
+        let index = self.packets.iter()
+            .rposition(|packet| matches!(packet, Packet::Bookmark(_)))
+            .map(|i| i + 1)
+            .unwrap_or(self.packets.len());
+        self.packets.insert(index, Bookmark { frame, name: name.into() }.into());
+    }
+    
+    /// Removes every bookmark with the given name. Returns the number of bookmarks removed.
+    pub fn remove_bookmark(&mut self, name: &str) -> usize {
+        let before = self.packets.len();
+        self.packets.retain(|packet| !matches!(packet, Packet::Bookmark(bookmark) if bookmark.name == name));
+        
+        before - self.packets.len()
+    }
+}
\ No newline at end of file
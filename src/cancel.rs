@@ -0,0 +1,169 @@
+//! Cooperative cancellation of long-running operations.
+//! 
+//! Interactive frontends can hand a [CancellationToken] to an operation running on a worker thread, and cancel it from
+//! the UI thread. The operation checks the token between packets (or other units of work), and stops with
+//! [`CancelError::Cancelled`] holding whatever it had done so far.
+
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::input::format::InputFormat;
+use crate::spec::packets::Encode;
+use crate::spec::stream::{ParseObserver, ParseProgress};
+use crate::spec::writer::Writer;
+use crate::spec::{LATEST_VERSION, MAGIC_NUMBER, ParseReport, TasdError, TasdFile};
+
+/// Shared flag for cancelling an operation. Clones refer to the same flag.
+/// 
+/// Also usable as a [ParseObserver], so a [StreamParser][crate::spec::stream::StreamParser] stops with
+/// [`TasdError::Cancelled`] once the token is cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+impl ParseObserver for CancellationToken {
+    fn on_packet(&mut self, _progress: &ParseProgress) -> ControlFlow<()> {
+        match self.is_cancelled() {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// Error returned by cancellable operations.
+#[derive(Debug)]
+pub enum CancelError<T> {
+    /// The token was cancelled before the operation finished. Contains the partial result.
+    Cancelled {
+        partial: T,
+    },
+    Failed(TasdError),
+}
+impl<T> From<TasdError> for CancelError<T> {
+    fn from(value: TasdError) -> Self {
+        Self::Failed(value)
+    }
+}
+impl<T> From<CancelError<T>> for TasdError {
+    fn from(value: CancelError<T>) -> Self {
+        match value {
+            CancelError::Cancelled { .. } => TasdError::Cancelled,
+            CancelError::Failed(err) => err,
+        }
+    }
+}
+
+impl TasdFile {
+    /// Same as [`Self::parse_slice_with_report`], but checks `token` before each packet.
+    /// 
+    /// If cancelled, the partial result holds the packets parsed so far.
+    pub fn parse_slice_cancellable(data: &[u8], token: &CancellationToken) -> Result<(Self, ParseReport), CancelError<(Self, ParseReport)>> {
+        Self::parse_slice_inner(data, Some(token), None)
+    }
+    
+    /// Same as [`Self::encode`], but checks `token` before each packet.
+    /// 
+    /// If cancelled, the partial result holds the bytes encoded so far.
+    pub fn encode_cancellable(&self, token: &CancellationToken) -> Result<Vec<u8>, CancelError<Vec<u8>>> {
+        let mut w = Writer::new();
+        
+        w.write_slice(&MAGIC_NUMBER);
+        w.write_slice(&LATEST_VERSION);
+        w.write_u8(self.keylen);
+        
+        for packet in &self.packets {
+            check(token, || w.to_vec())?;
+            w.write_slice(&packet.encode(self.keylen));
+        }
+        
+        Ok(w.to_vec())
+    }
+    
+    /// Same as [`Self::canonicalize`], but checks `token` before each step.
+    /// 
+    /// If cancelled, the file is left with only the steps before cancellation applied.
+    pub fn canonicalize_cancellable(&mut self, token: &CancellationToken) -> Result<(), CancelError<()>> {
+        let steps: [fn(&mut Self) -> usize; 3] = [Self::order_configuration, Self::dedup_metadata, Self::dedup_attributions];
+        for step in steps {
+            check(token, || ())?;
+            step(self);
+        }
+        
+        Ok(())
+    }
+    
+    /// [Converts][Self::convert_port_inputs] the input data of every port from the given format, checking `token` before
+    /// each port.
+    /// 
+    /// If cancelled, the partial result lists the ports which were converted.
+    pub fn convert_inputs_cancellable(&mut self, from: InputFormat, token: &CancellationToken) -> Result<(), CancelError<Vec<u8>>> {
+        let mut converted = vec![];
+        for port in self.input_bytes().into_keys() {
+            check(token, || converted.clone())?;
+            self.convert_port_inputs(port, from);
+            converted.push(port);
+        }
+        
+        Ok(())
+    }
+}
+
+/// Returns [`CancelError::Cancelled`] with the result of `partial` if the token has been cancelled.
+pub(crate) fn check<T, F: FnOnce() -> T>(token: &CancellationToken, partial: F) -> Result<(), CancelError<T>> {
+    match token.is_cancelled() {
+        true => Err(CancelError::Cancelled { partial: partial() }),
+        false => Ok(()),
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::{CancelError, CancellationToken};
+    use crate::input::format::{InputFormat, Polarity};
+    use crate::spec::packets::{Comment, InputChunk, Packet};
+    use crate::spec::stream::StreamParser;
+    use crate::spec::{TasdError, TasdFile};
+    
+    #[test]
+    fn cancellation() {
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "a".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x00] }.into());
+        let data = file.encode();
+        
+        let token = CancellationToken::new();
+        let (parsed, report) = TasdFile::parse_slice_cancellable(&data, &token).unwrap();
+        assert_eq!(parsed.packets, file.packets);
+        assert!(report.is_clean());
+        assert_eq!(file.encode_cancellable(&token).unwrap(), data);
+        
+        let mut parser = StreamParser::new(data.as_slice()).unwrap().with_observer(token.clone());
+        assert!(parser.next_packet().unwrap().is_some());
+        
+        token.cancel();
+        assert!(matches!(parser.next_packet(), Err(TasdError::Cancelled)));
+        assert!(matches!(TasdFile::parse_slice_cancellable(&data, &token), Err(CancelError::Cancelled { partial }) if partial.0.packets.is_empty()));
+        assert!(matches!(file.encode_cancellable(&token), Err(CancelError::Cancelled { partial }) if partial.len() == 7));
+        assert!(matches!(file.canonicalize_cancellable(&token), Err(CancelError::Cancelled { .. })));
+        
+        let inverted = InputFormat { polarity: Polarity::ActiveHigh, ..InputFormat::CANONICAL };
+        assert!(matches!(file.convert_inputs_cancellable(inverted, &token), Err(CancelError::Cancelled { partial }) if partial.is_empty()));
+        assert!(file.convert_inputs_cancellable(inverted, &CancellationToken::new()).is_ok());
+        assert_eq!(file.packets[1], Packet::from(InputChunk { port: 1, inputs: vec![0xFF] }));
+    }
+}
\ No newline at end of file
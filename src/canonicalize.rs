@@ -0,0 +1,186 @@
+use crate::merge::is_metadata;
+use crate::playlist::is_restart;
+use crate::spec::packets::{Packet, PacketKind};
+use crate::spec::TasdFile;
+
+/// Normalizes a name for comparison purposes, so that differently cased or composed spellings of the same name are equal.
+/// 
+/// With the `unicode` feature, the name is trimmed, converted to Unicode Normalization Form C, and case folded with the
+/// full Unicode case folding (e.g. `ß` folds to `ss`, and `ς` to `σ`), then normalized again as folding can leave
+/// decomposed characters. Without it, the name is only trimmed and lowercased, which is the same for most names, but
+/// doesn't fold characters whose lowercase form isn't their folded form. The result is only intended for comparisons,
+/// not for display.
+/// 
+/// # Example
+/// ```
+/// use tasd::canonicalize::fold_name;
+/// 
+/// assert_eq!(fold_name(" Arthur "), fold_name("ARTHUR"));
+/// # #[cfg(feature = "unicode")]
+/// assert_eq!(fold_name("Jose\u{0301}"), fold_name("JOSÉ"));
+/// # #[cfg(feature = "unicode")]
+/// assert_eq!(fold_name("Strauß"), fold_name("STRAUSS"));
+/// ```
+pub fn fold_name(name: &str) -> String {
+    #[cfg(feature = "unicode")]
+    {
+        use unicode_normalization::UnicodeNormalization;
+        let name: String = name.trim().nfc().collect();
+        caseless::default_case_fold_str(&name).nfc().collect()
+    }
+    #[cfg(not(feature = "unicode"))]
+    name.trim().to_lowercase()
+}
+
+impl TasdFile {
+    /// Applies all normalizations which don't change the meaning of the file.
+    /// 
+    /// Currently this includes:
+    /// - [`Self::order_configuration`]
+    /// - [`Self::dedup_metadata`]
+    /// - [`Self::dedup_attributions`]
+    pub fn canonicalize(&mut self) {
+        self.order_configuration();
+        self.dedup_metadata();
+        self.dedup_attributions();
+    }
+    
+    /// Moves [configuration][crate::spec::packets::PacketKind::is_configuration] packets which appear after input data to just before the
+    /// first input packet. The order of packets is otherwise unchanged. Each movie of an [archive][crate::playlist] is
+    /// ordered separately.
+    /// 
+    /// A PACKET_CHECKSUM covers the packet before it, so it's moved along with that packet.
+    /// 
+    /// Returns the number of packets moved, not counting checksums.
+    pub fn order_configuration(&mut self) -> usize {
+        let mut moved = 0;
+        for range in self.movie_ranges() {
+            let Some(first_input) = self.packets[range.clone()].iter().position(|packet| packet.kind().is_input()) else { continue };
+            
+            let start = range.start + first_input;
+            let tail: Vec<Packet> = self.packets.drain(start..range.end).collect();
+            let (mut configuration, mut rest) = (vec![], vec![]);
+            let mut is_configuration = false;
+            for packet in tail {
+                if packet.kind() != PacketKind::PacketChecksum {
+                    is_configuration = packet.kind().is_configuration();
+                    moved += is_configuration as usize;
+                }
+                
+                if is_configuration {
+                    configuration.push(packet);
+                } else {
+                    rest.push(packet);
+                }
+            }
+            self.packets.splice(start..start, configuration.into_iter().chain(rest));
+        }
+        
+        moved
+    }
+    
+    /// Removes metadata packets which are identical to an earlier packet of the same movie. Input-related packets are
+    /// never removed, as repeating them is meaningful.
+    /// 
+    /// Returns the number of packets removed.
+    pub fn dedup_metadata(&mut self) -> usize {
+        let mut seen: Vec<Packet> = vec![];
+        let before = self.packets.len();
+        self.packets.retain(|packet| {
+            if is_restart(packet) {
+                seen.clear();
+                true
+            } else if !is_metadata(packet.kind()) {
+                true
+            } else if seen.contains(packet) {
+                false
+            } else {
+                seen.push(packet.clone());
+                true
+            }
+        });
+        
+        before - self.packets.len()
+    }
+    
+    /// Removes ATTRIBUTION packets which have the same kind and [folded][fold_name] name as an earlier attribution.
+    /// 
+    /// Returns the number of packets removed.
+    pub fn dedup_attributions(&mut self) -> usize {
+        let mut seen = vec![];
+        let before = self.packets.len();
+        self.packets.retain(|packet| match packet {
+            Packet::Attribution(attribution) => {
+                let key = (attribution.kind, fold_name(&attribution.name));
+                if seen.contains(&key) {
+                    false
+                } else {
+                    seen.push(key);
+                    true
+                }
+            },
+            _ => true,
+        });
+        
+        before - self.packets.len()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Attribution, Comment, InputChunk, Packet, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn dedup_attributions() {
+        let mut file = TasdFile::default();
+        file.packets.push(Attribution { kind: 0x01, name: "Arthur".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "arthur ".into() }.into());
+        file.packets.push(Attribution { kind: 0x02, name: "Arthur".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "Zoë".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "ZOË".into() }.into());
+        #[cfg(feature = "unicode")]
+        file.packets.push(Attribution { kind: 0x01, name: "Zoe\u{0308}".into() }.into());
+        
+        let duplicates = file.packets.len() - 3;
+        assert_eq!(file.dedup_attributions(), duplicates);
+        assert_eq!(file.packets, vec![
+            Packet::from(Attribution { kind: 0x01, name: "Arthur".into() }),
+            Attribution { kind: 0x02, name: "Arthur".into() }.into(),
+            Attribution { kind: 0x01, name: "Zoë".into() }.into(),
+        ]);
+    }
+    
+    #[test]
+    fn dedup_metadata() {
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "a".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF] }.into());
+        file.packets.push(Comment { comment: "a".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF] }.into());
+        file.packets.push(Comment { comment: "b".into() }.into());
+        
+        assert_eq!(file.dedup_metadata(), 1);
+        assert_eq!(file.packets.len(), 4);
+        assert_eq!(file.packets[2], InputChunk { port: 1, inputs: vec![0xFF] }.into());
+    }
+    
+    #[test]
+    fn order_configuration_keeps_checksums() {
+        let mut file = TasdFile::default();
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF] }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x00] }.into());
+        file.add_checksums();
+        let inputs: Vec<Packet> = file.packets.iter().filter(|packet| packet.kind().is_input()).cloned().collect();
+        
+        assert_eq!(file.order_configuration(), 1);
+        assert_eq!(file.packets[0], PortController { port: 1, kind: 0x0101 }.into());
+        assert_eq!(file.packets.iter().filter(|packet| packet.kind().is_input()).cloned().collect::<Vec<_>>(), inputs);
+        assert!(file.verify_checksums().is_empty());
+    }
+}
\ No newline at end of file
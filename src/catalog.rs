@@ -0,0 +1,161 @@
+//! Catalog of the movies in a directory tree, for browsing and searching a library of files.
+//! 
+//! [`Catalog::scan`] reads the [metadata][TasdMeta] of every `.tasd` file under a directory on a pool of threads. Files
+//! are identified by their size and modification time, so [`Catalog::rescan`] only reads the files which are new or
+//! have changed since the last scan.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::batch::{glob, map_paths};
+use crate::spec::meta::TasdMeta;
+use crate::spec::TasdError;
+
+/// Metadata of one file of a [Catalog].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub path: PathBuf,
+    /// Size of the file in bytes, when it was read.
+    pub size: u64,
+    /// Modification time of the file, when it was read.
+    pub modified: SystemTime,
+    pub meta: TasdMeta,
+}
+
+/// Metadata of every `.tasd` file under a directory. See the [module docs][self].
+#[derive(Debug, Default)]
+pub struct Catalog {
+    /// Files which were read successfully, sorted by path.
+    pub entries: Vec<CatalogEntry>,
+    /// Files which couldn't be read, with their errors.
+    pub errors: Vec<(PathBuf, TasdError)>,
+}
+impl Catalog {
+    /// Reads the metadata of every `.tasd` file under `dir`, using one thread per available CPU.
+    pub fn scan<P: AsRef<Path>>(dir: P) -> Self {
+        let mut catalog = Self::default();
+        catalog.rescan(dir);
+        
+        catalog
+    }
+    
+    /// Updates the catalog to match the files under `dir`, reusing the entries of files whose size and modification
+    /// time haven't changed. Entries of files which no longer exist are removed.
+    pub fn rescan<P: AsRef<Path>>(&mut self, dir: P) {
+        let paths = glob(&dir.as_ref().join("**").join("*.tasd").to_string_lossy());
+        let entries = std::mem::take(&mut self.entries);
+        let cached: HashMap<&Path, &CatalogEntry> = entries.iter().map(|entry| (entry.path.as_path(), entry)).collect();
+        let results = map_paths(&paths, |path| read_entry(path, &cached));
+        
+        self.errors.clear();
+        for result in results {
+            match result {
+                Ok(entry) => self.entries.push(entry),
+                Err((path, err)) => self.errors.push((path, err)),
+            }
+        }
+        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        self.errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    
+    /// Entries whose CONSOLE_TYPE is `console`.
+    pub fn by_console(&self, console: u8) -> impl Iterator<Item = &CatalogEntry> {
+        self.entries.iter().filter(move |entry| entry.meta.console == Some(console))
+    }
+    
+    /// Entries with an author named `author`, ignoring case.
+    pub fn by_author<'a>(&'a self, author: &'a str) -> impl Iterator<Item = &'a CatalogEntry> {
+        self.entries.iter().filter(move |entry| entry.meta.authors.iter().any(|name| name.eq_ignore_ascii_case(author)))
+    }
+    
+    /// Entries with a GAME_TITLE containing `game`, ignoring case.
+    pub fn by_game<'a>(&'a self, game: &'a str) -> impl Iterator<Item = &'a CatalogEntry> {
+        let game = game.to_lowercase();
+        self.entries.iter().filter(move |entry| entry.meta.titles.iter().any(|title| title.to_lowercase().contains(&game)))
+    }
+    
+    /// The catalog as JSON, with one object per entry.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::json;
+        
+        json!(self.entries.iter().map(|entry| json!({
+            "path": entry.path,
+            "size": entry.size,
+            "modified": entry.modified.duration_since(SystemTime::UNIX_EPOCH).map(|since| since.as_secs()).ok(),
+            "version": entry.meta.version,
+            "console": entry.meta.console,
+            "region": entry.meta.region,
+            "titles": entry.meta.titles,
+            "authors": entry.meta.authors,
+            "category": entry.meta.category,
+            "total_frames": entry.meta.total_frames,
+            "rerecords": entry.meta.rerecords,
+            "duration": entry.meta.duration().map(|duration| duration.as_secs_f64()),
+        })).collect::<Vec<_>>())
+    }
+}
+
+/// Reads the entry of a file, or reuses its cached entry if the file hasn't changed.
+fn read_entry(path: &Path, cached: &HashMap<&Path, &CatalogEntry>) -> Result<CatalogEntry, (PathBuf, TasdError)> {
+    let fail = |err: TasdError| (path.to_path_buf(), err);
+    let metadata = std::fs::metadata(path).map_err(|err| fail(err.into()))?;
+    let (size, modified) = (metadata.len(), metadata.modified().map_err(|err| fail(err.into()))?);
+    if let Some(entry) = cached.get(path).filter(|entry| entry.size == size && entry.modified == modified) {
+        return Ok((*entry).clone());
+    }
+    
+    let file = File::open(path).map_err(|err| fail(err.into()))?;
+    let meta = TasdMeta::from_reader(file).map_err(fail)?;
+    
+    Ok(CatalogEntry { path: path.to_path_buf(), size, modified, meta })
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::catalog::Catalog;
+    use crate::spec::packets::{Attribution, ConsoleType, GameTitle};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn scan() {
+        let dir = std::env::temp_dir().join(format!("tasd-catalog-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nes")).unwrap();
+        
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "Author".into() }.into());
+        std::fs::write(dir.join("nes/smb.tasd"), file.encode()).unwrap();
+        file.packets[0] = ConsoleType { kind: 0x02, custom: None }.into();
+        file.packets[1] = GameTitle { title: "Super Mario World".into() }.into();
+        std::fs::write(dir.join("smw.tasd"), file.encode()).unwrap();
+        std::fs::write(dir.join("broken.tasd"), b"not a tasd file").unwrap();
+        
+        let mut catalog = Catalog::scan(&dir);
+        assert_eq!(catalog.entries.len(), 2);
+        assert_eq!(catalog.errors.len(), 1);
+        assert_eq!(catalog.by_console(0x01).count(), 1);
+        assert_eq!(catalog.by_author("author").count(), 2);
+        assert_eq!(catalog.by_game("mario world").next().unwrap().path, dir.join("smw.tasd"));
+        
+        // unchanged files are served from the cache, so an edited entry survives a rescan
+        catalog.entries[0].meta.titles = vec!["cached".into()];
+        file.packets[1] = GameTitle { title: "Super Mario World 2".into() }.into();
+        std::fs::write(dir.join("smw.tasd"), file.encode()).unwrap();
+        catalog.rescan(&dir);
+        assert_eq!(catalog.entries[0].meta.titles, ["cached"]);
+        assert_eq!(catalog.entries[1].meta.titles, ["Super Mario World 2"]);
+        
+        std::fs::remove_file(dir.join("smw.tasd")).unwrap();
+        catalog.rescan(&dir);
+        assert_eq!(catalog.entries.len(), 1);
+        assert_eq!(catalog.entries[0].meta.titles, ["cached"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,143 @@
+//! Structured parsing of CATEGORY strings.
+//!
+//! Categories are free text, so the same one is written many ways ("any%, warpless", "Any% (Warpless)",
+//! "ANY% - warpless"). [CategoryInfo] parses them into a completion goal and a set of branch qualifiers, and formats them
+//! back consistently, so movies can be sorted and grouped by category.
+
+use std::fmt::{Display, Formatter};
+use crate::spec::packets::Category;
+use crate::spec::TasdFile;
+
+/// Completion goal of a category, the part before any branch qualifiers.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Completion {
+    /// "any%", finishing the game as fast as possible.
+    Any,
+    /// "100%", collecting or completing everything.
+    Hundred,
+    /// "low%", finishing with as little as possible.
+    Low,
+    /// Any other percentage, e.g. "120%".
+    Percent(u16),
+    /// A goal which isn't a percentage, e.g. "game end glitch". Lowercased.
+    Named(String),
+}
+impl Display for Completion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Completion::Any => write!(f, "any%"),
+            Completion::Hundred => write!(f, "100%"),
+            Completion::Low => write!(f, "low%"),
+            Completion::Percent(percent) => write!(f, "{percent}%"),
+            Completion::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Parsed category, from [`CategoryInfo::parse`] or [`TasdFile::category_info`].
+/// 
+/// Branches are lowercased, sorted, and deduplicated, so categories which only differ in formatting compare equal. The
+/// [Display] implementation formats the category as e.g. `any% (2 players, warpless)`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CategoryInfo {
+    completion: Completion,
+    branches: Vec<String>,
+}
+impl CategoryInfo {
+    pub fn new<I: IntoIterator<Item = S>, S: AsRef<str>>(completion: Completion, branches: I) -> Self {
+        let mut branches: Vec<String> = branches.into_iter().map(|branch| normalize(&branch.as_ref().to_lowercase())).filter(|branch| !branch.is_empty()).collect();
+        branches.sort();
+        branches.dedup();
+        
+        Self {
+            completion,
+            branches,
+        }
+    }
+    
+    /// Parses a category string. Any text following the completion goal, separated by commas, slashes, dashes, or
+    /// parentheses, is treated as branch qualifiers.
+    /// 
+    /// Returns `None` if the string is empty.
+    pub fn parse(category: &str) -> Option<Self> {
+        let lower = category.to_lowercase();
+        let mut parts = lower.split(['(', ')', ',', '/']).flat_map(|part| part.split(" - ")).map(normalize).filter(|part| !part.is_empty());
+        
+        let first = parts.next()?;
+        let (goal, rest) = first.split_once(' ').unwrap_or((&first, ""));
+        let completion = match goal {
+            "any" | "any%" => Some(Completion::Any),
+            "100%" => Some(Completion::Hundred),
+            "low" | "low%" => Some(Completion::Low),
+            _ => goal.strip_suffix('%').and_then(|percent| percent.parse().ok()).map(Completion::Percent),
+        };
+        
+        Some(match completion {
+            Some(completion) => Self::new(completion, parts.chain([rest.to_string()])),
+            None => Self::new(Completion::Named(first.clone()), parts),
+        })
+    }
+    
+    pub fn completion(&self) -> &Completion {
+        &self.completion
+    }
+    
+    pub fn branches(&self) -> &[String] {
+        &self.branches
+    }
+    
+    /// Whether this category has the given branch qualifier, ignoring case and spacing.
+    pub fn has_branch(&self, branch: &str) -> bool {
+        self.branches.contains(&normalize(&branch.to_lowercase()))
+    }
+}
+impl Display for CategoryInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.completion)?;
+        if !self.branches.is_empty() {
+            write!(f, " ({})", self.branches.join(", "))?;
+        }
+        
+        Ok(())
+    }
+}
+
+impl TasdFile {
+    /// Parsed CATEGORY of this file, if any.
+    pub fn category_info(&self) -> Option<CategoryInfo> {
+        CategoryInfo::parse(&self.first::<Category>()?.category)
+    }
+}
+
+/// Trims and collapses runs of whitespace.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::category::{CategoryInfo, Completion};
+    
+    #[test]
+    fn parse() {
+        let info = CategoryInfo::parse("Any% (Warpless, 2 Players)").unwrap();
+        assert_eq!(info.completion(), &Completion::Any);
+        assert!(info.has_branch("warpless"));
+        assert_eq!(info.to_string(), "any% (2 players, warpless)");
+        assert_eq!(CategoryInfo::parse("ANY%  warpless / 2 players").unwrap(), info);
+        assert_eq!(CategoryInfo::parse("any - 2 players, warpless").unwrap(), info);
+        
+        assert_eq!(CategoryInfo::parse("100%").unwrap().to_string(), "100%");
+        assert_eq!(CategoryInfo::parse("120% (glitched)").unwrap().completion(), &Completion::Percent(120));
+        assert_eq!(CategoryInfo::parse("Game End Glitch, 2 players").unwrap().to_string(), "game end glitch (2 players)");
+        assert_eq!(CategoryInfo::parse(" ( ) "), None);
+        
+        let mut sorted = [CategoryInfo::parse("low%").unwrap(), CategoryInfo::parse("100%").unwrap(), info];
+        sorted.sort();
+        assert_eq!(sorted.iter().map(|info| info.to_string()).collect::<Vec<_>>(), ["any% (2 players, warpless)", "100%", "low%"]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,205 @@
+//! Comparing specific aspects of two files, for answering "is this the same movie?" without being thrown off by
+//! embedded files, timestamps, or packet order.
+//! 
+//! # Movie digest
+//! 
+//! [`TasdFile::movie_digest`] identifies a movie by the inputs it plays back, so re-dumps of the same inputs with
+//! different metadata get the same digest. It is the SHA-256 hash of the bytes returned by
+//! [`TasdFile::movie_digest_data`], which are, in order:
+//! 
+//! 1. The ASCII string `TASD-MOVIE-DIGEST-1`, identifying this version of the definition.
+//! 2. The kind of the first CONSOLE_TYPE packet (`0x00` if there is none), followed by the length of its custom name
+//!    as a u8 and the name itself (a length of `0x00` if there is no custom name).
+//! 3. The region of the first CONSOLE_REGION packet (u8, or `0x00` if there is none).
+//! 4. For every port with input data, in ascending order: the port number (u8), the type of its first PORT_CONTROLLER
+//!    (u16, or `0x0000` if there is none), the length of its input data (u64), and the input data itself. The input
+//!    data has BLANK_FRAMES applied, and is joined across INPUT_CHUNKs (and expanded from INPUT_DELTAs).
+//! 5. Every MEMORY_INIT, PORT_OVERREAD, TRANSITION, and MOVIE_TRANSITION packet, which change how the inputs play back
+//!    (e.g. resets), encoded with 2 byte keys. They're sorted by their encoding, and each is preceded by its length
+//!    (u64).
+//! 
+//! All integers are big-endian. Any two files which are [`inputs_eq`][TasdFile::inputs_eq] have the same digest.
+
+use crate::merge::is_metadata;
+use crate::spec::packets::{ConsoleRegion, ConsoleType, Encode, Packet, PacketKind};
+use crate::spec::TasdFile;
+use crate::util::{sha256, truncate_str};
+
+/// Whether a packet kind changes how the inputs play back, other than the console, controllers, and inputs themselves.
+fn affects_playback(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    matches!(kind, MemoryInit | PortOverread | Transition | MovieTransition)
+}
+
+/// Whether a packet kind is ignored by [`TasdFile::metadata_eq`].
+fn is_volatile(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    matches!(kind, TasLastModified | DumpCreated | DumpLastModified | MovieFile)
+}
+
+impl TasdFile {
+    /// Whether both files play back the same inputs: the same CONSOLE_TYPE and CONSOLE_REGION, for every port, the same
+    /// controller type and input data (with BLANK_FRAMES applied, and regardless of how it's split into chunks), and the
+    /// same MEMORY_INIT, PORT_OVERREAD, TRANSITION, and MOVIE_TRANSITION packets in any order.
+    pub fn inputs_eq(&self, other: &TasdFile) -> bool {
+        if self.first::<ConsoleType>() != other.first::<ConsoleType>()
+            || self.first::<ConsoleRegion>() != other.first::<ConsoleRegion>()
+            || self.playback_packets() != other.playback_packets() {
+            return false;
+        }
+        
+        let mut ports: Vec<u8> = self.input_bytes().into_keys().chain(other.input_bytes().into_keys()).collect();
+        ports.sort();
+        ports.dedup();
+        ports.iter().all(|port| {
+            self.controller_kind(*port) == other.controller_kind(*port)
+                && self.aligned_port_inputs(*port) == other.aligned_port_inputs(*port)
+        })
+    }
+    
+    /// Canonical bytes hashed by [`Self::movie_digest`], see [the module docs][self] for their exact layout.
+    pub fn movie_digest_data(&self) -> Vec<u8> {
+        let mut data = b"TASD-MOVIE-DIGEST-1".to_vec();
+        
+        let console = self.first::<ConsoleType>();
+        let custom = console.and_then(|console| console.custom.as_deref()).unwrap_or("");
+        let custom = truncate_str(custom, u8::MAX as usize);
+        data.push(console.map(|console| console.kind).unwrap_or(0x00));
+        data.push(custom.len() as u8);
+        data.extend_from_slice(custom.as_bytes());
+        data.push(self.first::<ConsoleRegion>().map(|region| region.region).unwrap_or(0x00));
+        
+        for port in self.input_bytes().into_keys() {
+            let inputs = self.aligned_port_inputs(port);
+            data.push(port);
+            data.extend_from_slice(&self.controller_kind(port).unwrap_or(0x0000).to_be_bytes());
+            data.extend_from_slice(&(inputs.len() as u64).to_be_bytes());
+            data.extend_from_slice(&inputs);
+        }
+        
+        for packet in self.playback_packets() {
+            data.extend_from_slice(&(packet.len() as u64).to_be_bytes());
+            data.extend_from_slice(&packet);
+        }
+        
+        data
+    }
+    
+    /// Encodings of the packets which [affect playback][affects_playback], sorted so their order doesn't matter.
+    fn playback_packets(&self) -> Vec<Vec<u8>> {
+        let mut packets: Vec<Vec<u8>> = self.packets.iter()
+            .filter(|packet| affects_playback(packet.kind()))
+            .map(|packet| packet.encode(2))
+            .collect();
+        packets.sort();
+        
+        packets
+    }
+    
+    /// Stable identity of the movie's inputs, usable as a key for deduplicating re-dumps. Metadata, timestamps, and
+    /// embedded files don't affect it.
+    pub fn movie_digest(&self) -> [u8; 32] {
+        sha256(&self.movie_digest_data())
+    }
+    
+    /// Whether both files have the same [metadata][crate::merge] packets, in any order.
+    /// 
+    /// Timestamps (TAS_LAST_MODIFIED, DUMP_CREATED, and DUMP_LAST_MODIFIED) and embedded MOVIE_FILEs are ignored.
+    pub fn metadata_eq(&self, other: &TasdFile) -> bool {
+        let relevant = |file: &TasdFile| -> Vec<Packet> {
+            file.packets.iter()
+                .filter(|packet| is_metadata(packet.kind()) && !is_volatile(packet.kind()))
+                .cloned()
+                .collect()
+        };
+        
+        let ours = relevant(self);
+        let mut theirs = relevant(other);
+        if ours.len() != theirs.len() {
+            return false;
+        }
+        ours.iter().all(|packet| match theirs.iter().position(|other| other == packet) {
+            Some(index) => {
+                theirs.swap_remove(index);
+                true
+            },
+            None => false,
+        })
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{BlankFrames, Comment, ConsoleRegion, ConsoleType, DumpCreated, GameTitle, Index, InputChunk, MemoryInit, MovieFile, MovieTransition, Packet, PortController, PortOverread, Transition, TransitionKind};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn compare() {
+        let mut a = TasdFile::default();
+        a.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        a.packets.push(GameTitle { title: "title".into() }.into());
+        a.packets.push(Comment { comment: "comment".into() }.into());
+        a.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        a.packets.push(InputChunk { port: 1, inputs: vec![0xFF, 0x00, 0x7F] }.into());
+        
+        let mut b = TasdFile::default();
+        b.packets.push(DumpCreated { epoch: 1 }.into());
+        b.packets.push(Comment { comment: "comment".into() }.into());
+        b.packets.push(GameTitle { title: "title".into() }.into());
+        b.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        b.packets.push(MovieFile { name: "movie.fm2".into(), data: vec![] }.into());
+        b.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        b.packets.push(BlankFrames { frames: 1 }.into());
+        b.packets.push(InputChunk { port: 1, inputs: vec![0x00] }.into());
+        b.packets.push(InputChunk { port: 1, inputs: vec![0x7F] }.into());
+        
+        assert!(a.inputs_eq(&b));
+        assert!(!a.metadata_eq(&b));
+        b.packets.retain(|packet| !matches!(packet, Packet::BlankFrames(_)));
+        assert!(!a.inputs_eq(&b));
+        assert!(a.metadata_eq(&b));
+        
+        b.packets.push(Comment { comment: "comment".into() }.into());
+        assert!(!a.metadata_eq(&b));
+        
+        // packets which change how the inputs play back
+        let mut b = a.clone();
+        b.packets.push(ConsoleRegion { region: 0x02 }.into());
+        assert!(!a.inputs_eq(&b));
+        for packet in [
+            Packet::from(Transition { port: 0, index: Index::Frame(1), kind: TransitionKind::SoftReset }),
+            MovieTransition { movie_frame: 1, kind: TransitionKind::PowerReset }.into(),
+            MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "wram".into(), data: None }.into(),
+            PortOverread { port: 1, overread: true }.into(),
+        ] {
+            let mut b = a.clone();
+            b.packets.push(packet);
+            assert!(!a.inputs_eq(&b));
+            assert_ne!(a.movie_digest(), b.movie_digest());
+        }
+    }
+    
+    #[test]
+    fn movie_digest() {
+        let mut a = TasdFile::default();
+        a.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        a.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        a.packets.push(InputChunk { port: 1, inputs: vec![0x7F, 0xFF] }.into());
+        
+        let mut data = b"TASD-MOVIE-DIGEST-1".to_vec();
+        data.extend([0x01, 0x00, 0x00, 0x01, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 2, 0x7F, 0xFF]);
+        assert_eq!(a.movie_digest_data(), data);
+        
+        let mut b = a.clone();
+        b.packets.insert(0, GameTitle { title: "title".into() }.into());
+        b.packets.push(DumpCreated { epoch: 1 }.into());
+        assert_eq!(a.movie_digest(), b.movie_digest());
+        
+        b.set_port_inputs(1, vec![0xFF, 0xFF]);
+        assert_ne!(a.movie_digest(), b.movie_digest());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,95 @@
+//! Conversions to and from the types of version 0.6 of the original `tasd` crate, for projects migrating between the
+//! two a piece at a time.
+//!
+//! Both crates encode the same format, so values are converted through their encoded bytes. Packets the other crate
+//! doesn't recognize (such as this crate's extension packets) become its `Unsupported` packet, and vice versa, so
+//! nothing is lost in either direction.
+//!
+//! Converting to the other crate can fail, as it rejects packets with an empty payload, which this crate writes for
+//! e.g. an empty COMMENT.
+
+use std::io::Cursor;
+use tasd06::packets::{Decode, DecodeError, Encode as _};
+use crate::spec::packets::{Encode, Packet, PacketError, Unsupported};
+use crate::spec::TasdFile;
+
+impl From<tasd06::packets::Packet> for Packet {
+    fn from(value: tasd06::packets::Packet) -> Self {
+        let mut data = vec![];
+        let _ = value.encode(&mut data);
+        
+        match Packet::decode_single(&data, 2) {
+            Ok((packet, _)) => packet,
+            Err(PacketError::InvalidPayload { key, payload }) => Unsupported { key, payload }.into(),
+            // the other crate always writes a complete header with a 2 byte key
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl TryFrom<Packet> for tasd06::packets::Packet {
+    type Error = DecodeError;
+    
+    fn try_from(value: Packet) -> Result<Self, Self::Error> {
+        Self::decode(&mut Cursor::new(value.encode(2)))
+    }
+}
+
+impl From<tasd06::TasdFile> for TasdFile {
+    fn from(value: tasd06::TasdFile) -> Self {
+        Self {
+            version: value.version,
+            keylen: value.keylen,
+            packets: value.packets.into_iter().map(Packet::from).collect(),
+            path: value.path.map(Into::into),
+        }
+    }
+}
+
+/// The other crate only supports 2 byte keys, so the converted file's `keylen` is always 2. Its path must be UTF-8,
+/// and is dropped if it isn't.
+impl TryFrom<TasdFile> for tasd06::TasdFile {
+    type Error = DecodeError;
+    
+    fn try_from(value: TasdFile) -> Result<Self, Self::Error> {
+        Ok(Self {
+            version: value.version,
+            keylen: 2,
+            packets: value.packets.into_iter().map(tasd06::packets::Packet::try_from).collect::<Result<_, _>>()?,
+            path: value.path.and_then(|path| path.try_into().ok()),
+        })
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{AvSync, Comment, Packet, Unsupported};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn conversions() {
+        let mut file = TasdFile::new();
+        file.keylen = 3;
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(AvSync { first_visible_frame: 90, audio_offset: -2_500 }.into());
+        file.packets.push(Unsupported { key: vec![0xAB, 0xCD], payload: vec![1, 2, 3] }.into());
+        file.path = Some("movie.tasd".into());
+        
+        let other = tasd06::TasdFile::try_from(file.clone()).unwrap();
+        assert_eq!(other.keylen, 2);
+        assert_eq!(other.packets.len(), file.packets.len());
+        assert_eq!(other.path.as_ref().map(|path| path.as_str()), Some("movie.tasd"));
+        
+        let back = TasdFile::from(other);
+        assert_eq!(back.packets, file.packets);
+        assert_eq!(back.path, file.path);
+        
+        // the other crate rejects empty payloads
+        let packet: Packet = Comment { comment: String::new() }.into();
+        assert!(tasd06::packets::Packet::try_from(packet).is_err());
+    }
+}
\ No newline at end of file
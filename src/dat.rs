@@ -0,0 +1,201 @@
+//! Identifying the game of a movie with a No-Intro or Redump dat file, from its GAME_IDENTIFIER hashes.
+//! 
+//! Dat files are the Logiqx XML format both projects publish, listing each game with the hashes of its ROMs (or disc
+//! tracks). [`Dat::lookup_rom`] finds the entry whose MD5, SHA-1, or SHA-256 matches a GAME_IDENTIFIER, and
+//! [`TasdFile::fill_from_dat`] uses it to fill in ROM_NAME and GAME_TITLE.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::spec::packets::{GameIdentifier, GameTitle, RomName};
+use crate::spec::TasdFile;
+
+/// ROM listed in a [Dat], along with the game it belongs to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DatEntry {
+    /// Name of the game, e.g. `Super Mario Bros. (World)`.
+    pub game: String,
+    /// File name of the ROM, e.g. `Super Mario Bros. (World).nes`.
+    pub rom: String,
+    pub size: Option<u64>,
+    /// Hashes in lowercase hexadecimal.
+    pub crc32: Option<String>,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+}
+impl DatEntry {
+    /// Regions of the game, from the first parenthesized part of its name (e.g. `["USA", "Europe"]` for
+    /// `Game (USA, Europe) (Rev 1)`).
+    pub fn regions(&self) -> Vec<&str> {
+        let Some((_, rest)) = self.game.split_once('(') else { return vec![] };
+        let Some((regions, _)) = rest.split_once(')') else { return vec![] };
+        
+        regions.split(',').map(str::trim).collect()
+    }
+    
+    /// Name of the game without any parenthesized tags, e.g. `Super Mario Bros.` for `Super Mario Bros. (World)`.
+    pub fn title(&self) -> &str {
+        self.game.split_once(" (").map(|(title, _)| title).unwrap_or(&self.game).trim()
+    }
+}
+
+/// Error parsing a [Dat].
+#[derive(Debug)]
+pub struct DatError(roxmltree::Error);
+impl Display for DatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid dat file: {}", self.0)
+    }
+}
+
+/// Parsed dat file, indexed by hash.
+#[derive(Debug, Clone, Default)]
+pub struct Dat {
+    /// Name from the dat file's header, e.g. `Nintendo - Nintendo Entertainment System (Headered)`.
+    pub name: Option<String>,
+    entries: Vec<DatEntry>,
+    /// Index into `entries` by GAME_IDENTIFIER kind and hash.
+    index: HashMap<(u8, String), usize>,
+}
+impl Dat {
+    pub fn parse(xml: &str) -> Result<Self, DatError> {
+        let doc = roxmltree::Document::parse(xml).map_err(DatError)?;
+        let root = doc.root_element();
+        let mut dat = Self {
+            name: root.children().find(|node| node.has_tag_name("header"))
+                .and_then(|header| header.children().find(|node| node.has_tag_name("name")))
+                .and_then(|name| name.text())
+                .map(str::to_string),
+            ..Default::default()
+        };
+        
+        for game in root.children().filter(|node| node.has_tag_name("game") || node.has_tag_name("machine")) {
+            let name = game.attribute("name").unwrap_or_default();
+            for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                let hash = |attribute: &str| rom.attribute(attribute).map(str::to_ascii_lowercase);
+                dat.push(DatEntry {
+                    game: name.to_string(),
+                    rom: rom.attribute("name").unwrap_or_default().to_string(),
+                    size: rom.attribute("size").and_then(|size| size.parse().ok()),
+                    crc32: hash("crc"),
+                    md5: hash("md5"),
+                    sha1: hash("sha1"),
+                    sha256: hash("sha256"),
+                });
+            }
+        }
+        
+        Ok(dat)
+    }
+    
+    pub fn entries(&self) -> &[DatEntry] {
+        &self.entries
+    }
+    
+    /// Adds an entry, which takes precedence over earlier entries with the same hashes.
+    pub fn push(&mut self, entry: DatEntry) {
+        let i = self.entries.len();
+        for (kind, hash) in [(0x01, &entry.md5), (0x02, &entry.sha1), (0x04, &entry.sha256)] {
+            if let Some(hash) = hash {
+                self.index.insert((kind, hash.clone()), i);
+            }
+        }
+        self.entries.push(entry);
+    }
+    
+    /// Entry whose hash matches a GAME_IDENTIFIER.
+    /// 
+    /// Only MD5, SHA-1, and SHA-256 identifiers encoded as raw binary or base 16 can be matched.
+    pub fn lookup_rom(&self, identifier: &GameIdentifier) -> Option<&DatEntry> {
+        let hash = match identifier.encoding {
+            0x01 => identifier.identifier.iter().map(|byte| format!("{byte:02x}")).collect(),
+            0x02 => String::from_utf8_lossy(&identifier.identifier).to_ascii_lowercase(),
+            _ => return None,
+        };
+        
+        self.index.get(&(identifier.kind, hash)).map(|i| &self.entries[*i])
+    }
+}
+
+/// Which packets [`TasdFile::fill_from_dat`] fills in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DatFillOptions {
+    /// Set ROM_NAME to the file name of the ROM.
+    pub rom_name: bool,
+    /// Set GAME_TITLE to the name of the game, without its tags.
+    pub game_title: bool,
+    /// Replace packets which are already present (removing any duplicates), instead of only adding missing ones.
+    pub overwrite: bool,
+}
+impl Default for DatFillOptions {
+    fn default() -> Self {
+        Self { rom_name: true, game_title: true, overwrite: false }
+    }
+}
+
+impl TasdFile {
+    /// Entry of `dat` matching the first GAME_IDENTIFIER which has one.
+    pub fn lookup_rom<'a>(&self, dat: &'a Dat) -> Option<&'a DatEntry> {
+        self.all::<GameIdentifier>().find_map(|identifier| dat.lookup_rom(identifier))
+    }
+    
+    /// Fills in ROM_NAME and GAME_TITLE from the entry of `dat` matching this file's GAME_IDENTIFIER, if any.
+    /// 
+    /// Returns the matching entry.
+    pub fn fill_from_dat(&mut self, dat: &Dat, options: &DatFillOptions) -> Option<DatEntry> {
+        let entry = self.lookup_rom(dat)?.clone();
+        
+        if options.rom_name && (options.overwrite || self.first::<RomName>().is_none()) {
+            self.set(RomName { name: entry.rom.clone() });
+        }
+        if options.game_title && (options.overwrite || self.first::<GameTitle>().is_none()) {
+            self.set(GameTitle { title: entry.title().to_string() });
+        }
+        
+        Some(entry)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::dat::{Dat, DatFillOptions};
+    use crate::spec::packets::{GameIdentifier, GameTitle, RomName};
+    use crate::spec::TasdFile;
+    
+    const DAT: &str = r#"<?xml version="1.0"?>
+<datafile>
+    <header><name>Nintendo - Nintendo Entertainment System</name></header>
+    <game name="Super Mario Bros. (World)">
+        <description>Super Mario Bros. (World)</description>
+        <rom name="Super Mario Bros. (World).nes" size="40976" crc="3337EC46" md5="811B027EAF99C2DEF7B933C5208636DE" sha1="EA343F4E445A9050D4B4FBAC2C77D0693B1D0922"/>
+    </game>
+</datafile>"#;
+
+    #[test]
+    fn lookup() {
+        let dat = Dat::parse(DAT).unwrap();
+        assert_eq!(dat.name.as_deref(), Some("Nintendo - Nintendo Entertainment System"));
+        assert_eq!(dat.entries()[0].regions(), ["World"]);
+        assert_eq!(dat.entries()[0].title(), "Super Mario Bros.");
+        
+        let md5 = GameIdentifier { kind: 0x01, encoding: 0x02, name: "".into(), identifier: b"811b027eaf99c2def7b933c5208636de".to_vec() };
+        assert_eq!(dat.lookup_rom(&md5).unwrap().size, Some(40976));
+        let sha1 = GameIdentifier { kind: 0x02, encoding: 0x01, name: "".into(), identifier: vec![0xEA, 0x34] };
+        assert!(dat.lookup_rom(&sha1).is_none());
+        
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "SMB".into() }.into());
+        file.packets.push(md5.into());
+        file.fill_from_dat(&dat, &DatFillOptions::default()).unwrap();
+        assert_eq!(file.first::<GameTitle>().unwrap().title, "SMB");
+        assert_eq!(file.first::<RomName>().unwrap().name, "Super Mario Bros. (World).nes");
+        file.fill_from_dat(&dat, &DatFillOptions { overwrite: true, ..Default::default() }).unwrap();
+        assert_eq!(file.first::<GameTitle>().unwrap().title, "Super Mario Bros.");
+        
+        assert!(Dat::parse("<datafile>").is_err());
+    }
+}
\ No newline at end of file
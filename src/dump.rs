@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use crate::spec::packets::{ConsoleType, EmulatorName, EmulatorVersion, GameTitle, LagFrameChunk, MovieTransition, Packet, PortController, TransitionKind};
+use crate::spec::TasdFile;
+use crate::start::StartCondition;
+
+/// Description of the movie being dumped, passed to [`Dumper::begin_session`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DumpSession {
+    /// CONSOLE_TYPE of the emulated console.
+    pub console: u8,
+    pub game_title: Option<String>,
+    pub emulator_name: Option<String>,
+    pub emulator_version: Option<String>,
+    /// Port number and PORT_CONTROLLER type of each connected controller.
+    pub controllers: Vec<(u8, u16)>,
+    /// How the movie starts, declared with [`TasdFile::set_start_condition`].
+    pub start: StartCondition,
+}
+
+/// Receives events from an emulator as a movie is played back, for producing a TASD dump.
+/// 
+/// This is the recording counterpart of [PlaybackDriver][crate::playback::PlaybackDriver]: emulators call these
+/// methods from their frame loop, and [TasdDumper] turns them into packets. Frames are movie frames, starting from `0`.
+#[allow(unused_variables)]
+pub trait Dumper {
+    /// Called once before the first frame.
+    fn begin_session(&mut self, session: &DumpSession);
+    
+    /// Called once per frame in which the console polled for input, with the input data of each port.
+    fn on_frame_inputs(&mut self, frame: u64, inputs: &[(u8, &[u8])]);
+    
+    /// Called for each frame in which the console didn't poll for input.
+    fn on_lag_frame(&mut self, frame: u64) {}
+    
+    /// Called when the console is reset, before the inputs of that frame. `transition_type` is `0x01` for a soft reset
+    /// and `0x02` for a power reset.
+    fn on_reset(&mut self, frame: u64, transition_type: u8) {}
+    
+    /// Called once after the last frame.
+    fn end_session(&mut self);
+}
+
+/// [Dumper] which builds a [TasdFile].
+/// 
+/// Input data is collected per port and written as one INPUT_CHUNK for each port when the session ends, along with
+/// TOTAL_FRAMES. Consecutive lag frames are merged into a single LAG_FRAME_CHUNK.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TasdDumper {
+    file: TasdFile,
+    inputs: BTreeMap<u8, Vec<u8>>,
+}
+impl TasdDumper {
+    pub fn new() -> Self {
+        Self {
+            file: TasdFile::new(),
+            inputs: BTreeMap::new(),
+        }
+    }
+    
+    /// File dumped so far. Input data is only included once the session has ended.
+    pub fn file(&self) -> &TasdFile {
+        &self.file
+    }
+    
+    pub fn into_file(self) -> TasdFile {
+        self.file
+    }
+}
+impl Default for TasdDumper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Dumper for TasdDumper {
+    fn begin_session(&mut self, session: &DumpSession) {
+        let packets = &mut self.file.packets;
+        packets.push(ConsoleType { kind: session.console, custom: None }.into());
+        if let Some(title) = &session.game_title {
+            packets.push(GameTitle { title: title.clone() }.into());
+        }
+        if let Some(name) = &session.emulator_name {
+            packets.push(EmulatorName { name: name.clone() }.into());
+        }
+        if let Some(version) = &session.emulator_version {
+            packets.push(EmulatorVersion { version: version.clone() }.into());
+        }
+        for (port, kind) in &session.controllers {
+            packets.push(PortController { port: *port, kind: *kind }.into());
+        }
+        self.file.set_start_condition(session.start);
+    }
+    
+    fn on_frame_inputs(&mut self, _frame: u64, inputs: &[(u8, &[u8])]) {
+        for (port, data) in inputs {
+            self.inputs.entry(*port).or_default().extend_from_slice(data);
+        }
+    }
+    
+    fn on_lag_frame(&mut self, frame: u64) {
+        let frame = frame.min(u32::MAX as u64) as u32;
+        match self.file.packets.last_mut() {
+            Some(Packet::LagFrameChunk(lag)) if lag.movie_frame as u64 + lag.count as u64 == frame as u64 => lag.count += 1,
+            _ => self.file.packets.push(LagFrameChunk { movie_frame: frame, count: 1 }.into()),
+        }
+    }
+    
+    fn on_reset(&mut self, frame: u64, transition_type: u8) {
+        let movie_frame = frame.min(u32::MAX as u64) as u32;
+        let Some(kind) = TransitionKind::from_code(transition_type) else { return };
+        self.file.packets.push(MovieTransition { movie_frame, kind }.into());
+    }
+    
+    fn end_session(&mut self) {
+        for (port, inputs) in std::mem::take(&mut self.inputs) {
+            self.file.set_port_inputs(port, inputs);
+        }
+        self.file.recompute_total_frames();
+        self.file.set_dump_last_modified_now();
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::dump::{DumpSession, Dumper, TasdDumper};
+    use crate::spec::packets::{ConsoleType, LagFrameChunk, MovieTransition, TotalFrames};
+    
+    #[test]
+    fn dumper() {
+        let mut dumper = TasdDumper::new();
+        dumper.begin_session(&DumpSession {
+            console: 0x01,
+            game_title: Some("Super Mario Bros.".into()),
+            controllers: vec![(1, 0x0101), (2, 0x0101)],
+            ..Default::default()
+        });
+        dumper.on_frame_inputs(0, &[(1, &[0xFF]), (2, &[0xFF])]);
+        dumper.on_lag_frame(1);
+        dumper.on_lag_frame(2);
+        dumper.on_reset(3, 0x01);
+        dumper.on_frame_inputs(3, &[(1, &[0x7F]), (2, &[0xFF])]);
+        dumper.on_lag_frame(4);
+        dumper.end_session();
+        
+        let file = dumper.into_file();
+        assert_eq!(file.first::<ConsoleType>().unwrap().kind, 0x01);
+        assert_eq!(file.port_inputs(1), vec![0xFF, 0x7F]);
+        assert_eq!(file.port_inputs(2), vec![0xFF, 0xFF]);
+        assert_eq!(file.first::<TotalFrames>().unwrap().frames, 2);
+        assert_eq!(file.all::<LagFrameChunk>().cloned().collect::<Vec<_>>(), vec![
+            LagFrameChunk { movie_frame: 1, count: 2 },
+            LagFrameChunk { movie_frame: 4, count: 1 },
+        ]);
+        assert_eq!(file.first::<MovieTransition>().unwrap().movie_frame, 3);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,150 @@
+//! Finding long repeated sequences of inputs within a movie, which are usually mistakes: a section dumped twice, or
+//! inputs pasted over the wrong frames while editing.
+//! 
+//! A duplicate starts where `min_len` frames in a row exactly repeat earlier frames, and continues through differences
+//! shorter than `min_len` frames, so a copy which was edited slightly afterwards is still found as one segment. Runs
+//! where the same input is held for at least `min_len` frames (e.g. waiting with nothing pressed) aren't counted.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use crate::spec::TasdFile;
+
+/// Multiplier of the rolling hash over frames.
+const HASH_BASE: u64 = 0x100000001B3;
+
+/// Frames which repeat an earlier part of the movie. See the [module docs][self].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateSegment {
+    /// Earlier frames which are repeated.
+    pub original: Range<u64>,
+    /// Frames which repeat `original`, of the same length.
+    pub copy: Range<u64>,
+    /// Fraction of the frames which are exactly the same in both, from `0.0` to `1.0`.
+    pub similarity: f64,
+}
+
+impl TasdFile {
+    /// Finds segments of at least `min_len` frames whose inputs repeat an earlier part of the movie, in the order they
+    /// appear. Frames combine the input data of every port with a known input size.
+    pub fn find_duplicate_segments(&self, min_len: usize) -> Vec<DuplicateSegment> {
+        let ports: Vec<_> = self.input_bytes().into_keys()
+            .filter_map(|port| Some((self.bytes_per_frame(port)?, self.port_inputs(port))))
+            .filter(|(size, _)| *size > 0)
+            .collect();
+        let stride: usize = ports.iter().map(|(size, _)| size).sum();
+        let len = ports.iter().map(|(size, inputs)| inputs.len() / size).max().unwrap_or(0);
+        if min_len == 0 || len < min_len * 2 {
+            return vec![];
+        }
+        
+        // every frame of every port side by side, with missing frames read as nothing pressed
+        let mut data = vec![0xFF; len * stride];
+        let mut offset = 0;
+        for (size, inputs) in &ports {
+            for (frame, input) in inputs.chunks_exact(*size).enumerate() {
+                data[(frame * stride + offset)..][..*size].copy_from_slice(input);
+            }
+            offset += size;
+        }
+        let frame = |i: usize| &data[(i * stride)..((i + 1) * stride)];
+        
+        // number of frames from each frame onwards with the same input
+        let mut held = vec![1usize; len];
+        for i in (0..(len - 1)).rev() {
+            if frame(i) == frame(i + 1) {
+                held[i] = held[i + 1] + 1;
+            }
+        }
+        
+        let hashes: Vec<u64> = (0..len).map(|i| {
+            let mut hasher = DefaultHasher::new();
+            frame(i).hash(&mut hasher);
+            hasher.finish()
+        }).collect();
+        let top = (1..min_len).fold(1u64, |power, _| power.wrapping_mul(HASH_BASE));
+        let mut window = hashes[..min_len].iter().fold(0u64, |hash, h| hash.wrapping_mul(HASH_BASE).wrapping_add(*h));
+        
+        let mut first_seen: HashMap<u64, usize> = HashMap::new();
+        let mut segments = vec![];
+        let mut skip_until = 0;
+        for i in 0..=(len - min_len) {
+            if i > 0 {
+                window = window.wrapping_sub(hashes[i - 1].wrapping_mul(top)).wrapping_mul(HASH_BASE).wrapping_add(hashes[i + min_len - 1]);
+            }
+            if i < skip_until || held[i] >= min_len {
+                continue;
+            }
+            
+            let original = match first_seen.get(&window) {
+                Some(&j) if j + min_len <= i && (0..min_len).all(|k| frame(j + k) == frame(i + k)) => j,
+                _ => {
+                    first_seen.entry(window).or_insert(i);
+                    continue;
+                },
+            };
+            
+            // extend through differences shorter than min_len, without running into the copy
+            let (mut length, mut same, mut differing) = (min_len, min_len, 0);
+            while original + length < i && i + length < len {
+                let matches = frame(original + length) == frame(i + length);
+                length += 1;
+                if matches {
+                    same += 1;
+                    differing = 0;
+                } else {
+                    differing += 1;
+                    if differing >= min_len {
+                        break;
+                    }
+                }
+            }
+            length -= differing;
+            
+            segments.push(DuplicateSegment {
+                original: (original as u64)..((original + length) as u64),
+                copy: (i as u64)..((i + length) as u64),
+                similarity: same as f64 / length as f64,
+            });
+            skip_until = i + length;
+        }
+        
+        segments
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{InputChunk, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn duplicate_segments() {
+        let section: Vec<u8> = (0..10).collect();
+        let mut inputs = section.clone();
+        inputs.extend([0xFF; 20]);
+        inputs.extend(&section);
+        inputs[34] = 0x80;
+        inputs.extend(30..40);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs }.into());
+        
+        let segments = file.find_duplicate_segments(4);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].original, 0..10);
+        assert_eq!(segments[0].copy, 30..40);
+        assert_eq!(segments[0].similarity, 0.9);
+        
+        // idle frames aren't duplicates, and a second port which differs hides the repeat
+        assert!(file.find_duplicate_segments(11).is_empty());
+        file.packets.push(PortController { port: 2, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 2, inputs: (0..50).collect() }.into());
+        assert!(file.find_duplicate_segments(4).is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,239 @@
+use std::ops::Range;
+use crate::spec::packets::{Index, InputChunk, InputDelta, InputMoment, Packet, TotalFrames, Transition};
+use crate::spec::TasdFile;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditError {
+    /// The port has no PORT_CONTROLLER, or its controller type doesn't have a known input size.
+    UnknownFrameSize(u8),
+    /// The files being combined declare different controllers for the same port.
+    ControllerMismatch(u8),
+    /// The range of frames starts after it ends.
+    InvalidRange(Range<u64>),
+    /// The frame is too far into the movie to be stored in memory.
+    FrameOutOfRange(u64),
+}
+
+impl TasdFile {
+    /// All INPUT_CHUNK data for a port, joined together.
+    /// 
+    /// If the port is stored as an [INPUT_DELTA][crate::input::delta] instead, the delta is expanded.
+    pub fn port_inputs(&self, port: u8) -> Vec<u8> {
+        if let Some(delta) = self.all::<InputDelta>().find(|delta| delta.port == port && delta.base != port) {
+            return delta.apply(&self.port_inputs(delta.base));
+        }
+        
+        let mut inputs = vec![];
+        for chunk in self.all::<InputChunk>().filter(|chunk| chunk.port == port) {
+            inputs.extend_from_slice(&chunk.inputs);
+        }
+        
+        inputs
+    }
+    
+    /// Replaces all INPUT_CHUNK data for a port with a single chunk, at the position of the port's first chunk.
+    /// 
+    /// If the port has no chunks yet, the new chunk is added to the end of the file.
+    pub fn set_port_inputs(&mut self, port: u8, inputs: Vec<u8>) {
+        let is_port = |packet: &Packet| matches!(packet, Packet::InputChunk(chunk) if chunk.port == port);
+        let index = self.packets.iter().position(is_port);
+        self.packets.retain(|packet| !is_port(packet));
+        
+        let chunk = InputChunk { port, inputs }.into();
+        match index {
+            Some(index) => self.packets.insert(index, chunk),
+            None => self.packets.push(chunk),
+        }
+    }
+    
+    /// Keeps only the given range of frames.
+    /// 
+    /// The input data of every port is cut to the range, and frame-indexed packets (TRANSITION, INPUT_MOMENT,
+    /// LAG_FRAME_CHUNK, MOVIE_TRANSITION, and BOOKMARK) outside of it are removed, while the rest are shifted to
+    /// match. TOTAL_FRAMES is updated if present.
+    pub fn trim(&mut self, frames: Range<u64>) -> Result<(), EditError> {
+        if frames.start > frames.end {
+            return Err(EditError::InvalidRange(frames));
+        }
+        
+        let mut trimmed = vec![];
+        for port in self.input_bytes().into_keys() {
+            let size = self.bytes_per_frame(port).ok_or(EditError::UnknownFrameSize(port))?;
+            let inputs = self.port_inputs(port);
+            let start = frame_offset(frames.start, size).unwrap_or(usize::MAX).min(inputs.len());
+            let end = frame_offset(frames.end, size).unwrap_or(usize::MAX).clamp(start, inputs.len());
+            trimmed.push((port, inputs[start..end].to_vec()));
+        }
+        for (port, inputs) in trimmed {
+            self.set_port_inputs(port, inputs);
+        }
+        
+        let len = frames.end - frames.start;
+        self.packets.retain_mut(|packet| match shift_frame(packet, frames.start) {
+            Some(frame) => frame < len,
+            None => true,
+        });
+        self.update_total_frames();
+        
+        Ok(())
+    }
+    
+    /// Overwrites this file's inputs with those of `other`, starting at frame `at`.
+    /// 
+    /// Only ports with input data in `other` are affected. If `at` is past the end of a port's inputs, the gap is filled
+    /// with frames where nothing is pressed (all bits set). TOTAL_FRAMES is updated if present.
+    pub fn splice(&mut self, other: &TasdFile, at: u64) -> Result<(), EditError> {
+        let mut spliced = vec![];
+        for port in other.input_bytes().into_keys() {
+            let kind = other.controller_kind(port);
+            if self.controller_kind(port).is_some() && self.controller_kind(port) != kind {
+                return Err(EditError::ControllerMismatch(port));
+            }
+            let size = other.bytes_per_frame(port).ok_or(EditError::UnknownFrameSize(port))?;
+            
+            let mut inputs = self.port_inputs(port);
+            let incoming = other.port_inputs(port);
+            let start = frame_offset(at, size).ok_or(EditError::FrameOutOfRange(at))?;
+            let end = start.checked_add(incoming.len()).ok_or(EditError::FrameOutOfRange(at))?;
+            if inputs.len() < end {
+                // any gap is filled with frames where nothing is pressed
+                inputs.resize(start.max(inputs.len()), 0xFF);
+                inputs.resize(end, 0);
+            }
+            inputs[start..end].copy_from_slice(&incoming);
+            spliced.push((port, inputs));
+        }
+        
+        for (port, inputs) in spliced {
+            if self.controller_kind(port).is_none() {
+                if let Some(controller) = other.packets.iter().find(|packet| matches!(packet, Packet::PortController(c) if c.port == port)) {
+                    self.packets.insert(0, controller.clone());
+                }
+            }
+            self.set_port_inputs(port, inputs);
+        }
+        self.update_total_frames();
+        
+        Ok(())
+    }
+    
+    fn update_total_frames(&mut self) {
+        if self.first::<TotalFrames>().is_some() {
+            self.recompute_total_frames();
+        }
+    }
+}
+
+/// Byte offset of `frame` in input data with `size` bytes per frame, or `None` if it doesn't fit in a `usize`.
+fn frame_offset(frame: u64, size: usize) -> Option<usize> {
+    usize::try_from(frame).ok()?.checked_mul(size)
+}
+
+/// Moves a frame-indexed packet `by` frames earlier, returning its new frame.
+/// 
+/// Returns `u64::MAX` for packets before the start, or `None` if the packet isn't frame-indexed.
+fn shift_frame(packet: &mut Packet, by: u64) -> Option<u64> {
+    map_frame(packet, |frame| frame.checked_sub(by).unwrap_or(u64::MAX))
+}
+
+/// Replaces the frame of a frame-indexed packet (TRANSITION, INPUT_MOMENT, LAG_FRAME_CHUNK, MOVIE_TRANSITION, or
+/// BOOKMARK) using `f`, returning the new frame.
+/// 
+/// Frames stored as u32 are saturated. Returns `None` if the packet isn't frame-indexed.
+pub(crate) fn map_frame<F: Fn(u64) -> u64>(packet: &mut Packet, f: F) -> Option<u64> {
+    match packet {
+        Packet::Transition(Transition { index: Index::Frame(frame), .. }) | Packet::InputMoment(InputMoment { index: Index::Frame(frame), .. }) => {
+            *frame = f(*frame);
+            Some(*frame)
+        },
+        Packet::LagFrameChunk(lag) => {
+            let frame = f(lag.movie_frame as u64);
+            lag.movie_frame = frame.min(u32::MAX as u64) as u32;
+            Some(frame)
+        },
+        Packet::MovieTransition(transition) => {
+            let frame = f(transition.movie_frame as u64);
+            transition.movie_frame = frame.min(u32::MAX as u64) as u32;
+            Some(frame)
+        },
+        Packet::Bookmark(bookmark) => {
+            bookmark.frame = f(bookmark.frame);
+            Some(bookmark.frame)
+        },
+        _ => None,
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use crate::edit::EditError;
+    use crate::spec::packets::{Bookmark, InputChunk, LagFrameChunk, PortController, TotalFrames};
+    use crate::spec::TasdFile;
+    
+    fn sample() -> TasdFile {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(TotalFrames { frames: 6 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0, 1, 2] }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![3, 4, 5] }.into());
+        file.packets.push(LagFrameChunk { movie_frame: 1, count: 1 }.into());
+        file.packets.push(Bookmark { frame: 4, name: "end".into() }.into());
+        
+        file
+    }
+    
+    #[test]
+    fn trim() {
+        let mut file = sample();
+        file.trim(2..5).unwrap();
+        assert_eq!(file.port_inputs(1), vec![2, 3, 4]);
+        assert_eq!(file.first::<TotalFrames>().unwrap().frames, 3);
+        assert!(file.first::<LagFrameChunk>().is_none());
+        assert_eq!(file.first::<Bookmark>().unwrap().frame, 2);
+        assert_eq!(file.all::<InputChunk>().count(), 1);
+        
+        file.packets.push(PortController { port: 2, kind: 0x0201 }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0; 6] }.into());
+        file.trim(1..u64::MAX).unwrap();
+        assert_eq!(file.port_inputs(1), vec![3, 4]);
+        assert_eq!(file.port_inputs(2).len(), 4);
+        
+        // nothing is changed if the range is invalid
+        let before = file.clone();
+        let range = Range { start: 3, end: 1 };
+        assert_eq!(file.trim(range.clone()), Err(EditError::InvalidRange(range)));
+        assert_eq!(file, before);
+    }
+    
+    #[test]
+    fn splice() {
+        let mut other = TasdFile::default();
+        other.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        other.packets.push(InputChunk { port: 1, inputs: vec![9, 9] }.into());
+        
+        let mut file = sample();
+        file.splice(&other, 1).unwrap();
+        assert_eq!(file.port_inputs(1), vec![0, 9, 9, 3, 4, 5]);
+        
+        file.splice(&other, 7).unwrap();
+        assert_eq!(file.port_inputs(1), vec![0, 9, 9, 3, 4, 5, 0xFF, 9, 9]);
+        assert_eq!(file.first::<TotalFrames>().unwrap().frames, 9);
+        
+        let before = file.clone();
+        assert_eq!(file.splice(&other, u64::MAX), Err(EditError::FrameOutOfRange(u64::MAX)));
+        assert_eq!(file, before);
+        
+        // ports are only changed once every port has been checked
+        other.packets.push(PortController { port: 2, kind: 0x0201 }.into());
+        other.packets.push(InputChunk { port: 2, inputs: vec![1, 2] }.into());
+        file.packets.push(PortController { port: 2, kind: 0x0101 }.into());
+        let before = file.clone();
+        assert_eq!(file.splice(&other, 0), Err(EditError::ControllerMismatch(2)));
+        assert_eq!(file, before);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,175 @@
+//! Normalization of the free-text EMULATOR_NAME, EMULATOR_VERSION, and EMULATOR_CORE packets.
+//!
+//! The same emulator is spelled many different ways across dumps ("BizHawk", "EmuHawk", "bizhawk 2.9.1"), which makes
+//! them hard to group. [`TasdFile::normalized_emulator`] maps them onto a known [Emulator] and canonical spellings.
+
+use strum_macros::Display;
+use crate::spec::packets::{EmulatorCore, EmulatorName, EmulatorVersion};
+use crate::spec::TasdFile;
+
+/// Emulators commonly used to produce TAS movies.
+#[derive(Debug, Display, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Emulator {
+    BizHawk,
+    #[strum(serialize = "DeSmuME")]
+    Desmume,
+    Dolphin,
+    #[strum(serialize = "FCEUX")]
+    Fceux,
+    Gens,
+    #[strum(serialize = "libTAS")]
+    LibTas,
+    #[strum(serialize = "lsnes")]
+    Lsnes,
+    Mesen,
+    Mupen64,
+    #[strum(serialize = "PSXjin")]
+    Psxjin,
+    Snes9x,
+    VisualBoyAdvance,
+}
+impl Emulator {
+    const ALIASES: &'static [(&'static str, Emulator)] = &[
+        ("bizhawk", Emulator::BizHawk),
+        ("emuhawk", Emulator::BizHawk),
+        ("desmume", Emulator::Desmume),
+        ("dolphin", Emulator::Dolphin),
+        ("fceux", Emulator::Fceux),
+        ("gens", Emulator::Gens),
+        ("gensrr", Emulator::Gens),
+        ("libtas", Emulator::LibTas),
+        ("lsnes", Emulator::Lsnes),
+        ("mesen", Emulator::Mesen),
+        ("mesen2", Emulator::Mesen),
+        ("mupen64", Emulator::Mupen64),
+        ("mupen64rr", Emulator::Mupen64),
+        ("mupen64plus", Emulator::Mupen64),
+        ("psxjin", Emulator::Psxjin),
+        ("snes9x", Emulator::Snes9x),
+        ("snes9xrr", Emulator::Snes9x),
+        ("vba", Emulator::VisualBoyAdvance),
+        ("vbam", Emulator::VisualBoyAdvance),
+        ("vbarr", Emulator::VisualBoyAdvance),
+        ("visualboyadvance", Emulator::VisualBoyAdvance),
+    ];
+    
+    /// Recognizes an EMULATOR_NAME, ignoring case, spacing, and punctuation.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = fold(name);
+        
+        Self::ALIASES.iter().find(|(alias, _)| *alias == name).map(|(_, emulator)| *emulator)
+    }
+    
+    /// Canonical spellings of this emulator's cores, if it has more than one.
+    pub fn cores(&self) -> &'static [&'static str] {
+        match self {
+            Emulator::BizHawk => &[
+                "Ares64", "BSNES", "BSNESv115+", "Encore", "Faust", "Gambatte", "GBHawk", "Genplus-gx", "MelonDS", "mGBA",
+                "Mupen64Plus", "NesHawk", "Nymashock", "Octoshock", "PicoDrive", "QuickNes", "SameBoy", "Snes9x",
+                "SubNESHawk", "VirtualJaguar",
+            ],
+            _ => &[],
+        }
+    }
+}
+
+/// Emulator information of a file, from [`TasdFile::normalized_emulator`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedEmulator {
+    pub emulator: Emulator,
+    /// Version number, without any "v" prefix. `None` if missing or unparseable.
+    pub version: Option<String>,
+    /// Core, with the canonical spelling if it's one of [`Emulator::cores`]. `None` if missing.
+    pub core: Option<String>,
+}
+
+impl TasdFile {
+    /// Recognizes the emulator this file was made with, from its EMULATOR_NAME, EMULATOR_VERSION, and EMULATOR_CORE.
+    /// 
+    /// Names with the version appended (e.g. "BizHawk 2.9.1") are also recognized, with the version used if there's no
+    /// EMULATOR_VERSION. Returns `None` if there's no EMULATOR_NAME, or it isn't recognized.
+    pub fn normalized_emulator(&self) -> Option<NormalizedEmulator> {
+        let (emulator, name_version) = split_name(&self.first::<EmulatorName>()?.name)?;
+        let version = match self.first::<EmulatorVersion>() {
+            Some(version) => normalize_version(&version.version),
+            None => name_version,
+        };
+        let core = self.first::<EmulatorCore>().map(|core| normalize_core(emulator, &core.core).unwrap_or_else(|| core.core.trim().to_string()));
+        
+        Some(NormalizedEmulator {
+            emulator,
+            version,
+            core,
+        })
+    }
+}
+
+/// Splits an EMULATOR_NAME into the emulator and any version number following it.
+pub(crate) fn split_name(name: &str) -> Option<(Emulator, Option<String>)> {
+    if let Some(emulator) = Emulator::from_name(name) {
+        return Some((emulator, None));
+    }
+    
+    let (name, version) = name.trim().rsplit_once(char::is_whitespace)?;
+    Some((Emulator::from_name(name)?, Some(normalize_version(version)?)))
+}
+
+/// Trims a version number and removes any "v" or "version" prefix. Returns `None` if it doesn't start with a digit.
+pub fn normalize_version(version: &str) -> Option<String> {
+    let version = version.trim();
+    let lower = version.to_lowercase();
+    let version = if lower.starts_with("version") {
+        version["version".len()..].trim_start()
+    } else if lower.starts_with('v') {
+        &version[1..]
+    } else {
+        version
+    };
+    
+    version.starts_with(|c: char| c.is_ascii_digit()).then(|| version.to_string())
+}
+
+/// Canonical spelling of one of `emulator`'s cores, ignoring case, spacing, and punctuation.
+pub fn normalize_core(emulator: Emulator, core: &str) -> Option<String> {
+    let folded = fold(core);
+    
+    emulator.cores().iter().find(|known| fold(known) == folded).map(|known| known.to_string())
+}
+
+fn fold(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric() || *c == '+').flat_map(char::to_lowercase).collect()
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::{normalize_version, Emulator, NormalizedEmulator};
+    use crate::spec::packets::{EmulatorCore, EmulatorName, EmulatorVersion};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn normalized() {
+        let mut file = TasdFile::default();
+        file.packets.push(EmulatorName { name: "EmuHawk 2.9.1".into() }.into());
+        file.packets.push(EmulatorCore { core: "nes hawk".into() }.into());
+        assert_eq!(file.normalized_emulator(), Some(NormalizedEmulator {
+            emulator: Emulator::BizHawk,
+            version: Some("2.9.1".into()),
+            core: Some("NesHawk".into()),
+        }));
+        
+        file.packets.push(EmulatorVersion { version: "v2.6.3".into() }.into());
+        assert_eq!(file.normalized_emulator().unwrap().version, Some("2.6.3".into()));
+        
+        file.packets[0] = EmulatorName { name: "Some Emulator".into() }.into();
+        assert_eq!(file.normalized_emulator(), None);
+        
+        assert_eq!(Emulator::from_name(" Snes9x-rr "), Some(Emulator::Snes9x));
+        assert_eq!(Emulator::Fceux.to_string(), "FCEUX");
+        assert_eq!(normalize_version("Version 1.0"), Some("1.0".into()));
+        assert_eq!(normalize_version("latest"), None);
+    }
+}
\ No newline at end of file
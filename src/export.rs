@@ -0,0 +1,6 @@
+//! Exporters for producing other formats from a [TasdFile][crate::spec::TasdFile].
+
+pub mod replay;
+pub mod report;
+pub mod subtitles;
+pub mod timeline;
\ No newline at end of file
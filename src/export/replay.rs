@@ -0,0 +1,287 @@
+use crate::export::report::{Assumption, ConversionReport};
+use crate::input::format::{BitOrder, InputFormat, Polarity, detect_input_format};
+use crate::lookup::{controller_buttons, controller_input_size};
+use crate::spec::packets::{LatchMap, Packet, PortOverread};
+use crate::spec::TasdFile;
+
+/// How bits past the end of a controller's buttons are filled in, for formats which are wider than the controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OverreadStrategy {
+    /// Use the port's PORT_OVERREAD packet. Ports without one are assumed to read high, like official controllers.
+    #[default]
+    FromFile,
+    /// Always read high (`1`).
+    High,
+    /// Always read low (`0`).
+    Low,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    pub overread: OverreadStrategy,
+    /// Whether BLANK_FRAMES is applied to the input data first.
+    pub apply_blank_frames: bool,
+}
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            overread: OverreadStrategy::default(),
+            apply_blank_frames: true,
+        }
+    }
+}
+
+/// Layout of a raw replay device format.
+struct ReplayLayout {
+    name: &'static str,
+    /// Total bytes per frame.
+    frame_bytes: usize,
+    /// Ports included, and the byte offset of each within a frame.
+    ports: &'static [(u8, usize)],
+    /// Bytes per controller.
+    controller_bytes: usize,
+    format: InputFormat,
+}
+
+/// r08: two NES controllers, one byte each, with the first button in the least significant bit and `1` = pressed.
+const R08: ReplayLayout = ReplayLayout {
+    name: "r08",
+    frame_bytes: 2,
+    ports: &[(1, 0), (2, 1)],
+    controller_bytes: 1,
+    format: InputFormat { polarity: Polarity::ActiveHigh, bit_order: BitOrder::LsbFirst },
+};
+
+/// r16m: two SNES ports with four data lines of two bytes each, with the first button in the most significant bit and
+/// `1` = pressed. Only the first data line of each port is used.
+const R16M: ReplayLayout = ReplayLayout {
+    name: "r16m",
+    frame_bytes: 16,
+    ports: &[(1, 0), (2, 8)],
+    controller_bytes: 2,
+    format: InputFormat { polarity: Polarity::ActiveHigh, bit_order: BitOrder::MsbFirst },
+};
+
+/// A single port's part of a replay stream, ready to be interleaved with the others.
+struct PortStream {
+    port: u8,
+    /// Byte offset of the port within each frame.
+    offset: usize,
+    kind: u16,
+    /// Bytes per frame of the port's controller.
+    size: usize,
+    /// Input data, already converted to the layout's format.
+    inputs: Vec<u8>,
+    /// Format the input data looks like it was in, if it doesn't look canonical.
+    detected: Option<InputFormat>,
+}
+
+impl TasdFile {
+    /// Value of the port's PORT_OVERREAD packet, if it has one.
+    pub fn overread(&self, port: u8) -> Option<bool> {
+        self.all::<PortOverread>().find(|overread| overread.port == port).map(|overread| overread.overread)
+    }
+    
+    /// Exports the inputs of ports 1 and 2 in the r08 format used by NES replay devices.
+    /// 
+    /// Inputs are repeated or dropped to match any [LATCH_MAP][crate::input::latch], as r08 holds one input per latch.
+    pub fn export_r08(&self, options: &ReplayOptions) -> Vec<u8> {
+        self.export_replay(&R08, options).0
+    }
+    
+    /// Same as [`Self::export_r08`], along with what couldn't be represented in r08 and what was assumed.
+    pub fn export_r08_with_report(&self, options: &ReplayOptions) -> (Vec<u8>, ConversionReport) {
+        self.export_replay(&R08, options)
+    }
+    
+    /// Exports the inputs of ports 1 and 2 in the r16m format used by SNES replay devices.
+    /// 
+    /// Inputs are repeated or dropped to match any [LATCH_MAP][crate::input::latch], as r16m holds one input per latch.
+    /// Bits 12 to 15 of each controller are past the end of the SNES controller's buttons, and are filled in according
+    /// to the [OverreadStrategy].
+    pub fn export_r16m(&self, options: &ReplayOptions) -> Vec<u8> {
+        self.export_replay(&R16M, options).0
+    }
+    
+    /// Same as [`Self::export_r16m`], along with what couldn't be represented in r16m and what was assumed.
+    pub fn export_r16m_with_report(&self, options: &ReplayOptions) -> (Vec<u8>, ConversionReport) {
+        self.export_replay(&R16M, options)
+    }
+    
+    fn export_replay(&self, layout: &ReplayLayout, options: &ReplayOptions) -> (Vec<u8>, ConversionReport) {
+        let mut report = self.replay_report(layout, options);
+        
+        // gathering, detecting, and converting each port's inputs is independent of the other ports, and takes most of
+        // the time for long movies
+        let streams: Vec<PortStream> = std::thread::scope(|scope| {
+            let handles: Vec<_> = layout.ports.iter()
+                .map(|(port, offset)| scope.spawn(move || self.replay_stream(*port, *offset, layout, options)))
+                .collect();
+            handles.into_iter().filter_map(|handle| handle.join().unwrap()).collect()
+        });
+        
+        let frames = streams.iter().map(|stream| stream.inputs.len() / stream.size).max().unwrap_or(0);
+        let mut data = vec![0u8; frames * layout.frame_bytes];
+        for stream in &streams {
+            if let Some(detected) = stream.detected {
+                report.add_assumption(Assumption::InputFormat { port: stream.port, detected });
+            }
+            
+            let buttons = controller_buttons(stream.kind).map(|buttons| buttons.len()).unwrap_or(stream.size * 8);
+            let high = match options.overread {
+                OverreadStrategy::FromFile => self.overread(stream.port).unwrap_or_else(|| {
+                    if buttons < layout.controller_bytes * 8 {
+                        report.add_assumption(Assumption::Overread { port: stream.port, high: true });
+                    }
+                    true
+                }),
+                OverreadStrategy::High => true,
+                OverreadStrategy::Low => false,
+            };
+            
+            // each controller byte becomes `(byte & keep) | set`, which fills in the bits past the end of the buttons
+            let mut keep = vec![0xFFu8; layout.controller_bytes];
+            let mut set = vec![0x00u8; layout.controller_bytes];
+            for bit in buttons..(layout.controller_bytes * 8) {
+                let mask = match layout.format.bit_order {
+                    BitOrder::MsbFirst => 0x80 >> (bit % 8),
+                    BitOrder::LsbFirst => 0x01 << (bit % 8),
+                };
+                keep[bit / 8] &= !mask;
+                if high {
+                    set[bit / 8] |= mask;
+                }
+            }
+            
+            let len = stream.size.min(layout.controller_bytes);
+            for (frame, input) in data.chunks_exact_mut(layout.frame_bytes).zip(stream.inputs.chunks_exact(stream.size)) {
+                let controller = &mut frame[stream.offset..(stream.offset + layout.controller_bytes)];
+                controller[..len].copy_from_slice(&input[..len]);
+                for ((byte, keep), set) in controller.iter_mut().zip(&keep).zip(&set) {
+                    *byte = (*byte & keep) | set;
+                }
+            }
+        }
+        
+        (data, report)
+    }
+    
+    /// Input data of a port converted for the layout, or `None` if the port's controller isn't known.
+    fn replay_stream(&self, port: u8, offset: usize, layout: &ReplayLayout, options: &ReplayOptions) -> Option<PortStream> {
+        let kind = self.controller_kind(port)?;
+        let size = controller_input_size(kind).filter(|size| *size > 0)?;
+        let mut inputs = match options.apply_blank_frames {
+            true => self.aligned_port_inputs(port),
+            false => self.port_inputs(port),
+        };
+        let detected = detect_input_format(&inputs, kind).filter(|detected| *detected != InputFormat::CANONICAL);
+        InputFormat::CANONICAL.convert(&mut inputs, layout.format);
+        if self.first::<LatchMap>().is_some() {
+            let first = if options.apply_blank_frames { 0 } else { self.blank_frames() };
+            inputs = self.expand_latches_from(&inputs, size, first);
+        }
+        
+        Some(PortStream { port, offset, kind, size, inputs, detected })
+    }
+    
+    /// Packets which the layout can't hold.
+    fn replay_report(&self, layout: &ReplayLayout, options: &ReplayOptions) -> ConversionReport {
+        let mut report = ConversionReport::default();
+        let included = |port: u8| layout.ports.iter().any(|(included, _)| *included == port);
+        for packet in &self.packets {
+            let port = match packet {
+                Packet::InputChunk(chunk) => Some(chunk.port),
+                Packet::InputDelta(delta) => Some(delta.port),
+                Packet::PortController(controller) => Some(controller.port),
+                Packet::PortOverread(overread) => Some(overread.port),
+                Packet::BlankFrames(_) if options.apply_blank_frames => continue,
+                Packet::LatchMap(_) => continue,
+                _ => None,
+            };
+            let reason = match port {
+                None => format!("{} only holds controller input", layout.name),
+                Some(port) if !included(port) => format!("port {port} isn't part of {}", layout.name),
+                Some(port) => match self.bytes_per_frame(port) {
+                    None | Some(0) => format!("the input size of port {port}'s controller is unknown"),
+                    Some(size) if size > layout.controller_bytes => format!("port {port}'s controller is wider than {}", layout.name),
+                    Some(_) => continue,
+                },
+            };
+            report.add_lossy(packet.kind(), reason);
+        }
+        
+        report
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::export::replay::{OverreadStrategy, ReplayOptions};
+    use crate::export::report::{Assumption, LossyPackets};
+    use crate::spec::packets::{BlankFrames, GameTitle, InputChunk, PacketKind, PortController, PortOverread};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn replay() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x7F, 0xFE] }.into());
+        assert_eq!(file.export_r08(&ReplayOptions::default()), vec![0x01, 0x00, 0x80, 0x00]);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 2, kind: 0x0201 }.into());
+        file.packets.push(PortOverread { port: 2, overread: false }.into());
+        // B and R held
+        file.packets.push(InputChunk { port: 2, inputs: vec![0x7F, 0xE0] }.into());
+        
+        let r16m = file.export_r16m(&ReplayOptions::default());
+        assert_eq!(r16m.len(), 16);
+        assert_eq!(r16m[8..10], [0x80, 0x10]);
+        let r16m = file.export_r16m(&ReplayOptions { overread: OverreadStrategy::High, ..Default::default() });
+        assert_eq!(r16m[8..10], [0x80, 0x1F]);
+        
+        file.packets.push(BlankFrames { frames: 1 }.into());
+        let r16m = file.export_r16m(&ReplayOptions::default());
+        assert_eq!(r16m.len(), 32);
+        assert_eq!(r16m[8..10], [0x00, 0x00]);
+        assert_eq!(r16m[24..26], [0x80, 0x10]);
+    }
+    
+    #[test]
+    fn latch_map() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x7F, 0xFE, 0xFF] }.into());
+        file.record_latches([2, 0]);
+        let (r08, report) = file.export_r08_with_report(&ReplayOptions::default());
+        assert_eq!(r08, vec![0x01, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        assert!(report.lossy.is_empty());
+    }
+    
+    #[test]
+    fn report() {
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+        file.packets.push(PortController { port: 3, kind: 0x0201 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF, 0xFF] }.into());
+        file.packets.push(InputChunk { port: 3, inputs: vec![0xFF, 0xFF] }.into());
+        
+        let (_, report) = file.export_r16m_with_report(&ReplayOptions::default());
+        assert_eq!(report.lossy, [
+            LossyPackets { kind: PacketKind::GameTitle, count: 1, reason: "r16m only holds controller input".into() },
+            LossyPackets { kind: PacketKind::PortController, count: 1, reason: "port 3 isn't part of r16m".into() },
+            LossyPackets { kind: PacketKind::InputChunk, count: 1, reason: "port 3 isn't part of r16m".into() },
+        ]);
+        assert_eq!(report.assumptions, [Assumption::Overread { port: 1, high: true }]);
+        assert_eq!(report.to_string().lines().last(), Some("assumed port 1 overreads high"));
+        
+        // the SNES controller doesn't fit in r08
+        let (_, report) = file.export_r08_with_report(&ReplayOptions::default());
+        assert!(report.lossy.iter().any(|lossy| lossy.reason == "port 1's controller is wider than r08"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,107 @@
+//! What was lost or assumed when converting between TASD and another format.
+//!
+//! Exporters and importers have `_with_report` variants returning a [ConversionReport] alongside their output, so
+//! callers can warn about conversions which weren't faithful instead of them happening silently.
+
+use std::fmt::{Display, Formatter};
+use crate::input::format::InputFormat;
+use crate::lookup::controller_type_lut;
+use crate::spec::packets::PacketKind;
+
+/// Packets of one kind which couldn't be represented in the target format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LossyPackets {
+    pub kind: PacketKind,
+    pub count: usize,
+    pub reason: String,
+}
+
+/// Something a conversion had to assume, because the source didn't say.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Assumption {
+    /// The file doesn't declare a console with a known frame rate, so this one was used to convert frames to times.
+    FrameRate(f64),
+    /// A port's input data was treated as being in the canonical format, although it looks like it's in `detected`.
+    InputFormat {
+        port: u8,
+        detected: InputFormat,
+    },
+    /// A port has no PORT_OVERREAD, so bits past the end of its controller's buttons were assumed to read `high`.
+    Overread {
+        port: u8,
+        high: bool,
+    },
+    /// A port has no PORT_CONTROLLER, so this controller type was declared for it.
+    Controller {
+        port: u8,
+        kind: u16,
+    },
+}
+impl Display for Assumption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Assumption::FrameRate(fps) => write!(f, "assumed a frame rate of {fps} fps"),
+            Assumption::InputFormat { port, detected } => write!(f, "assumed port {port} is in the canonical input format, although it looks like {:?}/{:?}", detected.polarity, detected.bit_order),
+            Assumption::Overread { port, high } => write!(f, "assumed port {port} overreads {}", if *high { "high" } else { "low" }),
+            Assumption::Controller { port, kind } => match controller_type_lut(*kind) {
+                Some(name) => write!(f, "assumed port {port} has a {name}"),
+                None => write!(f, "assumed port {port} has controller type {kind:#06X}"),
+            },
+        }
+    }
+}
+
+/// Fidelity of a conversion: what was dropped, what couldn't be represented, and what was assumed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConversionReport {
+    /// Fields of the source format with no TASD equivalent, which were dropped when importing.
+    pub dropped: Vec<String>,
+    /// TASD packets which couldn't be represented in the target format when exporting.
+    pub lossy: Vec<LossyPackets>,
+    pub assumptions: Vec<Assumption>,
+}
+impl ConversionReport {
+    /// Whether nothing was dropped or lost. Assumptions don't count, as they may well have been right.
+    pub fn is_lossless(&self) -> bool {
+        self.dropped.is_empty() && self.lossy.is_empty()
+    }
+    
+    pub fn add_dropped<S: Into<String>>(&mut self, field: S) {
+        let field = field.into();
+        if !self.dropped.contains(&field) {
+            self.dropped.push(field);
+        }
+    }
+    
+    /// Records a packet which couldn't be represented, counted together with others of its kind lost for the same
+    /// reason.
+    pub fn add_lossy<S: Into<String>>(&mut self, kind: PacketKind, reason: S) {
+        let reason = reason.into();
+        match self.lossy.iter_mut().find(|lossy| lossy.kind == kind && lossy.reason == reason) {
+            Some(lossy) => lossy.count += 1,
+            None => self.lossy.push(LossyPackets { kind, count: 1, reason }),
+        }
+    }
+    
+    pub fn add_assumption(&mut self, assumption: Assumption) {
+        if !self.assumptions.contains(&assumption) {
+            self.assumptions.push(assumption);
+        }
+    }
+}
+impl Display for ConversionReport {
+    /// One line per dropped field, lossy packet kind, and assumption.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for field in &self.dropped {
+            writeln!(f, "dropped {field}: no TASD equivalent")?;
+        }
+        for lossy in &self.lossy {
+            writeln!(f, "lost {} {} packet(s): {}", lossy.count, lossy.kind, lossy.reason)?;
+        }
+        for assumption in &self.assumptions {
+            writeln!(f, "{assumption}")?;
+        }
+        
+        Ok(())
+    }
+}
\ No newline at end of file
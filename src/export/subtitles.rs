@@ -0,0 +1,269 @@
+use std::fmt::Write;
+use std::time::Duration;
+use crate::export::report::{Assumption, ConversionReport};
+use crate::spec::packets::{Attribution, Category, GameTitle, Index, Packet, Rerecords, Transition};
+use crate::spec::TasdFile;
+use crate::timing::{DEFAULT_FRAME_RATE, frame_to_duration};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleOptions {
+    /// How long the movie's metadata (title, authors, category, and rerecords) is shown at the start.
+    pub metadata_duration: Duration,
+    /// How long each frame-anchored comment is shown.
+    pub comment_duration: Duration,
+    /// Whether comment times are shifted by BLANK_FRAMES. Comments on frames removed by a negative value are dropped.
+    pub apply_blank_frames: bool,
+    /// Whether comment times are relative to the [first visible frame][TasdFile::first_visible_frame], to line up
+    /// with encodes which cut the frames before it. Comments on earlier frames are dropped.
+    pub sync_to_first_visible_frame: bool,
+}
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            metadata_duration: Duration::from_secs(5),
+            comment_duration: Duration::from_secs(3),
+            apply_blank_frames: true,
+            sync_to_first_visible_frame: true,
+        }
+    }
+}
+
+/// A single line (or block of lines) of subtitle text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitle {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+impl TasdFile {
+    /// Subtitles for this movie, ordered by start time.
+    /// 
+    /// The first subtitle contains the movie's metadata, if it has any. Comments are taken from TRANSITION (frame
+    /// indexed) and MOVIE_TRANSITION packets which contain a COMMENT packet. Frames are converted to timestamps using
+    /// the file's [frame rate][TasdFile::frame_rate], counting from the first visible frame if the file has an AV_SYNC
+    /// packet (see [crate::sync]).
+    pub fn subtitles(&self, options: &SubtitleOptions) -> Vec<Subtitle> {
+        self.subtitles_with_report(options).0
+    }
+    
+    /// Same as [`Self::subtitles`], along with the comments which couldn't be placed and any assumed frame rate.
+    pub fn subtitles_with_report(&self, options: &SubtitleOptions) -> (Vec<Subtitle>, ConversionReport) {
+        let mut report = ConversionReport::default();
+        let fps = self.frame_rate().unwrap_or_else(|| {
+            report.add_assumption(Assumption::FrameRate(DEFAULT_FRAME_RATE));
+            DEFAULT_FRAME_RATE
+        });
+        // the first visible frame is numbered after BLANK_FRAMES is applied
+        let first_visible = match (options.sync_to_first_visible_frame, options.apply_blank_frames) {
+            (false, _) => 0,
+            (true, true) => self.first_visible_frame(),
+            (true, false) => self.first_visible_frame().saturating_add_signed(-self.blank_frames()),
+        };
+        let mut subtitles = vec![];
+        
+        let mut metadata = vec![];
+        if let Some(title) = self.first::<GameTitle>() {
+            metadata.push(title.title.clone());
+        }
+        let authors: Vec<_> = self.all::<Attribution>()
+            .filter(|attribution| attribution.kind == 0x01)
+            .map(|attribution| attribution.name.as_str())
+            .collect();
+        if !authors.is_empty() {
+            metadata.push(format!("by {}", authors.join(", ")));
+        }
+        if let Some(category) = self.first::<Category>() {
+            metadata.push(category.category.clone());
+        }
+        if let Some(rerecords) = self.first::<Rerecords>() {
+            metadata.push(format!("{} rerecords", rerecords.rerecords));
+        }
+        if !metadata.is_empty() {
+            subtitles.push(Subtitle {
+                start: Duration::ZERO,
+                end: options.metadata_duration,
+                text: metadata.join("\n"),
+            });
+        }
+        
+        let mut comments = vec![];
+        for packet in &self.packets {
+            let (frame, inner) = match packet {
+                Packet::Transition(Transition { index: Index::Frame(frame), kind, .. }) => (*frame, kind.packet()),
+                Packet::MovieTransition(transition) => (transition.movie_frame as u64, transition.kind.packet()),
+                Packet::Transition(transition) if matches!(transition.kind.packet(), Some(Packet::Comment(_))) => {
+                    report.add_lossy(packet.kind(), "comments are only placed at frame indexed transitions");
+                    continue;
+                },
+                Packet::Comment(_) => {
+                    report.add_lossy(packet.kind(), "comments outside of a transition have no time");
+                    continue;
+                },
+                _ => continue,
+            };
+            let frame = match options.apply_blank_frames {
+                true => self.aligned_frame(frame),
+                false => Some(frame),
+            };
+            match (frame, inner) {
+                (Some(frame), Some(Packet::Comment(_))) if frame < first_visible => report.add_lossy(packet.kind(), "the comment's frame is before the first visible frame"),
+                (Some(frame), Some(Packet::Comment(comment))) => {
+                    let start = frame_to_duration(frame - first_visible, fps);
+                    comments.push(Subtitle {
+                        start,
+                        end: start + options.comment_duration,
+                        text: comment.comment.clone(),
+                    });
+                },
+                (None, Some(Packet::Comment(_))) => report.add_lossy(packet.kind(), "the comment's frame was removed by BLANK_FRAMES"),
+                _ => (),
+            }
+        }
+        comments.sort_by_key(|subtitle| subtitle.start);
+        subtitles.extend(comments);
+        
+        (subtitles, report)
+    }
+    
+    /// Exports [`Self::subtitles`] in the SubRip (`.srt`) format.
+    /// 
+    /// Blank lines are removed from the text, as they would end the subtitle early.
+    pub fn export_srt(&self, options: &SubtitleOptions) -> String {
+        self.export_srt_with_report(options).0
+    }
+    
+    /// Same as [`Self::export_srt`], along with the report of [`Self::subtitles_with_report`].
+    pub fn export_srt_with_report(&self, options: &SubtitleOptions) -> (String, ConversionReport) {
+        let (subtitles, report) = self.subtitles_with_report(options);
+        let mut srt = String::new();
+        for (i, subtitle) in subtitles.iter().enumerate() {
+            let _ = write!(srt, "{}\n{} --> {}\n{}\n\n", i + 1, srt_time(subtitle.start), srt_time(subtitle.end), srt_text(&subtitle.text));
+        }
+        
+        (srt, report)
+    }
+    
+    /// Exports [`Self::subtitles`] in the Advanced SubStation Alpha (`.ass`) format.
+    /// 
+    /// Braces and backslashes in the text are escaped, so they're shown as written instead of starting override tags.
+    pub fn export_ass(&self, options: &SubtitleOptions) -> String {
+        self.export_ass_with_report(options).0
+    }
+    
+    /// Same as [`Self::export_ass`], along with the report of [`Self::subtitles_with_report`].
+    pub fn export_ass_with_report(&self, options: &SubtitleOptions) -> (String, ConversionReport) {
+        let (subtitles, report) = self.subtitles_with_report(options);
+        let mut ass = String::from(concat!(
+            "[Script Info]\n",
+            "ScriptType: v4.00+\n",
+            "\n",
+            "[V4+ Styles]\n",
+            "Format: Name, Fontname, Fontsize, PrimaryColour, OutlineColour, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV\n",
+            "Style: Default,Arial,20,&H00FFFFFF,&H00000000,1,2,0,2,10,10,10\n",
+            "\n",
+            "[Events]\n",
+            "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        ));
+        for subtitle in subtitles {
+            let _ = writeln!(ass, "Dialogue: 0,{},{},Default,,0,0,0,,{}", ass_time(subtitle.start), ass_time(subtitle.end), ass_text(&subtitle.text));
+        }
+        
+        (ass, report)
+    }
+}
+
+/// Text of an SRT subtitle, with `\r\n` and `\r` line endings replaced by `\n`, and blank lines removed.
+fn srt_text(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of an ASS subtitle, with line endings replaced by `\N`, and braces escaped. A word joiner is inserted after
+/// each backslash, so it can't combine with the next character into an override tag such as `\N`.
+fn ass_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.replace("\r\n", "\n").chars() {
+        match c {
+            '\r' | '\n' => escaped.push_str("\\N"),
+            '{' => escaped.push_str("\\{"),
+            '}' => escaped.push_str("\\}"),
+            '\\' => escaped.push_str("\\\u{2060}"),
+            c => escaped.push(c),
+        }
+    }
+    
+    escaped
+}
+
+/// Formats a timestamp as `HH:MM:SS,mmm`.
+fn srt_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!("{:02}:{:02}:{:02},{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+/// Formats a timestamp as `H:MM:SS.cc`.
+fn ass_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{}:{:02}:{:02}.{:02}", centis / 360_000, centis / 6000 % 60, centis / 100 % 60, centis % 100)
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::export::report::{Assumption, LossyPackets};
+    use crate::export::subtitles::SubtitleOptions;
+    use crate::spec::packets::{Attribution, AvSync, Comment, MovieTransition, PacketKind, Rerecords, TransitionKind};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn srt_and_ass() {
+        let mut file = TasdFile::default();
+        file.packets.push(Attribution { kind: 0x01, name: "alice".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "bob".into() }.into());
+        file.packets.push(Attribution { kind: 0x02, name: "verifier".into() }.into());
+        file.packets.push(Rerecords { rerecords: 1234 }.into());
+        file.packets.push(MovieTransition { movie_frame: 3900, kind: TransitionKind::PacketDerived(Box::new(Comment { comment: "wrong warp".into() }.into())) }.into());
+        
+        let options = SubtitleOptions::default();
+        assert_eq!(file.export_srt(&options), concat!(
+            "1\n00:00:00,000 --> 00:00:05,000\nby alice, bob\n1234 rerecords\n\n",
+            "2\n00:01:05,000 --> 00:01:08,000\nwrong warp\n\n",
+        ));
+        
+        let ass = file.export_ass(&options);
+        assert!(ass.contains("Dialogue: 0,0:00:00.00,0:00:05.00,Default,,0,0,0,,by alice, bob\\N1234 rerecords\n"));
+        assert!(ass.contains("Dialogue: 0,0:01:05.00,0:01:08.00,Default,,0,0,0,,wrong warp\n"));
+        
+        file.packets.push(Comment { comment: "no frame".into() }.into());
+        let (_, report) = file.export_srt_with_report(&options);
+        assert_eq!(report.assumptions, [Assumption::FrameRate(60.0)]);
+        assert_eq!(report.lossy, [LossyPackets { kind: PacketKind::Comment, count: 1, reason: "comments outside of a transition have no time".into() }]);
+        
+        // the encode starts a minute in
+        file.packets.push(AvSync { first_visible_frame: 3600, audio_offset: 0 }.into());
+        file.packets.push(MovieTransition { movie_frame: 60, kind: TransitionKind::PacketDerived(Box::new(Comment { comment: "cut".into() }.into())) }.into());
+        let (srt, report) = file.export_srt_with_report(&options);
+        assert!(srt.ends_with("2\n00:00:05,000 --> 00:00:08,000\nwrong warp\n\n"));
+        assert_eq!(report.lossy[1].reason, "the comment's frame is before the first visible frame");
+        let options = SubtitleOptions { sync_to_first_visible_frame: false, ..Default::default() };
+        assert_eq!(file.subtitles(&options).len(), 3);
+    }
+    
+    #[test]
+    fn escaping() {
+        let mut file = TasdFile::default();
+        let comment = "{\\b1}bold\r\n\r\nC:\\Names\rend";
+        file.packets.push(MovieTransition { movie_frame: 0, kind: TransitionKind::PacketDerived(Box::new(Comment { comment: comment.into() }.into())) }.into());
+        
+        let options = SubtitleOptions::default();
+        assert_eq!(file.export_srt(&options), "1\n00:00:00,000 --> 00:00:03,000\n{\\b1}bold\nC:\\Names\nend\n\n");
+        assert!(file.export_ass(&options).ends_with(",,\\{\\\u{2060}b1\\}bold\\N\\NC:\\\u{2060}Names\\Nend\n"));
+    }
+}
\ No newline at end of file
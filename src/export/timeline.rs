@@ -0,0 +1,205 @@
+use std::fmt::Write;
+use crate::export::report::{Assumption, ConversionReport};
+use crate::input::format::{InputFormat, detect_input_format};
+use crate::lookup::controller_buttons;
+use crate::spec::TasdFile;
+use crate::timing::{DEFAULT_FRAME_RATE, frame_to_duration};
+
+/// Period during which a button was held.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PressInterval {
+    pub start_frame: u64,
+    /// First frame on which the button was no longer held.
+    pub end_frame: u64,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ButtonTimeline {
+    pub name: &'static str,
+    pub intervals: Vec<PressInterval>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineOptions {
+    /// Whether BLANK_FRAMES is applied to the input data first.
+    pub apply_blank_frames: bool,
+    /// Whether press and release times are relative to the [first visible frame][TasdFile::first_visible_frame], to
+    /// line up with encodes which cut the frames before it. Times of earlier frames are clamped to zero.
+    pub sync_to_first_visible_frame: bool,
+}
+impl Default for TimelineOptions {
+    fn default() -> Self {
+        Self {
+            apply_blank_frames: true,
+            sync_to_first_visible_frame: true,
+        }
+    }
+}
+
+/// Press and release times of every button on a port, for driving input display overlays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputTimeline {
+    pub port: u8,
+    pub controller: u16,
+    pub frame_rate: f64,
+    pub frames: u64,
+    pub buttons: Vec<ButtonTimeline>,
+}
+impl InputTimeline {
+    /// Serializes this timeline as a JSON object.
+    /// 
+    /// ```json
+    /// {"port":1,"controller":257,"frame_rate":60,"frames":3,"buttons":[{"name":"A","intervals":[{"start_frame":0,"end_frame":2,"start_ms":0,"end_ms":33}]}]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(json, r#"{{"port":{},"controller":{},"frame_rate":{},"frames":{},"buttons":["#, self.port, self.controller, self.frame_rate, self.frames);
+        for (i, button) in self.buttons.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, r#"{{"name":"{}","intervals":["#, button.name);
+            for (j, interval) in button.intervals.iter().enumerate() {
+                if j > 0 {
+                    json.push(',');
+                }
+                let _ = write!(json, r#"{{"start_frame":{},"end_frame":{},"start_ms":{},"end_ms":{}}}"#, interval.start_frame, interval.end_frame, interval.start_ms, interval.end_ms);
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        
+        json
+    }
+}
+
+impl TasdFile {
+    /// Builds an [InputTimeline] from the INPUT_CHUNK data of a port.
+    /// 
+    /// Returns `None` if the port has no PORT_CONTROLLER, or its controller type doesn't have a known
+    /// [button layout][controller_buttons].
+    pub fn export_input_timeline(&self, port: u8, options: &TimelineOptions) -> Option<InputTimeline> {
+        self.export_input_timeline_with_report(port, options).map(|(timeline, _)| timeline)
+    }
+    
+    /// Same as [`Self::export_input_timeline`], along with the frame rate and input format it assumed, if any.
+    pub fn export_input_timeline_with_report(&self, port: u8, options: &TimelineOptions) -> Option<(InputTimeline, ConversionReport)> {
+        let controller = self.controller_kind(port)?;
+        let names = controller_buttons(controller)?;
+        let size = self.bytes_per_frame(port)?;
+        let mut report = ConversionReport::default();
+        let fps = self.frame_rate().unwrap_or_else(|| {
+            report.add_assumption(Assumption::FrameRate(DEFAULT_FRAME_RATE));
+            DEFAULT_FRAME_RATE
+        });
+        // the first visible frame is numbered after BLANK_FRAMES is applied
+        let first_visible = match (options.sync_to_first_visible_frame, options.apply_blank_frames) {
+            (false, _) => 0,
+            (true, true) => self.first_visible_frame(),
+            (true, false) => self.first_visible_frame().saturating_add_signed(-self.blank_frames()),
+        };
+        let ms = |frame: u64| frame_to_duration(frame.saturating_sub(first_visible), fps).as_millis() as u64;
+        
+        let inputs = match options.apply_blank_frames {
+            true => self.aligned_port_inputs(port),
+            false => self.port_inputs(port),
+        };
+        if let Some(detected) = detect_input_format(&inputs, controller).filter(|detected| *detected != InputFormat::CANONICAL) {
+            report.add_assumption(Assumption::InputFormat { port, detected });
+        }
+        let frames = (inputs.len() / size) as u64;
+        
+        let mut buttons: Vec<_> = names.iter().map(|name| ButtonTimeline { name, intervals: vec![] }).collect();
+        let mut held: Vec<Option<u64>> = vec![None; names.len()];
+        for frame in 0..=frames {
+            let data = inputs.get((frame as usize * size)..((frame as usize + 1) * size));
+            for (bit, start) in held.iter_mut().enumerate() {
+                let pressed = data.map(|data| data[bit / 8] & (0x80 >> (bit % 8)) == 0).unwrap_or(false);
+                match (pressed, *start) {
+                    (true, None) => *start = Some(frame),
+                    (false, Some(start_frame)) => {
+                        buttons[bit].intervals.push(PressInterval {
+                            start_frame,
+                            end_frame: frame,
+                            start_ms: ms(start_frame),
+                            end_ms: ms(frame),
+                        });
+                        *start = None;
+                    },
+                    _ => (),
+                }
+            }
+        }
+        
+        Some((InputTimeline {
+            port,
+            controller,
+            frame_rate: fps,
+            frames,
+            buttons,
+        }, report))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::export::report::Assumption;
+    use crate::export::timeline::TimelineOptions;
+    use crate::input::format::{BitOrder, InputFormat, Polarity};
+    use crate::spec::packets::{BlankFrames, ConsoleType, InputChunk, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn timeline() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        // A held for two frames, Right pressed on the last frame
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x7F, 0x7F, 0xFE] }.into());
+        
+        let timeline = file.export_input_timeline(1, &TimelineOptions::default()).unwrap();
+        assert_eq!(timeline.frames, 3);
+        assert_eq!(timeline.buttons[0].intervals.len(), 1);
+        assert_eq!(timeline.buttons[0].intervals[0].end_frame, 2);
+        assert_eq!(timeline.buttons[0].intervals[0].end_ms, 33);
+        assert_eq!(timeline.buttons[7].intervals[0].start_frame, 2);
+        assert_eq!(timeline.buttons[7].intervals[0].end_frame, 3);
+        assert!(timeline.buttons[1..7].iter().all(|button| button.intervals.is_empty()));
+        
+        let json = timeline.to_json();
+        assert!(json.starts_with(r#"{"port":1,"controller":257,"frame_rate":60,"frames":3,"buttons":[{"name":"A","intervals":[{"start_frame":0,"end_frame":2,"start_ms":0,"end_ms":33}]},"#));
+        
+        assert!(file.export_input_timeline(2, &TimelineOptions::default()).is_none());
+        
+        file.packets.push(BlankFrames { frames: 1 }.into());
+        let timeline = file.export_input_timeline(1, &TimelineOptions::default()).unwrap();
+        assert_eq!(timeline.frames, 4);
+        assert_eq!(timeline.buttons[0].intervals[0].start_frame, 1);
+        let timeline = file.export_input_timeline(1, &TimelineOptions { apply_blank_frames: false, ..Default::default() }).unwrap();
+        assert_eq!(timeline.frames, 3);
+        
+        let (_, report) = file.export_input_timeline_with_report(1, &TimelineOptions::default()).unwrap();
+        assert_eq!(report.assumptions, [Assumption::FrameRate(60.0)]);
+        
+        // mostly pressed, so it looks active-high
+        file.packets.insert(0, ConsoleType { kind: 0x01, custom: None }.into());
+        file.set_port_inputs(1, vec![0x80, 0x80, 0x00]);
+        let (_, report) = file.export_input_timeline_with_report(1, &TimelineOptions::default()).unwrap();
+        let detected = InputFormat { polarity: Polarity::ActiveHigh, bit_order: BitOrder::MsbFirst };
+        assert_eq!(report.assumptions, [Assumption::InputFormat { port: 1, detected }]);
+        assert!(report.is_lossless());
+        
+        file.set_av_sync(2, 0);
+        let timeline = file.export_input_timeline(1, &TimelineOptions::default()).unwrap();
+        assert_eq!(timeline.buttons[0].intervals[0].start_frame, 3);
+        assert_eq!(timeline.buttons[0].intervals[0].start_ms, 16);
+        // B is held from before the first visible frame
+        assert_eq!(timeline.buttons[1].intervals[0].start_frame, 1);
+        assert_eq!((timeline.buttons[1].intervals[0].start_ms, timeline.buttons[1].intervals[0].end_ms), (0, 33));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,160 @@
+use std::time::Duration;
+use crate::spec::packets::{Encode, OpaqueKind, Packet, PacketChecksum, TotalFrames, Verified};
+use crate::spec::{ChecksumMismatch, LATEST_VERSION, TasdFile};
+use crate::stats::PacketStats;
+use crate::timing::frame_to_duration;
+use crate::validate::{Issue, Severity};
+
+/// Everything [`TasdFile::health`] checks, in one structured result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// Result of [`TasdFile::validate`].
+    pub issues: Vec<Issue>,
+    pub verification: VerificationSummary,
+    pub timing: TimingSummary,
+    pub size: SizeSummary,
+    /// Packets this crate doesn't know how to decode.
+    pub unknown: Vec<UnknownPacket>,
+}
+impl HealthReport {
+    /// Whether there are no validation errors and no corrupted packets. Warnings and unknown packets are allowed.
+    pub fn is_healthy(&self) -> bool {
+        !self.issues.iter().any(|issue| issue.severity == Severity::Error) && self.verification.corrupted.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationSummary {
+    /// Value of the VERIFIED packet, if there is one.
+    pub verified: Option<bool>,
+    /// Number of [PacketChecksum] packets.
+    pub checksums: usize,
+    pub corrupted: Vec<ChecksumMismatch>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingSummary {
+    pub frame_rate: Option<f64>,
+    /// Frame count from the TOTAL_FRAMES packet.
+    pub declared_frames: Option<u32>,
+    /// Frame count derived from the input data.
+    pub computed_frames: Option<u32>,
+    /// Length of the movie, based on the computed (or else declared) frame count and the frame rate.
+    pub duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeSummary {
+    pub encoded_bytes: usize,
+    pub packets: usize,
+    pub stats: Vec<PacketStats>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownPacket {
+    /// Index into [`TasdFile::packets`].
+    pub index: usize,
+    pub key: Vec<u8>,
+    /// Either [`OpaqueKind::UnknownKey`] or [`OpaqueKind::VendorExperimental`].
+    pub kind: OpaqueKind,
+    pub payload_bytes: usize,
+}
+
+impl TasdFile {
+    /// Runs every available check on this file, for showing as a single summary.
+    pub fn health(&self) -> HealthReport {
+        let declared_frames = self.first::<TotalFrames>().map(|total| total.frames);
+        let computed_frames = self.computed_total_frames();
+        let frame_rate = self.frame_rate();
+        
+        HealthReport {
+            issues: self.validate(),
+            verification: VerificationSummary {
+                verified: self.first::<Verified>().map(|verified| verified.verified),
+                checksums: self.all::<PacketChecksum>().count(),
+                corrupted: self.verify_checksums(),
+            },
+            timing: TimingSummary {
+                frame_rate,
+                declared_frames,
+                computed_frames,
+                duration: computed_frames.or(declared_frames)
+                    .zip(frame_rate)
+                    .map(|(frames, fps)| frame_to_duration(frames as u64, fps)),
+            },
+            size: SizeSummary {
+                encoded_bytes: self.estimated_encoded_size(),
+                packets: self.packets.len(),
+                stats: self.packet_stats(),
+            },
+            unknown: self.unknown_packets().collect(),
+        }
+    }
+    
+    /// Packets whose keys aren't part of the spec (or the 0xFF group for non-standard packets), most likely because the
+    /// file was written for a newer version of the spec, so tools can warn which data they can't interpret.
+    /// 
+    /// These packets are kept unchanged through edits and re-encoding, so no data is lost by opening and saving a newer
+    /// file.
+    pub fn newer_spec_packets(&self) -> Vec<UnknownPacket> {
+        self.unknown_packets().filter(|packet| packet.kind == OpaqueKind::UnknownKey).collect()
+    }
+    
+    /// Whether the file declares a newer spec version than this crate implements.
+    pub fn is_newer_version(&self) -> bool {
+        self.version > u16::from_be_bytes(LATEST_VERSION)
+    }
+    
+    fn unknown_packets(&self) -> impl Iterator<Item = UnknownPacket> + '_ {
+        self.packets.iter().enumerate().filter_map(|(index, packet)| match packet {
+            Packet::Unsupported(unsupported) => Some(UnknownPacket {
+                index,
+                key: unsupported.key(),
+                kind: unsupported.opaque_kind(),
+                payload_bytes: unsupported.payload.len(),
+            }),
+            _ => None,
+        })
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::spec::packets::{ConsoleType, InputChunk, OpaqueKind, Packet, PacketChecksum, PortController, TotalFrames, Unsupported};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn health() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x03, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0301 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 4 * 120] }.into());
+        file.packets.push(TotalFrames { frames: 100 }.into());
+        file.add_checksums();
+        file.packets.push(Unsupported { key: vec![0xAA, 0xBB], payload: vec![1, 2, 3] }.into());
+        
+        let health = file.health();
+        assert!(health.is_healthy());
+        // TOTAL_FRAMES mismatch, and the unknown key
+        assert_eq!(health.issues.len(), 2);
+        assert_eq!(health.verification.checksums, 4);
+        assert_eq!(health.timing.declared_frames, Some(100));
+        assert_eq!(health.timing.computed_frames, Some(120));
+        assert_eq!(health.timing.duration, Some(Duration::from_secs(2)));
+        assert_eq!(health.size.encoded_bytes, file.encode().len());
+        assert_eq!(health.unknown.len(), 1);
+        assert_eq!(health.unknown[0].index, 8);
+        assert_eq!(health.unknown[0].key, vec![0xAA, 0xBB]);
+        assert_eq!(health.unknown[0].kind, OpaqueKind::UnknownKey);
+        
+        file.packets[7] = Packet::from(PacketChecksum { crc32: 0 });
+        let health = file.health();
+        assert!(!health.is_healthy());
+        assert_eq!(health.verification.corrupted[0].index, 6);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,111 @@
+//! Recording the edit history of a file, for dumps which are edited by several people or tools.
+//!
+//! History is opt-in. Saving with [`TasdFile::save_with_history`] compares the file against the version already saved
+//! at its path, and if anything changed, appends an EDIT_HISTORY packet recording who saved it, when, and which kinds
+//! of packets changed. [`TasdFile::history`] reads the records back, oldest first.
+
+use std::io::ErrorKind;
+use crate::spec::packets::{DumpLastModified, EditHistory, Encode, KeyedPacket, Packet, PacketChecksum};
+use crate::spec::{TasdError, TasdFile};
+use crate::storage::TasdStorage;
+use crate::timestamp::now_epoch;
+
+/// Whether packets with this key only change as a side effect of saving, and so aren't recorded as changes.
+fn is_save_artifact(key: &[u8]) -> bool {
+    key == EditHistory::KEY || key == DumpLastModified::KEY || key == PacketChecksum::KEY
+}
+
+impl TasdFile {
+    /// Every EDIT_HISTORY record in the file, oldest first.
+    pub fn history(&self) -> Vec<&EditHistory> {
+        self.all::<EditHistory>().collect()
+    }
+    
+    /// Keys of the packet types whose packets differ between `original` and this file, in the order they first appear
+    /// (in this file, then in `original`). Moving packets of a type around counts as changing it.
+    /// 
+    /// DUMP_LAST_MODIFIED, PACKET_CHECKSUM, and EDIT_HISTORY aren't included, as they change whenever a file is saved.
+    pub fn changed_keys(&self, original: &TasdFile) -> Vec<Vec<u8>> {
+        let mut keys: Vec<Vec<u8>> = vec![];
+        for packet in self.packets.iter().chain(&original.packets) {
+            let key = packet.key();
+            if !keys.contains(&key) && !is_save_artifact(&key) {
+                keys.push(key);
+            }
+        }
+        
+        let packets = |file: &TasdFile, key: &[u8]| -> Vec<Packet> {
+            file.packets.iter().filter(|packet| packet.key() == key).cloned().collect()
+        };
+        keys.retain(|key| packets(self, key) != packets(original, key));
+        
+        keys
+    }
+    
+    /// Appends an EDIT_HISTORY packet by `author` if any packets changed since `original`.
+    /// 
+    /// Returns `true` if a record was added.
+    pub fn record_edit(&mut self, original: &TasdFile, author: &str) -> bool {
+        let changed = self.changed_keys(original);
+        if changed.is_empty() {
+            return false;
+        }
+        
+        self.packets.push(EditHistory { epoch: now_epoch(), author: author.into(), changed }.into());
+        true
+    }
+    
+    /// Saves the file to the provided [TasdStorage] like [`Self::save_with`], first [recording][Self::record_edit] what
+    /// changed compared to the file already saved at [`Self::path`]. If nothing is saved there yet, every packet counts as
+    /// changed.
+    /// 
+    /// Returns `true` if a record was added.
+    pub fn save_with_history<S: TasdStorage>(&mut self, storage: S, author: &str) -> Result<bool, TasdError> {
+        let path = self.path.clone().ok_or(TasdError::MissingPath)?;
+        let original = match storage.read(&path) {
+            Ok(data) => TasdFile::parse_slice(&data)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => TasdFile::default(),
+            Err(err) => return Err(err.into()),
+        };
+        
+        let recorded = self.record_edit(&original, author);
+        self.save_with(storage)?;
+        
+        Ok(recorded)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Comment, DumpLastModified, GameTitle, KeyedPacket};
+    use crate::spec::TasdFile;
+    use crate::storage::MemoryStorage;
+    
+    #[test]
+    fn history() {
+        let storage = MemoryStorage::new();
+        let mut file = TasdFile { path: Some("movie.tasd".into()), ..Default::default() };
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        assert!(file.save_with_history(&storage, "dumper").unwrap());
+        assert!(!file.save_with_history(&storage, "dumper").unwrap());
+        
+        file.packets.push(Comment { comment: "synced".into() }.into());
+        file.packets.push(DumpLastModified { epoch: 1 }.into());
+        assert!(file.save_with_history(&storage, "editor").unwrap());
+        
+        let saved = TasdFile::parse_file_with(&storage, "movie.tasd").unwrap();
+        let history = saved.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].author, "dumper");
+        assert_eq!(history[0].changed, [GameTitle::KEY]);
+        assert_eq!(history[1].author, "editor");
+        assert_eq!(history[1].changed, [Comment::KEY]);
+        
+        let decoded = TasdFile::parse_slice(&saved.encode()).unwrap();
+        assert_eq!(decoded.packets, saved.packets);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use crate::lookup::controller_input_size;
+use crate::spec::packets::{BlankFrames, InputChunk, KeyedPacket, Packet, TotalFrames, Transition};
+use crate::spec::TasdFile;
+
+pub mod controllers;
+pub mod delta;
+pub mod format;
+pub mod latch;
+pub mod resample;
+pub mod track;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RechunkError {
+    /// The byte budget can't fit a single aligned group of frames for this port. Contains the minimum budget needed.
+    BufferTooSmall {
+        port: u8,
+        required: usize,
+    },
+    /// The port has no PORT_CONTROLLER, or a controller whose input size isn't known, so its frames can't be located.
+    UnknownInputSize(u8),
+}
+
+/// Controller plugged into a port from a given frame onwards.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ControllerSegment {
+    /// First frame of the input data using this controller.
+    pub start_frame: u64,
+    pub kind: u16,
+    /// Number of bytes of input data per frame, if the controller type's input size is known.
+    pub bytes_per_frame: Option<usize>,
+    /// Offset into the port's input data of the first frame.
+    pub offset: usize,
+}
+
+/// INPUT_CHUNK whose input data isn't a whole number of frames for its port's controller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MisalignedChunk {
+    /// Index into [`TasdFile::packets`].
+    pub index: usize,
+    pub port: u8,
+    /// Number of bytes of input data in the chunk.
+    pub len: usize,
+    /// Number of bytes per frame of the controller plugged into the port where the chunk starts.
+    pub stride: usize,
+}
+
+impl TasdFile {
+    /// Controller type declared for the given port by the first matching PORT_CONTROLLER packet, if any.
+    pub fn controller_kind(&self, port: u8) -> Option<u16> {
+        controller_kind(&self.packets, port)
+    }
+    
+    /// Number of bytes of input data per frame for the given port, based on its declared controller type.
+    pub fn bytes_per_frame(&self, port: u8) -> Option<usize> {
+        self.controller_kind(port).and_then(controller_input_size)
+    }
+    
+    /// Controllers used by a port over the course of the movie, in frame order.
+    /// 
+    /// The first controller is the one declared by PORT_CONTROLLER. Controllers can then be swapped mid-movie (such as
+    /// when a multitap is plugged in) by a frame-indexed, packet-derived TRANSITION containing a new PORT_CONTROLLER for
+    /// the port. Since the input data of a swapped controller can only be located if the size of the previous one is
+    /// known, swaps after a controller of unknown size are left out.
+    /// 
+    /// Returns an empty list if the port has no PORT_CONTROLLER.
+    pub fn controller_segments(&self, port: u8) -> Vec<ControllerSegment> {
+        controller_segments(&self.packets, port, 0)
+    }
+    
+    /// INPUT_CHUNK packets whose length isn't a multiple of the bytes per frame of their port's controller, which would
+    /// shift every later frame of the port out of alignment.
+    /// 
+    /// Ports without a known controller input size are skipped.
+    pub fn misaligned_chunks(&self) -> Vec<MisalignedChunk> {
+        let mut segments = BTreeMap::new();
+        let mut offsets: BTreeMap<u8, usize> = BTreeMap::new();
+        let mut misaligned = vec![];
+        for (index, packet) in self.packets.iter().enumerate() {
+            let Packet::InputChunk(chunk) = packet else { continue };
+            let segments = segments.entry(chunk.port).or_insert_with(|| self.controller_segments(chunk.port));
+            let offset = offsets.entry(chunk.port).or_insert(0);
+            
+            let stride = segments.iter().rev()
+                .find(|segment| segment.offset <= *offset)
+                .and_then(|segment| segment.bytes_per_frame);
+            if let Some(stride) = stride.filter(|stride| *stride > 0 && chunk.inputs.len() % stride != 0) {
+                misaligned.push(MisalignedChunk { index, port: chunk.port, len: chunk.inputs.len(), stride });
+            }
+            *offset += chunk.inputs.len();
+        }
+        
+        misaligned
+    }
+    
+    /// Controller type plugged into a port on the given frame of the input data, taking controller swaps into account.
+    pub fn controller_at(&self, port: u8, frame: u64) -> Option<u16> {
+        self.controller_segments(port).iter().rev()
+            .find(|segment| segment.start_frame <= frame)
+            .map(|segment| segment.kind)
+    }
+    
+    /// Total number of bytes of INPUT_CHUNK (or [INPUT_DELTA][delta]) data for each port.
+    pub fn input_bytes(&self) -> BTreeMap<u8, usize> {
+        input_bytes(&self.packets)
+    }
+    
+    /// Number of frames of INPUT_CHUNK data for each port, taking [controller swaps][Self::controller_segments] into
+    /// account.
+    /// 
+    /// Ports without a known controller input size are omitted. Any trailing partial frame is not counted.
+    pub fn input_frames(&self) -> BTreeMap<u8, u64> {
+        input_frames(&self.packets, 0)
+    }
+    
+    /// Frame count derived from the INPUT_CHUNK data, taking the longest port.
+    /// 
+    /// Returns `None` if there are no input chunks for any port with a known controller input size.
+    pub fn computed_total_frames(&self) -> Option<u32> {
+        computed_total_frames(&self.packets, 0)
+    }
+    
+    /// Updates the TOTAL_FRAMES packet to match the INPUT_CHUNK data, inserting one if necessary.
+    /// 
+    /// If there's more than one TOTAL_FRAMES packet, only the first is kept. The new frame count is returned, or `None`
+    /// if it couldn't be determined (in which case the file is left unchanged).
+    pub fn recompute_total_frames(&mut self) -> Option<u32> {
+        let frames = self.computed_total_frames()?;
+        
+        let mut found = false;
+        self.packets.retain_mut(|packet| match packet {
+            Packet::TotalFrames(total) => {
+                total.frames = frames;
+                !std::mem::replace(&mut found, true)
+            },
+            _ => true,
+        });
+        
+        if !found {
+            let index = self.packets.iter()
+                .position(|packet| matches!(packet, Packet::InputChunk(_)))
+                .unwrap_or(self.packets.len());
+            self.packets.insert(index, TotalFrames { frames }.into());
+        }
+        
+        Some(frames)
+    }
+    
+    /// Value of the BLANK_FRAMES packet, or `0` if there isn't one.
+    pub fn blank_frames(&self) -> i64 {
+        self.first::<BlankFrames>().map(|blank| blank.frames as i64).unwrap_or(0)
+    }
+    
+    /// Converts a frame of the input data into a frame of playback, by applying BLANK_FRAMES.
+    /// 
+    /// Returns `None` for frames which are removed by a negative BLANK_FRAMES.
+    pub fn aligned_frame(&self, frame: u64) -> Option<u64> {
+        align_frame(frame, self.blank_frames())
+    }
+    
+    /// All INPUT_CHUNK data for a port, with BLANK_FRAMES applied.
+    /// 
+    /// A positive BLANK_FRAMES prepends that many frames where nothing is pressed, and a negative one removes that many
+    /// frames from the start. If the port's input size isn't known, the data is returned unchanged.
+    pub fn aligned_port_inputs(&self, port: u8) -> Vec<u8> {
+        let mut inputs = self.port_inputs(port);
+        let Some(size) = self.bytes_per_frame(port) else { return inputs };
+        
+        let blank = self.blank_frames();
+        let bytes = blank.unsigned_abs() as usize * size;
+        if blank >= 0 {
+            inputs.splice(0..0, std::iter::repeat_n(0xFF, bytes));
+        } else {
+            inputs.drain(..bytes.min(inputs.len()));
+        }
+        
+        inputs
+    }
+    
+    /// Splits INPUT_CHUNK packets so that each fits in a replay device's input buffer.
+    /// 
+    /// Every resulting packet, once encoded with this file's keylen, is at most `max_bytes` long. The input data of each
+    /// chunk is a whole number of frames, and a multiple of `alignment` bytes (use `1` for no additional alignment).
+    /// Consecutive chunks for the same port are joined before splitting, so existing undersized chunks are filled up.
+    /// Chunks are also split where a [controller swap][Self::controller_segments] changes the port's frame size.
+    /// 
+    /// Every port with input data needs controllers of known input size, otherwise
+    /// [UnknownInputSize][RechunkError::UnknownInputSize] is returned.
+    /// 
+    /// Returns the number of INPUT_CHUNK packets in the file afterwards.
+    pub fn rechunk_inputs_for_device(&mut self, max_bytes: usize, alignment: usize) -> Result<usize, RechunkError> {
+        // chunk sizes are checked for every controller segment first, so the file is left unchanged if any don't fit
+        // (offset into the port's input data, chunk capacity) of each segment, by port
+        let mut capacities: BTreeMap<u8, Vec<(usize, usize)>> = BTreeMap::new();
+        for port in self.all::<InputChunk>().map(|chunk| chunk.port) {
+            if capacities.contains_key(&port) {
+                continue;
+            }
+            let segments = self.controller_segments(port);
+            if segments.is_empty() {
+                return Err(RechunkError::UnknownInputSize(port));
+            }
+            
+            let mut port_capacities = vec![];
+            for segment in segments {
+                let stride = segment.bytes_per_frame.ok_or(RechunkError::UnknownInputSize(port))?;
+                let unit = lcm(stride.max(1), alignment.max(1));
+                let mut capacity = max_bytes.saturating_sub(self.keylen as usize + 2) / unit * unit;
+                while capacity > 0 && encoded_chunk_len(self.keylen, capacity) > max_bytes {
+                    capacity = (capacity - 1) / unit * unit;
+                }
+                if capacity == 0 {
+                    return Err(RechunkError::BufferTooSmall { port, required: encoded_chunk_len(self.keylen, unit) });
+                }
+                port_capacities.push((segment.offset, capacity));
+            }
+            capacities.insert(port, port_capacities);
+        }
+        
+        let mut packets = Vec::with_capacity(self.packets.len());
+        let mut pending: Option<InputChunk> = None;
+        for packet in std::mem::take(&mut self.packets) {
+            match (packet, &mut pending) {
+                (Packet::InputChunk(chunk), Some(run)) if run.port == chunk.port => run.inputs.extend_from_slice(&chunk.inputs),
+                (Packet::InputChunk(chunk), _) => {
+                    if let Some(run) = pending.replace(chunk) {
+                        packets.push(run.into());
+                    }
+                },
+                (packet, _) => {
+                    if let Some(run) = pending.take() {
+                        packets.push(run.into());
+                    }
+                    packets.push(packet);
+                },
+            }
+        }
+        if let Some(run) = pending {
+            packets.push(run.into());
+        }
+        
+        let mut result = Vec::with_capacity(packets.len());
+        let mut offsets: BTreeMap<u8, usize> = BTreeMap::new();
+        for packet in packets {
+            let Packet::InputChunk(run) = packet else {
+                result.push(packet);
+                continue;
+            };
+            
+            let segments = &capacities[&run.port];
+            let offset = offsets.entry(run.port).or_insert(0);
+            let mut inputs = &run.inputs[..];
+            while !inputs.is_empty() {
+                let current = segments.iter().rposition(|(start, _)| *start <= *offset).unwrap_or(0);
+                let mut len = segments[current].1.min(inputs.len());
+                if let Some((next, _)) = segments.get(current + 1) {
+                    len = len.min(next - *offset);
+                }
+                
+                let (chunk, rest) = inputs.split_at(len);
+                result.push(InputChunk { port: run.port, inputs: chunk.to_vec() }.into());
+                inputs = rest;
+                *offset += len;
+            }
+        }
+        self.packets = result;
+        
+        Ok(self.packets.iter().filter(|packet| matches!(packet, Packet::InputChunk(_))).count())
+    }
+}
+
+/// Encoded size of an INPUT_CHUNK packet containing `inputs` bytes of input data.
+fn encoded_chunk_len(keylen: u8, inputs: usize) -> usize {
+    let payload = inputs + 1;
+    let exp = (usize::BITS - payload.leading_zeros()).div_ceil(8) as usize;
+    
+    keylen as usize + 1 + exp + payload
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    
+    a / x * b
+}
+/// Applies a BLANK_FRAMES value to a frame of the input data. Returns `None` if the frame is removed.
+pub(crate) fn align_frame(frame: u64, blank_frames: i64) -> Option<u64> {
+    frame.checked_add_signed(blank_frames)
+}
+
+/// Range of the input data holding `frame` (of the input data, without BLANK_FRAMES applied), given the port's
+/// [controller segments][TasdFile::controller_segments].
+pub(crate) fn frame_range(segments: &[ControllerSegment], frame: u64) -> Option<Range<usize>> {
+    let segment = segments.iter().rev().find(|segment| segment.start_frame <= frame)?;
+    let size = segment.bytes_per_frame?;
+    let start = segment.offset + (frame - segment.start_frame) as usize * size;
+    
+    Some(start..(start + size))
+}
+
+/// Number of whole frames in `bytes` of input data, given the port's [controller segments][TasdFile::controller_segments].
+pub(crate) fn frame_count(segments: &[ControllerSegment], bytes: usize) -> Option<u64> {
+    let segment = segments.iter().rev().find(|segment| segment.offset <= bytes)?;
+    let size = segment.bytes_per_frame.filter(|size| *size > 0)?;
+    
+    Some(segment.start_frame + ((bytes - segment.offset) / size) as u64)
+}
+
+/// Same as [`TasdFile::controller_kind`], for a slice of a file's packets.
+pub(crate) fn controller_kind(packets: &[Packet], port: u8) -> Option<u16> {
+    packets.iter().find_map(|packet| match packet {
+        Packet::PortController(controller) if controller.port == port => Some(controller.kind),
+        _ => None,
+    })
+}
+
+/// Same as [`TasdFile::controller_segments`], for a slice of a file's packets whose frames are counted from `start`,
+/// such as one movie of an [archive][crate::playlist].
+pub(crate) fn controller_segments(packets: &[Packet], port: u8, start: u64) -> Vec<ControllerSegment> {
+    let Some(kind) = controller_kind(packets, port) else { return vec![] };
+    let mut segments = vec![ControllerSegment { start_frame: 0, kind, bytes_per_frame: controller_input_size(kind), offset: 0 }];
+    
+    let mut swaps: Vec<_> = packets.iter().filter_map(Transition::from_packet)
+        .filter_map(|transition| match transition.kind.packet() {
+            Some(Packet::PortController(controller)) if controller.port == port => Some((transition.index.frame()?.saturating_sub(start), controller.kind)),
+            _ => None,
+        })
+        .collect();
+    swaps.sort_by_key(|(frame, _)| *frame);
+    
+    for (start_frame, kind) in swaps {
+        let previous = segments[segments.len() - 1];
+        let Some(size) = previous.bytes_per_frame else { break };
+        if start_frame <= previous.start_frame {
+            segments.pop();
+        }
+        let start_frame = start_frame.max(previous.start_frame);
+        let offset = previous.offset + (start_frame - previous.start_frame) as usize * size;
+        segments.push(ControllerSegment { start_frame, kind, bytes_per_frame: controller_input_size(kind), offset });
+    }
+    
+    segments
+}
+
+
+/// Same as [`TasdFile::input_bytes`], for a slice of a file's packets.
+pub(crate) fn input_bytes(packets: &[Packet]) -> BTreeMap<u8, usize> {
+    let mut bytes = BTreeMap::new();
+    for packet in packets {
+        match packet {
+            Packet::InputChunk(chunk) => *bytes.entry(chunk.port).or_insert(0) += chunk.inputs.len(),
+            Packet::InputDelta(delta) => *bytes.entry(delta.port).or_insert(0) += delta.len as usize,
+            _ => (),
+        }
+    }
+    
+    bytes
+}
+
+/// Same as [`TasdFile::input_frames`], for a slice of a file's packets whose frames are counted from `start`.
+pub(crate) fn input_frames(packets: &[Packet], start: u64) -> BTreeMap<u8, u64> {
+    input_bytes(packets).into_iter()
+        .filter_map(|(port, bytes)| Some((port, frame_count(&controller_segments(packets, port, start), bytes)?)))
+        .collect()
+}
+
+/// Same as [`TasdFile::computed_total_frames`], for a slice of a file's packets whose frames are counted from `start`.
+pub(crate) fn computed_total_frames(packets: &[Packet], start: u64) -> Option<u32> {
+    input_frames(packets, start).into_values()
+        .max()
+        .map(|frames| frames.min(u32::MAX as u64) as u32)
+}
\ No newline at end of file
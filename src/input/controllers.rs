@@ -0,0 +1,126 @@
+//! Declaring controllers for input data which doesn't say what's plugged in, e.g. after importing a format which only
+//! stores inputs.
+//!
+//! Each port with input data but no PORT_CONTROLLER gets one of the console's controller types. When the number of
+//! frames is known, the type whose input size matches the port's stride is picked, otherwise the console's standard
+//! controller is. Every guess is recorded in the [ConversionReport] as an [Assumption::Controller].
+
+use std::collections::BTreeMap;
+use crate::export::report::{Assumption, ConversionReport};
+use crate::lookup::controller_input_size;
+use crate::spec::packets::{ConsoleType, Packet, PortController, TotalFrames};
+use crate::spec::TasdFile;
+
+/// Options for [`TasdFile::declare_controllers`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ControllerOptions {
+    /// Controller types to declare for specific ports instead of guessing.
+    pub overrides: BTreeMap<u8, u16>,
+    /// Number of frames of input data, used to tell controllers apart by their input size. Defaults to TOTAL_FRAMES.
+    pub frames: Option<u64>,
+}
+
+/// Controller types which can be plugged into a port of the given CONSOLE_TYPE, with the standard controller first.
+/// 
+/// Returns an empty list for custom and unknown consoles.
+pub fn console_controllers(console: u8) -> &'static [u16] {
+    match console {
+        0x01 => &[0x0101],
+        0x02 => &[0x0201, 0x0203],
+        0x03 => &[0x0301],
+        0x04 => &[0x0401],
+        0x05 => &[0x0501],
+        0x06 => &[0x0601],
+        0x07 => &[0x0701],
+        0x08 => &[0x0801, 0x0802],
+        0x09 => &[0x0901, 0x0903],
+        _ => &[],
+    }
+}
+
+impl TasdFile {
+    /// Adds a PORT_CONTROLLER before the input data of every port which doesn't declare one, using the overrides in
+    /// `options` or a guess based on the console and the port's input size. See the [module docs][self].
+    /// 
+    /// Ports are left undeclared if there's no override, and the console isn't known.
+    pub fn declare_controllers(&mut self, options: &ControllerOptions, report: &mut ConversionReport) {
+        let console = self.first::<ConsoleType>().map(|console| console.kind);
+        let frames = options.frames.or_else(|| self.first::<TotalFrames>().map(|total| total.frames as u64)).filter(|frames| *frames > 0);
+        
+        for (port, bytes) in self.input_bytes() {
+            if self.controller_kind(port).is_some() {
+                continue;
+            }
+            
+            let kind = match options.overrides.get(&port) {
+                Some(kind) => *kind,
+                None => {
+                    let candidates = console.map(console_controllers).unwrap_or_default();
+                    let fits = |kind: &&u16| match (controller_input_size(**kind), frames) {
+                        (Some(size), Some(frames)) => size as u64 * frames == bytes as u64,
+                        (Some(size), None) => bytes.is_multiple_of(size),
+                        (None, _) => false,
+                    };
+                    let Some(kind) = candidates.iter().find(fits).or(candidates.first()) else { continue };
+                    
+                    report.add_assumption(Assumption::Controller { port, kind: *kind });
+                    *kind
+                },
+            };
+            
+            let is_port_input = |packet: &Packet| matches!(packet, Packet::InputChunk(chunk) if chunk.port == port)
+                || matches!(packet, Packet::InputDelta(delta) if delta.port == port);
+            let index = self.packets.iter().position(is_port_input).unwrap_or(self.packets.len());
+            self.packets.insert(index, PortController { port, kind }.into());
+        }
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use crate::export::report::{Assumption, ConversionReport};
+    use crate::input::controllers::ControllerOptions;
+    use crate::spec::packets::{ConsoleType, InputChunk, PortController, TotalFrames};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn declare_controllers() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x02, custom: None }.into());
+        file.packets.push(TotalFrames { frames: 10 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 20] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 40] }.into());
+        
+        let mut report = ConversionReport::default();
+        file.declare_controllers(&ControllerOptions::default(), &mut report);
+        assert_eq!(file.controller_kind(1), Some(0x0201));
+        assert_eq!(file.controller_kind(2), Some(0x0203));
+        assert_eq!(file.packets[2], PortController { port: 1, kind: 0x0201 }.into());
+        assert_eq!(report.assumptions, [Assumption::Controller { port: 1, kind: 0x0201 }, Assumption::Controller { port: 2, kind: 0x0203 }]);
+        
+        // declared ports are left alone, and overrides aren't assumptions
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0102 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 4] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 4] }.into());
+        file.packets.push(InputChunk { port: 3, inputs: vec![0xFF; 4] }.into());
+        let options = ControllerOptions { overrides: BTreeMap::from([(3, 0xFFFF)]), frames: None };
+        let mut report = ConversionReport::default();
+        file.declare_controllers(&options, &mut report);
+        assert_eq!(file.controller_kind(1), Some(0x0102));
+        assert_eq!(file.controller_kind(2), Some(0x0101));
+        assert_eq!(file.controller_kind(3), Some(0xFFFF));
+        assert_eq!(report.assumptions, [Assumption::Controller { port: 2, kind: 0x0101 }]);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 4] }.into());
+        file.declare_controllers(&ControllerOptions::default(), &mut report);
+        assert_eq!(file.controller_kind(1), None);
+    }
+}
\ No newline at end of file
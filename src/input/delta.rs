@@ -0,0 +1,168 @@
+//! Delta compression of input data between ports.
+//! 
+//! Multitap movies often have ports which are never used, or which mostly mirror another port. Storing such a port as
+//! an [InputDelta] against another port only records the bytes where they differ, which can shrink 4-player dumps
+//! considerably. INPUT_DELTA is an extension packet, so compression is opt-in: use
+//! [`TasdFile::compress_port_deltas`] to apply it, and [`TasdFile::expand_port_deltas`] to restore plain INPUT_CHUNKs
+//! (e.g. before sending the file to a replay device).
+//! 
+//! [`TasdFile::port_inputs`] and [`TasdFile::input_bytes`] expand deltas transparently. Code which walks the
+//! INPUT_CHUNK packets directly should expand the file first.
+
+use crate::spec::packets::{InputChunk, InputDelta, Packet};
+use crate::spec::TasdFile;
+
+/// Port whose input data is cheaper to store as a delta against another port.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PortRedundancy {
+    pub port: u8,
+    pub base: u8,
+    /// Number of bytes which differ from the base port.
+    pub changes: usize,
+    /// Number of bytes saved by storing the port as an INPUT_DELTA.
+    pub saved: usize,
+}
+
+impl InputDelta {
+    /// Encodes `inputs` as the differences from `base_inputs`.
+    pub fn from_inputs(port: u8, base: u8, base_inputs: &[u8], inputs: &[u8]) -> Self {
+        let changes = inputs.iter().enumerate()
+            .map(|(i, byte)| (i as u32, byte ^ base_inputs.get(i).copied().unwrap_or(0x00)))
+            .filter(|(_, xor)| *xor != 0)
+            .collect();
+        
+        Self { port, base, len: inputs.len() as u32, changes }
+    }
+    
+    /// Reconstructs the input data of the port from the input data of the base port.
+    /// 
+    /// Changes past `len` are ignored.
+    pub fn apply(&self, base_inputs: &[u8]) -> Vec<u8> {
+        let mut inputs = base_inputs.to_vec();
+        inputs.resize(self.len as usize, 0x00);
+        for (offset, xor) in &self.changes {
+            if let Some(byte) = inputs.get_mut(*offset as usize) {
+                *byte ^= xor;
+            }
+        }
+        
+        inputs
+    }
+}
+
+/// Size of the payload of an INPUT_DELTA with this many changes.
+fn delta_size(changes: usize) -> usize {
+    6 + changes * 5
+}
+
+impl TasdFile {
+    /// Finds ports which would be smaller stored as an [InputDelta] against another port.
+    /// 
+    /// Ports are considered in ascending order, and each is compared against the ports before it which aren't themselves
+    /// redundant, so a delta never depends on another delta. Ports which already have an INPUT_DELTA are ignored.
+    pub fn redundant_ports(&self) -> Vec<PortRedundancy> {
+        let ports: Vec<(u8, Vec<u8>)> = self.input_bytes().into_keys()
+            .filter(|port| !self.all::<InputDelta>().any(|delta| delta.port == *port))
+            .map(|port| (port, self.port_inputs(port)))
+            .collect();
+        
+        let mut bases: Vec<&(u8, Vec<u8>)> = vec![];
+        let mut redundant = vec![];
+        for entry @ (port, inputs) in &ports {
+            let best = bases.iter()
+                .map(|(base, base_inputs)| (*base, InputDelta::from_inputs(*port, *base, base_inputs, inputs).changes.len()))
+                .min_by_key(|(_, changes)| *changes)
+                .filter(|(_, changes)| delta_size(*changes) < inputs.len());
+            
+            match best {
+                Some((base, changes)) => redundant.push(PortRedundancy {
+                    port: *port,
+                    base,
+                    changes,
+                    saved: inputs.len() - delta_size(changes),
+                }),
+                None => bases.push(entry),
+            }
+        }
+        
+        redundant
+    }
+    
+    /// Replaces the INPUT_CHUNKs of every [redundant port][Self::redundant_ports] with a single INPUT_DELTA, at the
+    /// position of the port's first chunk.
+    /// 
+    /// Returns the number of ports compressed.
+    pub fn compress_port_deltas(&mut self) -> usize {
+        let redundant = self.redundant_ports();
+        for entry in &redundant {
+            let delta = InputDelta::from_inputs(entry.port, entry.base, &self.port_inputs(entry.base), &self.port_inputs(entry.port));
+            let is_port = |packet: &Packet| matches!(packet, Packet::InputChunk(chunk) if chunk.port == entry.port);
+            let index = self.packets.iter().position(is_port).unwrap_or(self.packets.len());
+            self.packets.retain(|packet| !is_port(packet));
+            self.packets.insert(index, delta.into());
+        }
+        
+        redundant.len()
+    }
+    
+    /// Replaces every INPUT_DELTA with an equivalent INPUT_CHUNK.
+    /// 
+    /// Returns the number of packets expanded.
+    pub fn expand_port_deltas(&mut self) -> usize {
+        let expanded: Vec<_> = self.packets.iter().enumerate()
+            .filter_map(|(i, packet)| match packet {
+                Packet::InputDelta(delta) => Some((i, InputChunk { port: delta.port, inputs: delta.apply(&self.port_inputs(delta.base)) })),
+                _ => None,
+            })
+            .collect();
+        
+        for (i, chunk) in &expanded {
+            self.packets[*i] = chunk.clone().into();
+        }
+        
+        expanded.len()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{InputChunk, InputDelta, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn port_deltas() {
+        let mut file = TasdFile::default();
+        for port in 1..=4 {
+            file.packets.push(PortController { port, kind: 0x0101 }.into());
+        }
+        let inputs: Vec<u8> = (0..100).map(|i| i as u8).collect();
+        let mut similar = inputs.clone();
+        similar[50] = 0x00;
+        file.packets.push(InputChunk { port: 1, inputs: inputs.clone() }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 100] }.into());
+        file.packets.push(InputChunk { port: 3, inputs: vec![0xFF; 100] }.into());
+        file.packets.push(InputChunk { port: 4, inputs: similar.clone() }.into());
+        let original = file.clone();
+        
+        let redundant = file.redundant_ports();
+        assert_eq!(redundant.len(), 2);
+        assert_eq!((redundant[0].port, redundant[0].base, redundant[0].changes), (3, 2, 0));
+        assert_eq!((redundant[1].port, redundant[1].base, redundant[1].changes), (4, 1, 1));
+        
+        assert_eq!(file.compress_port_deltas(), 2);
+        assert_eq!(file.all::<InputDelta>().count(), 2);
+        assert_eq!(file.port_inputs(3), vec![0xFF; 100]);
+        assert_eq!(file.port_inputs(4), similar);
+        assert_eq!(file.input_bytes(), original.input_bytes());
+        
+        let encoded = file.encode();
+        assert!(encoded.len() < original.encode().len() - 150);
+        let mut decoded = TasdFile::parse_slice(&encoded).unwrap();
+        assert_eq!(decoded.expand_port_deltas(), 2);
+        assert_eq!(decoded.packets, original.packets);
+    }
+}
\ No newline at end of file
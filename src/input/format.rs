@@ -0,0 +1,155 @@
+//! Conversion between the canonical TASD input format and the formats used by other dumpers.
+//! 
+//! TASD input data is active-low (a cleared bit means the button is pressed), with the first button in the most
+//! significant bit of each byte (see [controller_buttons]). Some dumpers use the opposite polarity, bit order, or both.
+
+use crate::lookup::{controller_buttons, controller_input_size};
+use crate::spec::TasdFile;
+
+/// Which bit value means a button is pressed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Polarity {
+    /// Pressed buttons are `0` (canonical).
+    #[default]
+    ActiveLow,
+    /// Pressed buttons are `1`.
+    ActiveHigh,
+}
+
+/// Order of the buttons within each byte.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// First button in the most significant bit (canonical).
+    #[default]
+    MsbFirst,
+    /// First button in the least significant bit.
+    LsbFirst,
+}
+
+/// Layout of raw input data. The [default][InputFormat::default] is the canonical TASD format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct InputFormat {
+    pub polarity: Polarity,
+    pub bit_order: BitOrder,
+}
+impl InputFormat {
+    pub const CANONICAL: InputFormat = InputFormat { polarity: Polarity::ActiveLow, bit_order: BitOrder::MsbFirst };
+    
+    /// Converts input data in this format into `target`'s format, in place.
+    pub fn convert(&self, data: &mut [u8], target: InputFormat) {
+        // one loop per case, so each compiles to a simple (vectorizable) pass over the data
+        match (self.polarity != target.polarity, self.bit_order != target.bit_order) {
+            (false, false) => (),
+            (true, false) => data.iter_mut().for_each(|byte| *byte = !*byte),
+            (false, true) => data.iter_mut().for_each(|byte| *byte = byte.reverse_bits()),
+            (true, true) => data.iter_mut().for_each(|byte| *byte = !byte.reverse_bits()),
+        }
+    }
+    
+    /// Whether the button with the given index (per [controller_buttons]) is pressed in a frame of input data in this
+    /// format.
+    pub fn is_pressed(&self, frame: &[u8], button: usize) -> bool {
+        let mask = match self.bit_order {
+            BitOrder::MsbFirst => 0x80 >> (button % 8),
+            BitOrder::LsbFirst => 0x01 << (button % 8),
+        };
+        let set = frame.get(button / 8).is_some_and(|byte| byte & mask != 0);
+        
+        set == (self.polarity == Polarity::ActiveHigh)
+    }
+}
+
+/// Guesses the format of raw input data for the given controller type.
+/// 
+/// Polarity is detected by assuming most buttons are released most of the time. Bit order is detected by counting
+/// frames where opposite d-pad directions would be held at once, which is impossible on original hardware; controllers
+/// without a d-pad in their [button layout][controller_buttons] are assumed to be [BitOrder::MsbFirst].
+/// 
+/// Returns `None` if there's no data, or the controller type's input size isn't known.
+pub fn detect_input_format(inputs: &[u8], controller: u16) -> Option<InputFormat> {
+    let size = controller_input_size(controller).filter(|size| *size > 0)?;
+    if inputs.len() < size {
+        return None;
+    }
+    
+    let ones: u64 = inputs.iter().map(|byte| byte.count_ones() as u64).sum();
+    let polarity = if ones * 2 >= inputs.len() as u64 * 8 { Polarity::ActiveLow } else { Polarity::ActiveHigh };
+    
+    let mut bit_order = BitOrder::MsbFirst;
+    if let Some(buttons) = controller_buttons(controller) {
+        let index = |name: &str| buttons.iter().position(|button| *button == name);
+        if let (Some(up), Some(down), Some(left), Some(right)) = (index("Up"), index("Down"), index("Left"), index("Right")) {
+            let conflicts = |bit_order: BitOrder| {
+                // byte and mask of each direction, worked out once rather than for every frame
+                let bit = |button: usize| (button / 8, match bit_order {
+                    BitOrder::MsbFirst => 0x80u8 >> (button % 8),
+                    BitOrder::LsbFirst => 0x01u8 << (button % 8),
+                });
+                let [up, down, left, right] = [up, down, left, right].map(bit);
+                let pressed_bits = match polarity {
+                    Polarity::ActiveHigh => 0x00,
+                    Polarity::ActiveLow => 0xFF,
+                };
+                let pressed = |frame: &[u8], (byte, mask): (usize, u8)| frame.get(byte).is_some_and(|byte| (byte ^ pressed_bits) & mask != 0);
+                inputs.chunks_exact(size)
+                    .filter(|frame| (pressed(frame, up) && pressed(frame, down)) || (pressed(frame, left) && pressed(frame, right)))
+                    .count()
+            };
+            if conflicts(BitOrder::LsbFirst) < conflicts(BitOrder::MsbFirst) {
+                bit_order = BitOrder::LsbFirst;
+            }
+        }
+    }
+    
+    Some(InputFormat { polarity, bit_order })
+}
+
+impl TasdFile {
+    /// All INPUT_CHUNK data for a port, converted to the given format for export.
+    pub fn port_inputs_as(&self, port: u8, format: InputFormat) -> Vec<u8> {
+        let mut inputs = self.port_inputs(port);
+        InputFormat::CANONICAL.convert(&mut inputs, format);
+        
+        inputs
+    }
+    
+    /// Converts all INPUT_CHUNK data for a port from the given format into the canonical format, e.g. after importing
+    /// from a dumper which uses a different format.
+    pub fn convert_port_inputs(&mut self, port: u8, from: InputFormat) {
+        let mut inputs = self.port_inputs(port);
+        from.convert(&mut inputs, InputFormat::CANONICAL);
+        self.set_port_inputs(port, inputs);
+    }
+    
+    /// [Guesses][detect_input_format] the format of a port's input data.
+    pub fn detect_port_format(&self, port: u8) -> Option<InputFormat> {
+        detect_input_format(&self.port_inputs(port), self.controller_kind(port)?)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::input::format::{BitOrder, InputFormat, Polarity, detect_input_format};
+    
+    #[test]
+    fn detect_and_convert() {
+        // mostly idle, with A+B, Up, Up+Right, and A held
+        let canonical = [0xFF, 0x3F, 0xF7, 0xF6, 0xFF, 0x7F];
+        assert_eq!(detect_input_format(&canonical, 0x0101), Some(InputFormat::CANONICAL));
+        
+        let foreign_format = InputFormat { polarity: Polarity::ActiveHigh, bit_order: BitOrder::LsbFirst };
+        let mut foreign = canonical;
+        InputFormat::CANONICAL.convert(&mut foreign, foreign_format);
+        assert_eq!(foreign, [0x00, 0x03, 0x10, 0x90, 0x00, 0x01]);
+        assert!(foreign_format.is_pressed(&foreign[2..3], 4));
+        assert_eq!(detect_input_format(&foreign, 0x0101), Some(foreign_format));
+        
+        foreign_format.convert(&mut foreign, InputFormat::CANONICAL);
+        assert_eq!(foreign, canonical);
+        assert_eq!(detect_input_format(&[], 0x0101), None);
+    }
+}
\ No newline at end of file
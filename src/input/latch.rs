@@ -0,0 +1,112 @@
+//! Mapping frames to controller latches, for games which poll the controllers irregularly.
+//! 
+//! Replay devices feed one input per latch, so input data stored with one input per frame only replays correctly if
+//! every frame latches exactly once. For games which latch several times on some frames (e.g. to work around the DPCM
+//! bug) or not at all (lag), [LatchMap] packets record how many latches each frame has, and the
+//! [replay exporters][crate::export::replay] repeat or drop inputs to match.
+//! 
+//! Dumps which already store one input per latch don't need a latch map.
+
+use std::collections::BTreeMap;
+use crate::spec::packets::{LatchMap, Packet};
+use crate::spec::TasdFile;
+
+impl TasdFile {
+    /// Number of times the controllers are latched on `frame`. Frames which aren't covered by a LATCH_MAP latch once.
+    pub fn latches_on_frame(&self, frame: u64) -> u8 {
+        self.latch_overrides().get(&frame).copied().unwrap_or(1)
+    }
+    
+    /// Replaces any LATCH_MAP packets with one recording `latches`, the number of latches of each frame starting at
+    /// frame `0`. Only the frames between the first and last one which don't latch exactly once are stored.
+    pub fn record_latches<I: IntoIterator<Item = u8>>(&mut self, latches: I) {
+        self.packets.retain(|packet| !matches!(packet, Packet::LatchMap(_)));
+        
+        let latches: Vec<u8> = latches.into_iter().collect();
+        let Some(first) = latches.iter().position(|count| *count != 1) else { return };
+        let last = latches.iter().rposition(|count| *count != 1).unwrap_or(first);
+        let Ok(frame) = u32::try_from(first) else { return };
+        
+        let index = self.packets.iter().position(|packet| packet.kind().is_input()).unwrap_or(self.packets.len());
+        self.packets.insert(index, LatchMap { frame, latches: latches[first..=last].to_vec() }.into());
+    }
+    
+    /// Index of the first latch of `frame`, counting from `0`.
+    pub fn frame_to_latch(&self, frame: u64) -> u64 {
+        let extra: i64 = self.latch_overrides().range(..frame).map(|(_, count)| *count as i64 - 1).sum();
+        frame.saturating_add_signed(extra)
+    }
+    
+    /// Frame on which the latch with index `latch` happens.
+    pub fn latch_to_frame(&self, latch: u64) -> u64 {
+        let (mut frame, mut latches) = (0, 0);
+        for (override_frame, count) in self.latch_overrides() {
+            // frames before the override latch once each
+            if latch < latches + (override_frame - frame) {
+                break;
+            }
+            latches += override_frame - frame;
+            if latch < latches + count as u64 {
+                return override_frame;
+            }
+            latches += count as u64;
+            frame = override_frame + 1;
+        }
+        
+        frame + (latch - latches)
+    }
+    
+    /// Converts input data with one input of `size` bytes per frame into one input per latch, by repeating or dropping
+    /// the input of each frame.
+    pub fn expand_latches(&self, inputs: &[u8], size: usize) -> Vec<u8> {
+        self.expand_latches_from(inputs, size, 0)
+    }
+    
+    /// Same as [`Self::expand_latches`], for input data whose first frame is `first`.
+    pub(crate) fn expand_latches_from(&self, inputs: &[u8], size: usize, first: i64) -> Vec<u8> {
+        let overrides = self.latch_overrides();
+        let mut expanded = Vec::with_capacity(inputs.len());
+        for (i, input) in inputs.chunks_exact(size).enumerate() {
+            let count = u64::try_from(first + i as i64).ok().and_then(|frame| overrides.get(&frame)).copied().unwrap_or(1);
+            for _ in 0..count {
+                expanded.extend_from_slice(input);
+            }
+        }
+        
+        expanded
+    }
+    
+    /// Latch counts of every frame covered by a LATCH_MAP packet. Later packets take precedence.
+    fn latch_overrides(&self) -> BTreeMap<u64, u8> {
+        self.all::<LatchMap>()
+            .flat_map(|map| map.latches.iter().enumerate().map(|(i, count)| (map.frame as u64 + i as u64, *count)))
+            .collect()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{InputChunk, LatchMap, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn latch_map() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0, 1, 2, 3, 4] }.into());
+        file.record_latches([1, 2, 1, 0, 1, 1]);
+        
+        assert_eq!(file.packets[1], LatchMap { frame: 1, latches: vec![2, 1, 0] }.into());
+        assert_eq!((0..5).map(|frame| file.latches_on_frame(frame)).collect::<Vec<_>>(), [1, 2, 1, 0, 1]);
+        assert_eq!((0..6).map(|frame| file.frame_to_latch(frame)).collect::<Vec<_>>(), [0, 1, 3, 4, 4, 5]);
+        assert_eq!((0..6).map(|latch| file.latch_to_frame(latch)).collect::<Vec<_>>(), [0, 1, 1, 2, 4, 5]);
+        assert_eq!(file.expand_latches(&file.port_inputs(1), 1), vec![0, 1, 1, 2, 4]);
+        
+        file.record_latches([1, 1]);
+        assert!(file.first::<LatchMap>().is_none());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+//! Resampling of input data between frame rates, e.g. from an NTSC movie to the frame count of the PAL release.
+//!
+//! **Experimental, and not suitable for verification.** Regional versions of a game almost never run the same logic on
+//! the same frames, so a resampled movie won't sync. This is only meant for research, such as lining up the inputs of
+//! regional versions over time to compare them.
+//!
+//! Each target frame takes the inputs of the source frame nearest to it in time. Depending on the direction, some
+//! source frames end up used twice or not at all, which is recorded in a [ResampleReport].
+
+use crate::input::track::InputTrack;
+use crate::spec::packets::{ConsoleRegion, ConsoleType, Region};
+use crate::spec::TasdFile;
+use crate::timing::frame_rate;
+
+/// Source frames which resampling used more than once, or skipped, from [`InputTrack::resample`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ResampleReport {
+    pub source_frames: usize,
+    pub target_frames: usize,
+    /// Source frames whose inputs appear on more than one target frame.
+    pub duplicated: Vec<usize>,
+    /// Source frames whose inputs don't appear in the target at all.
+    pub dropped: Vec<usize>,
+}
+
+/// Nearest source frame for each frame of the resampled movie, which lasts as long as the `frames` frames of the source.
+pub fn resample_frames(frames: usize, from_fps: f64, to_fps: f64) -> Vec<usize> {
+    let target_frames = (frames as f64 * to_fps / from_fps).round() as usize;
+    
+    (0..target_frames)
+        .map(|frame| ((frame as f64 * from_fps / to_fps).round() as usize).min(frames.saturating_sub(1)))
+        .collect()
+}
+
+impl InputTrack {
+    /// Resamples this track from `from_fps` to `to_fps`, using the nearest source frame for each target frame. See the
+    /// [module docs][crate::input::resample] for why the result isn't expected to sync.
+    pub fn resample(&self, from_fps: f64, to_fps: f64) -> (InputTrack, ResampleReport) {
+        let mapping = resample_frames(self.len(), from_fps, to_fps);
+        let mut uses = vec![0usize; self.len()];
+        let mut track = InputTrack::new(self.frame_size());
+        for source in &mapping {
+            uses[*source] += 1;
+            track.push(&self.frame(*source).unwrap_or_default());
+        }
+        
+        let report = ResampleReport {
+            source_frames: self.len(),
+            target_frames: mapping.len(),
+            duplicated: (0..self.len()).filter(|frame| uses[*frame] > 1).collect(),
+            dropped: (0..self.len()).filter(|frame| uses[*frame] == 0).collect(),
+        };
+        
+        (track, report)
+    }
+}
+
+impl TasdFile {
+    /// Resamples a port's inputs from this file's region to the frame rate of `region` on the same console.
+    /// Experimental, see the [module docs][crate::input::resample].
+    /// 
+    /// Returns `None` if the frame rate of either region isn't known, or the port's frame size isn't known.
+    pub fn resample_port(&self, port: u8, region: Region) -> Option<(InputTrack, ResampleReport)> {
+        let console = self.first::<ConsoleType>()?.kind;
+        let from = self.first::<ConsoleRegion>().map(|region| region.region).unwrap_or(Region::Ntsc as u8);
+        let from_fps = frame_rate(console, from)?;
+        let to_fps = frame_rate(console, region as u8)?;
+        
+        Some(self.input_track(port)?.resample(from_fps, to_fps))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::input::resample::resample_frames;
+    use crate::spec::packets::{ConsoleType, InputChunk, PortController, Region};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn resample() {
+        // 60 -> 50 fps drops every 6th frame, and 50 -> 60 fps repeats every 5th
+        assert_eq!(resample_frames(12, 60.0, 50.0), [0, 1, 2, 4, 5, 6, 7, 8, 10, 11]);
+        assert_eq!(resample_frames(5, 50.0, 60.0), [0, 1, 2, 3, 3, 4]);
+        assert!(resample_frames(0, 60.0, 50.0).is_empty());
+        
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x03, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0301 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: (0..24).map(|frame| [frame, 0xFF, 0xFF, 0xFF]).collect::<Vec<_>>().concat() }.into());
+        
+        let (track, report) = file.resample_port(1, Region::Pal).unwrap();
+        assert_eq!(track.len(), 20);
+        assert_eq!(report.dropped, [3, 9, 15, 21]);
+        assert!(report.duplicated.is_empty());
+        assert_eq!(track.frame(3), Some(vec![4, 0xFF, 0xFF, 0xFF]));
+        
+        let (back, report) = track.resample(50.0, 60.0);
+        assert_eq!(back.len(), 24);
+        assert_eq!(report.duplicated.len(), 4);
+        assert!(file.resample_port(2, Region::Pal).is_none());
+    }
+}
\ No newline at end of file
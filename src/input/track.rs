@@ -0,0 +1,251 @@
+//! Packed, per-button storage of a port's input data.
+//!
+//! INPUT_CHUNK stores each frame's bytes one after another, so questions like "on which frames is A held?" have to
+//! visit every byte of the movie. [InputTrack] instead keeps one bitset per button, which makes such scans touch only
+//! that button's bits, 64 frames at a time. It takes one bit per button per frame, the same as the raw data.
+
+use crate::spec::packets::InputChunk;
+use crate::spec::TasdFile;
+
+/// Input data of a single port, stored as one bitset per button.
+/// 
+/// Buttons are numbered by their bit in the canonical input format, the same as [controller_buttons]: button `0` is
+/// the most significant bit of the first byte of each frame. Bits are set for pressed buttons, regardless of the
+/// active-low encoding of INPUT_CHUNK.
+/// 
+/// [controller_buttons]: crate::lookup::controller_buttons
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InputTrack {
+    frame_size: usize,
+    frames: usize,
+    /// One bitset per button, with bit `frame % 64` of word `frame / 64` set if the button is pressed on that frame.
+    columns: Vec<Vec<u64>>,
+}
+impl InputTrack {
+    /// Creates an empty track for frames of `frame_size` bytes.
+    pub fn new(frame_size: usize) -> Self {
+        Self {
+            frame_size,
+            frames: 0,
+            columns: vec![vec![]; frame_size * 8],
+        }
+    }
+    
+    /// Packs input data in the canonical format, with `frame_size` bytes per frame.
+    /// 
+    /// If the data ends partway through a frame, the missing bytes are treated as having nothing pressed.
+    pub fn from_inputs(frame_size: usize, inputs: &[u8]) -> Self {
+        let mut track = Self::new(frame_size);
+        if frame_size == 0 {
+            return track;
+        }
+        
+        track.frames = inputs.len().div_ceil(frame_size);
+        for column in &mut track.columns {
+            column.resize(track.frames.div_ceil(64), 0);
+        }
+        for (frame, data) in inputs.chunks(frame_size).enumerate() {
+            track.set_frame_bits(frame, data);
+        }
+        
+        track
+    }
+    
+    /// Packs the inputs of every INPUT_CHUNK for `port`, in order.
+    pub fn from_chunks<'a, I: IntoIterator<Item = &'a InputChunk>>(frame_size: usize, port: u8, chunks: I) -> Self {
+        let inputs: Vec<u8> = chunks.into_iter()
+            .filter(|chunk| chunk.port == port)
+            .flat_map(|chunk| chunk.inputs.iter().copied())
+            .collect();
+        
+        Self::from_inputs(frame_size, &inputs)
+    }
+    
+    /// Bytes of input data per frame.
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+    
+    /// Number of buttons (bits) per frame.
+    pub fn buttons(&self) -> usize {
+        self.columns.len()
+    }
+    
+    /// Number of frames.
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+    
+    /// Whether `button` is pressed on `frame`. Buttons and frames past the end are never pressed.
+    pub fn is_pressed(&self, frame: usize, button: usize) -> bool {
+        frame < self.frames && self.columns.get(button).is_some_and(|column| column[frame / 64] & (1 << (frame % 64)) != 0)
+    }
+    
+    /// Presses or releases `button` on `frame`.
+    /// 
+    /// # Panics
+    /// If `frame` or `button` is out of bounds.
+    pub fn set_pressed(&mut self, frame: usize, button: usize, pressed: bool) {
+        assert!(frame < self.frames, "frame {frame} is out of bounds for a track of {} frames", self.frames);
+        let word = &mut self.columns[button][frame / 64];
+        if pressed {
+            *word |= 1 << (frame % 64);
+        } else {
+            *word &= !(1 << (frame % 64));
+        }
+    }
+    
+    /// Appends a frame of input data in the canonical format. Missing bytes are treated as having nothing pressed, and
+    /// extra bytes are ignored.
+    pub fn push(&mut self, data: &[u8]) {
+        if self.frames.is_multiple_of(64) {
+            for column in &mut self.columns {
+                column.push(0);
+            }
+        }
+        self.frames += 1;
+        self.set_frame_bits(self.frames - 1, &data[..data.len().min(self.frame_size)]);
+    }
+    
+    /// Input data of a single frame in the canonical format, or `None` if it's out of bounds.
+    pub fn frame(&self, frame: usize) -> Option<Vec<u8>> {
+        if frame >= self.frames {
+            return None;
+        }
+        
+        let mut data = vec![0xFF; self.frame_size];
+        for button in 0..self.buttons() {
+            if self.is_pressed(frame, button) {
+                data[button / 8] &= !(0x80 >> (button % 8));
+            }
+        }
+        
+        Some(data)
+    }
+    
+    /// Number of frames `button` is pressed on.
+    pub fn presses(&self, button: usize) -> usize {
+        self.columns.get(button).map_or(0, |column| column.iter().map(|word| word.count_ones() as usize).sum())
+    }
+    
+    /// Frames `button` is pressed on, in order.
+    pub fn pressed_frames(&self, button: usize) -> impl Iterator<Item = usize> + '_ {
+        self.columns.get(button).into_iter().flatten().enumerate().flat_map(|(index, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                
+                Some(index * 64 + bit)
+            })
+        })
+    }
+    
+    /// Unpacks the track into input data in the canonical format.
+    pub fn to_inputs(&self) -> Vec<u8> {
+        let mut inputs = vec![0xFF; self.frames * self.frame_size];
+        for button in 0..self.buttons() {
+            let mask = 0x80 >> (button % 8);
+            for frame in self.pressed_frames(button) {
+                inputs[frame * self.frame_size + button / 8] &= !mask;
+            }
+        }
+        
+        inputs
+    }
+    
+    /// Unpacks the track into a single INPUT_CHUNK for `port`.
+    pub fn to_chunk(&self, port: u8) -> InputChunk {
+        InputChunk { port, inputs: self.to_inputs() }
+    }
+    
+    fn set_frame_bits(&mut self, frame: usize, data: &[u8]) {
+        for (byte, value) in data.iter().enumerate() {
+            let mut pressed = !value;
+            while pressed != 0 {
+                let bit = pressed.leading_zeros() as usize;
+                self.columns[byte * 8 + bit][frame / 64] |= 1 << (frame % 64);
+                pressed &= !(0x80 >> bit);
+            }
+        }
+    }
+}
+
+impl TasdFile {
+    /// All input data for a port, packed into an [InputTrack].
+    /// 
+    /// Returns `None` if the size of the port's controller isn't known.
+    pub fn input_track(&self, port: u8) -> Option<InputTrack> {
+        Some(InputTrack::from_inputs(self.bytes_per_frame(port)?, &self.port_inputs(port)))
+    }
+    
+    /// Replaces all input data for a port with the contents of `track`, as with [`TasdFile::set_port_inputs`].
+    pub fn set_input_track(&mut self, port: u8, track: &InputTrack) {
+        self.set_port_inputs(port, track.to_inputs());
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::input::track::InputTrack;
+    use crate::spec::packets::{InputChunk, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn pack_and_unpack() {
+        // 2 byte frames, long enough to span more than one word per button
+        let mut inputs: Vec<u8> = (0..150u8).flat_map(|frame| [!frame, 0xFF]).collect();
+        inputs.extend([0x7F, 0xEF]);
+        let track = InputTrack::from_inputs(2, &inputs);
+        assert_eq!(track.len(), 151);
+        assert_eq!(track.buttons(), 16);
+        assert_eq!(track.to_inputs(), inputs);
+        
+        // button 7 is the lowest bit of the first byte, so it's pressed on odd frames
+        assert_eq!(track.presses(7), 75);
+        assert!(track.pressed_frames(7).all(|frame| frame % 2 == 1));
+        assert_eq!(track.pressed_frames(11).collect::<Vec<_>>(), [150]);
+        assert_eq!(track.frame(150), Some(vec![0x7F, 0xEF]));
+        assert_eq!(track.frame(151), None);
+        
+        // a partial frame at the end is padded with released buttons
+        assert_eq!(InputTrack::from_inputs(2, &[0x00, 0x00, 0x7F]).to_inputs(), [0x00, 0x00, 0x7F, 0xFF]);
+        
+        let mut built = InputTrack::new(1);
+        for frame in 0..130u8 {
+            built.push(&[frame]);
+        }
+        built.set_pressed(128, 0, true);
+        assert!(built.is_pressed(128, 0));
+        assert_eq!(built.frame(128), Some(vec![0x00]));
+        assert_eq!(built.to_chunk(1), InputChunk { port: 1, inputs: (0..128).chain([0x00, 0x81]).collect() });
+    }
+    
+    #[test]
+    fn file_tracks() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF, 0xFF, 0x7F, 0xFF] }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF, 0xEF] }.into());
+        
+        let mut track = file.input_track(1).unwrap();
+        assert_eq!(track, InputTrack::from_chunks(2, 1, file.all::<InputChunk>()));
+        assert_eq!(track.pressed_frames(0).collect::<Vec<_>>(), [1]);
+        track.set_pressed(0, 0, true);
+        file.set_input_track(1, &track);
+        assert_eq!(file.port_inputs(1), [0x7F, 0xFF, 0x7F, 0xFF, 0xFF, 0xEF]);
+        
+        assert_eq!(file.input_track(2), None);
+    }
+}
\ No newline at end of file
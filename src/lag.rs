@@ -0,0 +1,208 @@
+//! Adding lag frames to dumps from emulators which don't record them, so timing and statistics which depend on
+//! LAG_FRAME_CHUNK still work.
+//!
+//! A [LagModel] decides which frames were lag frames, either by guessing from the input data ([RepeatedInputs]) or from
+//! an external source such as an emulator's lag log ([LagLog]). [`TasdFile::apply_lag_model`] then adds the result as
+//! LAG_FRAME_CHUNK packets, and [`TasdFile::inject_lag_frames`] does the same for frames computed any other way.
+
+use std::num::ParseIntError;
+use std::ops::RangeInclusive;
+use crate::spec::packets::{LagFrameChunk, Packet};
+use crate::spec::TasdFile;
+
+/// Way of deciding which frames of a file were lag frames.
+pub trait LagModel {
+    /// Frames of the input data which were lag frames, in any order.
+    fn lag_frames(&self, file: &TasdFile) -> Vec<u64>;
+}
+impl<F: Fn(&TasdFile) -> Vec<u64>> LagModel for F {
+    fn lag_frames(&self, file: &TasdFile) -> Vec<u64> {
+        self(file)
+    }
+}
+
+/// Counts frames whose inputs repeat those of the previous frame on every port as lag frames, for movie formats which
+/// store an input for every frame by repeating the last one while the game isn't polling.
+/// 
+/// This is a rough guess: held buttons look the same as lag, so only repeats in runs of at least `min_run` frames are
+/// counted, which suits games where the player rarely holds a button perfectly still for long.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RepeatedInputs {
+    pub min_run: usize,
+}
+impl Default for RepeatedInputs {
+    fn default() -> Self {
+        Self { min_run: 1 }
+    }
+}
+impl LagModel for RepeatedInputs {
+    fn lag_frames(&self, file: &TasdFile) -> Vec<u64> {
+        let ports: Vec<_> = file.input_bytes().into_keys()
+            .filter_map(|port| Some((file.bytes_per_frame(port)?, file.port_inputs(port))))
+            .filter(|(size, _)| *size > 0)
+            .collect();
+        let frames = ports.iter().map(|(size, inputs)| inputs.len() / size).min().unwrap_or(0);
+        let repeats = |frame: usize| ports.iter().all(|(size, inputs)| {
+            inputs[((frame - 1) * size)..(frame * size)] == inputs[(frame * size)..((frame + 1) * size)]
+        });
+        
+        let mut lag = vec![];
+        let mut run = vec![];
+        for frame in 1..=frames {
+            if frame < frames && repeats(frame) {
+                run.push(frame as u64);
+                continue;
+            }
+            if run.len() >= self.min_run {
+                lag.append(&mut run);
+            }
+            run.clear();
+        }
+        
+        lag
+    }
+}
+
+/// Error from [`LagLog::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LagLogError {
+    InvalidFrame(ParseIntError),
+    /// A range of frames starts after it ends.
+    ReversedRange(u64, u64),
+}
+impl From<ParseIntError> for LagLogError {
+    fn from(value: ParseIntError) -> Self {
+        Self::InvalidFrame(value)
+    }
+}
+
+/// Lag frames listed by an external log, such as one exported from an emulator.
+/// 
+/// Frames are stored as inclusive ranges, and only expanded up to the end of the movie's input data when the model is
+/// applied, so a log with a huge range can't exhaust memory.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LagLog {
+    pub ranges: Vec<RangeInclusive<u64>>,
+}
+impl LagLog {
+    /// Parses a log with one frame (`120`) or inclusive range of frames (`120-125`) per line. Blank lines and lines
+    /// starting with `#` are ignored.
+    /// 
+    /// ```
+    /// use tasd::lag::LagLog;
+    /// 
+    /// let log = LagLog::parse("# lag frames\n3\n10-12\n").unwrap();
+    /// assert_eq!(log.ranges, [3..=3, 10..=12]);
+    /// ```
+    pub fn parse(log: &str) -> Result<Self, LagLogError> {
+        let mut ranges = vec![];
+        for line in log.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (start, end) = match line.split_once('-') {
+                Some((start, end)) => (start.trim().parse()?, end.trim().parse()?),
+                None => (line.parse()?, line.parse()?),
+            };
+            if start > end {
+                return Err(LagLogError::ReversedRange(start, end));
+            }
+            ranges.push(start..=end);
+        }
+        
+        Ok(Self { ranges })
+    }
+}
+impl LagModel for LagLog {
+    /// Every logged frame before the end of the movie's input data. Later frames are ignored.
+    fn lag_frames(&self, file: &TasdFile) -> Vec<u64> {
+        let Some(last) = file.input_frames().into_values().max().and_then(|frames| frames.checked_sub(1)) else { return vec![] };
+        self.ranges.iter()
+            .filter(|range| *range.start() <= last)
+            .flat_map(|range| *range.start()..=(*range.end()).min(last))
+            .collect()
+    }
+}
+
+impl TasdFile {
+    /// Adds the lag frames decided by `model` as LAG_FRAME_CHUNK packets, if the file doesn't have any lag data yet.
+    /// 
+    /// Returns the number of packets added.
+    pub fn apply_lag_model<M: LagModel>(&mut self, model: &M) -> usize {
+        if self.first::<LagFrameChunk>().is_some() {
+            return 0;
+        }
+        
+        let frames = model.lag_frames(self);
+        self.inject_lag_frames(frames)
+    }
+    
+    /// Adds LAG_FRAME_CHUNK packets to the end of the file for the given frames, combining consecutive frames into one
+    /// chunk. Frames which are already covered by a chunk, or don't fit in a LAG_FRAME_CHUNK, are skipped.
+    /// 
+    /// Returns the number of packets added.
+    pub fn inject_lag_frames<I: IntoIterator<Item = u64>>(&mut self, frames: I) -> usize {
+        let covered: Vec<_> = self.all::<LagFrameChunk>()
+            .map(|lag| lag.movie_frame as u64..(lag.movie_frame as u64 + lag.count as u64))
+            .collect();
+        let mut frames: Vec<u64> = frames.into_iter()
+            .filter(|frame| *frame <= u32::MAX as u64 && !covered.iter().any(|range| range.contains(frame)))
+            .collect();
+        frames.sort_unstable();
+        frames.dedup();
+        
+        let mut chunks: Vec<LagFrameChunk> = vec![];
+        for frame in frames {
+            match chunks.last_mut() {
+                Some(lag) if lag.movie_frame as u64 + lag.count as u64 == frame => lag.count += 1,
+                _ => chunks.push(LagFrameChunk { movie_frame: frame as u32, count: 1 }),
+            }
+        }
+        
+        let added = chunks.len();
+        self.packets.extend(chunks.into_iter().map(Packet::from));
+        
+        added
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::lag::{LagLog, LagLogError, LagModel, RepeatedInputs};
+    use crate::spec::packets::{InputChunk, LagFrameChunk, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn lag_models() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(PortController { port: 2, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0, 1, 1, 2, 2, 2, 3] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0, 0, 0, 0, 0, 5, 5] }.into());
+        
+        let mut repeated = file.clone();
+        assert_eq!(repeated.apply_lag_model(&RepeatedInputs::default()), 2);
+        assert_eq!(repeated.all::<LagFrameChunk>().cloned().collect::<Vec<_>>(), [
+            LagFrameChunk { movie_frame: 2, count: 1 },
+            LagFrameChunk { movie_frame: 4, count: 1 },
+        ]);
+        assert_eq!(repeated.apply_lag_model(&LagLog { ranges: vec![0..=0] }), 0);
+        assert_eq!(file.clone().apply_lag_model(&RepeatedInputs { min_run: 2 }), 0);
+        
+        let log = LagLog::parse("5-6\n\n1\n5").unwrap();
+        assert_eq!(file.apply_lag_model(&log), 2);
+        assert_eq!(file.inject_lag_frames([3, 6, 2]), 1);
+        assert_eq!(file.all::<LagFrameChunk>().cloned().collect::<Vec<_>>(), [
+            LagFrameChunk { movie_frame: 1, count: 1 },
+            LagFrameChunk { movie_frame: 5, count: 2 },
+            LagFrameChunk { movie_frame: 2, count: 2 },
+        ]);
+        assert!(LagLog::parse("x").is_err());
+        assert_eq!(LagLog::parse("5-3"), Err(LagLogError::ReversedRange(5, 3)));
+        
+        // ranges are only expanded up to the end of the input data
+        let log = LagLog::parse(&format!("0-{}", u64::MAX)).unwrap();
+        assert_eq!(log.lag_frames(&file), [0, 1, 2, 3, 4, 5, 6]);
+    }
+}
\ No newline at end of file
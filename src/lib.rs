@@ -1,4 +1,58 @@
 
+extern crate self as tasd;
+
+/// Packet types and their keys, re-exported from [`spec::packets`] for shorter paths (e.g. `tasd::packets::GameTitle`).
+pub use spec::packets;
+pub use spec::{TasdError, TasdFile};
+
+pub mod batch;
+pub mod bookmark;
+pub mod cancel;
+pub mod compare;
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "dat")]
+pub mod dat;
+pub mod canonicalize;
+pub mod catalog;
+pub mod category;
+pub mod dump;
+pub mod duplicates;
+pub mod edit;
+pub mod emulator;
+pub mod export;
+pub mod health;
+pub mod history;
+pub mod input;
+pub mod lag;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "spdx")]
+pub mod license;
+pub mod link;
 pub mod lookup;
+pub mod memory;
+pub mod merge;
+pub mod metrics;
+pub mod playback;
+pub mod playlist;
+pub mod prelude;
+pub mod provenance;
+pub mod publish;
+#[cfg(feature = "regex")]
+pub mod replace;
+pub mod seek;
+pub mod start;
 pub mod util;
-pub mod spec;
\ No newline at end of file
+pub mod spec;
+pub mod stats;
+pub mod surgery;
+pub mod sync;
+pub mod template;
+pub mod validate;
+pub mod net;
+pub mod storage;
+pub mod timing;
+pub mod timestamp;
+#[cfg(feature = "upload")]
+pub mod upload;
\ No newline at end of file
@@ -0,0 +1,393 @@
+//! Dumping a movie by running it on a libretro core, without a frontend.
+//!
+//! [`dump`] loads a core and a ROM, runs the core headlessly as fast as possible, and feeds it input from an
+//! [InputSource] one frame at a time. Whatever the core reads is recorded by a [Dumper], so the result is a TASD dump
+//! of the movie as the emulated console saw it, including which frames were lag frames.
+//!
+//! Libretro has no way of asking a core whether a frame lagged, so it's decided by a [LagHeuristic] from what the core
+//! did during the frame. Cores keep global state and their callbacks carry no context, so only one core can be run at a
+//! time on each thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_uint, c_void, CStr, CString, OsStr};
+use std::path::Path;
+use libloading::{Library, Symbol};
+use crate::dump::{DumpSession, Dumper, TasdDumper};
+use crate::lookup::{controller_buttons, controller_input_size};
+use crate::spec::packets::RomName;
+use crate::spec::TasdFile;
+use crate::start::StartCondition;
+
+const RETRO_API_VERSION: c_uint = 1;
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_DEVICE_ID_JOYPAD_MASK: c_uint = 256;
+const RETRO_ENVIRONMENT_GET_CAN_DUPE: c_uint = 3;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_ENVIRONMENT_GET_INPUT_BITMASKS: c_uint = 51 | 0x10000;
+
+/// Names of the libretro joypad buttons, indexed by their `RETRO_DEVICE_ID_JOYPAD_*` ID. These match the names used by
+/// [controller_buttons].
+const JOYPAD_BUTTONS: [&str; 12] = ["B", "Y", "Select", "Start", "Up", "Down", "Left", "Right", "A", "X", "L", "R"];
+
+#[derive(Debug)]
+pub enum LibretroError {
+    /// The core couldn't be loaded, or is missing a libretro function.
+    Library(libloading::Error),
+    Io(std::io::Error),
+    /// The core implements a different version of the libretro API.
+    ApiVersion(u32),
+    /// The core refused to load the ROM.
+    LoadGame,
+    /// A controller's input size isn't known, so its input data can't be passed to the core.
+    UnknownController(u16),
+    /// A controller's port number is `0`, which has no libretro port.
+    InvalidPort(u8),
+}
+impl From<libloading::Error> for LibretroError {
+    fn from(value: libloading::Error) -> Self {
+        Self::Library(value)
+    }
+}
+impl From<std::io::Error> for LibretroError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// How a frame is decided to be a lag frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LagHeuristic {
+    /// The core didn't read input during the frame. Accurate for cores which only read input when the game reads the
+    /// controller, which most cycle-accurate cores do.
+    #[default]
+    NoInputRead,
+    /// The core didn't read input, or repeated the previous video frame instead of drawing a new one. Useful for cores
+    /// which poll input every frame regardless of the game, at the risk of counting frames where the game deliberately
+    /// didn't redraw the screen.
+    NoInputReadOrDupedVideo,
+}
+
+/// Provides the input data for each frame of a [`dump`].
+#[allow(unused_variables)]
+pub trait InputSource {
+    /// Input data of each port for `frame`, in the canonical format of the port's controller. Ports which aren't listed
+    /// have nothing pressed. Returning `None` ends the dump.
+    fn inputs(&mut self, frame: u64) -> Option<Vec<(u8, Vec<u8>)>>;
+    
+    /// Called after each frame has run, with whether it was a lag frame.
+    fn frame_done(&mut self, frame: u64, lag: bool) {}
+}
+impl<F: FnMut(u64) -> Option<Vec<(u8, Vec<u8>)>>> InputSource for F {
+    fn inputs(&mut self, frame: u64) -> Option<Vec<(u8, Vec<u8>)>> {
+        self(frame)
+    }
+}
+
+/// [InputSource] playing back the input data of a [TasdFile].
+/// 
+/// TASD input data has no entries for lag frames, so the same inputs are offered again after a lag frame rather than
+/// moving on to the next.
+pub struct MovieInputs {
+    ports: Vec<(u8, usize, Vec<u8>)>,
+    index: usize,
+}
+impl MovieInputs {
+    /// Input data of every port with a known controller input size, with BLANK_FRAMES applied.
+    pub fn new(file: &TasdFile) -> Self {
+        let ports = file.input_bytes().into_keys()
+            .filter_map(|port| Some((port, file.bytes_per_frame(port).filter(|size| *size > 0)?, file.aligned_port_inputs(port))))
+            .collect();
+        
+        Self { ports, index: 0 }
+    }
+}
+impl InputSource for MovieInputs {
+    fn inputs(&mut self, _frame: u64) -> Option<Vec<(u8, Vec<u8>)>> {
+        let inputs: Vec<(u8, Vec<u8>)> = self.ports.iter()
+            .filter_map(|(port, size, inputs)| Some((*port, inputs.get((self.index * size)..((self.index + 1) * size))?.to_vec())))
+            .collect();
+        
+        (!inputs.is_empty()).then_some(inputs)
+    }
+    
+    fn frame_done(&mut self, _frame: u64, lag: bool) {
+        if !lag {
+            self.index += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibretroOptions {
+    /// CONSOLE_TYPE of the console the core emulates.
+    pub console: u8,
+    /// Port number and PORT_CONTROLLER type of each connected controller. Port `1` is libretro port `0`.
+    pub controllers: Vec<(u8, u16)>,
+    pub lag: LagHeuristic,
+    /// Stops the dump after this many frames, even if the input source has more.
+    pub max_frames: Option<u64>,
+}
+
+/// What a core did during one frame, collected by the callbacks.
+#[derive(Debug, Default)]
+struct FrameState {
+    /// Controller type and input data of each port.
+    ports: Vec<(u8, u16, Vec<u8>)>,
+    input_read: bool,
+    duped_video: bool,
+}
+impl FrameState {
+    fn is_lag(&self, heuristic: LagHeuristic) -> bool {
+        match heuristic {
+            LagHeuristic::NoInputRead => !self.input_read,
+            LagHeuristic::NoInputReadOrDupedVideo => !self.input_read || self.duped_video,
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<FrameState> = RefCell::new(FrameState::default());
+}
+
+/// Value of the libretro joypad button `id` in a frame of input data in the canonical format. `0` if the controller
+/// has no such button.
+fn joypad_state(kind: u16, data: &[u8], id: c_uint) -> i16 {
+    let pressed = |id: usize| -> bool {
+        let Some(button) = controller_buttons(kind).and_then(|buttons| buttons.iter().position(|name| *name == JOYPAD_BUTTONS[id])) else { return false };
+        data.get(button / 8).is_some_and(|byte| byte & (0x80 >> (button % 8)) == 0)
+    };
+    
+    match id {
+        RETRO_DEVICE_ID_JOYPAD_MASK => (0..JOYPAD_BUTTONS.len()).filter(|id| pressed(*id)).fold(0, |mask, id| mask | (1 << id)),
+        id if (id as usize) < JOYPAD_BUTTONS.len() => pressed(id as usize) as i16,
+        _ => 0,
+    }
+}
+
+extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        RETRO_ENVIRONMENT_GET_CAN_DUPE => {
+            if !data.is_null() {
+                // SAFETY: the core passes a pointer to a bool for this command
+                unsafe { *(data as *mut bool) = true };
+            }
+            true
+        },
+        // output isn't displayed, so any pixel format and input descriptors are fine
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT | RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS | RETRO_ENVIRONMENT_GET_INPUT_BITMASKS => true,
+        _ => false,
+    }
+}
+
+extern "C" fn video_refresh(data: *const c_void, _width: c_uint, _height: c_uint, _pitch: usize) {
+    STATE.with_borrow_mut(|state| state.duped_video = data.is_null());
+}
+
+extern "C" fn audio_sample(_left: i16, _right: i16) {}
+
+extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    frames
+}
+
+// many cores poll every frame whether or not the game reads the controller, so only input_state counts as a read
+extern "C" fn input_poll() {}
+
+extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    STATE.with_borrow_mut(|state| {
+        state.input_read = true;
+        if device != RETRO_DEVICE_JOYPAD {
+            return 0;
+        }
+        
+        match state.ports.iter().find(|(number, _, _)| *number as c_uint == port + 1) {
+            Some((_, kind, data)) => joypad_state(*kind, data, id),
+            None => 0,
+        }
+    })
+}
+
+/// Calls a core function when dropped, so the core is shut down however [`run`] returns.
+struct OnDrop<'a>(Symbol<'a, extern "C" fn()>);
+impl Drop for OnDrop<'_> {
+    fn drop(&mut self) {
+        (self.0)();
+    }
+}
+
+#[repr(C)]
+struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+/// Runs `rom` on the libretro core at `core` with inputs from `source`, reporting each frame to `dumper`.
+/// 
+/// The core's library name and version are passed to the dumper as the emulator name and version. Returns the number
+/// of frames run.
+pub fn run<P: AsRef<OsStr>, S: InputSource, D: Dumper>(core: P, rom: &Path, options: &LibretroOptions, source: &mut S, dumper: &mut D) -> Result<u64, LibretroError> {
+    for (port, kind) in &options.controllers {
+        if *port == 0 {
+            return Err(LibretroError::InvalidPort(*port));
+        }
+        controller_input_size(*kind).filter(|size| *size > 0).ok_or(LibretroError::UnknownController(*kind))?;
+    }
+    
+    // SAFETY: loading a library runs its initializers, which is inherent to running a core chosen by the caller
+    let library = unsafe { Library::new(core)? };
+    // SAFETY: the signatures match the libretro API, and every call below follows its required order
+    unsafe {
+        let api_version: Symbol<extern "C" fn() -> c_uint> = library.get(b"retro_api_version")?;
+        if api_version() != RETRO_API_VERSION {
+            return Err(LibretroError::ApiVersion(api_version()));
+        }
+        
+        library.get::<extern "C" fn(extern "C" fn(c_uint, *mut c_void) -> bool)>(b"retro_set_environment")?(environment);
+        library.get::<extern "C" fn(extern "C" fn(*const c_void, c_uint, c_uint, usize))>(b"retro_set_video_refresh")?(video_refresh);
+        library.get::<extern "C" fn(extern "C" fn(i16, i16))>(b"retro_set_audio_sample")?(audio_sample);
+        library.get::<extern "C" fn(extern "C" fn(*const i16, usize) -> usize)>(b"retro_set_audio_sample_batch")?(audio_sample_batch);
+        library.get::<extern "C" fn(extern "C" fn())>(b"retro_set_input_poll")?(input_poll);
+        library.get::<extern "C" fn(extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16)>(b"retro_set_input_state")?(input_state);
+        let init: Symbol<extern "C" fn()> = library.get(b"retro_init")?;
+        let deinit: Symbol<extern "C" fn()> = library.get(b"retro_deinit")?;
+        let get_system_info: Symbol<extern "C" fn(*mut SystemInfo)> = library.get(b"retro_get_system_info")?;
+        let load_game: Symbol<extern "C" fn(*const GameInfo) -> bool> = library.get(b"retro_load_game")?;
+        let unload_game: Symbol<extern "C" fn()> = library.get(b"retro_unload_game")?;
+        let set_port_device: Symbol<extern "C" fn(c_uint, c_uint)> = library.get(b"retro_set_controller_port_device")?;
+        let run_frame: Symbol<extern "C" fn()> = library.get(b"retro_run")?;
+        
+        init();
+        let _deinit = OnDrop(deinit);
+        let mut info = SystemInfo {
+            library_name: std::ptr::null(),
+            library_version: std::ptr::null(),
+            valid_extensions: std::ptr::null(),
+            need_fullpath: false,
+            block_extract: false,
+        };
+        get_system_info(&mut info);
+        let text = |text: *const c_char| (!text.is_null()).then(|| CStr::from_ptr(text).to_string_lossy().into_owned());
+        
+        let path = CString::new(rom.to_string_lossy().as_bytes()).map_err(|err| LibretroError::Io(err.into()))?;
+        let data = if info.need_fullpath { vec![] } else { std::fs::read(rom)? };
+        let game = GameInfo {
+            path: path.as_ptr(),
+            data: if data.is_empty() { std::ptr::null() } else { data.as_ptr() as *const c_void },
+            size: data.len(),
+            meta: std::ptr::null(),
+        };
+        if !load_game(&game) {
+            return Err(LibretroError::LoadGame);
+        }
+        let _unload_game = OnDrop(unload_game);
+        for (port, _) in &options.controllers {
+            set_port_device(*port as c_uint - 1, RETRO_DEVICE_JOYPAD);
+        }
+        
+        dumper.begin_session(&DumpSession {
+            console: options.console,
+            game_title: None,
+            emulator_name: text(info.library_name),
+            emulator_version: text(info.library_version),
+            controllers: options.controllers.clone(),
+            start: StartCondition::PowerOn,
+        });
+        
+        let mut frame = 0;
+        while options.max_frames.is_none_or(|max| frame < max) {
+            let Some(inputs) = source.inputs(frame) else { break };
+            let ports: Vec<(u8, u16, Vec<u8>)> = options.controllers.iter()
+                .map(|(port, kind)| {
+                    let size = controller_input_size(*kind).unwrap_or(0);
+                    let mut data = inputs.iter().find(|(number, _)| number == port).map(|(_, data)| data.clone()).unwrap_or_default();
+                    data.resize(size, 0xFF);
+                    (*port, *kind, data)
+                })
+                .collect();
+            STATE.set(FrameState { ports, ..Default::default() });
+            
+            run_frame();
+            
+            let state = STATE.take();
+            let lag = state.is_lag(options.lag);
+            if lag {
+                dumper.on_lag_frame(frame);
+            } else {
+                let inputs: Vec<(u8, &[u8])> = state.ports.iter().map(|(port, _, data)| (*port, data.as_slice())).collect();
+                dumper.on_frame_inputs(frame, &inputs);
+            }
+            source.frame_done(frame, lag);
+            frame += 1;
+        }
+        dumper.end_session();
+        
+        Ok(frame)
+    }
+}
+
+/// Runs `rom` on the libretro core at `core` with inputs from `source`, and returns the resulting dump. See [`run`].
+/// 
+/// The ROM's file name is included as ROM_NAME.
+pub fn dump<P: AsRef<OsStr>, S: InputSource>(core: P, rom: &Path, options: &LibretroOptions, source: &mut S) -> Result<TasdFile, LibretroError> {
+    let mut dumper = TasdDumper::new();
+    run(core, rom, options, source, &mut dumper)?;
+    
+    let mut file = dumper.into_file();
+    if let Some(name) = rom.file_name() {
+        file.packets.insert(1, RomName { name: name.to_string_lossy().into_owned() }.into());
+    }
+    
+    Ok(file)
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::libretro::{joypad_state, FrameState, InputSource, LagHeuristic, MovieInputs, RETRO_DEVICE_ID_JOYPAD_MASK};
+    use crate::spec::packets::{InputChunk, PortController};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn joypad() {
+        // NES A and Start, SNES B and R
+        assert_eq!(joypad_state(0x0101, &[0x6F], 8), 1);
+        assert_eq!(joypad_state(0x0101, &[0x6F], 0), 0);
+        assert_eq!(joypad_state(0x0101, &[0x6F], RETRO_DEVICE_ID_JOYPAD_MASK), (1 << 8) | (1 << 3));
+        assert_eq!(joypad_state(0x0201, &[0x7F, 0xEF], RETRO_DEVICE_ID_JOYPAD_MASK), (1 << 0) | (1 << 11));
+        assert_eq!(joypad_state(0x0101, &[0x00], 9), 0);
+        
+        let state = FrameState { input_read: true, duped_video: true, ..Default::default() };
+        assert!(!state.is_lag(LagHeuristic::NoInputRead));
+        assert!(state.is_lag(LagHeuristic::NoInputReadOrDupedVideo));
+    }
+    
+    #[test]
+    fn movie_inputs() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x7F, 0xBF] }.into());
+        
+        let mut source = MovieInputs::new(&file);
+        assert_eq!(source.inputs(0), Some(vec![(1, vec![0x7F])]));
+        source.frame_done(0, true);
+        assert_eq!(source.inputs(1), Some(vec![(1, vec![0x7F])]));
+        source.frame_done(1, false);
+        assert_eq!(source.inputs(2), Some(vec![(1, vec![0xBF])]));
+        source.frame_done(2, false);
+        assert_eq!(source.inputs(3), None);
+    }
+}
\ No newline at end of file
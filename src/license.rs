@@ -0,0 +1,176 @@
+//! Whether the licenses of two movies allow combining them, e.g. before [merging][crate::merge] or splicing.
+//!
+//! Licenses are compared by broad category rather than by their full terms: works under "no derivatives" licenses
+//! can't be combined at all, works under different copyleft licenses can't be combined with each other, and copyleft
+//! works can't be combined with non-commercial ones. Permissive licenses combine with anything else. This errs on the
+//! side of reporting conflicts (e.g. GPL-2.0-or-later and GPL-3.0-only are treated as different copyleft licenses),
+//! and isn't legal advice.
+
+use spdx::{Expression, LicenseReq};
+use spdx::expression::{ExprNode, Operator};
+use crate::spec::packets::MovieLicense;
+use crate::spec::TasdFile;
+
+/// Result of [`license_compatibility`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseCompatibility {
+    Compatible,
+    /// Every choice of licenses conflicts. Contains the reason for the first choice.
+    Incompatible(String),
+    /// A license isn't on the SPDX list, or is missing, so it can't be compared.
+    Unknown(String),
+}
+
+/// Broad category of a license, which decides what it can be combined with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Category {
+    Permissive,
+    NonCommercial,
+    Copyleft,
+    NoDerivatives,
+}
+
+fn category(req: &LicenseReq) -> Option<Category> {
+    let id = req.license.id()?;
+    Some(if id.name.contains("-ND") {
+        Category::NoDerivatives
+    } else if id.is_copyleft() || id.name.contains("-SA") {
+        Category::Copyleft
+    } else if id.name.contains("-NC") {
+        Category::NonCommercial
+    } else {
+        Category::Permissive
+    })
+}
+
+/// Whether works under `a` and `b` can be combined.
+fn req_compatibility(a: &LicenseReq, b: &LicenseReq) -> LicenseCompatibility {
+    let (Some(a_category), Some(b_category)) = (category(a), category(b)) else {
+        let unknown = if category(a).is_none() { a } else { b };
+        return LicenseCompatibility::Unknown(format!("{unknown} isn't an SPDX license"));
+    };
+    if a_category == Category::NoDerivatives || b_category == Category::NoDerivatives {
+        let nd = if a_category == Category::NoDerivatives { a } else { b };
+        return LicenseCompatibility::Incompatible(format!("{nd} doesn't allow derivative works"));
+    }
+    if a == b {
+        return LicenseCompatibility::Compatible;
+    }
+    
+    match (a_category, b_category) {
+        (Category::Copyleft, Category::Copyleft) => LicenseCompatibility::Incompatible(format!("{a} and {b} are different copyleft licenses")),
+        (Category::Copyleft, Category::NonCommercial) | (Category::NonCommercial, Category::Copyleft) => {
+            LicenseCompatibility::Incompatible(format!("{a} and {b} can't be combined, as copyleft licenses don't allow non-commercial restrictions"))
+        },
+        _ => LicenseCompatibility::Compatible,
+    }
+}
+
+/// Each set of licenses which satisfies the expression on its own, i.e. the expression in disjunctive normal form.
+fn alternatives(expression: &Expression) -> Vec<Vec<LicenseReq>> {
+    let mut stack: Vec<Vec<Vec<LicenseReq>>> = vec![];
+    for node in expression.iter() {
+        match node {
+            ExprNode::Req(req) => stack.push(vec![vec![req.req.clone()]]),
+            ExprNode::Op(op) => {
+                let (Some(b), Some(a)) = (stack.pop(), stack.pop()) else { continue };
+                stack.push(match op {
+                    Operator::Or => a.into_iter().chain(b).collect(),
+                    Operator::And => a.iter().flat_map(|a| b.iter().map(move |b| a.iter().chain(b).cloned().collect())).collect(),
+                });
+            },
+        }
+    }
+    
+    stack.pop().unwrap_or_default()
+}
+
+/// Whether works licensed under `a` and `b` can be combined, for any choice of licenses allowed by `OR`s.
+/// 
+/// ```
+/// # use spdx::Expression;
+/// # use tasd::license::{license_compatibility, LicenseCompatibility};
+/// let compatibility = |a, b| license_compatibility(&Expression::parse(a).unwrap(), &Expression::parse(b).unwrap());
+/// assert_eq!(compatibility("CC-BY-4.0", "CC-BY-SA-4.0"), LicenseCompatibility::Compatible);
+/// assert_eq!(compatibility("CC-BY-SA-4.0 OR MIT", "GPL-3.0-only"), LicenseCompatibility::Compatible);
+/// assert!(matches!(compatibility("CC-BY-ND-4.0", "CC0-1.0"), LicenseCompatibility::Incompatible(_)));
+/// ```
+pub fn license_compatibility(a: &Expression, b: &Expression) -> LicenseCompatibility {
+    let mut result = None;
+    for a in alternatives(a) {
+        for b in alternatives(b) {
+            let conflict = a.iter()
+                .flat_map(|a| b.iter().map(move |b| req_compatibility(a, b)))
+                .find(|compatibility| *compatibility != LicenseCompatibility::Compatible);
+            let Some(conflict) = conflict else { return LicenseCompatibility::Compatible };
+            
+            // an unknown license might still turn out to be compatible, so it's reported over an incompatible one
+            let is_unknown = |result: &LicenseCompatibility| matches!(result, LicenseCompatibility::Unknown(_));
+            if result.as_ref().is_none_or(|result| !is_unknown(result) && is_unknown(&conflict)) {
+                result = Some(conflict);
+            }
+        }
+    }
+    
+    result.unwrap_or(LicenseCompatibility::Compatible)
+}
+
+impl TasdFile {
+    /// Whether this movie's license allows combining it with `other`. Files with more than one MOVIE_LICENSE must meet
+    /// all of them.
+    /// 
+    /// Returns [LicenseCompatibility::Unknown] if either file has no MOVIE_LICENSE, or one isn't a valid SPDX expression.
+    pub fn license_compatibility(&self, other: &TasdFile) -> LicenseCompatibility {
+        match (self.license_expression(), other.license_expression()) {
+            (Ok(a), Ok(b)) => license_compatibility(&a, &b),
+            (Err(reason), _) | (_, Err(reason)) => LicenseCompatibility::Unknown(reason),
+        }
+    }
+    
+    /// Every MOVIE_LICENSE of the file, joined with `AND`.
+    fn license_expression(&self) -> Result<Expression, String> {
+        let licenses: Vec<String> = self.all::<MovieLicense>().map(|license| format!("({})", license.license)).collect();
+        if licenses.is_empty() {
+            return Err("no MOVIE_LICENSE".into());
+        }
+        
+        Expression::parse(&licenses.join(" AND ")).map_err(|err| format!("license is not a valid SPDX expression ({})", err.reason))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use spdx::Expression;
+    use crate::license::{license_compatibility, LicenseCompatibility};
+    use crate::spec::packets::MovieLicense;
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn compatibility() {
+        let compatibility = |a, b| license_compatibility(&Expression::parse(a).unwrap(), &Expression::parse(b).unwrap());
+        assert_eq!(compatibility("CC0-1.0", "CC-BY-NC-4.0"), LicenseCompatibility::Compatible);
+        assert_eq!(compatibility("GPL-3.0-only", "GPL-3.0-only"), LicenseCompatibility::Compatible);
+        assert!(matches!(compatibility("GPL-2.0-only", "CC-BY-SA-4.0"), LicenseCompatibility::Incompatible(_)));
+        assert!(matches!(compatibility("CC-BY-NC-SA-4.0", "CC-BY-SA-4.0"), LicenseCompatibility::Incompatible(_)));
+        assert!(matches!(compatibility("CC-BY-SA-4.0", "CC-BY-NC-4.0"), LicenseCompatibility::Incompatible(_)));
+        assert!(matches!(compatibility("CC-BY-ND-4.0", "CC-BY-ND-4.0"), LicenseCompatibility::Incompatible(_)));
+        
+        // every license of an AND has to be compatible, but only one of an OR
+        assert!(matches!(compatibility("MIT AND CC-BY-SA-4.0", "GPL-3.0-only"), LicenseCompatibility::Incompatible(_)));
+        assert_eq!(compatibility("(MIT AND CC-BY-SA-4.0) OR CC0-1.0", "GPL-3.0-only"), LicenseCompatibility::Compatible);
+        assert!(matches!(compatibility("LicenseRef-custom", "MIT"), LicenseCompatibility::Unknown(_)));
+        
+        let mut a = TasdFile::default();
+        let mut b = TasdFile::default();
+        assert!(matches!(a.license_compatibility(&b), LicenseCompatibility::Unknown(_)));
+        a.packets.push(MovieLicense { license: "CC-BY-SA-4.0".into() }.into());
+        b.packets.push(MovieLicense { license: "CC0-1.0 OR GPL-3.0-only".into() }.into());
+        assert_eq!(a.license_compatibility(&b), LicenseCompatibility::Compatible);
+        b.packets.push(MovieLicense { license: "GPL-3.0-only".into() }.into());
+        assert!(matches!(a.license_compatibility(&b), LicenseCompatibility::Incompatible(_)));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,185 @@
+//! Linked multi-console sessions, such as two Game Boys connected by a link cable.
+//! 
+//! A linked dump is a single file with a [LinkSession] packet listing each console. Port numbers are namespaced by
+//! console: the upper nibble is the console's index into [`LinkSession::consoles`], and the lower nibble is the port
+//! number on that console. Console 0 therefore uses the normal port numbers, so each console can hold up to 15 ports.
+//! 
+//! Packets which aren't tied to a port (metadata, lag frames, etc.) apply to the session as a whole.
+
+use crate::spec::packets::{ConsoleType, LinkSession, Packet};
+use crate::spec::TasdFile;
+
+/// Number of port numbers reserved for each console.
+pub const PORTS_PER_CONSOLE: u8 = 0x10;
+
+/// Why [`TasdFile::link`] couldn't combine a set of files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// More consoles than can be numbered in the upper nibble of a port number.
+    TooManyConsoles(usize),
+    /// A port number which doesn't fit in the lower nibble, so it would collide with the ports of another console.
+    PortOutOfRange {
+        console: u8,
+        port: u8,
+    },
+}
+
+/// Namespaced port number of `port` on the given console, or `None` if either doesn't fit in its nibble.
+/// 
+/// ```
+/// use tasd::link::{linked_port, split_port};
+/// 
+/// assert_eq!(linked_port(1, 1), Some(0x11));
+/// assert_eq!(linked_port(1, 0x10), None);
+/// assert_eq!(split_port(0x11), (1, 1));
+/// ```
+pub fn linked_port(console: u8, port: u8) -> Option<u8> {
+    if console >= PORTS_PER_CONSOLE || port >= PORTS_PER_CONSOLE {
+        return None;
+    }
+    
+    console.checked_mul(PORTS_PER_CONSOLE)?.checked_add(port)
+}
+
+/// Splits a namespaced port number into its console index and local port number.
+pub fn split_port(port: u8) -> (u8, u8) {
+    (port / PORTS_PER_CONSOLE, port % PORTS_PER_CONSOLE)
+}
+
+/// Port number of a packet tied to a single port, if any.
+pub(crate) fn port(packet: &Packet) -> Option<u8> {
+    match packet {
+        Packet::PortController(packet) => Some(packet.port),
+        Packet::PortOverread(packet) => Some(packet.port),
+        Packet::InputChunk(packet) => Some(packet.port),
+        Packet::InputMoment(packet) => Some(packet.port),
+        Packet::Transition(packet) if packet.port != 0 => Some(packet.port),
+        _ => None,
+    }
+}
+
+/// Same as [port], but allows the port number to be changed.
+fn port_mut(packet: &mut Packet) -> Option<&mut u8> {
+    match packet {
+        Packet::PortController(packet) => Some(&mut packet.port),
+        Packet::PortOverread(packet) => Some(&mut packet.port),
+        Packet::InputChunk(packet) => Some(&mut packet.port),
+        Packet::InputMoment(packet) => Some(&mut packet.port),
+        Packet::Transition(packet) if packet.port != 0 => Some(&mut packet.port),
+        _ => None,
+    }
+}
+
+impl TasdFile {
+    /// Whether this file is a linked multi-console session.
+    pub fn is_linked(&self) -> bool {
+        self.first::<LinkSession>().is_some()
+    }
+    
+    /// Combines one file per console into a single linked session.
+    /// 
+    /// Metadata is taken from the first file, and each file's port packets are namespaced to its console. Consoles
+    /// without a CONSOLE_TYPE are listed as custom (`0xFF`).
+    /// 
+    /// Fails if there are more than [PORTS_PER_CONSOLE] files, or a file uses a port number of [PORTS_PER_CONSOLE] or
+    /// more.
+    pub fn link(files: &[TasdFile]) -> Result<TasdFile, LinkError> {
+        if files.len() > PORTS_PER_CONSOLE as usize {
+            return Err(LinkError::TooManyConsoles(files.len()));
+        }
+        
+        let mut linked = TasdFile::default();
+        let consoles = files.iter().map(|file| file.first::<ConsoleType>().map(|console| console.kind).unwrap_or(0xFF)).collect();
+        linked.packets.push(LinkSession { consoles }.into());
+        
+        for (console, file) in files.iter().enumerate() {
+            for packet in &file.packets {
+                let mut packet = packet.clone();
+                match port_mut(&mut packet) {
+                    Some(port) => *port = linked_port(console as u8, *port).ok_or(LinkError::PortOutOfRange { console: console as u8, port: *port })?,
+                    None if console > 0 => continue,
+                    None => (),
+                }
+                linked.packets.push(packet);
+            }
+        }
+        if let Some(first) = files.first() {
+            linked.version = first.version;
+            linked.keylen = first.keylen;
+        }
+        linked.order_configuration();
+        
+        Ok(linked)
+    }
+    
+    /// Splits a linked session into one file per console, with normal port numbers and that console's CONSOLE_TYPE.
+    /// 
+    /// Packets which aren't tied to a port are copied to every file. Returns `None` if this file isn't linked.
+    pub fn split_linked(&self) -> Option<Vec<TasdFile>> {
+        let session = self.first::<LinkSession>()?;
+        
+        Some(session.consoles.iter().enumerate().map(|(console, kind)| {
+            let mut file = TasdFile { version: self.version, keylen: self.keylen, packets: vec![], path: None };
+            for packet in &self.packets {
+                if matches!(packet, Packet::LinkSession(_) | Packet::ConsoleType(_)) {
+                    continue;
+                }
+                let mut packet = packet.clone();
+                match port_mut(&mut packet) {
+                    Some(port) if split_port(*port).0 as usize != console => continue,
+                    Some(port) => *port = split_port(*port).1,
+                    None => (),
+                }
+                file.packets.push(packet);
+            }
+            file.packets.insert(0, ConsoleType { kind: *kind, custom: None }.into());
+            
+            file
+        }).collect())
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::link::LinkError;
+    use crate::spec::packets::{ConsoleType, InputChunk, LinkSession, PortController};
+    use crate::spec::TasdFile;
+    
+    fn console(kind: u8, inputs: Vec<u8>) -> TasdFile {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0501 }.into());
+        file.packets.push(InputChunk { port: 1, inputs }.into());
+        
+        file
+    }
+    
+    #[test]
+    fn link() {
+        let files = vec![console(0x05, vec![0xFF; 3]), console(0x06, vec![0x7F; 3])];
+        let linked = TasdFile::link(&files).unwrap();
+        assert!(linked.is_linked());
+        assert_eq!(linked.first::<LinkSession>().unwrap().consoles, vec![0x05, 0x06]);
+        assert_eq!(linked.port_inputs(0x11), vec![0x7F; 3]);
+        assert!(linked.validate().is_empty());
+        
+        let split = linked.split_linked().unwrap();
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].encode(), files[0].encode());
+        assert_eq!(split[1].encode(), files[1].encode());
+        
+        let mut invalid = linked.clone();
+        invalid.packets.push(InputChunk { port: 0x11, inputs: vec![0xFF] }.into());
+        invalid.packets.push(InputChunk { port: 0x21, inputs: vec![0xFF] }.into());
+        assert_eq!(invalid.validate().len(), 2);
+        
+        let mut wide = console(0x05, vec![0xFF]);
+        wide.packets.push(InputChunk { port: 0x10, inputs: vec![0xFF] }.into());
+        assert_eq!(TasdFile::link(&[console(0x05, vec![0xFF]), wide]), Err(LinkError::PortOutOfRange { console: 1, port: 0x10 }));
+        assert_eq!(TasdFile::link(&vec![console(0x05, vec![0xFF]); 17]), Err(LinkError::TooManyConsoles(17)));
+    }
+}
\ No newline at end of file
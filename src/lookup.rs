@@ -132,6 +132,26 @@ pub fn controller_type_lut(kind: u16) -> Option<String> {
     }.into())
 }
 
+/// Number of bytes of input data each frame, for a given controller type.
+/// 
+/// Returns `None` for unknown, reserved, or variable-width controller types.
+pub fn controller_input_size(kind: u16) -> Option<usize> {
+    Some(match kind {
+        0x0101 => 1,
+        0x0201 => 2,
+        0x0203 => 4,
+        0x0301..=0x0305 | 0x0308 => 4,
+        0x0401 => 8,
+        0x0501 | 0x0601 => 1,
+        0x0701 => 2,
+        0x0801 => 1,
+        0x0802 => 2,
+        0x0901 => 1,
+        0x0903 => 2,
+        _ => return None
+    })
+}
+
 pub fn input_moment_lut(kind: u8) -> Option<String> {
     Some(match kind {
         0x01 => "Frame",
@@ -161,4 +181,78 @@ pub fn transition_kind_lut(kind: u8) -> Option<String> {
         0xFF => "Packet Derived",
         _ => return None
     }.into())
+}
+
+pub fn random_seed_algorithm_lut(kind: u8) -> Option<String> {
+    Some(match kind {
+        0x01 => "SplitMix64",
+        _ => return None
+    }.into())
+}
+
+/// Name of a CONSOLE_SETTING. The high byte of `setting` is the CONSOLE_TYPE it applies to, or `0x00` for any console.
+pub fn console_setting_lut(setting: u16) -> Option<String> {
+    Some(match setting {
+        0x0001 => "Video Output",
+        0x0101 => "CIC Lockout",
+        0x0102 => "Console Model",
+        0x0201 => "Region Lockout",
+        0x0202 => "CPU Revision",
+        0x0801 => "TMSS",
+        0x0802 => "Region Jumper",
+        0x0803 => "Video Jumper",
+        _ => return None
+    }.into())
+}
+
+/// Name of the `value` of a CONSOLE_SETTING, or `None` if the setting or value isn't known.
+pub fn console_setting_value_lut(setting: u16, value: u8) -> Option<String> {
+    Some(match (setting, value) {
+        (0x0001, 0x01) => "RF",
+        (0x0001, 0x02) => "Composite",
+        (0x0001, 0x03) => "S-Video",
+        (0x0001, 0x04) => "RGB",
+        (0x0001, 0x05) => "Component",
+        (0x0001, 0x06) => "HDMI",
+        (0x0101, 0x00) => "Enabled",
+        (0x0101, 0x01) => "Disabled",
+        (0x0102, 0x01) => "Front-Loader (NES-001)",
+        (0x0102, 0x02) => "Top-Loader (NES-101)",
+        (0x0102, 0x03) => "Famicom",
+        (0x0102, 0x04) => "AV Famicom",
+        (0x0201, 0x00) => "Enforced",
+        (0x0201, 0x01) => "Bypassed",
+        (0x0202, 0x01) => "5A22-01",
+        (0x0202, 0x02) => "5A22-02",
+        (0x0202, 0x03) => "5A22-03",
+        (0x0801, 0x00) => "Not Present",
+        (0x0801, 0x01) => "Present",
+        (0x0802, 0x00) => "Domestic",
+        (0x0802, 0x01) => "Overseas",
+        (0x0803, 0x00) => "60 Hz",
+        (0x0803, 0x01) => "50 Hz",
+        _ => return None
+    }.into())
+}
+
+pub fn savestate_anchor_lut(kind: u8) -> Option<String> {
+    Some(match kind {
+        0x01 => "Emulator Savestate",
+        0x02 => "Cartridge Save Data",
+        0x03 => "Console Memory Snapshot",
+        0xFF => "Other",
+        _ => return None
+    }.into())
+}
+
+/// Names of the buttons of a controller type, in the order of the bits of its input data (most significant bit of the
+/// first byte first). Bits past the end of the list are unused.
+/// 
+/// A cleared bit means the button is pressed. Returns `None` for controller types without a known button layout.
+pub fn controller_buttons(kind: u16) -> Option<&'static [&'static str]> {
+    Some(match kind {
+        0x0101 => &["A", "B", "Select", "Start", "Up", "Down", "Left", "Right"],
+        0x0201 => &["B", "Y", "Select", "Start", "Up", "Down", "Left", "Right", "A", "X", "L", "R"],
+        _ => return None
+    })
 }
\ No newline at end of file
@@ -0,0 +1,260 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::spec::packets::{ConsoleType, MemoryInit, Packet, RandomSeed};
+use crate::spec::TasdFile;
+
+/// Initial contents of a memory device, as described by the data type of a MEMORY_INIT packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryInitData {
+    /// `0x01`: the device doesn't need to be initialized, so its contents are unknown.
+    Unspecified,
+    /// `0x02`: all `0x00`.
+    Zeros,
+    /// `0x03`: all `0xFF`.
+    Ones,
+    /// `0x04`: `00 00 00 00 FF FF FF FF`, repeating.
+    Alternating,
+    /// `0x05`: pseudo-random bytes generated from a seed, as recorded by a RANDOM_SEED packet.
+    Random {
+        algorithm: u8,
+        seed: u64,
+    },
+    /// `0xFF`: data embedded in the packet.
+    Custom(Vec<u8>),
+}
+impl MemoryInitData {
+    /// Materializes `len` bytes of memory contents.
+    /// 
+    /// Custom data is truncated or padded with `0x00` to fit. Returns `None` for [`Self::Unspecified`], or for random data
+    /// from an unknown algorithm.
+    pub fn generate(&self, len: usize) -> Option<Vec<u8>> {
+        Some(match self {
+            Self::Unspecified => return None,
+            Self::Zeros => vec![0x00; len],
+            Self::Ones => vec![0xFF; len],
+            Self::Alternating => (0..len).map(|i| if i % 8 < 4 { 0x00 } else { 0xFF }).collect(),
+            Self::Random { algorithm: 0x01, seed } => {
+                let mut rng = SplitMix64::new(*seed);
+                let mut data: Vec<u8> = (0..len.div_ceil(8)).flat_map(|_| rng.next_u64().to_le_bytes()).collect();
+                data.truncate(len);
+                data
+            },
+            Self::Random { .. } => return None,
+            Self::Custom(data) => {
+                let mut data = data.clone();
+                data.resize(len, 0x00);
+                data
+            },
+        })
+    }
+}
+
+impl MemoryInit {
+    /// Initial memory contents described by this packet, using `seed` for random data.
+    /// 
+    /// Returns `None` if the data type isn't known, or the data is random and there's no seed to reproduce it with.
+    pub fn init_data(&self, seed: Option<&RandomSeed>) -> Option<MemoryInitData> {
+        Some(match self.data_type {
+            0x01 => MemoryInitData::Unspecified,
+            0x02 => MemoryInitData::Zeros,
+            0x03 => MemoryInitData::Ones,
+            0x04 => MemoryInitData::Alternating,
+            0x05 => seed.map(|seed| MemoryInitData::Random { algorithm: seed.algorithm, seed: seed.seed })?,
+            0xFF => MemoryInitData::Custom(self.data.clone().unwrap_or_default()),
+            _ => return None,
+        })
+    }
+}
+
+impl TasdFile {
+    /// Initial memory contents described by a MEMORY_INIT packet, using the RANDOM_SEED packet for its device (if any).
+    pub fn memory_init_data(&self, init: &MemoryInit) -> Option<MemoryInitData> {
+        init.init_data(self.all::<RandomSeed>().find(|seed| seed.device == init.device))
+    }
+}
+
+/// Save memory of a cartridge or accessory, whose initial contents can be stored as MEMORY_INIT custom data and
+/// written back out for flashing onto the real device before verification.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Peripheral {
+    /// N64 Controller Pak plugged into a port.
+    ControllerPak(u8),
+    Eeprom,
+    Sram,
+    Flash,
+}
+impl Peripheral {
+    /// MEMORY_INIT device code of this peripheral on the given CONSOLE_TYPE.
+    /// 
+    /// Cartridge save memory uses the console's "Cartridge Save Data" device. Devices without a code in the spec (N64
+    /// save memory and Controller Paks) use `0xFFFF` "Custom/Other Device", and are told apart by [`Self::name`].
+    pub fn device(&self, console: u8) -> u16 {
+        match (self, console) {
+            (Self::ControllerPak(_), _) => 0xFFFF,
+            (_, 0x01 | 0x02 | 0x05..=0x09) => u16::from_be_bytes([console, 0x02]),
+            _ => 0xFFFF,
+        }
+    }
+    
+    /// Name of the MEMORY_INIT packet for this peripheral.
+    pub fn name(&self) -> String {
+        match self {
+            Self::ControllerPak(port) => format!("Controller Pak {port}"),
+            Self::Eeprom => "EEPROM".into(),
+            Self::Sram => "SRAM".into(),
+            Self::Flash => "Flash".into(),
+        }
+    }
+    
+    /// File name the contents are written to by [`TasdFile::extract_peripherals`], using the extensions most N64 tools
+    /// and flashers expect.
+    pub fn file_name(&self) -> String {
+        match self {
+            Self::ControllerPak(port) => format!("controller_pak_{port}.mpk"),
+            Self::Eeprom => "save.eep".into(),
+            Self::Sram => "save.sra".into(),
+            Self::Flash => "save.fla".into(),
+        }
+    }
+    
+    fn matches(&self, init: &MemoryInit, console: u8) -> bool {
+        init.device == self.device(console) && init.name == self.name()
+    }
+}
+
+impl TasdFile {
+    /// Sets the initial contents of `peripheral` by adding a MEMORY_INIT packet with custom data, or replacing the data
+    /// of an existing one. New packets are added before the input data, as required of configuration packets.
+    pub fn attach_peripheral(&mut self, peripheral: Peripheral, data: Vec<u8>) {
+        let console = self.console_kind();
+        let existing = self.packets.iter_mut().find_map(|packet| match packet {
+            Packet::MemoryInit(init) if peripheral.matches(init, console) => Some(init),
+            _ => None,
+        });
+        if let Some(init) = existing {
+            init.data_type = 0xFF;
+            init.data = Some(data);
+            return;
+        }
+        
+        let init = MemoryInit { data_type: 0xFF, device: peripheral.device(console), required: true, name: peripheral.name(), data: Some(data) };
+        let index = self.packets.iter().position(|packet| packet.kind().is_input()).unwrap_or(self.packets.len());
+        self.packets.insert(index, init.into());
+    }
+    
+    /// Initial contents of `peripheral`, if the file has a MEMORY_INIT packet with custom data for it.
+    pub fn peripheral_data(&self, peripheral: Peripheral) -> Option<&[u8]> {
+        let console = self.console_kind();
+        self.all::<MemoryInit>()
+            .find(|init| init.data_type == 0xFF && peripheral.matches(init, console))
+            .and_then(|init| init.data.as_deref())
+    }
+    
+    /// Writes the initial contents of every peripheral with custom data to `dir`, named by [`Peripheral::file_name`].
+    /// 
+    /// Returns the paths of the written files.
+    pub fn extract_peripherals<P: AsRef<Path>>(&self, dir: P) -> io::Result<Vec<PathBuf>> {
+        let ports = (1..=4).map(Peripheral::ControllerPak);
+        let mut written = vec![];
+        for peripheral in [Peripheral::Eeprom, Peripheral::Sram, Peripheral::Flash].into_iter().chain(ports) {
+            if let Some(data) = self.peripheral_data(peripheral) {
+                let path = dir.as_ref().join(peripheral.file_name());
+                std::fs::write(&path, data)?;
+                written.push(path);
+            }
+        }
+        
+        Ok(written)
+    }
+    
+    fn console_kind(&self) -> u8 {
+        self.first::<ConsoleType>().map(|console| console.kind).unwrap_or(0xFF)
+    }
+}
+
+/// SplitMix64 pseudo-random number generator, used for random memory contents.
+/// 
+/// It's tiny and fully specified, so other implementations can reproduce the exact same bytes from a seed.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{MemoryInitData, Peripheral, SplitMix64};
+    use crate::spec::packets::{ConsoleType, InputChunk, MemoryInit, RandomSeed};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn generate() {
+        assert_eq!(MemoryInitData::Unspecified.generate(4), None);
+        assert_eq!(MemoryInitData::Ones.generate(3), Some(vec![0xFF; 3]));
+        assert_eq!(MemoryInitData::Alternating.generate(10), Some(vec![0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]));
+        assert_eq!(MemoryInitData::Custom(vec![1, 2, 3]).generate(2), Some(vec![1, 2]));
+        assert_eq!(MemoryInitData::Custom(vec![1]).generate(2), Some(vec![1, 0]));
+        
+        // reference values of SplitMix64 with a seed of 0
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220A8397B1DCDAF);
+        assert_eq!(rng.next_u64(), 0x6E789E6AA1B965F4);
+        
+        let random = MemoryInitData::Random { algorithm: 0x01, seed: 1234 }.generate(13).unwrap();
+        assert_eq!(random.len(), 13);
+        assert_eq!(MemoryInitData::Random { algorithm: 0x01, seed: 1234 }.generate(13).unwrap(), random);
+        assert_ne!(MemoryInitData::Random { algorithm: 0x01, seed: 1235 }.generate(13).unwrap(), random);
+        assert_eq!(MemoryInitData::Random { algorithm: 0x02, seed: 1234 }.generate(13), None);
+        
+        let init = MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "".into(), data: None };
+        assert_eq!(init.init_data(None), Some(MemoryInitData::Zeros));
+        assert_eq!(MemoryInit { data_type: 0x06, ..init.clone() }.init_data(None), None);
+        
+        let mut file = TasdFile::default();
+        let init = MemoryInit { data_type: 0x05, ..init };
+        file.packets.push(init.clone().into());
+        assert_eq!(file.memory_init_data(&init), None);
+        file.packets.push(RandomSeed { device: 0x0102, algorithm: 0x01, seed: 7 }.into());
+        file.packets.push(RandomSeed { device: 0x0101, algorithm: 0x01, seed: 1234 }.into());
+        assert_eq!(file.memory_init_data(&init), Some(MemoryInitData::Random { algorithm: 0x01, seed: 1234 }));
+    }
+    
+    #[test]
+    fn peripherals() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x03, custom: None }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 4] }.into());
+        file.attach_peripheral(Peripheral::Eeprom, vec![1; 512]);
+        file.attach_peripheral(Peripheral::ControllerPak(2), vec![2; 4]);
+        file.attach_peripheral(Peripheral::Eeprom, vec![3; 512]);
+        
+        assert_eq!(file.packets.len(), 4);
+        assert_eq!(file.first::<MemoryInit>().unwrap().device, 0xFFFF);
+        assert!(file.packets[3].kind().is_input());
+        assert_eq!(file.peripheral_data(Peripheral::Eeprom), Some(&[3; 512][..]));
+        assert_eq!(file.peripheral_data(Peripheral::ControllerPak(1)), None);
+        assert_eq!(Peripheral::Sram.device(0x02), 0x0202);
+        
+        let dir = std::env::temp_dir().join(format!("tasd-peripherals-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let written = file.extract_peripherals(&dir).unwrap();
+        assert_eq!(written, [dir.join("save.eep"), dir.join("controller_pak_2.mpk")]);
+        assert_eq!(std::fs::read(&written[1]).unwrap(), vec![2; 4]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter};
+use crate::lookup::console_type_lut;
+use crate::spec::packets::{ConsoleType, Packet, PacketKind, Rerecords};
+use crate::spec::TasdFile;
+#[cfg(feature = "spdx")]
+use crate::license::LicenseCompatibility;
+
+/// How RERECORDS counts are combined when merging two files.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum RerecordsPolicy {
+    /// Add both counts together. Appropriate when the movies were produced independently (e.g. joining segments).
+    #[default]
+    Sum,
+    /// Use the larger count. Appropriate when one movie was derived from the other (e.g. re-dumps or conversions).
+    Max,
+    /// Keep the count of the file being merged into.
+    KeepExisting,
+    /// Use the count of the file being merged from.
+    KeepIncoming,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeOptions {
+    pub rerecords: RerecordsPolicy,
+}
+
+/// Reason to think twice before combining two files, from [`TasdFile::merge_warnings`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MergeWarning {
+    /// The files are for different consoles.
+    ConsoleMismatch {
+        ours: u8,
+        theirs: u8,
+    },
+    /// The movies' licenses don't allow combining them, see [crate::license].
+    #[cfg(feature = "spdx")]
+    IncompatibleLicenses(String),
+}
+impl Display for MergeWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let console = |kind: u8| console_type_lut(kind).unwrap_or_else(|| format!("{kind:#04X}"));
+        match self {
+            MergeWarning::ConsoleMismatch { ours, theirs } => write!(f, "files are for different consoles ({} and {})", console(*ours), console(*theirs)),
+            #[cfg(feature = "spdx")]
+            MergeWarning::IncompatibleLicenses(reason) => write!(f, "licenses are incompatible: {reason}"),
+        }
+    }
+}
+
+impl Rerecords {
+    /// Sum of both counts, saturating at `u32::MAX`.
+    pub fn sum(&self, other: &Rerecords) -> Rerecords {
+        Rerecords { rerecords: self.rerecords.saturating_add(other.rerecords) }
+    }
+    
+    pub fn max(&self, other: &Rerecords) -> Rerecords {
+        Rerecords { rerecords: self.rerecords.max(other.rerecords) }
+    }
+    
+    pub fn combine(&self, other: &Rerecords, policy: RerecordsPolicy) -> Rerecords {
+        match policy {
+            RerecordsPolicy::Sum => self.sum(other),
+            RerecordsPolicy::Max => self.max(other),
+            RerecordsPolicy::KeepExisting => self.clone(),
+            RerecordsPolicy::KeepIncoming => other.clone(),
+        }
+    }
+}
+
+/// Whether packets of this kind describe the movie as a whole, rather than its inputs or
+/// [console configuration][PacketKind::is_configuration].
+pub(crate) fn is_metadata(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    !kind.is_configuration() && matches!(kind,
+        GameTitle | RomName | Attribution | Category | EmulatorName | EmulatorVersion | EmulatorCore | TasLastModified
+        | DumpCreated | DumpLastModified | TotalFrames | Rerecords | SourceLink | BlankFrames | Verified
+        | GameIdentifier | MovieLicense | MovieFile | Comment
+    )
+}
+
+/// Whether a file may contain more than one packet of this (metadata) kind.
+fn is_repeatable(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    matches!(kind, Attribution | SourceLink | GameIdentifier | MovieFile | Comment)
+}
+
+impl TasdFile {
+    /// Problems with combining this file with `other`, either by [merging metadata][Self::merge_metadata] or by
+    /// [splicing][Self::splice] inputs. Neither refuses to combine files with warnings, so callers should check first.
+    /// 
+    /// Licenses are only checked with the `spdx` feature. Files whose license compatibility is
+    /// [unknown][LicenseCompatibility::Unknown] (e.g. they have no MOVIE_LICENSE) aren't warned about.
+    pub fn merge_warnings(&self, other: &TasdFile) -> Vec<MergeWarning> {
+        let mut warnings = vec![];
+        if let (Some(ours), Some(theirs)) = (self.first::<ConsoleType>(), other.first::<ConsoleType>()) {
+            if ours.kind != theirs.kind {
+                warnings.push(MergeWarning::ConsoleMismatch { ours: ours.kind, theirs: theirs.kind });
+            }
+        }
+        #[cfg(feature = "spdx")]
+        if let LicenseCompatibility::Incompatible(reason) = self.license_compatibility(other) {
+            warnings.push(MergeWarning::IncompatibleLicenses(reason));
+        }
+        
+        warnings
+    }
+    
+    /// Merges the metadata packets of `other` into this file. Input and console configuration packets are not affected.
+    /// 
+    /// Repeatable metadata (e.g. ATTRIBUTION or COMMENT) from `other` is added unless an identical packet already exists.
+    /// Other metadata is only added if this file doesn't already have a packet of that kind, with the exception of
+    /// RERECORDS which are combined according to [`MergeOptions::rerecords`].
+    pub fn merge_metadata(&mut self, other: &TasdFile, options: &MergeOptions) {
+        let mut insert = self.packets.iter()
+            .rposition(|packet| is_metadata(packet.kind()))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        
+        for packet in other.packets.iter().filter(|packet| is_metadata(packet.kind())) {
+            let kind = packet.kind();
+            let existing = self.packets.iter().position(|existing| existing.kind() == kind);
+            
+            match (packet, existing) {
+                (Packet::Rerecords(incoming), Some(i)) => if let Packet::Rerecords(rerecords) = &self.packets[i] {
+                    self.packets[i] = rerecords.combine(incoming, options.rerecords).into();
+                },
+                (_, Some(_)) if !is_repeatable(kind) => (),
+                _ => if !self.packets.contains(packet) {
+                    self.packets.insert(insert, packet.clone());
+                    insert += 1;
+                },
+            }
+        }
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{MergeOptions, MergeWarning, RerecordsPolicy};
+    use crate::spec::packets::{Attribution, ConsoleType, GameTitle, InputChunk, MemoryInit, MovieLicense, Packet, RandomSeed, Rerecords};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn rerecords() {
+        let a = Rerecords { rerecords: 100 };
+        let b = Rerecords { rerecords: 250 };
+        assert_eq!(a.combine(&b, RerecordsPolicy::Sum).rerecords, 350);
+        assert_eq!(a.combine(&b, RerecordsPolicy::Max).rerecords, 250);
+        assert_eq!(a.combine(&b, RerecordsPolicy::KeepExisting).rerecords, 100);
+        assert_eq!(a.combine(&b, RerecordsPolicy::KeepIncoming).rerecords, 250);
+        assert_eq!(Rerecords { rerecords: u32::MAX }.sum(&b).rerecords, u32::MAX);
+    }
+    
+    #[test]
+    fn merge_metadata() {
+        let mut a = TasdFile::default();
+        a.packets.push(GameTitle { title: "A".into() }.into());
+        a.packets.push(Attribution { kind: 0x01, name: "first".into() }.into());
+        a.packets.push(Rerecords { rerecords: 10 }.into());
+        a.packets.push(InputChunk { port: 1, inputs: vec![0x00] }.into());
+        
+        let mut b = TasdFile::default();
+        b.packets.push(GameTitle { title: "B".into() }.into());
+        b.packets.push(Attribution { kind: 0x01, name: "first".into() }.into());
+        b.packets.push(Attribution { kind: 0x01, name: "second".into() }.into());
+        b.packets.push(Rerecords { rerecords: 5 }.into());
+        b.packets.push(InputChunk { port: 1, inputs: vec![0xFF] }.into());
+        
+        let mut merged = a.clone();
+        merged.merge_metadata(&b, &MergeOptions::default());
+        assert_eq!(merged.packets, vec![
+            Packet::from(GameTitle { title: "A".into() }),
+            Attribution { kind: 0x01, name: "first".into() }.into(),
+            Rerecords { rerecords: 15 }.into(),
+            Attribution { kind: 0x01, name: "second".into() }.into(),
+            InputChunk { port: 1, inputs: vec![0x00] }.into(),
+        ]);
+        
+        let mut merged = a.clone();
+        merged.merge_metadata(&b, &MergeOptions { rerecords: RerecordsPolicy::Max });
+        assert_eq!(merged.packets[2], Rerecords { rerecords: 10 }.into());
+    }
+    
+    #[test]
+    fn configuration_not_merged() {
+        let mut a = TasdFile::default();
+        a.packets.push(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "RAM".into(), data: None }.into());
+        a.packets.push(RandomSeed { device: 0x0101, algorithm: 0x01, seed: 1 }.into());
+        
+        let mut b = TasdFile::default();
+        b.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        b.packets.push(MemoryInit { data_type: 0x03, device: 0x0101, required: false, name: "SRAM".into(), data: None }.into());
+        b.packets.push(RandomSeed { device: 0x0101, algorithm: 0x01, seed: 2 }.into());
+        b.packets.push(GameTitle { title: "B".into() }.into());
+        
+        let mut merged = a.clone();
+        merged.merge_metadata(&b, &MergeOptions::default());
+        assert_eq!(merged.packets, [vec![GameTitle { title: "B".into() }.into()], a.packets.clone()].concat());
+    }
+    
+    #[test]
+    fn merge_warnings() {
+        let mut a = TasdFile::default();
+        a.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        a.packets.push(MovieLicense { license: "CC-BY-SA-4.0".into() }.into());
+        let mut b = a.clone();
+        assert!(a.merge_warnings(&b).is_empty());
+        
+        b.packets[0] = ConsoleType { kind: 0x02, custom: None }.into();
+        b.packets[1] = MovieLicense { license: "CC-BY-NC-4.0".into() }.into();
+        let warnings = a.merge_warnings(&b);
+        assert_eq!(warnings[0], MergeWarning::ConsoleMismatch { ours: 0x01, theirs: 0x02 });
+        #[cfg(feature = "spdx")]
+        assert!(matches!(warnings[1], MergeWarning::IncompatibleLicenses(_)));
+    }
+}
\ No newline at end of file
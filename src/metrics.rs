@@ -0,0 +1,171 @@
+//! Parse statistics for long-running services embedding this crate, e.g. for exporting to Prometheus.
+//!
+//! Counts are reported to a [ParseMetrics], which is small enough to implement as an adapter over any metrics library,
+//! so this crate doesn't need to depend on one. [ParseCounters] is a ready-made implementation which keeps running
+//! totals, and can render them in the Prometheus text format.
+//!
+//! Metrics are recorded by [`TasdFile::parse_slice_with_metrics`], and by a [StreamParser] given
+//! [`StreamParser::with_metrics`].
+//!
+//! [StreamParser]: crate::spec::stream::StreamParser
+//! [`StreamParser::with_metrics`]: crate::spec::stream::StreamParser::with_metrics
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::spec::packets::PacketKind;
+use crate::spec::{ParseReport, TasdError, TasdFile};
+
+/// Statistic counted while parsing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// Packets decoded, excluding PADDING.
+    PacketsParsed,
+    /// Bytes read from the source, including headers and skipped packets.
+    BytesDecoded,
+    /// Packets whose key isn't known to this crate.
+    UnknownKeys,
+    /// Non-fatal problems: packets which failed to decode or verify, and [byte-swapped fields][crate::spec::lenient].
+    Warnings,
+}
+impl Counter {
+    pub const ALL: [Counter; 4] = [Counter::PacketsParsed, Counter::BytesDecoded, Counter::UnknownKeys, Counter::Warnings];
+    
+    /// Prometheus-style name of this counter, e.g. `tasd_packets_parsed_total`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::PacketsParsed => "tasd_packets_parsed_total",
+            Counter::BytesDecoded => "tasd_bytes_decoded_total",
+            Counter::UnknownKeys => "tasd_unknown_keys_total",
+            Counter::Warnings => "tasd_parse_warnings_total",
+        }
+    }
+    
+    pub fn description(&self) -> &'static str {
+        match self {
+            Counter::PacketsParsed => "Number of TASD packets parsed.",
+            Counter::BytesDecoded => "Number of bytes of TASD data parsed.",
+            Counter::UnknownKeys => "Number of TASD packets with an unknown key.",
+            Counter::Warnings => "Number of non-fatal problems found while parsing TASD data.",
+        }
+    }
+}
+
+/// Receives counts from parsers. Implementations must be cheap, as they may be called for every packet.
+/// 
+/// Implemented for closures taking a [Counter] and the amount to add to it.
+pub trait ParseMetrics: Send + Sync {
+    fn increment(&self, counter: Counter, value: u64);
+}
+impl<F: Fn(Counter, u64) + Send + Sync> ParseMetrics for F {
+    fn increment(&self, counter: Counter, value: u64) {
+        self(counter, value)
+    }
+}
+
+/// [ParseMetrics] which keeps a running total of each [Counter], and can be shared between threads.
+#[derive(Debug, Default)]
+pub struct ParseCounters {
+    packets_parsed: AtomicU64,
+    bytes_decoded: AtomicU64,
+    unknown_keys: AtomicU64,
+    warnings: AtomicU64,
+}
+impl ParseCounters {
+    pub fn get(&self, counter: Counter) -> u64 {
+        self.counter(counter).load(Ordering::Relaxed)
+    }
+    
+    /// Renders every counter in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        for counter in Counter::ALL {
+            let name = counter.name();
+            let _ = write!(text, "# HELP {name} {}\n# TYPE {name} counter\n{name} {}\n", counter.description(), self.get(counter));
+        }
+        
+        text
+    }
+    
+    fn counter(&self, counter: Counter) -> &AtomicU64 {
+        match counter {
+            Counter::PacketsParsed => &self.packets_parsed,
+            Counter::BytesDecoded => &self.bytes_decoded,
+            Counter::UnknownKeys => &self.unknown_keys,
+            Counter::Warnings => &self.warnings,
+        }
+    }
+}
+impl ParseMetrics for ParseCounters {
+    fn increment(&self, counter: Counter, value: u64) {
+        self.counter(counter).fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+impl TasdFile {
+    /// Parses a file the same as [`Self::parse_slice_with_report`], reporting what was parsed to `metrics`.
+    /// 
+    /// Nothing is recorded if the file can't be parsed.
+    pub fn parse_slice_with_metrics(data: &[u8], metrics: &dyn ParseMetrics) -> Result<(Self, ParseReport), TasdError> {
+        let (file, report) = Self::parse_slice_with_report(data)?;
+        let unknown = file.packets.iter().filter(|packet| packet.kind() == PacketKind::Unsupported).count();
+        let warnings = report.skipped.len() + report.corrupted.len() + report.byte_swaps.len();
+        
+        metrics.increment(Counter::PacketsParsed, file.packets.len() as u64);
+        metrics.increment(Counter::BytesDecoded, data.len() as u64);
+        metrics.increment(Counter::UnknownKeys, unknown as u64);
+        metrics.increment(Counter::Warnings, warnings as u64);
+        
+        Ok((file, report))
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use crate::metrics::{Counter, ParseCounters};
+    use crate::spec::packets::{KEY_CONSOLE_TYPE, Comment, GameTitle, Unsupported};
+    use crate::spec::stream::StreamParser;
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn parse_counters() {
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(Unsupported { key: vec![0xAB, 0xCD], payload: vec![1, 2, 3] }.into());
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        let data = file.encode();
+        
+        let counters = ParseCounters::default();
+        TasdFile::parse_slice_with_metrics(&data, &counters).unwrap();
+        assert_eq!(counters.get(Counter::PacketsParsed), 3);
+        assert_eq!(counters.get(Counter::BytesDecoded), data.len() as u64);
+        assert_eq!(counters.get(Counter::UnknownKeys), 1);
+        assert_eq!(counters.get(Counter::Warnings), 0);
+        
+        let counters = Arc::new(ParseCounters::default());
+        let mut parser = StreamParser::new(data.as_slice()).unwrap().with_metrics(counters.clone());
+        while parser.next_packet().unwrap().is_some() {}
+        assert_eq!(counters.get(Counter::PacketsParsed), 3);
+        assert_eq!(counters.get(Counter::BytesDecoded), data.len() as u64);
+        assert_eq!(counters.get(Counter::UnknownKeys), 1);
+        
+        let text = counters.to_prometheus();
+        assert!(text.starts_with("# HELP tasd_packets_parsed_total Number of TASD packets parsed.\n# TYPE tasd_packets_parsed_total counter\ntasd_packets_parsed_total 3\n"));
+        assert!(text.contains("\ntasd_parse_warnings_total 0\n"));
+        
+        // CONSOLE_TYPE without a payload is skipped
+        file.packets.push(Unsupported { key: KEY_CONSOLE_TYPE.to_vec(), payload: vec![] }.into());
+        let warnings = AtomicU64::new(0);
+        let metrics = |counter: Counter, value: u64| if counter == Counter::Warnings {
+            warnings.fetch_add(value, Ordering::Relaxed);
+        };
+        let (_, report) = TasdFile::parse_slice_with_metrics(&file.encode(), &metrics).unwrap();
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+    }
+}
\ No newline at end of file
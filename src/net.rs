@@ -0,0 +1,302 @@
+//! Simple framing protocol for transporting TASD packets live, as they're produced.
+//!
+//! The protocol works over any bidirectional byte stream (e.g. a [`TcpStream`][std::net::TcpStream]):
+//!
+//! 1. The sender writes the 7 byte TASD header (magic number, version, and keylen).
+//! 2. The receiver replies with a single [HANDSHAKE_ACK] byte if it accepts the header, or [HANDSHAKE_NAK] otherwise.
+//!    Headers with the wrong magic number, or a keylen other than 1 to 8, are rejected.
+//! 3. The sender writes each encoded packet as a frame, prefixed with its length as a big-endian u32.
+//! 4. The sender writes a frame length of 0 to indicate the end of the dump.
+//!
+//! With the `websocket` feature, [WsStream] carries the protocol over a WebSocket, sending each step as a single binary
+//! message.
+
+use std::io::{ErrorKind, Read, Write};
+use crate::spec::packets::{Encode, Packet};
+use crate::spec::stream::StreamParser;
+use crate::spec::{LATEST_VERSION, MAGIC_NUMBER, TasdError};
+
+pub const HANDSHAKE_ACK: u8 = 0x06;
+pub const HANDSHAKE_NAK: u8 = 0x15;
+
+/// Sending end of a live dump.
+pub struct StreamSender<S: Read + Write> {
+    inner: S,
+    keylen: u8,
+}
+impl<S: Read + Write> StreamSender<S> {
+    /// Performs the header handshake, returning an error if the receiver rejected it.
+    pub fn connect(mut inner: S, keylen: u8) -> Result<Self, TasdError> {
+        inner.write_all(&MAGIC_NUMBER)?;
+        inner.write_all(&LATEST_VERSION)?;
+        inner.write_all(&[keylen])?;
+        inner.flush()?;
+        
+        let mut response = [0u8];
+        inner.read_exact(&mut response)?;
+        if response[0] != HANDSHAKE_ACK {
+            return Err(TasdError::HandshakeRejected);
+        }
+        
+        Ok(Self {
+            inner,
+            keylen,
+        })
+    }
+    
+    pub fn send(&mut self, packet: &Packet) -> Result<(), TasdError> {
+        let data = packet.encode(self.keylen);
+        let len = u32::try_from(data.len()).map_err(|_| std::io::Error::from(ErrorKind::InvalidInput))?;
+        
+        self.inner.write_all(&len.to_be_bytes())?;
+        self.inner.write_all(&data)?;
+        self.inner.flush()?;
+        
+        Ok(())
+    }
+    
+    /// Signals the end of the dump to the receiver, and returns the underlying stream.
+    pub fn finish(mut self) -> Result<S, TasdError> {
+        self.inner.write_all(&0u32.to_be_bytes())?;
+        self.inner.flush()?;
+        
+        Ok(self.inner)
+    }
+}
+
+/// Accepts a live dump from a [StreamSender], returning a parser which yields each packet as it arrives.
+pub fn accept<S: Read + Write>(mut inner: S) -> Result<StreamParser<FrameReader<S>>, TasdError> {
+    let mut header = [0u8; 7];
+    inner.read_exact(&mut header)?;
+    let error = if header[..4] != MAGIC_NUMBER {
+        Some(TasdError::MagicNumberMismatch(header[..4].to_vec()))
+    } else if !(1..=8).contains(&header[6]) {
+        Some(TasdError::HandshakeRejected)
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        inner.write_all(&[HANDSHAKE_NAK])?;
+        inner.flush()?;
+        return Err(error);
+    }
+    inner.write_all(&[HANDSHAKE_ACK])?;
+    inner.flush()?;
+    
+    let version = u16::from_be_bytes([header[4], header[5]]);
+    Ok(StreamParser::with_header(FrameReader::new(inner), version, header[6]))
+}
+
+/// Adapter which strips the frame lengths from a stream, producing the plain packet data.
+/// 
+/// Reads return EOF once the terminating empty frame has been received.
+pub struct FrameReader<R: Read> {
+    inner: R,
+    remaining: usize,
+    finished: bool,
+}
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            finished: false,
+        }
+    }
+    
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<R: Read> Read for FrameReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+        
+        if self.remaining == 0 {
+            let mut len = [0u8; 4];
+            self.inner.read_exact(&mut len)?;
+            self.remaining = u32::from_be_bytes(len) as usize;
+            if self.remaining == 0 {
+                self.finished = true;
+                return Ok(0);
+            }
+        }
+        
+        let len = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read;
+        
+        Ok(read)
+    }
+}
+
+/// Byte stream over a WebSocket, for using [StreamSender] and [accept] with WebSocket connections.
+/// 
+/// Everything written is sent as one binary message when the stream is flushed, which the protocol does after each
+/// step. Reads return the data of each binary message received in turn, and EOF once the connection is closed.
+#[cfg(feature = "websocket")]
+pub struct WsStream<S: Read + Write> {
+    socket: tungstenite::WebSocket<S>,
+    incoming: Vec<u8>,
+    read: usize,
+    outgoing: Vec<u8>,
+}
+#[cfg(feature = "websocket")]
+impl<S: Read + Write> WsStream<S> {
+    /// Wraps a WebSocket which has completed its opening handshake, e.g. from [`tungstenite::client`] or
+    /// [`tungstenite::accept`].
+    pub fn new(socket: tungstenite::WebSocket<S>) -> Self {
+        Self {
+            socket,
+            incoming: vec![],
+            read: 0,
+            outgoing: vec![],
+        }
+    }
+    
+    pub fn into_inner(self) -> tungstenite::WebSocket<S> {
+        self.socket
+    }
+}
+#[cfg(feature = "websocket")]
+impl<S: Read + Write> Read for WsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use tungstenite::Message;
+        
+        while self.read == self.incoming.len() {
+            match self.socket.read().map_err(ws_io_error)? {
+                Message::Binary(data) => {
+                    self.incoming = data;
+                    self.read = 0;
+                },
+                Message::Close(_) => return Ok(0),
+                Message::Text(_) => return Err(std::io::Error::new(ErrorKind::InvalidData, "expected a binary message")),
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => (),
+            }
+        }
+        
+        let len = buf.len().min(self.incoming.len() - self.read);
+        buf[..len].copy_from_slice(&self.incoming[self.read..(self.read + len)]);
+        self.read += len;
+        
+        Ok(len)
+    }
+}
+#[cfg(feature = "websocket")]
+impl<S: Read + Write> Write for WsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.outgoing.extend_from_slice(buf);
+        
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.outgoing.is_empty() {
+            let message = tungstenite::Message::Binary(std::mem::take(&mut self.outgoing));
+            self.socket.send(message).map_err(ws_io_error)?;
+        }
+        
+        self.socket.flush().map_err(ws_io_error)
+    }
+}
+
+#[cfg(feature = "websocket")]
+fn ws_io_error(err: tungstenite::Error) -> std::io::Error {
+    use tungstenite::Error;
+    
+    match err {
+        Error::Io(err) => err,
+        Error::ConnectionClosed | Error::AlreadyClosed => ErrorKind::UnexpectedEof.into(),
+        err => std::io::Error::other(err),
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use crate::net::{accept, StreamSender};
+    use crate::spec::packets::{Comment, GameTitle, Packet};
+    use crate::spec::TasdError;
+    
+    #[test]
+    fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        let packets: Vec<Packet> = vec![
+            GameTitle { title: "title".into() }.into(),
+            Comment { comment: "c".repeat(70000) }.into(),
+        ];
+        
+        let sent = packets.clone();
+        let sender = thread::spawn(move || {
+            let mut sender = StreamSender::connect(TcpStream::connect(addr).unwrap(), 2).unwrap();
+            for packet in &sent {
+                sender.send(packet).unwrap();
+            }
+            sender.finish().unwrap();
+        });
+        
+        let (stream, _) = listener.accept().unwrap();
+        let parser = accept(stream).unwrap();
+        assert_eq!(parser.keylen(), 2);
+        let received = parser.collect::<Result<Vec<Packet>, TasdError>>().unwrap();
+        assert_eq!(received, packets);
+        
+        sender.join().unwrap();
+    }
+    
+    #[test]
+    fn handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        for keylen in [0, 9] {
+            let sender = thread::spawn(move || StreamSender::connect(TcpStream::connect(addr).unwrap(), keylen).err());
+            let (stream, _) = listener.accept().unwrap();
+            assert!(matches!(accept(stream).err(), Some(TasdError::HandshakeRejected)));
+            assert!(matches!(sender.join().unwrap(), Some(TasdError::HandshakeRejected)));
+        }
+    }
+    
+    #[test]
+    #[cfg(feature = "websocket")]
+    fn websocket() {
+        use crate::net::WsStream;
+        
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        let packets: Vec<Packet> = vec![
+            GameTitle { title: "title".into() }.into(),
+            Comment { comment: "c".repeat(70000) }.into(),
+        ];
+        
+        let sent = packets.clone();
+        let sender = thread::spawn(move || {
+            let (socket, _) = tungstenite::client(format!("ws://{addr}/dump"), TcpStream::connect(addr).unwrap()).unwrap();
+            let mut sender = StreamSender::connect(WsStream::new(socket), 2).unwrap();
+            for packet in &sent {
+                sender.send(packet).unwrap();
+            }
+            sender.finish().unwrap();
+        });
+        
+        let (stream, _) = listener.accept().unwrap();
+        let parser = accept(WsStream::new(tungstenite::accept(stream).unwrap())).unwrap();
+        let received = parser.collect::<Result<Vec<Packet>, TasdError>>().unwrap();
+        assert_eq!(received, packets);
+        
+        sender.join().unwrap();
+    }
+}
\ No newline at end of file
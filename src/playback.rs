@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::input::{align_frame, ControllerSegment, frame_count, frame_range, MisalignedChunk};
+use crate::spec::packets::{LagFrameChunk, MovieTransition, Transition, TransitionKind};
+use crate::spec::TasdFile;
+use crate::timing::{DEFAULT_FRAME_RATE, frame_to_duration};
+
+/// Receives events from a [Playback] as it walks through a movie.
+/// 
+/// All methods have empty default implementations, so only the events of interest need to be handled.
+#[allow(unused_variables)]
+pub trait PlaybackDriver {
+    /// Called once per frame, with the input data of each port for that frame.
+    /// 
+    /// Ports are listed in ascending order. Ports without a known controller input size, or which have run out of input
+    /// data, are omitted. If a port's controller is [swapped][TasdFile::controller_segments] mid-movie, the size of its
+    /// input data changes to match from the frame of the swap.
+    fn on_frame(&mut self, frame: u64, inputs: &[(u8, &[u8])]) {}
+    
+    /// Called at the start of a LAG_FRAME_CHUNK.
+    fn on_lag(&mut self, frame: u64, lag: &LagFrameChunk) {}
+    
+    /// Called before [`Self::on_transition`] for soft and power resets.
+    fn on_reset(&mut self, frame: u64, transition_type: u8) {}
+    
+    /// Called for every frame-indexed TRANSITION and every MOVIE_TRANSITION on this frame.
+    fn on_transition(&mut self, frame: u64, transition: PlaybackTransition) {}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PlaybackTransition<'a> {
+    Port(&'a Transition),
+    Movie(&'a MovieTransition),
+}
+impl PlaybackTransition<'_> {
+    pub fn transition_type(&self) -> u8 {
+        match self {
+            Self::Port(transition) => transition.kind.code(),
+            Self::Movie(transition) => transition.kind.code(),
+        }
+    }
+    
+    pub fn kind(&self) -> &TransitionKind {
+        match self {
+            Self::Port(transition) => &transition.kind,
+            Self::Movie(transition) => &transition.kind,
+        }
+    }
+}
+
+/// Speed passed to [`Playback::with_speed`] which isn't finite and positive.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InvalidSpeed(pub f64);
+
+/// Walks through a [TasdFile] one frame at a time, calling a [PlaybackDriver] for each event.
+/// 
+/// By default playback is paced to the file's native [frame rate][TasdFile::frame_rate], so [`Self::step`] can be
+/// called directly from an emulator frontend's main loop. The pacing can be sped up with [`Self::with_speed`], or
+/// disabled entirely with [`Self::unthrottled`].
+/// 
+/// BLANK_FRAMES is applied by default, so frame numbers passed to the driver are [aligned][TasdFile::aligned_frame]
+/// rather than indices into the input data. This can be disabled with [`Self::ignore_blank_frames`].
+pub struct Playback<'a> {
+    file: &'a TasdFile,
+    ports: Vec<PortInputs>,
+    /// Transitions and lag of each frame of playback, in the order they're sent to the driver.
+    events: HashMap<u64, Vec<Event<'a>>>,
+    blank_frames: i64,
+    frame: u64,
+    total: u64,
+    fps: f64,
+    /// Frame which was played when `start` was recorded.
+    start_frame: u64,
+    speed: Option<f64>,
+    start: Option<Instant>,
+}
+impl<'a> Playback<'a> {
+    pub fn new(file: &'a TasdFile) -> Self {
+        let mut playback = Self {
+            file,
+            ports: vec![],
+            events: HashMap::new(),
+            blank_frames: file.blank_frames(),
+            frame: 0,
+            total: 0,
+            fps: file.frame_rate().unwrap_or(DEFAULT_FRAME_RATE),
+            start_frame: 0,
+            speed: Some(1.0),
+            start: None,
+        };
+        playback.load_inputs();
+        
+        playback
+    }
+    
+    /// Like [`Self::new`], but refuses input data with [misaligned chunks][TasdFile::misaligned_chunks] instead of playing
+    /// it with every frame after the first such chunk shifted. The first misaligned chunk is returned.
+    pub fn try_new(file: &'a TasdFile) -> Result<Self, MisalignedChunk> {
+        match file.misaligned_chunks().into_iter().next() {
+            Some(chunk) => Err(chunk),
+            None => Ok(Self::new(file)),
+        }
+    }
+    
+    /// Plays the input data as-is, without applying BLANK_FRAMES.
+    pub fn ignore_blank_frames(mut self) -> Self {
+        self.blank_frames = 0;
+        self.load_inputs();
+        self
+    }
+    
+    /// Plays back at a multiple of the native frame rate (e.g. `2.0` for double speed).
+    /// 
+    /// Speeds which aren't finite and positive are rejected, as frames would never (or instantly) become due. Use
+    /// [`Self::unthrottled`] to play as fast as possible.
+    pub fn with_speed(mut self, speed: f64) -> Result<Self, InvalidSpeed> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(InvalidSpeed(speed));
+        }
+        
+        self.speed = Some(speed);
+        Ok(self)
+    }
+    
+    /// Plays back as fast as possible.
+    pub fn unthrottled(mut self) -> Self {
+        self.speed = None;
+        self
+    }
+    
+    /// Index of the next frame to be played.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+    
+    /// Number of frames of input data in the file.
+    pub fn total_frames(&self) -> u64 {
+        self.total
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.total
+    }
+    
+    /// Plays the next frame, waiting until it's due first if playback is throttled.
+    /// 
+    /// Returns `false` once every frame has been played.
+    pub fn step<D: PlaybackDriver>(&mut self, driver: &mut D) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        
+        if let Some(speed) = self.speed {
+            let start = *self.start.get_or_insert_with(Instant::now);
+            let due = start + frame_to_duration(self.frame - self.start_frame, self.fps * speed);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        
+        let frame = self.frame;
+        for event in self.events.get(&frame).into_iter().flatten() {
+            match *event {
+                Event::Transition(transition) => {
+                    if transition.kind().is_reset() {
+                        driver.on_reset(frame, transition.transition_type());
+                    }
+                    driver.on_transition(frame, transition);
+                },
+                Event::Lag(lag) => driver.on_lag(frame, lag),
+            }
+        }
+        
+        let inputs: Vec<(u8, &[u8])> = self.ports.iter()
+            .filter_map(|port| Some((port.port, port.frame(frame, self.blank_frames)?)))
+            .collect();
+        driver.on_frame(frame, &inputs);
+        
+        self.frame += 1;
+        true
+    }
+    
+    /// Moves playback to the given frame. Events for the skipped frames are not sent.
+    /// 
+    /// Throttled playback continues at the normal pace from the new frame.
+    pub fn seek(&mut self, frame: u64) {
+        self.frame = frame.min(self.total);
+        self.start_frame = self.frame;
+        self.start = None;
+    }
+    
+    /// [Seeks][Self::seek] to the frame of the first [bookmark][TasdFile::bookmark] with the given name.
+    /// 
+    /// Returns `false` if there's no such bookmark.
+    pub fn jump_to_bookmark(&mut self, name: &str) -> bool {
+        match self.file.bookmark(name) {
+            Some(bookmark) => {
+                self.seek(align_frame(bookmark.frame, self.blank_frames).unwrap_or(0));
+                true
+            },
+            None => false,
+        }
+    }
+    
+    /// Plays every remaining frame.
+    pub fn run<D: PlaybackDriver>(&mut self, driver: &mut D) {
+        while self.step(driver) {}
+    }
+    
+    /// Time remaining until the end of the movie at the native frame rate.
+    pub fn remaining(&self) -> Duration {
+        frame_to_duration(self.total - self.frame.min(self.total), self.fps)
+    }
+    
+    fn load_inputs(&mut self) {
+        self.ports = self.file.input_bytes().into_keys()
+            .filter_map(|port| {
+                let segments = self.file.controller_segments(port);
+                let size = segments.first()?.bytes_per_frame.filter(|size| *size > 0)?;
+                let inputs = self.file.port_inputs(port);
+                let frames = frame_count(&segments, inputs.len())?;
+                
+                Some(PortInputs { port, segments, inputs, blank: vec![0xFF; size], frames })
+            })
+            .collect();
+        self.total = self.ports.iter()
+            .map(|port| port.frames.saturating_add_signed(self.blank_frames))
+            .max()
+            .unwrap_or(0);
+        
+        // indexed by frame up front, so each step doesn't have to search the whole file
+        let file = self.file;
+        let transitions = file.all::<Transition>()
+            .filter_map(|transition| Some((transition.index.frame()?, Event::Transition(PlaybackTransition::Port(transition)))));
+        let movie_transitions = file.all::<MovieTransition>()
+            .map(|transition| (transition.movie_frame as u64, Event::Transition(PlaybackTransition::Movie(transition))));
+        let lags = file.all::<LagFrameChunk>().map(|lag| (lag.movie_frame as u64, Event::Lag(lag)));
+        self.events.clear();
+        for (index, event) in transitions.chain(movie_transitions).chain(lags) {
+            if let Some(frame) = align_frame(index, self.blank_frames) {
+                self.events.entry(frame).or_default().push(event);
+            }
+        }
+    }
+}
+
+
+/// Event of a frame of playback, other than its inputs.
+#[derive(Debug, Copy, Clone)]
+enum Event<'a> {
+    Transition(PlaybackTransition<'a>),
+    Lag(&'a LagFrameChunk),
+}
+
+/// Input data of one port, along with what's needed to find the data of each frame.
+struct PortInputs {
+    port: u8,
+    segments: Vec<ControllerSegment>,
+    inputs: Vec<u8>,
+    /// A frame where nothing is pressed, for frames added by BLANK_FRAMES.
+    blank: Vec<u8>,
+    /// Number of frames of input data, without BLANK_FRAMES applied.
+    frames: u64,
+}
+impl PortInputs {
+    /// Input data of a frame of playback.
+    fn frame(&self, frame: u64, blank_frames: i64) -> Option<&[u8]> {
+        match frame.checked_add_signed(-blank_frames) {
+            Some(frame) if frame < self.frames => self.inputs.get(frame_range(&self.segments, frame)?),
+            Some(_) => None,
+            None => Some(&self.blank),
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::playback::{Playback, PlaybackDriver, PlaybackTransition};
+    use crate::spec::packets::{BlankFrames, ConsoleType, Index, InputChunk, LagFrameChunk, PortController, Transition, TransitionKind};
+    use crate::spec::TasdFile;
+    
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+    impl PlaybackDriver for Recorder {
+        fn on_frame(&mut self, frame: u64, inputs: &[(u8, &[u8])]) {
+            self.events.push(format!("frame {frame} {inputs:?}"));
+        }
+        
+        fn on_lag(&mut self, frame: u64, lag: &LagFrameChunk) {
+            self.events.push(format!("lag {frame} {}", lag.count));
+        }
+        
+        fn on_reset(&mut self, frame: u64, transition_type: u8) {
+            self.events.push(format!("reset {frame} {transition_type}"));
+        }
+        
+        fn on_transition(&mut self, frame: u64, transition: PlaybackTransition) {
+            self.events.push(format!("transition {frame} {}", transition.transition_type()));
+        }
+    }
+    
+    fn sample() -> TasdFile {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(PortController { port: 2, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x01, 0x02, 0x03] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0x10, 0x20] }.into());
+        file.packets.push(LagFrameChunk { movie_frame: 1, count: 4 }.into());
+        file.packets.push(Transition { port: 0, index: Index::Frame(2), kind: TransitionKind::SoftReset }.into());
+        
+        file
+    }
+    
+    #[test]
+    fn events() {
+        let file = sample();
+        let mut recorder = Recorder::default();
+        let mut playback = Playback::new(&file).unthrottled();
+        assert_eq!(playback.total_frames(), 3);
+        playback.run(&mut recorder);
+        
+        assert_eq!(recorder.events, vec![
+            "frame 0 [(1, [1]), (2, [16])]",
+            "lag 1 4",
+            "frame 1 [(1, [2]), (2, [32])]",
+            "reset 2 1",
+            "transition 2 1",
+            "frame 2 [(1, [3])]",
+        ]);
+        assert!(playback.is_finished());
+        assert!(!playback.step(&mut recorder));
+    }
+    
+    #[test]
+    fn bookmarks() {
+        let mut file = sample();
+        file.add_bookmark(2, "reset");
+        file.add_bookmark(1, "lag");
+        assert_eq!(file.bookmarks().iter().map(|bookmark| bookmark.frame).collect::<Vec<_>>(), vec![1, 2]);
+        
+        let mut recorder = Recorder::default();
+        let mut playback = Playback::new(&file).unthrottled();
+        assert!(playback.jump_to_bookmark("reset"));
+        assert!(!playback.jump_to_bookmark("missing"));
+        playback.run(&mut recorder);
+        assert_eq!(recorder.events, vec!["reset 2 1", "transition 2 1", "frame 2 [(1, [3])]"]);
+        
+        assert_eq!(file.remove_bookmark("lag"), 1);
+        assert_eq!(file.bookmarks().len(), 1);
+    }
+    
+    #[test]
+    fn blank_frames() {
+        let mut file = sample();
+        file.packets.push(BlankFrames { frames: 2 }.into());
+        let mut recorder = Recorder::default();
+        let mut playback = Playback::new(&file).unthrottled();
+        assert_eq!(playback.total_frames(), 5);
+        playback.run(&mut recorder);
+        assert_eq!(recorder.events[0], "frame 0 [(1, [255]), (2, [255])]");
+        assert_eq!(recorder.events[3], "lag 3 4");
+        
+        file.first_mut::<BlankFrames>().unwrap().frames = -1;
+        let mut recorder = Recorder::default();
+        Playback::new(&file).unthrottled().run(&mut recorder);
+        assert_eq!(recorder.events[0], "lag 0 4");
+        assert_eq!(recorder.events[1], "frame 0 [(1, [2]), (2, [32])]");
+        
+        assert_eq!(Playback::new(&file).ignore_blank_frames().total_frames(), 3);
+    }
+    
+    #[test]
+    fn controller_swap() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x01, 0x02, 0x10, 0x11, 0x12, 0x13, 0x20] }.into());
+        // a multitap is plugged in on frame 2
+        let swap = PortController { port: 1, kind: 0x0203 }.into();
+        file.packets.push(Transition { port: 1, index: Index::Frame(2), kind: TransitionKind::PacketDerived(Box::new(swap)) }.into());
+        
+        assert_eq!(file.controller_segments(1).len(), 2);
+        assert_eq!(file.controller_segments(1)[1].offset, 2);
+        assert_eq!(file.controller_at(1, 1), Some(0x0101));
+        assert_eq!(file.controller_at(1, 2), Some(0x0203));
+        assert_eq!(file.computed_total_frames(), Some(3));
+        
+        file.packets.push(BlankFrames { frames: 1 }.into());
+        let mut recorder = Recorder::default();
+        let mut playback = Playback::new(&file).unthrottled();
+        assert_eq!(playback.total_frames(), 4);
+        playback.run(&mut recorder);
+        let frames: Vec<_> = recorder.events.iter().filter(|event| event.starts_with("frame")).collect();
+        assert_eq!(frames, vec![
+            "frame 0 [(1, [255])]",
+            "frame 1 [(1, [1])]",
+            "frame 2 [(1, [2])]",
+            "frame 3 [(1, [16, 17, 18, 19])]",
+        ]);
+    }
+    
+    #[test]
+    fn throttled() {
+        let file = sample();
+        let start = Instant::now();
+        Playback::new(&file).with_speed(2.0).unwrap().run(&mut Recorder::default());
+        
+        // the third frame is due 2 frames (at double speed) after the first
+        assert!(start.elapsed() >= Duration::from_secs_f64(1.0 / 60.1));
+        
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(Playback::new(&file).with_speed(speed).is_err());
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,150 @@
+//! Archives of several movies played back-to-back (e.g. a trilogy verified in one sitting).
+//! 
+//! The movies are stored one after another in a single file. Every movie after the first begins with a MOVIE_TRANSITION
+//! of type [RESTART_TRANSITION], whose frame is where that movie starts in the combined input stream. Frame-indexed
+//! packets use combined frame numbers, so the archive can be played straight through with [Playback][crate::playback::Playback].
+
+use std::ops::Range;
+use crate::edit::map_frame;
+use crate::spec::packets::{InputChunk, MovieTransition, Packet, TotalFrames, TransitionKind};
+use crate::spec::TasdFile;
+
+/// MOVIE_TRANSITION type ("Restart TASD File") which ends one movie and starts the next.
+pub const RESTART_TRANSITION: u8 = 0x03;
+
+pub(crate) fn is_restart(packet: &Packet) -> bool {
+    matches!(packet, Packet::MovieTransition(transition) if transition.kind.code() == RESTART_TRANSITION)
+}
+
+/// Frame in the combined input stream where the movie made up of `packets` starts, from its restart MOVIE_TRANSITION.
+pub(crate) fn movie_start(packets: &[Packet]) -> u64 {
+    match packets.first() {
+        Some(Packet::MovieTransition(transition)) if transition.kind.code() == RESTART_TRANSITION => transition.movie_frame as u64,
+        _ => 0,
+    }
+}
+
+impl TasdFile {
+    /// Number of movies in this file. Always at least 1.
+    pub fn movie_count(&self) -> usize {
+        self.packets.iter().filter(|packet| is_restart(packet)).count() + 1
+    }
+    
+    /// Range of [packet indices][TasdFile::packets] belonging to each movie. Each range after the first starts with the
+    /// movie's restart MOVIE_TRANSITION.
+    pub fn movie_ranges(&self) -> Vec<Range<usize>> {
+        let mut starts: Vec<usize> = vec![0];
+        starts.extend(self.packets.iter().enumerate().filter(|(_, packet)| is_restart(packet)).map(|(i, _)| i));
+        starts.push(self.packets.len());
+        
+        starts.windows(2).map(|pair| pair[0]..pair[1]).collect()
+    }
+    
+    /// Splits this file into its individual movies. Restart transitions are removed, and frame-indexed packets are
+    /// shifted so that each movie starts at frame 0.
+    /// 
+    /// A file which isn't an archive is returned as a single movie.
+    pub fn split_movies(&self) -> Vec<TasdFile> {
+        self.movie_ranges().into_iter().map(|range| {
+            let start = movie_start(&self.packets[range.clone()]);
+            
+            let mut movie = TasdFile { version: self.version, keylen: self.keylen, packets: vec![], path: None };
+            for packet in &self.packets[range] {
+                if is_restart(packet) {
+                    continue;
+                }
+                let mut packet = packet.clone();
+                map_frame(&mut packet, |frame| frame.saturating_sub(start));
+                movie.packets.push(packet);
+            }
+            
+            movie
+        }).collect()
+    }
+    
+    /// Combines several movies into a single archive, which can be split apart again with [`Self::split_movies`].
+    /// 
+    /// Each movie's length is its [computed frame count][TasdFile::computed_total_frames], or else its TOTAL_FRAMES. Ports
+    /// whose input data is shorter than that are padded with frames where nothing is pressed, so that every movie starts
+    /// on the same frame across all ports. Any packet checksums are removed, since shifting frames invalidates them.
+    /// 
+    /// The version and keylen are taken from the first movie.
+    pub fn concat_movies(movies: Vec<TasdFile>) -> TasdFile {
+        let mut archive = TasdFile::default();
+        if let Some(first) = movies.first() {
+            archive.version = first.version;
+            archive.keylen = first.keylen;
+        }
+        
+        let mut offset = 0u64;
+        for (i, mut movie) in movies.into_iter().enumerate() {
+            movie.remove_checksums();
+            let frames = movie.computed_total_frames()
+                .or(movie.first::<TotalFrames>().map(|total| total.frames))
+                .unwrap_or(0) as u64;
+            
+            if i > 0 {
+                archive.packets.push(MovieTransition {
+                    movie_frame: offset.min(u32::MAX as u64) as u32,
+                    kind: TransitionKind::RestartFile,
+                }.into());
+            }
+            for (port, frame_count) in movie.input_frames() {
+                let size = movie.bytes_per_frame(port).unwrap_or(0);
+                if frame_count < frames && size > 0 {
+                    movie.packets.push(InputChunk { port, inputs: vec![0xFF; (frames - frame_count) as usize * size] }.into());
+                }
+            }
+            for mut packet in movie.packets {
+                map_frame(&mut packet, |frame| frame.saturating_add(offset));
+                archive.packets.push(packet);
+            }
+            
+            offset += frames;
+        }
+        
+        archive
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Bookmark, InputChunk, MovieTransition, Packet, PortController, TotalFrames};
+    use crate::spec::TasdFile;
+    
+    fn movie(frames: usize) -> TasdFile {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(TotalFrames { frames: frames as u32 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![frames as u8; frames] }.into());
+        file.packets.push(Bookmark { frame: 1, name: "b".into() }.into());
+        
+        file
+    }
+    
+    #[test]
+    fn concat_and_split() {
+        let movies = vec![movie(3), movie(5), movie(2)];
+        let archive = TasdFile::concat_movies(movies.clone());
+        assert_eq!(archive.movie_count(), 3);
+        assert_eq!(archive.port_inputs(1).len(), 10);
+        assert_eq!(archive.all::<MovieTransition>().map(|transition| transition.movie_frame).collect::<Vec<_>>(), vec![3, 8]);
+        assert_eq!(archive.bookmarks().iter().map(|bookmark| bookmark.frame).collect::<Vec<_>>(), vec![1, 4, 9]);
+        assert!(archive.validate().is_empty());
+        
+        let mut mismatched = archive.clone();
+        let index = mismatched.packets.iter().enumerate().filter(|(_, packet)| matches!(packet, Packet::TotalFrames(_))).nth(1).unwrap().0;
+        mismatched.packets[index] = TotalFrames { frames: 4 }.into();
+        let issues = mismatched.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].index, Some(index));
+        assert_eq!(issues[0].message, "declares 4 frames, but the input data contains 5 frames");
+        
+        assert_eq!(archive.split_movies(), movies);
+        assert_eq!(movie(1).split_movies(), vec![movie(1)]);
+    }
+}
\ No newline at end of file
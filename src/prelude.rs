@@ -0,0 +1,29 @@
+//! The most commonly used types and traits, for importing all at once:
+//!
+//! ```
+//! use tasd::prelude::*;
+//!
+//! let mut file = TasdFile::default();
+//! file.packets.push(packets::GameTitle { title: "Super Mario Bros.".into() }.into());
+//! assert_eq!(file.packets[0].kind(), PacketKind::GameTitle);
+//! assert_eq!(file.packets[0].key(), KEY_GAME_TITLE);
+//! ```
+//!
+//! Packet structs aren't included, as their names (e.g. `Comment` or `Category`) easily clash with other types. They're
+//! available as `tasd::packets::*` instead.
+
+pub use crate::dump::{DumpSession, Dumper, TasdDumper};
+pub use crate::spec::{TasdError, TasdFile};
+pub use crate::spec::packets::{self, Decode, Encode, EncodePayload, KeyedPacket, Packet, PacketError, PacketKind};
+pub use crate::spec::packets::{
+    KEY_CONSOLE_TYPE, KEY_CONSOLE_REGION, KEY_GAME_TITLE, KEY_ROM_NAME, KEY_ATTRIBUTION, KEY_CATEGORY,
+    KEY_EMULATOR_NAME, KEY_EMULATOR_VERSION, KEY_EMULATOR_CORE, KEY_TAS_LAST_MODIFIED, KEY_DUMP_CREATED,
+    KEY_DUMP_LAST_MODIFIED, KEY_TOTAL_FRAMES, KEY_RERECORDS, KEY_SOURCE_LINK, KEY_BLANK_FRAMES, KEY_VERIFIED,
+    KEY_MEMORY_INIT, KEY_GAME_IDENTIFIER, KEY_MOVIE_LICENSE, KEY_MOVIE_FILE, KEY_PORT_CONTROLLER, KEY_PORT_OVERREAD,
+    KEY_NES_LATCH_FILTER, KEY_NES_CLOCK_FILTER, KEY_NES_GAME_GENIE_CODE, KEY_SNES_LATCH_FILTER,
+    KEY_SNES_CLOCK_FILTER, KEY_SNES_GAME_GENIE_CODE, KEY_SNES_LATCH_TRAIN, KEY_GENESIS_GAME_GENIE_CODE,
+    KEY_INPUT_CHUNK, KEY_INPUT_MOMENT, KEY_TRANSITION, KEY_LAG_FRAME_CHUNK, KEY_MOVIE_TRANSITION, KEY_COMMENT,
+    KEY_EXPERIMENTAL, KEY_UNSPECIFIED, KEY_PACKET_CHECKSUM, KEY_SAVESTATE_ANCHOR, KEY_BOOKMARK, KEY_LINK_SESSION,
+    KEY_RANDOM_SEED, KEY_INPUT_DELTA, KEY_TABLE_OF_CONTENTS, KEY_PADDING, KEY_PROVENANCE, KEY_EDIT_HISTORY,
+    KEY_CONSOLE_SETTING, KEY_LATCH_MAP, KEY_AV_SYNC,
+};
\ No newline at end of file
@@ -0,0 +1,124 @@
+//! Recording which tool added each packet, for debugging files which pass through several tools.
+//!
+//! Provenance is opt-in. A tool calls [`TasdFile::begin_provenance`] before adding its packets, which appends a
+//! PROVENANCE packet naming the tool and its version. Every packet after it, up to the next PROVENANCE, is attributed
+//! to that tool. Reordering packets (e.g. with [`TasdFile::canonicalize`]) can move packets out from under their
+//! PROVENANCE, so it's best-effort information for debugging rather than something to rely on.
+
+use crate::spec::packets::{Packet, Provenance};
+use crate::spec::TasdFile;
+
+impl Provenance {
+    pub fn new<S: Into<String>>(tool: S, version: S) -> Self {
+        Self {
+            tool: tool.into(),
+            version: version.into(),
+        }
+    }
+    
+    /// Provenance naming this crate, for packets added by its own tools.
+    pub fn this_crate() -> Self {
+        Self::new("tasd", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+impl TasdFile {
+    /// Attributes the packets added after this call to `provenance`, by appending a PROVENANCE packet.
+    /// 
+    /// Nothing is added if the packets at the end of the file are already attributed to the same tool and version, and
+    /// a PROVENANCE with no packets after it is replaced rather than followed by another.
+    pub fn begin_provenance(&mut self, provenance: Provenance) {
+        if self.provenance(self.packets.len()) == Some(&provenance) {
+            return;
+        }
+        
+        match self.packets.last_mut() {
+            Some(Packet::Provenance(last)) => *last = provenance,
+            _ => self.packets.push(provenance.into()),
+        }
+    }
+    
+    /// Tool which added the packet at `index`, from the closest PROVENANCE before it. Passing the number of packets
+    /// gives the tool any newly added packets would be attributed to.
+    pub fn provenance(&self, index: usize) -> Option<&Provenance> {
+        self.packets[..index.min(self.packets.len())].iter().rev().find_map(|packet| match packet {
+            Packet::Provenance(provenance) => Some(provenance),
+            _ => None,
+        })
+    }
+    
+    /// Every packet other than PROVENANCE, along with its index and the tool which added it.
+    pub fn packets_with_provenance(&self) -> impl Iterator<Item = (usize, &Packet, Option<&Provenance>)> {
+        let mut current = None;
+        self.packets.iter().enumerate().filter_map(move |(index, packet)| match packet {
+            Packet::Provenance(provenance) => {
+                current = Some(provenance);
+                None
+            },
+            packet => Some((index, packet, current)),
+        })
+    }
+    
+    /// Number of packets added by each tool, in the order the tools first appear. Packets before the first PROVENANCE
+    /// aren't counted.
+    pub fn provenance_counts(&self) -> Vec<(&Provenance, usize)> {
+        let mut counts: Vec<(&Provenance, usize)> = vec![];
+        for (_, _, provenance) in self.packets_with_provenance() {
+            let Some(provenance) = provenance else { continue };
+            match counts.iter_mut().find(|(counted, _)| *counted == provenance) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((provenance, 1)),
+            }
+        }
+        
+        counts
+    }
+    
+    /// Removes every PROVENANCE packet, returning how many were removed.
+    pub fn strip_provenance(&mut self) -> usize {
+        let before = self.packets.len();
+        self.packets.retain(|packet| !matches!(packet, Packet::Provenance(_)));
+        
+        before - self.packets.len()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Comment, GameTitle, Provenance};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn provenance() {
+        let dumper = Provenance::new("dumper", "1.0");
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.begin_provenance(dumper.clone());
+        file.begin_provenance(Provenance::this_crate());
+        file.begin_provenance(dumper.clone());
+        assert_eq!(file.packets.len(), 2);
+        
+        file.packets.push(Comment { comment: "dumped".into() }.into());
+        file.begin_provenance(dumper.clone());
+        file.packets.push(Comment { comment: "also dumped".into() }.into());
+        file.begin_provenance(Provenance::this_crate());
+        file.packets.push(Comment { comment: "edited".into() }.into());
+        
+        assert_eq!(file.provenance(0), None);
+        assert_eq!(file.provenance(2), Some(&dumper));
+        assert_eq!(file.provenance(file.packets.len()), Some(&Provenance::this_crate()));
+        assert_eq!(file.packets_with_provenance().map(|(index, _, _)| index).collect::<Vec<_>>(), [0, 2, 3, 5]);
+        assert_eq!(file.provenance_counts(), [(&dumper, 2), (&Provenance::this_crate(), 1)]);
+        
+        let decoded = TasdFile::parse_slice(&file.encode()).unwrap();
+        assert_eq!(decoded.packets, file.packets);
+        
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        assert_eq!(file.strip_provenance(), 2);
+        assert_eq!(file.provenance(file.packets.len()), None);
+    }
+}
\ No newline at end of file
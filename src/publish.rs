@@ -0,0 +1,148 @@
+use crate::spec::packets::{Encode, Packet, PacketKind};
+use crate::spec::TasdFile;
+use crate::util::crc32;
+
+/// Which packets [`TasdFile::strip_private_data`] removes or redacts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StripOptions {
+    /// If set, only packets of these kinds are kept. Applied before [`Self::deny`].
+    pub allow: Option<Vec<PacketKind>>,
+    /// Packets of these kinds are removed.
+    pub deny: Vec<PacketKind>,
+    /// Whether user names in file system paths (e.g. `/home/name/` or `C:\Users\name\`) in UNSPECIFIED packets are
+    /// replaced with `user`.
+    pub redact_paths: bool,
+}
+impl Default for StripOptions {
+    fn default() -> Self {
+        Self {
+            allow: None,
+            deny: vec![PacketKind::MovieFile, PacketKind::Comment],
+            redact_paths: true,
+        }
+    }
+}
+impl StripOptions {
+    fn keeps(&self, kind: PacketKind) -> bool {
+        self.allow.as_ref().map(|allow| allow.contains(&kind)).unwrap_or(true) && !self.deny.contains(&kind)
+    }
+}
+
+/// Directories whose next path component is a user name.
+const HOME_DIRS: &[&str] = &["/home/", "/Users/", "\\Users\\", "\\users\\"];
+
+/// Replaces the user name following any home directory in `text` with `user`.
+fn redact_paths(text: &str) -> String {
+    let mut result = text.to_string();
+    for dir in HOME_DIRS {
+        let separator = dir.chars().last().unwrap_or('/');
+        let mut search = 0;
+        while let Some(found) = result[search..].find(dir) {
+            let start = search + found + dir.len();
+            let end = result[start..].find(separator).map(|i| start + i).unwrap_or(result.len());
+            result.replace_range(start..end, "user");
+            search = start + "user".len();
+        }
+    }
+    
+    result
+}
+
+impl TasdFile {
+    /// Keeps only the packets whose kind matches the predicate.
+    /// 
+    /// A [PacketChecksum][crate::spec::packets::PacketChecksum] directly after a removed packet is removed with it.
+    /// Returns the number of packets removed.
+    pub fn retain_kinds<F: FnMut(PacketKind) -> bool>(&mut self, mut f: F) -> usize {
+        let before = self.packets.len();
+        let mut removed_previous = false;
+        self.packets.retain(|packet| {
+            let keep = match packet {
+                Packet::PacketChecksum(_) if removed_previous => false,
+                _ => f(packet.kind()),
+            };
+            removed_previous = !keep;
+            
+            keep
+        });
+        
+        before - self.packets.len()
+    }
+    
+    /// Removes or redacts packets which a submitter may not want published, producing a clean copy for publication.
+    /// 
+    /// Packets are removed according to the [StripOptions]. TRANSITION and MOVIE_TRANSITION packets which wrap a
+    /// removed kind of packet are removed as well. Returns the number of packets removed.
+    pub fn strip_private_data(&mut self, options: &StripOptions) -> usize {
+        let before = self.packets.len();
+        self.retain_kinds(|kind| options.keeps(kind));
+        
+        let mut removed_previous = false;
+        self.packets.retain(|packet| {
+            let inner = match packet {
+                Packet::Transition(transition) => transition.kind.packet(),
+                Packet::MovieTransition(transition) => transition.kind.packet(),
+                Packet::PacketChecksum(_) => return !removed_previous,
+                _ => None,
+            };
+            removed_previous = inner.is_some_and(|inner| !options.keeps(inner.kind()));
+            
+            !removed_previous
+        });
+        
+        if options.redact_paths {
+            for i in 0..self.packets.len() {
+                let Packet::Unspecified(unspecified) = &mut self.packets[i] else { continue };
+                let Ok(text) = std::str::from_utf8(&unspecified.payload) else { continue };
+                let redacted = redact_paths(text).into_bytes();
+                if redacted == unspecified.payload {
+                    continue;
+                }
+                unspecified.payload = redacted;
+                
+                // keep the packet's checksum valid
+                let crc = crc32(&self.packets[i].encode(self.keylen));
+                if let Some(Packet::PacketChecksum(checksum)) = self.packets.get_mut(i + 1) {
+                    checksum.crc32 = crc;
+                }
+            }
+        }
+        
+        before - self.packets.len()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::publish::{StripOptions, redact_paths};
+    use crate::spec::packets::{Comment, GameTitle, MovieFile, MovieTransition, Packet, PacketKind, TransitionKind, Unspecified};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn strip() {
+        assert_eq!(redact_paths("C:\\Users\\alice\\movies\\run.bk2"), "C:\\Users\\user\\movies\\run.bk2");
+        assert_eq!(redact_paths("/home/bob"), "/home/user");
+        
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(MovieFile { name: "run.bk2".into(), data: vec![1, 2, 3] }.into());
+        file.packets.push(Comment { comment: "private".into() }.into());
+        file.packets.push(MovieTransition { movie_frame: 5, kind: TransitionKind::PacketDerived(Box::new(Comment { comment: "x".into() }.into())) }.into());
+        file.packets.push(Unspecified { payload: b"/home/bob/emu".to_vec() }.into());
+        file.add_checksums();
+        
+        let mut public = file.clone();
+        assert_eq!(public.strip_private_data(&StripOptions::default()), 6);
+        assert_eq!(public.packets.len(), 4);
+        assert!(public.verify_checksums().is_empty());
+        assert_eq!(public.packets[2], Packet::from(Unspecified { payload: b"/home/user/emu".to_vec() }));
+        
+        let mut public = file.clone();
+        public.strip_private_data(&StripOptions { allow: Some(vec![PacketKind::GameTitle]), ..Default::default() });
+        assert_eq!(public.packets, vec![GameTitle { title: "title".into() }.into()]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,181 @@
+//! Regex search and replace over the text of metadata packets, e.g. for fixing an author's name or a dead link across
+//! a whole archive.
+//!
+//! [`TasdFile::preview_replace_text`] reports what would change without modifying anything, so edits can be reviewed
+//! before they're applied with [`TasdFile::replace_text`].
+
+use regex::Regex;
+use crate::spec::packets::{Packet, PacketKind};
+use crate::spec::TasdFile;
+
+/// Every packet type containing text which [`TasdFile::replace_text`] can edit.
+pub const TEXT_KINDS: &[PacketKind] = &[
+    PacketKind::ConsoleType,
+    PacketKind::GameTitle,
+    PacketKind::RomName,
+    PacketKind::Attribution,
+    PacketKind::Category,
+    PacketKind::EmulatorName,
+    PacketKind::EmulatorVersion,
+    PacketKind::EmulatorCore,
+    PacketKind::SourceLink,
+    PacketKind::MemoryInit,
+    PacketKind::GameIdentifier,
+    PacketKind::MovieLicense,
+    PacketKind::MovieFile,
+    PacketKind::NesGameGenieCode,
+    PacketKind::SnesGameGenieCode,
+    PacketKind::GenesisGameGenieCode,
+    PacketKind::Comment,
+    PacketKind::Bookmark,
+];
+
+/// Text of a packet which matched, from [`TasdFile::replace_text`] or [`TasdFile::preview_replace_text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextReplacement {
+    /// Index of the packet within [`TasdFile::packets`].
+    pub index: usize,
+    pub kind: PacketKind,
+    /// Number of matches within the text.
+    pub matches: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl TasdFile {
+    /// Replaces every match of `pattern` in the text of packets of the given kinds (see [TEXT_KINDS]) with
+    /// `replacement`, which can refer to capture groups as in [`Regex::replace_all`].
+    /// 
+    /// Returns each packet whose text matched, in file order.
+    /// 
+    /// ```
+    /// # use tasd::spec::packets::{Attribution, PacketKind};
+    /// # use tasd::spec::TasdFile;
+    /// let mut file = TasdFile::default();
+    /// file.packets.push(Attribution { kind: 0x01, name: "Old Name".into() }.into());
+    /// 
+    /// let pattern = regex::Regex::new("^Old Name$").unwrap();
+    /// let replaced = file.replace_text(&pattern, "New Name", &[PacketKind::Attribution]);
+    /// assert_eq!(replaced[0].after, "New Name");
+    /// ```
+    pub fn replace_text(&mut self, pattern: &Regex, replacement: &str, kinds: &[PacketKind]) -> Vec<TextReplacement> {
+        let replacements = self.preview_replace_text(pattern, replacement, kinds);
+        for replaced in &replacements {
+            if let Some(text) = text_mut(&mut self.packets[replaced.index]) {
+                *text = replaced.after.clone();
+            }
+        }
+        
+        replacements
+    }
+    
+    /// Same as [`TasdFile::replace_text`], but only reports what would be replaced, without changing the file.
+    pub fn preview_replace_text(&self, pattern: &Regex, replacement: &str, kinds: &[PacketKind]) -> Vec<TextReplacement> {
+        self.packets.iter().enumerate()
+            .filter(|(_, packet)| kinds.contains(&packet.kind()))
+            .filter_map(|(index, packet)| {
+                let before = text(packet)?;
+                let matches = pattern.find_iter(before).count();
+                
+                (matches > 0).then(|| TextReplacement {
+                    index,
+                    kind: packet.kind(),
+                    matches,
+                    before: before.to_string(),
+                    after: pattern.replace_all(before, replacement).into_owned(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn text(packet: &Packet) -> Option<&str> {
+    Some(match packet {
+        Packet::ConsoleType(packet) => packet.custom.as_deref()?,
+        Packet::GameTitle(packet) => &packet.title,
+        Packet::RomName(packet) => &packet.name,
+        Packet::Attribution(packet) => &packet.name,
+        Packet::Category(packet) => &packet.category,
+        Packet::EmulatorName(packet) => &packet.name,
+        Packet::EmulatorVersion(packet) => &packet.version,
+        Packet::EmulatorCore(packet) => &packet.core,
+        Packet::SourceLink(packet) => &packet.link,
+        Packet::MemoryInit(packet) => &packet.name,
+        Packet::GameIdentifier(packet) => &packet.name,
+        Packet::MovieLicense(packet) => &packet.license,
+        Packet::MovieFile(packet) => &packet.name,
+        Packet::NesGameGenieCode(packet) => &packet.code,
+        Packet::SnesGameGenieCode(packet) => &packet.code,
+        Packet::GenesisGameGenieCode(packet) => &packet.code,
+        Packet::Comment(packet) => &packet.comment,
+        Packet::Bookmark(packet) => &packet.name,
+        _ => return None,
+    })
+}
+
+fn text_mut(packet: &mut Packet) -> Option<&mut String> {
+    Some(match packet {
+        Packet::ConsoleType(packet) => packet.custom.as_mut()?,
+        Packet::GameTitle(packet) => &mut packet.title,
+        Packet::RomName(packet) => &mut packet.name,
+        Packet::Attribution(packet) => &mut packet.name,
+        Packet::Category(packet) => &mut packet.category,
+        Packet::EmulatorName(packet) => &mut packet.name,
+        Packet::EmulatorVersion(packet) => &mut packet.version,
+        Packet::EmulatorCore(packet) => &mut packet.core,
+        Packet::SourceLink(packet) => &mut packet.link,
+        Packet::MemoryInit(packet) => &mut packet.name,
+        Packet::GameIdentifier(packet) => &mut packet.name,
+        Packet::MovieLicense(packet) => &mut packet.license,
+        Packet::MovieFile(packet) => &mut packet.name,
+        Packet::NesGameGenieCode(packet) => &mut packet.code,
+        Packet::SnesGameGenieCode(packet) => &mut packet.code,
+        Packet::GenesisGameGenieCode(packet) => &mut packet.code,
+        Packet::Comment(packet) => &mut packet.comment,
+        Packet::Bookmark(packet) => &mut packet.name,
+        _ => return None,
+    })
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use crate::replace::{TextReplacement, TEXT_KINDS};
+    use crate::spec::packets::{Attribution, Comment, GameTitle, PacketKind, SourceLink};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn replace_text() {
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "bigbass".into() }.into());
+        file.packets.push(SourceLink { link: "http://tasvideos.org/1234M".into() }.into());
+        file.packets.push(Comment { comment: "thanks bigbass and bigbass's friends".into() }.into());
+        let original = file.clone();
+        
+        let pattern = Regex::new(r"\bbigbass\b").unwrap();
+        let preview = file.preview_replace_text(&pattern, "bigbass1997", &[PacketKind::Attribution]);
+        assert_eq!(preview, [TextReplacement {
+            index: 1,
+            kind: PacketKind::Attribution,
+            matches: 1,
+            before: "bigbass".into(),
+            after: "bigbass1997".into(),
+        }]);
+        assert_eq!(file, original);
+        
+        let replaced = file.replace_text(&pattern, "bigbass1997", TEXT_KINDS);
+        assert_eq!(replaced.iter().map(|replaced| (replaced.index, replaced.matches)).collect::<Vec<_>>(), [(1, 1), (3, 2)]);
+        assert_eq!(file.packets[3], Comment { comment: "thanks bigbass1997 and bigbass1997's friends".into() }.into());
+        
+        // capture groups
+        let pattern = Regex::new(r"^http://tasvideos\.org/(\d+)M$").unwrap();
+        file.replace_text(&pattern, "https://tasvideos.org/${1}M", &[PacketKind::SourceLink]);
+        assert_eq!(file.packets[2], SourceLink { link: "https://tasvideos.org/1234M".into() }.into());
+        assert!(file.replace_text(&pattern, "", TEXT_KINDS).is_empty());
+    }
+}
\ No newline at end of file
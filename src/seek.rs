@@ -0,0 +1,222 @@
+use crate::edit::EditError;
+use crate::input::align_frame;
+use crate::spec::packets::{InputChunk, LagFrameChunk, MovieTransition, Packet, Transition};
+use crate::spec::TasdFile;
+
+/// Soft or power reset which happens before the target of a [SeekPlan].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SeekReset {
+    pub frame: u64,
+    /// `0x01` for a soft reset, `0x02` for a power reset.
+    pub transition_type: u8,
+}
+
+/// Input data of one port for the frames of a [SeekPlan].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeekInputs {
+    pub port: u8,
+    pub bytes_per_frame: usize,
+    /// Byte offset of the first frame within the port's input data.
+    pub offset: usize,
+    pub inputs: Vec<u8>,
+}
+
+/// Everything an emulator needs to reach a frame by fast-forwarding from the nearest reset, instead of replaying the
+/// whole movie (e.g. when rewinding).
+/// 
+/// Frames use the same numbering as [Playback][crate::playback::Playback], with BLANK_FRAMES applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeekPlan {
+    pub target: u64,
+    /// Frame to fast-forward from. This is the frame of the last reset at or before the target, or `0` if there is none
+    /// (in which case the emulator should start from power-on).
+    pub start: u64,
+    /// Every reset at or before the target, in order. The last one (if any) happens at `start`, before its inputs.
+    pub resets: Vec<SeekReset>,
+    /// Input data of each port from `start` up to (but not including) the target, in ascending port order.
+    pub inputs: Vec<SeekInputs>,
+    /// Number of lag frames, from LAG_FRAME_CHUNK packets, between `start` and the target.
+    pub lag_frames: u64,
+}
+impl SeekPlan {
+    /// Number of frames of input data to fast-forward through.
+    pub fn frames(&self) -> u64 {
+        self.target - self.start
+    }
+    
+    /// Number of frames to emulate, for emulators whose frame counter includes lag frames.
+    pub fn emulated_frames(&self) -> u64 {
+        self.frames() + self.lag_frames
+    }
+}
+
+impl TasdFile {
+    /// Builds a [SeekPlan] for reaching `target`.
+    /// 
+    /// The target is clamped to the number of frames of input data.
+    pub fn seek_plan(&self, target: u64) -> SeekPlan {
+        let blank = self.blank_frames();
+        let ports: Vec<_> = self.input_bytes().into_keys()
+            .filter_map(|port| Some((port, self.bytes_per_frame(port)?, self.aligned_port_inputs(port))))
+            .filter(|(_, size, _)| *size > 0)
+            .collect();
+        let total = ports.iter().map(|(_, size, inputs)| (inputs.len() / size) as u64).max().unwrap_or(0);
+        let target = target.min(total);
+        
+        let port_resets = self.all::<Transition>()
+            .filter_map(|transition| Some((transition.index.frame()?, &transition.kind)));
+        let movie_resets = self.all::<MovieTransition>()
+            .map(|transition| (transition.movie_frame as u64, &transition.kind));
+        let mut resets: Vec<_> = port_resets.chain(movie_resets)
+            .filter(|(_, kind)| kind.is_reset())
+            .filter_map(|(frame, kind)| Some(SeekReset { frame: align_frame(frame, blank)?, transition_type: kind.code() }))
+            .filter(|reset| reset.frame <= target)
+            .collect();
+        resets.sort_by_key(|reset| reset.frame);
+        let start = resets.last().map(|reset| reset.frame).unwrap_or(0);
+        
+        let inputs = ports.into_iter().map(|(port, size, inputs)| {
+            let offset = (start as usize * size).min(inputs.len());
+            let end = (target as usize * size).clamp(offset, inputs.len());
+            SeekInputs {
+                port,
+                bytes_per_frame: size,
+                offset,
+                inputs: inputs[offset..end].to_vec(),
+            }
+        }).collect();
+        
+        let lag_frames = self.all::<LagFrameChunk>()
+            .filter_map(|lag| Some((align_frame(lag.movie_frame as u64, blank)?, lag.count as u64)))
+            .filter(|(frame, _)| (start..target).contains(frame))
+            .map(|(_, count)| count)
+            .sum();
+        
+        SeekPlan {
+            target,
+            start,
+            resets,
+            inputs,
+            lag_frames,
+        }
+    }
+    
+    /// Builds a minimal file for replaying the frames up to `end_frame`, starting from the last reset at or before
+    /// `start_frame`, so a desync on hardware can be reproduced without replaying the whole movie.
+    /// 
+    /// The segment keeps only what affects replay: the console, controller, and filter configuration, MEMORY_INIT, game
+    /// identification, and the inputs and frame-indexed packets from the reset onwards, with frames shifted to start at
+    /// the reset (which stays at frame `0`). PORT_CONTROLLER is updated for controller swaps before the reset. Frames use
+    /// the same numbering as [`Self::seek_plan`].
+    pub fn extract_verification_segment(&self, start_frame: u64, end_frame: u64) -> Result<TasdFile, EditError> {
+        let boundary = self.seek_plan(start_frame).start;
+        let blank = self.blank_frames();
+        
+        let mut segment = TasdFile { version: self.version, keylen: self.keylen, packets: vec![], path: None };
+        segment.packets.extend(self.packets.iter().filter(|packet| is_segment_packet(packet)).cloned());
+        for port in self.input_bytes().into_keys() {
+            segment.packets.push(InputChunk { port, inputs: self.port_inputs(port) }.into());
+        }
+        
+        // BLANK_FRAMES only applies from power-on, so it's dropped if the segment starts at a later reset
+        let start = match boundary {
+            0 => 0,
+            _ => {
+                segment.packets.retain(|packet| !matches!(packet, Packet::BlankFrames(_)));
+                boundary.saturating_add_signed(-blank)
+            },
+        };
+        segment.trim(start..end_frame.saturating_add_signed(-blank))?;
+        
+        for packet in &mut segment.packets {
+            if let Packet::PortController(controller) = packet {
+                controller.kind = self.controller_at(controller.port, start).unwrap_or(controller.kind);
+            }
+        }
+        
+        Ok(segment)
+    }
+}
+
+/// Whether a packet is kept in a [verification segment][TasdFile::extract_verification_segment]. Input data is added
+/// separately, as it may be stored as INPUT_DELTA.
+fn is_segment_packet(packet: &Packet) -> bool {
+    matches!(packet,
+        Packet::ConsoleType(_) | Packet::ConsoleRegion(_) | Packet::GameTitle(_) | Packet::RomName(_) | Packet::GameIdentifier(_) |
+        Packet::TotalFrames(_) | Packet::BlankFrames(_) | Packet::MemoryInit(_) | Packet::Experimental(_) |
+        Packet::PortController(_) | Packet::PortOverread(_) | Packet::NesLatchFilter(_) | Packet::NesClockFilter(_) |
+        Packet::NesGameGenieCode(_) | Packet::SnesLatchFilter(_) | Packet::SnesClockFilter(_) | Packet::SnesGameGenieCode(_) |
+        Packet::SnesLatchTrain(_) | Packet::GenesisGameGenieCode(_) | Packet::ConsoleSetting(_) |
+        Packet::InputMoment(_) | Packet::Transition(_) | Packet::LagFrameChunk(_) | Packet::MovieTransition(_)
+    )
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Bookmark, Comment, ConsoleType, GameTitle, Index, InputChunk, LagFrameChunk, MovieTransition, PortController, Transition, TransitionKind};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn seek_plan() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0, 1, 2, 3, 4, 5, 6, 7] }.into());
+        file.packets.push(Transition { port: 0, index: Index::Frame(2), kind: TransitionKind::PowerReset }.into());
+        file.packets.push(MovieTransition { movie_frame: 5, kind: TransitionKind::SoftReset }.into());
+        file.packets.push(LagFrameChunk { movie_frame: 1, count: 3 }.into());
+        file.packets.push(LagFrameChunk { movie_frame: 6, count: 2 }.into());
+        
+        let plan = file.seek_plan(4);
+        assert_eq!(plan.start, 2);
+        assert_eq!(plan.resets.len(), 1);
+        assert_eq!(plan.resets[0].transition_type, 0x02);
+        assert_eq!(plan.inputs[0].offset, 2);
+        assert_eq!(plan.inputs[0].inputs, vec![2, 3]);
+        assert_eq!(plan.lag_frames, 0);
+        
+        let plan = file.seek_plan(100);
+        assert_eq!(plan.target, 8);
+        assert_eq!(plan.start, 5);
+        assert_eq!(plan.resets.len(), 2);
+        assert_eq!(plan.inputs[0].inputs, vec![5, 6, 7]);
+        assert_eq!(plan.lag_frames, 2);
+        assert_eq!(plan.emulated_frames(), 5);
+        
+        let plan = file.seek_plan(1);
+        assert_eq!(plan.start, 0);
+        assert!(plan.resets.is_empty());
+        assert_eq!(plan.inputs[0].inputs, vec![0]);
+    }
+    
+    #[test]
+    fn verification_segment() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(Comment { comment: "not needed".into() }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0, 1, 2, 3, 4, 5, 6, 7] }.into());
+        file.packets.push(Transition { port: 1, index: Index::Frame(1), kind: TransitionKind::PacketDerived(Box::new(PortController { port: 1, kind: 0x0102 }.into())) }.into());
+        file.packets.push(Transition { port: 0, index: Index::Frame(3), kind: TransitionKind::SoftReset }.into());
+        file.packets.push(Bookmark { frame: 4, name: "".into() }.into());
+        file.packets.push(LagFrameChunk { movie_frame: 5, count: 1 }.into());
+        
+        let segment = file.extract_verification_segment(5, 7).unwrap();
+        assert!(segment.first::<Comment>().is_none());
+        assert!(segment.first::<Bookmark>().is_none());
+        assert_eq!(segment.first::<GameTitle>().unwrap().title, "title");
+        assert_eq!(segment.first::<PortController>().unwrap().kind, 0x0102);
+        assert_eq!(segment.port_inputs(1), vec![3, 4, 5, 6]);
+        assert_eq!(segment.first::<Transition>().unwrap().index, Index::Frame(0));
+        assert_eq!(segment.first::<LagFrameChunk>().unwrap().movie_frame, 2);
+        
+        // without an earlier reset, the segment starts from power-on
+        let segment = file.extract_verification_segment(2, 3).unwrap();
+        assert_eq!(segment.port_inputs(1), vec![0, 1, 2]);
+        assert_eq!(segment.first::<PortController>().unwrap().kind, 0x0101);
+    }
+}
\ No newline at end of file
@@ -1,14 +1,32 @@
+use std::io::Write;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::spec::packets::{DumpCreated, Encode, Packet, PacketError};
+use crate::cancel::{CancelError, CancellationToken, check};
+use crate::spec::lenient::{ByteSwap, LenientOptions, read_swapped_length};
+use crate::spec::packets::{DumpCreated, Encode, KeyedPacket, Packet, PacketChecksum, PacketError};
 use crate::spec::reader::Reader;
 use crate::spec::writer::Writer;
+use crate::storage::{FsStorage, TasdStorage};
+use crate::timestamp::now_epoch;
+use crate::util::{crc32, HashingWriter};
 
+pub mod align;
+pub mod arena;
+pub mod borrowed;
+pub mod dynamic;
+pub mod lenient;
+pub mod meta;
 pub mod packets;
+pub mod profile;
 pub mod reader;
+pub mod spill;
+pub mod stream;
+pub mod toc;
 pub mod writer;
 
 pub const LATEST_VERSION: [u8; 2] = [0x00, 0x01];
+/// Version of the spec draft whose packets are available with the `unstable-spec` feature. See
+/// [DRAFT_KINDS][packets::DRAFT_KINDS].
+pub const DRAFT_VERSION: [u8; 2] = [0x00, 0x02];
 pub const MAGIC_NUMBER: [u8; 4] = [0x54, 0x41, 0x53, 0x44];
 
 #[derive(Debug)]
@@ -18,6 +36,12 @@ pub enum TasdError {
     MissingHeader,
     MagicNumberMismatch(Vec<u8>),
     MissingPath,
+    HandshakeRejected,
+    /// The operation was stopped early, e.g. by a [ParseObserver][crate::spec::stream::ParseObserver].
+    Cancelled,
+    /// Moving part of a file would shift PADDING which may be there to align the packets after it, see
+    /// [`TasdSurgeon::replace`][crate::surgery::TasdSurgeon::replace].
+    AlignmentPadding,
 }
 impl From<std::io::Error> for TasdError {
     fn from(value: std::io::Error) -> Self {
@@ -30,6 +54,43 @@ impl From<PacketError> for TasdError {
     }
 }
 
+/// Converts a read error to `eof` if it's because the data ran out, e.g. for reporting a file which is cut off the same
+/// way as a slice which is.
+pub(crate) fn eof_as<E: Into<TasdError>>(err: std::io::Error, eof: E) -> TasdError {
+    match err.kind() {
+        std::io::ErrorKind::UnexpectedEof => eof.into(),
+        _ => err.into(),
+    }
+}
+
+/// A packet whose [PacketChecksum] did not match its encoded bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumMismatch {
+    /// Index into [`TasdFile::packets`] of the corrupted packet.
+    pub index: usize,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// Non-fatal problems encountered while parsing a [TasdFile].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseReport {
+    /// Packets which were skipped because their payload could not be decoded.
+    pub skipped: Vec<PacketError>,
+    /// Packets which were decoded, but failed checksum verification.
+    pub corrupted: Vec<ChecksumMismatch>,
+    /// Fields which looked like they were written in little-endian, from
+    /// [`TasdFile::parse_slice_lenient`][crate::spec::TasdFile::parse_slice_lenient].
+    /// 
+    /// Boxed rather than a Vec, as it's rarely filled in, and keeps the report small enough to return in errors.
+    pub byte_swaps: Box<[ByteSwap]>,
+}
+impl ParseReport {
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty() && self.corrupted.is_empty() && self.byte_swaps.is_empty()
+    }
+}
+
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TasdFile {
@@ -51,7 +112,7 @@ impl TasdFile {
         let mut tasd = Self::default();
         tasd.packets.push(
             DumpCreated {
-                epoch: SystemTime::now().duration_since(UNIX_EPOCH).expect("Time has gone backwards?").as_secs() as i64
+                epoch: now_epoch()
             }.into()
         );
         
@@ -59,8 +120,13 @@ impl TasdFile {
     }
     
     pub fn parse_file<P: Into<PathBuf>>(path: P) -> Result<Self, TasdError> {
+        Self::parse_file_with(FsStorage, path)
+    }
+    
+    /// Loads and parses a file from the provided [TasdStorage].
+    pub fn parse_file_with<S: TasdStorage, P: Into<PathBuf>>(storage: S, path: P) -> Result<Self, TasdError> {
         let path = path.into();
-        let data = std::fs::read(&path)?;
+        let data = storage.read(&path)?;
         let mut file = Self::parse_slice(&data)?;
         file.path = Some(path);
         
@@ -68,13 +134,34 @@ impl TasdFile {
     }
     
     pub fn parse_slice(data: &[u8]) -> Result<Self, TasdError> {
+        let (file, report) = Self::parse_slice_with_report(data)?;
+        for err in report.skipped {
+            if let PacketError::InvalidPayload { key, payload } = err {
+                println!("InvalidPayload! Skipping. ({key:02X?}, {payload:02X?}");
+            }
+        }
+        
+        Ok(file)
+    }
+    
+    /// Parses a file the same as [`Self::parse_slice`], but also returns a [ParseReport] of any non-fatal problems.
+    /// 
+    /// Any [PacketChecksum] packets are verified against the raw bytes of the packet before them. Packets which fail
+    /// verification are still included in the file, and listed in [`ParseReport::corrupted`].
+    /// 
+    /// PADDING packets are skipped, and don't separate a packet from its checksum.
+    pub fn parse_slice_with_report(data: &[u8]) -> Result<(Self, ParseReport), TasdError> {
+        Self::parse_slice_inner(data, None, None).map_err(|err| err.into())
+    }
+    
+    pub(crate) fn parse_slice_inner(data: &[u8], token: Option<&CancellationToken>, lenient: Option<&LenientOptions>) -> Result<(Self, ParseReport), CancelError<(Self, ParseReport)>> {
         let mut r = Reader::new(&data);
         if r.remaining() < 7 {
-            return Err(TasdError::MissingHeader);
+            return Err(TasdError::MissingHeader.into());
         }
         let magic = r.read_len(4);
         if magic != MAGIC_NUMBER {
-            return Err(TasdError::MagicNumberMismatch(magic.to_vec()));
+            return Err(TasdError::MagicNumberMismatch(magic.to_vec()).into());
         }
         
         let mut file = Self {
@@ -83,19 +170,53 @@ impl TasdFile {
             packets: vec![],
             path: None,
         };
+        let mut report = ParseReport::default();
+        // collected separately, as the report's boxed slice can't be pushed to
+        let mut byte_swaps = vec![];
         
+        // byte range and index of the most recently decoded packet, for checksum verification
+        let mut previous: Option<(usize, usize, usize)> = None;
         while r.remaining() > 0 {
             use PacketError::*;
-            match Packet::with_reader(&mut r, file.keylen) {
-                Ok(packet) => file.packets.push(packet),
+            if let Some(token) = token {
+                check(token, || (file.clone(), ParseReport { byte_swaps: byte_swaps.clone().into(), ..report.clone() }))?;
+            }
+            let start = r.pos();
+            let mut result = Packet::with_reader(&mut r, file.keylen);
+            if matches!(result, Err(MissingPayload)) && lenient.is_some_and(|options| options.fix_byte_order) {
+                if let Some((packet, swap, end)) = read_swapped_length(data, start, file.keylen, file.packets.len()) {
+                    byte_swaps.push(swap);
+                    r.set_pos(end);
+                    result = Ok(packet);
+                }
+            }
+            match result {
+                Ok(Packet::PacketChecksum(checksum)) => {
+                    if let Some((index, start, end)) = previous.take() {
+                        let actual = crc32(&data[start..end]);
+                        if actual != checksum.crc32 {
+                            report.corrupted.push(ChecksumMismatch { index, expected: checksum.crc32, actual });
+                        }
+                    }
+                    file.packets.push(checksum.into());
+                },
+                Ok(Packet::Padding(_)) => (),
+                Ok(packet) => {
+                    previous = Some((file.packets.len(), start, r.pos()));
+                    file.packets.push(packet);
+                },
                 Err(err) => match err {
-                    MissingKey | MismatchedKey | MissingPayloadLength | UnsupportedExponent(_) => return Err(err.into()),
-                    InvalidPayload { key, payload } => println!("InvalidPayload! Skipping. ({key:02X?}, {payload:02X?}"),
+                    MissingKey | MismatchedKey | MissingPayloadLength | MissingPayload | UnsupportedExponent(_) => return Err(TasdError::from(err).into()),
+                    InvalidPayload { .. } => {
+                        previous = None;
+                        report.skipped.push(err);
+                    },
                 }
             }
         }
+        report.byte_swaps = byte_swaps.into();
         
-        Ok(file)
+        Ok((file, report))
     }
     
     /// Encodes data in this [TasdFile] into a TASD formatted Vec of bytes.
@@ -113,12 +234,125 @@ impl TasdFile {
         w.to_vec()
     }
     
+    /// Size in bytes of [`Self::encode`], computed from the lengths of the packets without encoding the file, e.g. to
+    /// report the size of a large dump or reserve space for it before it's written.
+    /// 
+    /// Packet types which can't compute their length directly (e.g. [dynamic packets][crate::spec::dynamic]) are
+    /// encoded one at a time to measure them.
+    pub fn estimated_encoded_size(&self) -> usize {
+        let header = MAGIC_NUMBER.len() + LATEST_VERSION.len() + 1;
+        header + self.packets.iter().map(|packet| packet.encoded_len(self.keylen)).sum::<usize>()
+    }
+    
+    /// Encodes this file to `out` one packet at a time, instead of building the whole encoding in memory first.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(&MAGIC_NUMBER)?;
+        out.write_all(&LATEST_VERSION)?;
+        out.write_all(&[self.keylen])?;
+        for packet in &self.packets {
+            out.write_all(&packet.encode(self.keylen))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Same as [`TasdFile::write_to`], also returning the SHA-256 hash of the encoded file, computed as it's written.
+    pub fn write_to_hashed<W: Write>(&self, out: W) -> std::io::Result<(W, [u8; 32])> {
+        let mut out = HashingWriter::new(out);
+        self.write_to(&mut out)?;
+        
+        Ok(out.finish())
+    }
+    
+    /// First packet of type `T`, if any.
+    /// 
+    /// ```
+    /// # use tasd::spec::TasdFile;
+    /// use tasd::spec::packets::GameTitle;
+    /// 
+    /// let mut file = TasdFile::new();
+    /// file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+    /// assert_eq!(file.first::<GameTitle>().unwrap().title, "Super Mario Bros.");
+    /// ```
+    pub fn first<T: KeyedPacket>(&self) -> Option<&T> {
+        self.packets.iter().find_map(T::from_packet)
+    }
+    
+    /// Mutable reference to the first packet of type `T`, if any.
+    pub fn first_mut<T: KeyedPacket>(&mut self) -> Option<&mut T> {
+        self.packets.iter_mut().find_map(T::from_packet_mut)
+    }
+    
+    /// Iterates over every packet of type `T`, in file order.
+    pub fn all<T: KeyedPacket + 'static>(&self) -> impl Iterator<Item = &T> {
+        self.packets.iter().filter_map(T::from_packet)
+    }
+    
+    /// Replaces the first packet of type `T` with `packet` and removes the rest, or adds `packet` to the end of the file
+    /// if there are none.
+    pub fn set<T: KeyedPacket + Into<Packet>>(&mut self, packet: T) {
+        let is_kind = |packet: &Packet| T::from_packet(packet).is_some();
+        match self.packets.iter().position(is_kind) {
+            Some(index) => {
+                self.packets[index] = packet.into();
+                let mut first = true;
+                self.packets.retain(|packet| !is_kind(packet) || std::mem::take(&mut first));
+            },
+            None => self.packets.push(packet.into()),
+        }
+    }
+    
+    /// Inserts a [PacketChecksum] after every packet, replacing any existing checksums.
+    /// 
+    /// Checksums are calculated using this file's current [`keylen`][field@TasdFile::keylen], so they must be
+    /// regenerated if the keylen or any packets are changed.
+    pub fn add_checksums(&mut self) {
+        self.remove_checksums();
+        
+        let packets = std::mem::take(&mut self.packets);
+        for packet in packets {
+            let crc32 = crc32(&packet.encode(self.keylen));
+            self.packets.push(packet);
+            self.packets.push(PacketChecksum { crc32 }.into());
+        }
+    }
+    
+    /// Checks every [PacketChecksum] against the packet before it, as encoded with this file's keylen.
+    /// 
+    /// Checksums which don't follow another packet are ignored.
+    pub fn verify_checksums(&self) -> Vec<ChecksumMismatch> {
+        let mut mismatches = vec![];
+        for (index, pair) in self.packets.windows(2).enumerate() {
+            if let [packet, Packet::PacketChecksum(checksum)] = pair {
+                if matches!(packet, Packet::PacketChecksum(_)) {
+                    continue;
+                }
+                let actual = crc32(&packet.encode(self.keylen));
+                if actual != checksum.crc32 {
+                    mismatches.push(ChecksumMismatch { index, expected: checksum.crc32, actual });
+                }
+            }
+        }
+        
+        mismatches
+    }
+    
+    /// Removes all [PacketChecksum] packets from this file.
+    pub fn remove_checksums(&mut self) {
+        self.packets.retain(|packet| !matches!(packet, Packet::PacketChecksum(_)));
+    }
+    
     /// Attempts to save this file to the path specified in [`self.path`][field@TasdFile::path].
     /// 
     /// If the path is `None`, or any IO errors are encountered, an `Err` is returned, otherwise `Ok(())`.
     pub fn save(&self) -> Result<(), TasdError> {
+        self.save_with(FsStorage)
+    }
+    
+    /// Same as [`Self::save`], but writes to the provided [TasdStorage] instead of the filesystem.
+    pub fn save_with<S: TasdStorage>(&self, storage: S) -> Result<(), TasdError> {
         if let Some(path) = self.path.as_ref() {
-            std::fs::write(path, self.encode()).map_err(|err| err.into())
+            storage.write(path, &self.encode()).map_err(|err| err.into())
         } else {
             Err(TasdError::MissingPath)
         }
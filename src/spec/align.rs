@@ -0,0 +1,80 @@
+//! Block-aligned encoding, for devices which read files straight from storage.
+//! 
+//! Replay device firmware reading from an SD card can DMA input data directly out of block reads if each INPUT_CHUNK
+//! payload starts on a block boundary. [`TasdFile::encode_block_aligned`] inserts PADDING packets to arrange this.
+
+use crate::spec::packets::{Encode, Packet};
+use crate::spec::writer::{padding_packet, Writer};
+use crate::spec::{LATEST_VERSION, MAGIC_NUMBER, TasdFile};
+
+/// Block size of SD cards, and the usual alignment for [`TasdFile::encode_block_aligned`].
+pub const SD_BLOCK_SIZE: usize = 512;
+
+impl TasdFile {
+    /// Encodes this file with [Padding] inserted before INPUT_CHUNK packets as needed, so that the payload of every
+    /// INPUT_CHUNK starts at an offset (from the start of the file) which is a multiple of `block` bytes.
+    /// 
+    /// Existing padding is left out, since the offsets it was made for no longer apply. An alignment of `0` or `1` is the
+    /// same as no alignment.
+    pub fn encode_block_aligned(&self, block: usize) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.write_slice(&MAGIC_NUMBER);
+        w.write_slice(&LATEST_VERSION);
+        w.write_u8(self.keylen);
+        
+        for packet in self.packets.iter().filter(|packet| !matches!(packet, Packet::Padding(_))) {
+            let encoded = packet.encode(self.keylen);
+            if let (Packet::InputChunk(chunk), true) = (packet, block > 1) {
+                let payload_start = w.len() + encoded.len() - (1 + chunk.inputs.len());
+                let gap = (block - payload_start % block) % block;
+                if gap > 0 {
+                    let padding = (0..)
+                        .find_map(|blocks| padding_packet((gap + blocks * block) as u64, self.keylen))
+                        .unwrap();
+                    w.write_slice(&padding);
+                }
+            }
+            w.write_slice(&encoded);
+        }
+        
+        w.to_vec()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::align::SD_BLOCK_SIZE;
+    use crate::spec::packets::{Comment, InputChunk, read_raw};
+    use crate::spec::reader::Reader;
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn block_aligned() {
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 700] }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 509] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 3] }.into());
+        
+        let data = file.encode_block_aligned(SD_BLOCK_SIZE);
+        let mut r = Reader::new(&data);
+        r.advance(7);
+        let mut chunks = 0;
+        while r.remaining() > 0 {
+            let (key, payload) = read_raw(&mut r, 2).unwrap();
+            if key == [0xFE, 0x01] {
+                assert_eq!((r.pos() - payload.remaining()) % SD_BLOCK_SIZE, 0);
+                chunks += 1;
+            }
+        }
+        assert_eq!(chunks, 3);
+        
+        // padding is skipped when parsing
+        assert_eq!(TasdFile::parse_slice(&data).unwrap().packets, file.packets);
+        assert_eq!(file.encode_block_aligned(1), file.encode());
+    }
+}
\ No newline at end of file
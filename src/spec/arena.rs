@@ -0,0 +1,125 @@
+//! Parsing many files one after another with as few allocations as possible, e.g. on a server indexing an archive.
+//!
+//! A [PacketArena] holds the bytes of one file at a time, and parses them into [PacketRef]s which borrow their strings
+//! and payloads from those bytes rather than allocating their own. Everything borrowed from a file is freed at once
+//! when the arena moves on to the next one, and the arena's buffer keeps its capacity, so once it has grown to fit the
+//! largest file, parsing only allocates the list of packets and any packets [PacketRef] doesn't borrow.
+
+use std::io::Read;
+use std::path::Path;
+use crate::spec::borrowed::{PacketRef, PacketRefs};
+use crate::spec::packets::PacketError;
+use crate::spec::{TasdError, TasdFile};
+
+/// Reusable buffer for parsing files into [PacketRef]s. See [the module docs][self].
+#[derive(Debug, Default)]
+pub struct PacketArena {
+    data: Vec<u8>,
+}
+impl PacketArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Creates an arena which can hold a file of `bytes` without growing.
+    pub fn with_capacity(bytes: usize) -> Self {
+        Self { data: Vec::with_capacity(bytes) }
+    }
+    
+    /// Number of bytes the arena can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+    
+    /// Reads all of `reader` into the arena, replacing the previous file, and parses it.
+    pub fn parse_reader<R: Read>(&mut self, mut reader: R) -> Result<ArenaFile<'_>, TasdError> {
+        self.data.clear();
+        reader.read_to_end(&mut self.data)?;
+        
+        ArenaFile::parse(&self.data)
+    }
+    
+    /// Reads the file at `path` into the arena, replacing the previous file, and parses it.
+    pub fn parse_file<P: AsRef<Path>>(&mut self, path: P) -> Result<ArenaFile<'_>, TasdError> {
+        self.parse_reader(std::fs::File::open(path)?)
+    }
+    
+    /// Copies `data` into the arena, replacing the previous file, and parses it.
+    pub fn parse_slice(&mut self, data: &[u8]) -> Result<ArenaFile<'_>, TasdError> {
+        self.data.clear();
+        self.data.extend_from_slice(data);
+        
+        ArenaFile::parse(&self.data)
+    }
+}
+
+/// File parsed by a [PacketArena], borrowing from it until the arena is used for the next file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaFile<'a> {
+    pub version: u16,
+    pub keylen: u8,
+    pub packets: Vec<PacketRef<'a>>,
+}
+impl<'a> ArenaFile<'a> {
+    /// Parses every packet, skipping those with an invalid payload the same as [`TasdFile::parse_slice`].
+    fn parse(data: &'a [u8]) -> Result<Self, TasdError> {
+        let refs = PacketRefs::new(data)?;
+        let (version, keylen) = (refs.version(), refs.keylen());
+        let mut packets = vec![];
+        for packet in refs {
+            match packet {
+                Ok(packet) => packets.push(packet),
+                Err(PacketError::InvalidPayload { .. }) => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        
+        Ok(Self { version, keylen, packets })
+    }
+    
+    /// Copies everything borrowed from the arena into a [TasdFile].
+    pub fn into_owned(self) -> TasdFile {
+        TasdFile {
+            version: self.version,
+            keylen: self.keylen,
+            packets: self.packets.into_iter().map(PacketRef::into_owned).collect(),
+            path: None,
+        }
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::arena::PacketArena;
+    use crate::spec::borrowed::PacketRef;
+    use crate::spec::packets::{Comment, GameTitle, InputChunk};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn arena() {
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 1000] }.into());
+        let large = file.encode();
+        file.packets.truncate(1);
+        file.packets.push(Comment { comment: "short".into() }.into());
+        let small = file.encode();
+        
+        let mut arena = PacketArena::new();
+        let parsed = arena.parse_slice(&large).unwrap();
+        assert!(matches!(parsed.packets[1], PacketRef::InputChunk { port: 1, inputs } if inputs.len() == 1000));
+        let capacity = arena.capacity();
+        
+        // the next file reuses the buffer
+        let parsed = arena.parse_reader(small.as_slice()).unwrap();
+        assert_eq!(parsed.clone().into_owned(), TasdFile::parse_slice(&small).unwrap());
+        assert_eq!(parsed.packets.len(), 2);
+        assert_eq!(arena.capacity(), capacity);
+        
+        assert!(arena.parse_slice(&small[..4]).is_err());
+    }
+}
\ No newline at end of file
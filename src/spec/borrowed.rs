@@ -0,0 +1,217 @@
+use std::borrow::Cow;
+use crate::spec::packets::{Attribution, Bookmark, Category, Comment, EmulatorCore, EmulatorName, EmulatorVersion, GameTitle,
+    GenesisGameGenieCode, InputChunk, MovieFile, MovieLicense, NesGameGenieCode, Packet, PacketError, PacketKind, read_raw,
+    RomName, SnesGameGenieCode, SourceLink};
+use crate::spec::reader::Reader;
+use crate::spec::{MAGIC_NUMBER, TasdError};
+
+/// Packet which borrows its strings and data from the bytes it was decoded from.
+/// 
+/// Strings are only allocated if they contain invalid UTF-8 which needs replacing, or are modified by the caller. This
+/// makes scanning a file for metadata (e.g. listing the titles and authors of many movies) much cheaper than a full
+/// [`TasdFile::parse_slice`][crate::spec::TasdFile::parse_slice].
+/// 
+/// INPUT_CHUNK data is borrowed as well. Other packets are decoded as normal and kept in [`PacketRef::Owned`]. Use
+/// [`PacketRef::into_owned`] to get a [Packet] for the rest of the API.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketRef<'a> {
+    /// Packet whose payload is a single string, i.e. GAME_TITLE, ROM_NAME, CATEGORY, EMULATOR_NAME, EMULATOR_VERSION,
+    /// EMULATOR_CORE, SOURCE_LINK, MOVIE_LICENSE, COMMENT, or any of the Game Genie codes.
+    Text {
+        kind: PacketKind,
+        text: Cow<'a, str>,
+    },
+    Attribution {
+        kind: u8,
+        name: Cow<'a, str>,
+    },
+    MovieFile {
+        name: Cow<'a, str>,
+        data: &'a [u8],
+    },
+    Bookmark {
+        frame: u64,
+        name: Cow<'a, str>,
+    },
+    InputChunk {
+        port: u8,
+        inputs: &'a [u8],
+    },
+    Owned(Packet),
+}
+impl<'a> PacketRef<'a> {
+    /// Decodes the next packet from `r`, the same as [`Packet::with_reader`].
+    pub fn with_reader(r: &mut Reader<'a>, keylen: u8) -> Result<Self, PacketError> {
+        let (key, mut payload) = read_raw(r, keylen)?;
+        
+        Ok(match PacketKind::from_key(key) {
+            kind if is_text(kind) => Self::Text { kind, text: payload.read_str(payload.remaining()) },
+            PacketKind::Attribution if payload.remaining() >= 1 => Self::Attribution {
+                kind: payload.read_u8(),
+                name: payload.read_str(payload.remaining()),
+            },
+            PacketKind::MovieFile if payload.remaining() >= 1 && payload.remaining() > payload.peek_u8() as usize => {
+                let len = payload.read_u8() as usize;
+                Self::MovieFile {
+                    name: payload.read_str(len),
+                    data: payload.read_remaining(),
+                }
+            },
+            PacketKind::Bookmark if payload.remaining() >= 8 => Self::Bookmark {
+                frame: payload.read_u64(),
+                name: payload.read_str(payload.remaining()),
+            },
+            PacketKind::InputChunk if payload.remaining() >= 1 => Self::InputChunk {
+                port: payload.read_u8(),
+                inputs: payload.read_remaining(),
+            },
+            _ => Self::Owned(Packet::decode_keyed(key, payload, keylen)?),
+        })
+    }
+    
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Self::Text { kind, .. } => *kind,
+            Self::Attribution { .. } => PacketKind::Attribution,
+            Self::MovieFile { .. } => PacketKind::MovieFile,
+            Self::Bookmark { .. } => PacketKind::Bookmark,
+            Self::InputChunk { .. } => PacketKind::InputChunk,
+            Self::Owned(packet) => packet.kind(),
+        }
+    }
+    
+    /// Converts this into an owned [Packet], copying any borrowed strings and data.
+    pub fn into_owned(self) -> Packet {
+        match self {
+            Self::Text { kind, text } => {
+                let text = text.into_owned();
+                match kind {
+                    PacketKind::GameTitle => GameTitle { title: text }.into(),
+                    PacketKind::RomName => RomName { name: text }.into(),
+                    PacketKind::Category => Category { category: text }.into(),
+                    PacketKind::EmulatorName => EmulatorName { name: text }.into(),
+                    PacketKind::EmulatorVersion => EmulatorVersion { version: text }.into(),
+                    PacketKind::EmulatorCore => EmulatorCore { core: text }.into(),
+                    PacketKind::SourceLink => SourceLink { link: text }.into(),
+                    PacketKind::MovieLicense => MovieLicense { license: text }.into(),
+                    PacketKind::NesGameGenieCode => NesGameGenieCode { code: text }.into(),
+                    PacketKind::SnesGameGenieCode => SnesGameGenieCode { code: text }.into(),
+                    PacketKind::GenesisGameGenieCode => GenesisGameGenieCode { code: text }.into(),
+                    _ => Comment { comment: text }.into(),
+                }
+            },
+            Self::Attribution { kind, name } => Attribution { kind, name: name.into_owned() }.into(),
+            Self::MovieFile { name, data } => MovieFile { name: name.into_owned(), data: data.to_vec() }.into(),
+            Self::Bookmark { frame, name } => Bookmark { frame, name: name.into_owned() }.into(),
+            Self::InputChunk { port, inputs } => InputChunk { port, inputs: inputs.to_vec() }.into(),
+            Self::Owned(packet) => packet,
+        }
+    }
+}
+impl<'a> From<PacketRef<'a>> for Packet {
+    fn from(value: PacketRef<'a>) -> Self {
+        value.into_owned()
+    }
+}
+
+fn is_text(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    matches!(kind, GameTitle | RomName | Category | EmulatorName | EmulatorVersion | EmulatorCore | SourceLink | MovieLicense
+        | NesGameGenieCode | SnesGameGenieCode | GenesisGameGenieCode | Comment)
+}
+
+/// Iterator over the [PacketRef]s of a TASD file held in memory.
+/// 
+/// Packets which fail to decode are returned as errors. Iteration stops after an error which leaves the rest of the
+/// file unreadable (anything other than [`PacketError::InvalidPayload`]).
+pub struct PacketRefs<'a> {
+    r: Reader<'a>,
+    version: u16,
+    keylen: u8,
+}
+impl<'a> PacketRefs<'a> {
+    /// Reads and verifies the TASD header at the start of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self, TasdError> {
+        let mut r = Reader::new(data);
+        if r.remaining() < 7 {
+            return Err(TasdError::MissingHeader);
+        }
+        let magic = r.read_len(4);
+        if magic != MAGIC_NUMBER {
+            return Err(TasdError::MagicNumberMismatch(magic.to_vec()));
+        }
+        
+        Ok(Self {
+            version: r.read_u16(),
+            keylen: r.read_u8(),
+            r,
+        })
+    }
+    
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+    
+    pub fn keylen(&self) -> u8 {
+        self.keylen
+    }
+}
+impl<'a> Iterator for PacketRefs<'a> {
+    type Item = Result<PacketRef<'a>, PacketError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.r.remaining() == 0 {
+            return None;
+        }
+        
+        let packet = PacketRef::with_reader(&mut self.r, self.keylen);
+        if matches!(packet, Err(ref err) if !matches!(err, PacketError::InvalidPayload { .. })) {
+            let remaining = self.r.remaining();
+            self.r.advance(remaining);
+        }
+        
+        Some(packet)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use crate::spec::borrowed::{PacketRef, PacketRefs};
+    use crate::spec::packets::{Attribution, Bookmark, Comment, ConsoleType, GameTitle, MovieFile, Packet, PacketKind};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn borrowed() {
+        let mut file = TasdFile::default();
+        let packets: Vec<Packet> = vec![
+            GameTitle { title: "Super Mario Bros.".into() }.into(),
+            Attribution { kind: 0x01, name: "author".into() }.into(),
+            MovieFile { name: "movie.fm2".into(), data: vec![1, 2, 3] }.into(),
+            Bookmark { frame: 60, name: "1-2".into() }.into(),
+            ConsoleType { kind: 0x01, custom: None }.into(),
+        ];
+        file.packets.extend(packets.clone());
+        let mut data = file.encode();
+        // invalid UTF-8 in a comment
+        data.extend([0xFF, 0x01, 0x01, 0x02, 0x61, 0xFF]);
+        
+        let refs: Vec<_> = PacketRefs::new(&data).unwrap().map(Result::unwrap).collect();
+        assert_eq!(refs.len(), 6);
+        assert!(matches!(refs[0], PacketRef::Text { kind: PacketKind::GameTitle, text: Cow::Borrowed("Super Mario Bros.") }));
+        assert!(matches!(refs[2], PacketRef::MovieFile { name: Cow::Borrowed("movie.fm2"), data: [1, 2, 3] }));
+        assert!(matches!(refs[4], PacketRef::Owned(Packet::ConsoleType(_))));
+        assert!(matches!(refs[5], PacketRef::Text { text: Cow::Owned(_), .. }));
+        
+        let owned: Vec<Packet> = refs.into_iter().map(PacketRef::into_owned).collect();
+        assert_eq!(owned[..5], packets[..]);
+        assert_eq!(owned[5], Comment { comment: "a\u{FFFD}".into() }.into());
+        
+        assert!(PacketRefs::new(&data[..5]).is_err());
+        assert_eq!(PacketRefs::new(&data[..data.len() - 1]).unwrap().filter(Result::is_err).count(), 1);
+    }
+}
\ No newline at end of file
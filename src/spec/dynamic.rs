@@ -0,0 +1,209 @@
+use std::any::Any;
+use std::fmt::{Debug, Display, Formatter};
+use crate::spec::packets::{Encode, Packet, PacketError, Unsupported};
+use crate::spec::reader::Reader;
+use crate::spec::TasdFile;
+
+/// Object-safe facade over a packet, allowing packet types to be defined outside of this crate.
+/// 
+/// Dynamic packets are stored in a [TasdFile] as [`Packet::Dynamic`], and are encoded like any other packet. When
+/// parsing, their keys are initially decoded as [Unsupported] packets, which can be converted back with
+/// [`TasdFile::resolve_dynamic`].
+pub trait TasdPacket: Debug + Send + Sync + 'static {
+    fn key(&self) -> Vec<u8>;
+    
+    fn name(&self) -> String;
+    
+    /// Encodes the entire packet, including its key and payload length.
+    fn encode(&self, keylen: u8) -> Vec<u8>;
+    
+    fn clone_box(&self) -> Box<dyn TasdPacket>;
+    
+    fn as_any(&self) -> &dyn Any;
+}
+impl Display for dyn TasdPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl TasdPacket for Packet {
+    fn key(&self) -> Vec<u8> {
+        Encode::key(self)
+    }
+    
+    fn name(&self) -> String {
+        self.kind().to_string()
+    }
+    
+    fn encode(&self, keylen: u8) -> Vec<u8> {
+        Encode::encode(self, keylen)
+    }
+    
+    fn clone_box(&self) -> Box<dyn TasdPacket> {
+        Box::new(self.clone())
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Boxed [TasdPacket] which can be stored in a [Packet].
+/// 
+/// Two dynamic packets are equal if they have the same key and encode to the same bytes.
+#[derive(Debug)]
+pub struct DynamicPacket(pub Box<dyn TasdPacket>);
+impl DynamicPacket {
+    pub fn new<T: TasdPacket>(packet: T) -> Self {
+        Self(Box::new(packet))
+    }
+    
+    /// Returns the inner packet if it's of type `T`.
+    pub fn downcast_ref<T: TasdPacket>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+impl Clone for DynamicPacket {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+impl PartialEq for DynamicPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.key() == other.0.key() && self.0.encode(8) == other.0.encode(8)
+    }
+}
+impl Encode for DynamicPacket {
+    fn encode(&self, keylen: u8) -> Vec<u8> {
+        self.0.encode(keylen)
+    }
+    
+    fn key(&self) -> Vec<u8> {
+        self.0.key()
+    }
+}
+impl From<DynamicPacket> for Packet {
+    fn from(value: DynamicPacket) -> Self {
+        Self::Dynamic(value)
+    }
+}
+
+/// Decodes the payload of a dynamic packet with the given key.
+pub type DynamicDecoder = fn(key: &[u8], payload: Reader) -> Result<Box<dyn TasdPacket>, PacketError>;
+
+/// Set of decoders for dynamic packet types, looked up by key.
+#[derive(Debug, Clone, Default)]
+pub struct DynamicRegistry {
+    decoders: Vec<(Vec<u8>, DynamicDecoder)>,
+}
+impl DynamicRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Registers a decoder for `key`, replacing any decoder previously registered for it.
+    pub fn register(&mut self, key: &[u8], decoder: DynamicDecoder) {
+        self.decoders.retain(|(existing, _)| existing != key);
+        self.decoders.push((key.to_vec(), decoder));
+    }
+    
+    /// Decodes an [Unsupported] packet with a registered decoder. Returns `None` if no decoder matches its key.
+    pub fn decode(&self, packet: &Unsupported) -> Option<Result<DynamicPacket, PacketError>> {
+        let (_, decoder) = self.decoders.iter().find(|(key, _)| *key == packet.key)?;
+        
+        Some(decoder(&packet.key, Reader::new(&packet.payload)).map(DynamicPacket))
+    }
+}
+
+impl TasdFile {
+    /// Converts every [Unsupported] packet with a key known to `registry` into a [`Packet::Dynamic`].
+    /// 
+    /// Packets which fail to decode are left unchanged. Returns the number of packets converted.
+    pub fn resolve_dynamic(&mut self, registry: &DynamicRegistry) -> usize {
+        let mut count = 0;
+        for packet in self.packets.iter_mut() {
+            if let Packet::Unsupported(unsupported) = packet {
+                if let Some(Ok(dynamic)) = registry.decode(unsupported) {
+                    *packet = dynamic.into();
+                    count += 1;
+                }
+            }
+        }
+        
+        count
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use crate::spec::dynamic::{DynamicPacket, DynamicRegistry, TasdPacket};
+    use crate::spec::packets::{Comment, Packet, PacketError};
+    use crate::spec::reader::Reader;
+    use crate::spec::writer::Writer;
+    use crate::spec::TasdFile;
+    
+    const KEY_SPLITS: &[u8] = &[0xF0, 0x01];
+    
+    #[derive(Debug, Clone, PartialEq)]
+    struct Splits {
+        frames: Vec<u32>,
+    }
+    impl TasdPacket for Splits {
+        fn key(&self) -> Vec<u8> {
+            KEY_SPLITS.to_vec()
+        }
+        
+        fn name(&self) -> String {
+            "SPLITS".into()
+        }
+        
+        fn encode(&self, keylen: u8) -> Vec<u8> {
+            let mut w = Writer::new();
+            self.frames.iter().for_each(|frame| w.write_u32(*frame));
+            
+            w.into_packet(KEY_SPLITS, keylen)
+        }
+        
+        fn clone_box(&self) -> Box<dyn TasdPacket> {
+            Box::new(self.clone())
+        }
+        
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+    
+    fn decode_splits(_key: &[u8], mut payload: Reader) -> Result<Box<dyn TasdPacket>, PacketError> {
+        let mut frames = vec![];
+        while payload.remaining() >= 4 {
+            frames.push(payload.read_u32());
+        }
+        
+        Ok(Box::new(Splits { frames }))
+    }
+    
+    #[test]
+    fn round_trip() {
+        let mut file = TasdFile::new();
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(DynamicPacket::new(Splits { frames: vec![100, 2500] }).into());
+        
+        let mut parsed = TasdFile::parse_slice(&file.encode()).unwrap();
+        assert!(matches!(parsed.packets[2], Packet::Unsupported(_)));
+        
+        let mut registry = DynamicRegistry::new();
+        registry.register(KEY_SPLITS, decode_splits);
+        assert_eq!(parsed.resolve_dynamic(&registry), 1);
+        assert_eq!(parsed.packets, file.packets);
+        
+        let Packet::Dynamic(dynamic) = &parsed.packets[2] else { panic!() };
+        assert_eq!(dynamic.downcast_ref::<Splits>().unwrap().frames, vec![100, 2500]);
+        assert_eq!(dynamic.0.to_string(), "SPLITS");
+        assert_eq!(parsed.packets[2].kind().to_string(), "DYNAMIC");
+    }
+}
\ No newline at end of file
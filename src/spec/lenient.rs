@@ -0,0 +1,207 @@
+//! Lenient parsing of files from third-party dumpers, which sometimes write numbers in little-endian by mistake.
+//!
+//! A little-endian value read as big-endian is usually absurd (e.g. 1000 frames becomes 3,892,510,720), while the
+//! same bytes reversed are a sensible value. [`TasdFile::parse_slice_lenient`] looks for fields like that, and can
+//! correct them, listing each one in [`ParseReport::byte_swaps`].
+
+use crate::spec::packets::{Packet, PacketKind};
+use crate::spec::{ParseReport, TasdError, TasdFile};
+use crate::timestamp::{epoch_mut, MAX_SECONDS_EPOCH};
+
+/// Largest TOTAL_FRAMES treated as sensible: a week of frames at 60 fps.
+pub const MAX_PLAUSIBLE_FRAMES: u32 = 60 * 60 * 60 * 24 * 7;
+/// Largest RERECORDS treated as sensible.
+pub const MAX_PLAUSIBLE_RERECORDS: u32 = 100_000_000;
+
+/// Options for [`TasdFile::parse_slice_lenient`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct LenientOptions {
+    /// Whether fields which look byte-swapped are corrected, instead of only being reported.
+    /// 
+    /// Byte-swapped payload lengths are only recovered from when this is set, as the rest of the file can't be parsed
+    /// otherwise.
+    pub fix_byte_order: bool,
+}
+
+/// A field which looked like it was written in little-endian, from [`ParseReport::byte_swaps`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ByteSwap {
+    /// Index into [`TasdFile::packets`] of the packet the field belongs to.
+    pub index: usize,
+    pub kind: PacketKind,
+    /// Name of the field, e.g. `"frames"`, or `"payload length"` for the length in the packet's header.
+    pub field: &'static str,
+    /// Value as it was read. Signed fields are stored as their raw bits.
+    pub value: u64,
+    /// Value with its bytes reversed.
+    pub swapped: u64,
+    /// Whether the field was changed to `swapped`.
+    pub corrected: bool,
+}
+
+/// Byte-swapped `value`, if `value` is more than `max` but its bytes reversed aren't.
+pub fn swapped_u32(value: u32, max: u32) -> Option<u32> {
+    let swapped = value.swap_bytes();
+    (value > max && swapped <= max).then_some(swapped)
+}
+
+/// Byte-swapped TOTAL_FRAMES, if it matches the number of frames of input data (when known) or is
+/// [absurd][MAX_PLAUSIBLE_FRAMES] while the swapped value isn't.
+fn swapped_frames(frames: u32, computed: Option<u32>) -> Option<u32> {
+    match computed {
+        Some(computed) if frames == computed => None,
+        Some(computed) if frames.swap_bytes() == computed => Some(computed),
+        _ => swapped_u32(frames, MAX_PLAUSIBLE_FRAMES),
+    }
+}
+
+/// Byte-swapped timestamp, if it's too far from the Unix epoch to be in seconds or milliseconds, while the swapped
+/// value is a positive number of seconds.
+fn swapped_epoch(epoch: i64) -> Option<i64> {
+    let swapped = epoch.swap_bytes();
+    let millis = MAX_SECONDS_EPOCH.saturating_mul(1000);
+    (epoch.unsigned_abs() > millis as u64 && (1..=MAX_SECONDS_EPOCH).contains(&swapped)).then_some(swapped)
+}
+
+/// Decodes the packet at `start` of `data` with its payload length read as little-endian, for recovering from a
+/// [MissingPayload][crate::spec::packets::PacketError::MissingPayload] error. `index` is the index the packet will
+/// have in the file.
+/// 
+/// Returns the packet, the swap, and the position after the packet, or `None` if the swapped length doesn't fit the
+/// data either.
+pub(crate) fn read_swapped_length(data: &[u8], start: usize, keylen: u8, index: usize) -> Option<(Packet, ByteSwap, usize)> {
+    let len_start = start + keylen as usize + 1;
+    let exp = *data.get(len_start - 1)? as usize;
+    if !(2..=8).contains(&exp) || data.len() < len_start + exp {
+        return None;
+    }
+    
+    let mut value = [0u8; 8];
+    value[(8 - exp)..].copy_from_slice(&data[len_start..(len_start + exp)]);
+    let value = u64::from_be_bytes(value);
+    let swapped = value.swap_bytes() >> (8 * (8 - exp));
+    let end = (len_start + exp).checked_add(usize::try_from(swapped).ok()?)?;
+    if end > data.len() {
+        return None;
+    }
+    
+    let mut packet = data[start..end].to_vec();
+    packet[(len_start - start)..(len_start - start + exp)].reverse();
+    let (packet, _) = Packet::decode_single(&packet, keylen).ok()?;
+    let swap = ByteSwap {
+        index,
+        kind: packet.kind(),
+        field: "payload length",
+        value,
+        swapped,
+        corrected: true,
+    };
+    
+    Some((packet, swap, end))
+}
+
+impl TasdFile {
+    /// Parses a file the same as [`Self::parse_slice_with_report`], but also looks for numbers which were written in
+    /// little-endian by mistake, and corrects them if [`LenientOptions::fix_byte_order`] is set. See the
+    /// [module docs][self].
+    /// 
+    /// Packet payload lengths, TOTAL_FRAMES, RERECORDS, and timestamps are checked. Every field found is listed in
+    /// [`ParseReport::byte_swaps`].
+    /// 
+    /// A payload length is only noticed when it runs past the end of the data. A little-endian length which happens to
+    /// fit inside the file is read as-is, so the packet (and those after it) are silently misparsed, and may be skipped
+    /// or decoded as different packets without any byte swap being reported.
+    pub fn parse_slice_lenient(data: &[u8], options: &LenientOptions) -> Result<(Self, ParseReport), TasdError> {
+        let (mut file, mut report) = Self::parse_slice_inner(data, None, Some(options)).map_err(TasdError::from)?;
+        report.byte_swaps = report.byte_swaps.iter().copied().chain(file.byte_swapped_fields(options.fix_byte_order)).collect();
+        
+        Ok((file, report))
+    }
+    
+    /// Fields of the decoded packets which look byte-swapped, correcting them if `fix` is set.
+    fn byte_swapped_fields(&mut self, fix: bool) -> Vec<ByteSwap> {
+        let computed = self.computed_total_frames();
+        let mut swaps = vec![];
+        for (index, packet) in self.packets.iter_mut().enumerate() {
+            let kind = packet.kind();
+            let swap = |field, value: u64, swapped: u64| ByteSwap { index, kind, field, value, swapped, corrected: fix };
+            match packet {
+                Packet::TotalFrames(total) => if let Some(swapped) = swapped_frames(total.frames, computed) {
+                    swaps.push(swap("frames", total.frames as u64, swapped as u64));
+                    if fix {
+                        total.frames = swapped;
+                    }
+                },
+                Packet::Rerecords(rerecords) => if let Some(swapped) = swapped_u32(rerecords.rerecords, MAX_PLAUSIBLE_RERECORDS) {
+                    swaps.push(swap("rerecords", rerecords.rerecords as u64, swapped as u64));
+                    if fix {
+                        rerecords.rerecords = swapped;
+                    }
+                },
+                packet => if let Some(epoch) = epoch_mut(packet) {
+                    if let Some(swapped) = swapped_epoch(*epoch) {
+                        swaps.push(swap("epoch", *epoch as u64, swapped as u64));
+                        if fix {
+                            *epoch = swapped;
+                        }
+                    }
+                },
+            }
+        }
+        
+        swaps
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::lenient::{swapped_u32, LenientOptions};
+    use crate::spec::packets::{DumpCreated, Encode, InputChunk, PacketKind, PortController, Rerecords, TotalFrames};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn byte_swaps() {
+        assert_eq!(swapped_u32(0xE803_0000, 1_000_000), Some(1000));
+        assert_eq!(swapped_u32(1000, 1_000_000), None);
+        assert_eq!(swapped_u32(0xFFFF_FFFF, 1_000_000), None);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 0x0102] }.into());
+        file.packets.push(TotalFrames { frames: 0x0201_0000 }.into());
+        file.packets.push(Rerecords { rerecords: 1234u32.swap_bytes() }.into());
+        file.packets.push(DumpCreated { epoch: 1_700_000_000i64.swap_bytes() }.into());
+        let mut data = file.encode();
+        
+        let (parsed, report) = TasdFile::parse_slice_lenient(&data, &LenientOptions::default()).unwrap();
+        assert_eq!(parsed, file);
+        assert_eq!(report.byte_swaps.iter().map(|swap| (swap.index, swap.field, swap.swapped, swap.corrected)).collect::<Vec<_>>(), [
+            (2, "frames", 0x0102, false),
+            (3, "rerecords", 1234, false),
+            (4, "epoch", 1_700_000_000, false),
+        ]);
+        assert!(!report.is_clean());
+        
+        // INPUT_CHUNK with its 2 byte payload length in little-endian
+        let length = file.packets[0].encode(2).len() + 3;
+        data[7 + length..][..2].reverse();
+        assert!(TasdFile::parse_slice_lenient(&data, &LenientOptions::default()).is_err());
+        let (parsed, report) = TasdFile::parse_slice_lenient(&data, &LenientOptions { fix_byte_order: true }).unwrap();
+        assert_eq!(parsed.first::<TotalFrames>().unwrap().frames, 0x0102);
+        assert_eq!(parsed.first::<Rerecords>().unwrap().rerecords, 1234);
+        assert_eq!(parsed.first::<DumpCreated>().unwrap().epoch, 1_700_000_000);
+        assert_eq!((report.byte_swaps[0].kind, report.byte_swaps[0].field), (PacketKind::InputChunk, "payload length"));
+        assert_eq!((report.byte_swaps[0].value, report.byte_swaps[0].swapped), (0x0301, 0x0103));
+        assert_eq!(report.byte_swaps.len(), 4);
+        
+        // well-formed files aren't changed
+        let file = TasdFile::new();
+        let (parsed, report) = TasdFile::parse_slice_lenient(&file.encode(), &LenientOptions { fix_byte_order: true }).unwrap();
+        assert_eq!(parsed, file);
+        assert!(report.is_clean());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,185 @@
+//! Reading only the metadata of a file, e.g. for listing thousands of movies in a library.
+//! 
+//! [`TasdMeta::from_slice`] walks the packets by their payload lengths, and only decodes the few kinds it keeps. Input
+//! data, embedded files, and memory contents are skipped without being copied, which makes it much cheaper than a full
+//! [`TasdFile::parse_slice`][crate::spec::TasdFile::parse_slice] for large dumps. [`TasdMeta::from_reader`] does the
+//! same for a file on disk, seeking past the payloads it skips so they're never read at all.
+
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::time::Duration;
+use crate::spec::packets::{read_raw, unpadded_key, Packet, PacketError, PacketKind};
+use crate::spec::reader::Reader;
+use crate::spec::{eof_as, MAGIC_NUMBER, TasdError};
+use crate::timing::{frame_rate, frame_to_duration};
+
+/// Metadata of a file, from [`TasdMeta::from_slice`]. Fields for packets which may only appear once hold the first one.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TasdMeta {
+    pub version: u16,
+    pub keylen: u8,
+    /// Kind of the CONSOLE_TYPE packet.
+    pub console: Option<u8>,
+    pub region: Option<u8>,
+    /// Every GAME_TITLE, in order.
+    pub titles: Vec<String>,
+    /// Name of every ATTRIBUTION of an author, in order.
+    pub authors: Vec<String>,
+    pub category: Option<String>,
+    pub total_frames: Option<u32>,
+    pub rerecords: Option<u32>,
+    /// Number of packets in the file, including the skipped ones.
+    pub packets: usize,
+}
+impl TasdMeta {
+    /// Reads the header and metadata packets of an encoded file.
+    /// 
+    /// Metadata packets with invalid payloads are ignored, but a file which is cut off or has a broken payload length
+    /// is an error, the same as with a full parse.
+    pub fn from_slice(data: &[u8]) -> Result<Self, TasdError> {
+        let mut r = Reader::new(data);
+        if r.remaining() < 7 {
+            return Err(TasdError::MissingHeader);
+        }
+        let magic = r.read_len(4);
+        if magic != MAGIC_NUMBER {
+            return Err(TasdError::MagicNumberMismatch(magic.to_vec()));
+        }
+        
+        let mut meta = Self {
+            version: r.read_u16(),
+            keylen: r.read_u8(),
+            ..Default::default()
+        };
+        while r.remaining() > 0 {
+            let (key, payload) = read_raw(&mut r, meta.keylen)?;
+            meta.add_packet(key, payload);
+        }
+        
+        Ok(meta)
+    }
+    
+    /// Reads the header and metadata packets of a file the same as [`Self::from_slice`], seeking past the payloads of
+    /// the other packets instead of reading them.
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self, TasdError> {
+        let mut r = BufReader::new(reader);
+        let end = r.seek(SeekFrom::End(0))?;
+        r.seek(SeekFrom::Start(0))?;
+        
+        let mut header = [0u8; 7];
+        r.read_exact(&mut header).map_err(|err| eof_as(err, TasdError::MissingHeader))?;
+        if header[..4] != MAGIC_NUMBER {
+            return Err(TasdError::MagicNumberMismatch(header[..4].to_vec()));
+        }
+        
+        let mut meta = Self {
+            version: u16::from_be_bytes([header[4], header[5]]),
+            keylen: header[6],
+            ..Default::default()
+        };
+        let mut key = vec![0u8; meta.keylen as usize];
+        let mut pos = header.len() as u64;
+        while pos < end {
+            let mut exp = [0u8];
+            r.read_exact(&mut key).map_err(|err| eof_as(err, PacketError::MissingKey))?;
+            r.read_exact(&mut exp).map_err(|err| eof_as(err, PacketError::MissingPayloadLength))?;
+            if exp[0] > 8 {
+                return Err(PacketError::UnsupportedExponent(exp[0]).into());
+            }
+            let mut plen = [0u8; 8];
+            r.read_exact(&mut plen[(8 - exp[0] as usize)..]).map_err(|err| eof_as(err, PacketError::MissingPayloadLength))?;
+            
+            pos += key.len() as u64 + 1 + exp[0] as u64;
+            let plen = u64::from_be_bytes(plen);
+            if end - pos < plen {
+                return Err(PacketError::MissingPayload.into());
+            }
+            pos += plen;
+            
+            let key = unpadded_key(&key);
+            if is_meta(PacketKind::from_key(key)) {
+                let mut payload = vec![0u8; plen as usize];
+                r.read_exact(&mut payload)?;
+                meta.add_packet(key, Reader::new(&payload));
+            } else {
+                r.seek_relative(plen as i64)?;
+                meta.packets += 1;
+            }
+        }
+        
+        Ok(meta)
+    }
+    
+    /// Frame rate of the console and region, assuming NTSC if there's no CONSOLE_REGION. See [frame_rate][crate::timing::frame_rate].
+    pub fn frame_rate(&self) -> Option<f64> {
+        frame_rate(self.console?, self.region.unwrap_or(0x01))
+    }
+    
+    /// Length of the movie according to TOTAL_FRAMES and the frame rate.
+    pub fn duration(&self) -> Option<Duration> {
+        Some(frame_to_duration(self.total_frames? as u64, self.frame_rate()?))
+    }
+    
+    /// Counts a packet, and keeps its value if it's one of the metadata packets.
+    fn add_packet(&mut self, key: &[u8], payload: Reader) {
+        self.packets += 1;
+        if !is_meta(PacketKind::from_key(key)) {
+            return;
+        }
+        
+        match Packet::decode_keyed(key, payload, self.keylen) {
+            Ok(Packet::ConsoleType(console)) => { self.console.get_or_insert(console.kind); },
+            Ok(Packet::ConsoleRegion(region)) => { self.region.get_or_insert(region.region); },
+            Ok(Packet::GameTitle(title)) => self.titles.push(title.title),
+            Ok(Packet::Attribution(attribution)) if attribution.kind == 0x01 => self.authors.push(attribution.name),
+            Ok(Packet::Category(category)) => { self.category.get_or_insert(category.category); },
+            Ok(Packet::TotalFrames(total)) => { self.total_frames.get_or_insert(total.frames); },
+            Ok(Packet::Rerecords(rerecords)) => { self.rerecords.get_or_insert(rerecords.rerecords); },
+            _ => (),
+        }
+    }
+}
+
+/// Whether a packet kind is decoded by [`TasdMeta::from_slice`].
+fn is_meta(kind: PacketKind) -> bool {
+    use PacketKind::*;
+    matches!(kind, ConsoleType | ConsoleRegion | GameTitle | Attribution | Category | TotalFrames | Rerecords)
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::spec::meta::TasdMeta;
+    use crate::spec::packets::{Attribution, ConsoleType, GameTitle, InputChunk, PacketError, TotalFrames};
+    use crate::spec::{TasdError, TasdFile};
+    
+    #[test]
+    fn from_slice() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x03, custom: None }.into());
+        file.packets.push(GameTitle { title: "Super Mario 64".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "author".into() }.into());
+        file.packets.push(Attribution { kind: 0x02, name: "verifier".into() }.into());
+        file.packets.push(TotalFrames { frames: 6000 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 6000] }.into());
+        
+        let meta = TasdMeta::from_slice(&file.encode()).unwrap();
+        assert_eq!(meta.console, Some(0x03));
+        assert_eq!(meta.titles, ["Super Mario 64"]);
+        assert_eq!(meta.authors, ["author"]);
+        assert_eq!(meta.total_frames, Some(6000));
+        assert_eq!(meta.packets, 6);
+        assert_eq!(meta.duration().unwrap().as_secs(), 100);
+        
+        let data = file.encode();
+        assert!(matches!(TasdMeta::from_slice(&data[..(data.len() - 1)]), Err(TasdError::Packet(_))));
+        assert!(matches!(TasdMeta::from_slice(b"TASD"), Err(TasdError::MissingHeader)));
+        
+        assert_eq!(TasdMeta::from_reader(Cursor::new(&data)).unwrap(), meta);
+        assert!(matches!(TasdMeta::from_reader(Cursor::new(&data[..(data.len() - 1)])), Err(TasdError::Packet(PacketError::MissingPayload))));
+        assert!(matches!(TasdMeta::from_reader(Cursor::new(b"TASD")), Err(TasdError::MissingHeader)));
+    }
+}
\ No newline at end of file
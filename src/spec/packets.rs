@@ -1,16 +1,125 @@
-use std::cmp::min;
+use std::cell::Cell;
 use std::fmt::Debug;
+use crate::spec::dynamic::DynamicPacket;
 use crate::spec::reader::Reader;
-use crate::spec::writer::Writer;
+use crate::spec::writer::{packet_header_len, Writer};
+use crate::util::truncate_str;
+pub use tasd_derive::TasdPacket;
+
+/// Generates the [Packet] and [PacketKind] enums, along with all of their dispatch, from a single list of packet types
+/// and their keys. Adding a new packet type only requires adding it to the list at the invocation of this macro.
+/// 
+/// [Unsupported] is always included after the listed types, and is used for any key not in the list. It's followed by
+/// `Dynamic`, which holds packet types defined outside of this crate.
+/// 
+/// Entries may have attributes, e.g. `#[cfg(feature = "unstable-spec")]` for draft packets, which are applied to
+/// everything generated for them.
+macro_rules! define_packets {
+    ($($(#[$meta:meta])* $name:ident => $key:ident),* $(,)?) => {
+        #[derive(Debug, Clone, PartialEq)]
+        #[non_exhaustive]
+        pub enum Packet {
+            $($(#[$meta])* $name($name),)*
+            Unsupported(Unsupported),
+            Dynamic(DynamicPacket),
+        }
+        impl Packet {
+            /// Decodes a payload from a file with the given keylen into the packet type matching `key`.
+            pub(crate) fn decode_keyed(key: &[u8], payload: Reader, keylen: u8) -> Result<Packet, PacketError> {
+                Ok(match key {
+                    $($(#[$meta])* $key => Packet::$name($name::decode_with_keylen(key, payload, keylen)?),)*
+                    
+                    _ => Packet::Unsupported(Unsupported::decode_with_keylen(key, payload, keylen)?)
+                })
+            }
+            
+            pub fn kind(&self) -> PacketKind {
+                match self {
+                    $($(#[$meta])* Self::$name(packet) => packet.kind(),)*
+                    Self::Unsupported(packet) => packet.kind(),
+                    Self::Dynamic(_) => PacketKind::Dynamic,
+                }
+            }
+        }
+        impl Encode for Packet {
+            fn encode(&self, keylen: u8) -> Vec<u8> {
+                match self {
+                    $($(#[$meta])* Self::$name(packet) => packet.encode(keylen),)*
+                    Self::Unsupported(packet) => packet.encode(keylen),
+                    Self::Dynamic(packet) => packet.encode(keylen),
+                }
+            }
+            
+            fn key(&self) -> Vec<u8> {
+                match self {
+                    $($(#[$meta])* Self::$name(packet) => packet.key(),)*
+                    Self::Unsupported(packet) => packet.key(),
+                    Self::Dynamic(packet) => packet.key(),
+                }
+            }
+            
+            fn encoded_len(&self, keylen: u8) -> usize {
+                match self {
+                    $($(#[$meta])* Self::$name(packet) => packet.encoded_len(keylen),)*
+                    Self::Unsupported(packet) => packet.encoded_len(keylen),
+                    Self::Dynamic(packet) => packet.encoded_len(keylen),
+                }
+            }
+        }
+        
+        #[derive(Debug, Copy, Clone, PartialEq, strum_macros::Display, strum_macros::EnumString)]
+        #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+        pub enum PacketKind {
+            $($(#[$meta])* $name,)*
+            Unsupported,
+            /// A packet type defined outside of this crate. See [TasdPacket][crate::spec::dynamic::TasdPacket].
+            Dynamic,
+        }
+        impl PacketKind {
+            /// Kind of packet encoded with `key`, or [`PacketKind::Unsupported`] if the key isn't known.
+            pub fn from_key(key: &[u8]) -> PacketKind {
+                match key {
+                    $($(#[$meta])* $key => PacketKind::$name,)*
+                    _ => PacketKind::Unsupported,
+                }
+            }
+        }
+        
+        /// Keys of every packet type this crate decodes, in the order they're listed in [Packet].
+        pub const PACKET_KEYS: &[&[u8]] = &[$($(#[$meta])* $key,)*];
+        
+        $($(#[$meta])* impl_keyed_packet!($name => $key);)*
+        impl_keyed_packet!(Unsupported => &[]);
+    };
+}
 
-macro_rules! impl_from_packet {
-    ($($name:ident)*) => ($(
+macro_rules! impl_keyed_packet {
+    ($name:ident => $key:expr) => {
         impl From<$name> for Packet {
             fn from(value: $name) -> Self {
                 Self::$name(value)
             }
         }
-    )*)
+        
+        impl KeyedPacket for $name {
+            const KEY: &'static [u8] = $key;
+            const KIND: PacketKind = PacketKind::$name;
+            
+            fn from_packet(packet: &Packet) -> Option<&Self> {
+                match packet {
+                    Packet::$name(value) => Some(value),
+                    _ => None,
+                }
+            }
+            
+            fn from_packet_mut(packet: &mut Packet) -> Option<&mut Self> {
+                match packet {
+                    Packet::$name(value) => Some(value),
+                    _ => None,
+                }
+            }
+        }
+    };
 }
 
 pub const KEY_CONSOLE_TYPE: &[u8] =         &[0x00, 0x01];
@@ -61,11 +170,29 @@ pub const KEY_COMMENT: &[u8] =              &[0xFF, 0x01];
 pub const KEY_EXPERIMENTAL: &[u8] =         &[0xFF, 0xFE];
 pub const KEY_UNSPECIFIED: &[u8] =          &[0xFF, 0xFF];
 
-#[derive(Debug)]
+// Extension packets specific to this crate. These are not part of the TASD spec, and use keys
+// from the 0xFF group so that other parsers will treat them as unknown packets.
+pub const KEY_PACKET_CHECKSUM: &[u8] =      &[0xFF, 0x80];
+pub const KEY_SAVESTATE_ANCHOR: &[u8] =     &[0xFF, 0x81];
+pub const KEY_BOOKMARK: &[u8] =             &[0xFF, 0x82];
+pub const KEY_LINK_SESSION: &[u8] =         &[0xFF, 0x83];
+pub const KEY_RANDOM_SEED: &[u8] =          &[0xFF, 0x84];
+pub const KEY_INPUT_DELTA: &[u8] =          &[0xFF, 0x85];
+pub const KEY_TABLE_OF_CONTENTS: &[u8] =    &[0xFF, 0x86];
+pub const KEY_PADDING: &[u8] =              &[0xFF, 0x87];
+pub const KEY_PROVENANCE: &[u8] =           &[0xFF, 0x88];
+pub const KEY_EDIT_HISTORY: &[u8] =         &[0xFF, 0x89];
+pub const KEY_CONSOLE_SETTING: &[u8] =      &[0xFF, 0x8A];
+pub const KEY_LATCH_MAP: &[u8] =            &[0xFF, 0x8B];
+pub const KEY_AV_SYNC: &[u8] =              &[0xFF, 0x8C];
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PacketError {
     MissingKey,
     MismatchedKey,
     MissingPayloadLength,
+    /// The payload length runs past the end of the data.
+    MissingPayload,
     UnsupportedExponent(u8),
     InvalidPayload {
         key: Vec<u8>,
@@ -81,11 +208,50 @@ impl PacketError {
     }
 }
 
+/// Arguments rejected by a validating packet constructor, such as [`PortController::new`].
+/// 
+/// The packet structs can still be built directly with any values, e.g. to represent files which break these rules.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    /// BLANK_FRAMES of `0`, which has no effect and is usually a missing value.
+    ZeroBlankFrames,
+    /// Port `0`. Ports are numbered from `1`.
+    ZeroPort,
+}
+
 
-pub trait Decode: Sized + Debug + Clone + PartialEq {
+/// Compile-time key and kind of a packet type, along with access to it from within a [Packet].
+/// 
+/// Implemented for every packet type, which allows generic lookups such as `file.first::<GameTitle>()`.
+pub trait KeyedPacket: Sized {
+    /// Key this packet type is encoded with. Empty for [Unsupported], whose key is only known at runtime.
+    const KEY: &'static [u8];
+    const KIND: PacketKind;
+    
+    fn from_packet(packet: &Packet) -> Option<&Self>;
+    fn from_packet_mut(packet: &mut Packet) -> Option<&mut Self>;
+}
+
+/// Decoding of a packet from its payload.
+/// 
+/// Decoding never panics, whatever the payload. It fails with [`PacketError::InvalidPayload`] if the payload is too
+/// short, if a packet with no variable length field has bytes left over, if a length within the payload runs past its
+/// end, or if a SNES_LATCH_TRAIN has a partial point. Strings are decoded lossily, with invalid UTF-8 replaced by U+FFFD
+/// rather than rejected. Data following the fixed fields of a TRANSITION or MOVIE_TRANSITION is only decoded as a packet
+/// when the transition type is 0xFF, and is ignored otherwise; decoding that packet can fail with any error
+/// [`Packet::with_reader`] can. Transitions nested more than [MAX_TRANSITION_DEPTH] deep fail with `InvalidPayload`.
+pub trait Decode: KeyedPacket + Debug + Clone + PartialEq {
     fn decode(key: &[u8], payload: Reader) -> Result<Self, PacketError>;
     
-    fn kind(&self) -> PacketKind;
+    /// Decodes a payload from a file whose keys are `keylen` bytes long. Only packets which embed other packets need
+    /// the keylen, as `key` has any padding removed, so this defaults to [`Decode::decode`].
+    fn decode_with_keylen(key: &[u8], payload: Reader, _keylen: u8) -> Result<Self, PacketError> {
+        Self::decode(key, payload)
+    }
+    
+    fn kind(&self) -> PacketKind {
+        Self::KIND
+    }
     fn name(&self) -> String {
         self.kind().to_string()
     }
@@ -95,351 +261,225 @@ pub trait Encode: Debug + Clone + PartialEq {
     fn encode(&self, keylen: u8) -> Vec<u8>;
     
     fn key(&self) -> Vec<u8>;
+    
+    /// Length of [`Self::encode`], which packet types can compute without encoding the packet.
+    fn encoded_len(&self, keylen: u8) -> usize {
+        self.encode(keylen).len()
+    }
 }
 
-
-#[derive(Debug, Clone, PartialEq)]
-#[non_exhaustive]
-pub enum Packet {
-    ConsoleType(ConsoleType),
-    ConsoleRegion(ConsoleRegion),
-    GameTitle(GameTitle),
-    RomName(RomName),
-    Attribution(Attribution),
-    Category(Category),
-    EmulatorName(EmulatorName),
-    EmulatorVersion(EmulatorVersion),
-    EmulatorCore(EmulatorCore),
-    TasLastModified(TasLastModified),
-    DumpCreated(DumpCreated),
-    DumpLastModified(DumpLastModified),
-    TotalFrames(TotalFrames),
-    Rerecords(Rerecords),
-    SourceLink(SourceLink),
-    BlankFrames(BlankFrames),
-    Verified(Verified),
-    MemoryInit(MemoryInit),
-    GameIdentifier(GameIdentifier),
-    MovieLicense(MovieLicense),
-    MovieFile(MovieFile),
-    PortController(PortController),
-    PortOverread(PortOverread),
-    NesLatchFilter(NesLatchFilter),
-    NesClockFilter(NesClockFilter),
-    NesGameGenieCode(NesGameGenieCode),
-    SnesLatchFilter(SnesLatchFilter),
-    SnesClockFilter(SnesClockFilter),
-    SnesGameGenieCode(SnesGameGenieCode),
-    SnesLatchTrain(SnesLatchTrain),
-    GenesisGameGenieCode(GenesisGameGenieCode),
-    InputChunk(InputChunk),
-    InputMoment(InputMoment),
-    Transition(Transition),
-    LagFrameChunk(LagFrameChunk),
-    MovieTransition(MovieTransition),
-    Comment(Comment),
-    Experimental(Experimental),
-    Unspecified(Unspecified),
-    Unsupported(Unsupported),
+/// Writes the payload of a packet type with a fixed key. [Encode] is implemented automatically for these types.
+pub trait EncodePayload: KeyedPacket {
+    fn encode_payload(&self, w: &mut Writer, keylen: u8);
+    
+    /// Length of the payload written by [`Self::encode_payload`].
+    fn payload_len(&self, keylen: u8) -> usize {
+        let mut w = Writer::new();
+        self.encode_payload(&mut w, keylen);
+        
+        w.len()
+    }
+}
+impl<T: EncodePayload + Debug + Clone + PartialEq> Encode for T {
+    fn encode(&self, keylen: u8) -> Vec<u8> {
+        let mut w = Writer::new();
+        self.encode_payload(&mut w, keylen);
+        
+        w.into_packet(Self::KEY, keylen)
+    }
+    
+    fn encoded_len(&self, keylen: u8) -> usize {
+        let len = self.payload_len(keylen);
+        packet_header_len(Self::KEY, keylen, len as u64) + len
+    }
+    
+    fn key(&self) -> Vec<u8> {
+        Self::KEY.to_vec()
+    }
 }
+
+
 impl Packet {
     pub fn with_reader(r: &mut Reader, keylen: u8) -> Result<Packet, PacketError> {
-        if r.remaining() < keylen as usize {
-            return Err(PacketError::MissingKey);
-        }
-        let key = r.read_len(keylen as usize).to_vec();
-        
-        if r.remaining() < 1 {
-            return Err(PacketError::MissingPayloadLength);
-        }
-        let exp = r.read_u8() as usize;
+        let (key, payload) = read_raw(r, keylen)?;
         
-        if r.remaining() < exp {
-            return Err(PacketError::MissingPayloadLength);
-        }
-        if exp > 8 {
-            return Err(PacketError::UnsupportedExponent(exp as u8));
-        }
-        
-        let mut plen = [0u8; 8];
-        for i in (0..exp).rev() {
-            plen[plen.len() - i - 1] = r.read_u8();
-        }
-        let plen = u64::from_be_bytes(plen);
-        
-        let payload = r.read_len(plen as usize);
-        let payload = Reader::new(&payload);
-        
-        let key = key.as_slice();
-        Ok(match key {
-            KEY_CONSOLE_TYPE => Packet::ConsoleType(ConsoleType::decode(key, payload)?),
-            KEY_CONSOLE_REGION => Packet::ConsoleRegion(ConsoleRegion::decode(key, payload)?),
-            KEY_GAME_TITLE => Packet::GameTitle(GameTitle::decode(key, payload)?),
-            KEY_ROM_NAME => Packet::RomName(RomName::decode(key, payload)?),
-            KEY_ATTRIBUTION => Packet::Attribution(Attribution::decode(key, payload)?),
-            KEY_CATEGORY => Packet::Category(Category::decode(key, payload)?),
-            KEY_EMULATOR_NAME => Packet::EmulatorName(EmulatorName::decode(key, payload)?),
-            KEY_EMULATOR_VERSION => Packet::EmulatorVersion(EmulatorVersion::decode(key, payload)?),
-            KEY_EMULATOR_CORE => Packet::EmulatorCore(EmulatorCore::decode(key, payload)?),
-            KEY_TAS_LAST_MODIFIED => Packet::TasLastModified(TasLastModified::decode(key, payload)?),
-            KEY_DUMP_CREATED => Packet::DumpCreated(DumpCreated::decode(key, payload)?),
-            KEY_DUMP_LAST_MODIFIED => Packet::DumpLastModified(DumpLastModified::decode(key, payload)?),
-            KEY_TOTAL_FRAMES => Packet::TotalFrames(TotalFrames::decode(key, payload)?),
-            KEY_RERECORDS => Packet::Rerecords(Rerecords::decode(key, payload)?),
-            KEY_SOURCE_LINK => Packet::SourceLink(SourceLink::decode(key, payload)?),
-            KEY_BLANK_FRAMES => Packet::BlankFrames(BlankFrames::decode(key, payload)?),
-            KEY_VERIFIED => Packet::Verified(Verified::decode(key, payload)?),
-            KEY_MEMORY_INIT => Packet::MemoryInit(MemoryInit::decode(key, payload)?),
-            KEY_GAME_IDENTIFIER => Packet::GameIdentifier(GameIdentifier::decode(key, payload)?),
-            KEY_MOVIE_LICENSE => Packet::MovieLicense(MovieLicense::decode(key, payload)?),
-            KEY_MOVIE_FILE => Packet::MovieFile(MovieFile::decode(key, payload)?),
-            KEY_PORT_CONTROLLER => Packet::PortController(PortController::decode(key, payload)?),
-            KEY_PORT_OVERREAD => Packet::PortOverread(PortOverread::decode(key, payload)?),
-            KEY_NES_LATCH_FILTER => Packet::NesLatchFilter(NesLatchFilter::decode(key, payload)?),
-            KEY_NES_CLOCK_FILTER => Packet::NesClockFilter(NesClockFilter::decode(key, payload)?),
-            KEY_NES_GAME_GENIE_CODE => Packet::NesGameGenieCode(NesGameGenieCode::decode(key, payload)?),
-            KEY_SNES_LATCH_FILTER => Packet::SnesLatchFilter(SnesLatchFilter::decode(key, payload)?),
-            KEY_SNES_CLOCK_FILTER => Packet::SnesClockFilter(SnesClockFilter::decode(key, payload)?),
-            KEY_SNES_GAME_GENIE_CODE => Packet::SnesGameGenieCode(SnesGameGenieCode::decode(key, payload)?),
-            KEY_SNES_LATCH_TRAIN => Packet::SnesLatchTrain(SnesLatchTrain::decode(key, payload)?),
-            KEY_GENESIS_GAME_GENIE_CODE => Packet::GenesisGameGenieCode(GenesisGameGenieCode::decode(key, payload)?),
-            KEY_INPUT_CHUNK => Packet::InputChunk(InputChunk::decode(key, payload)?),
-            KEY_INPUT_MOMENT => Packet::InputMoment(InputMoment::decode(key, payload)?),
-            KEY_TRANSITION => Packet::Transition(Transition::decode(key, payload)?),
-            KEY_LAG_FRAME_CHUNK => Packet::LagFrameChunk(LagFrameChunk::decode(key, payload)?),
-            KEY_MOVIE_TRANSITION => Packet::MovieTransition(MovieTransition::decode(key, payload)?),
-            KEY_COMMENT => Packet::Comment(Comment::decode(key, payload)?),
-            KEY_EXPERIMENTAL => Packet::Experimental(Experimental::decode(key, payload)?),
-            KEY_UNSPECIFIED => Packet::Unspecified(Unspecified::decode(key, payload)?),
-            
-            _ => Packet::Unsupported(Unsupported::decode(key, payload)?)
-        })
+        Self::decode_keyed(key, payload, keylen)
     }
     
-    pub fn kind(&self) -> PacketKind {
-        match self {
-            Self::ConsoleType(packet) => packet.kind(),
-            Self::ConsoleRegion(packet) => packet.kind(),
-            Self::GameTitle(packet) => packet.kind(),
-            Self::RomName(packet) => packet.kind(),
-            Self::Attribution(packet) => packet.kind(),
-            Self::Category(packet) => packet.kind(),
-            Self::EmulatorName(packet) => packet.kind(),
-            Self::EmulatorVersion(packet) => packet.kind(),
-            Self::EmulatorCore(packet) => packet.kind(),
-            Self::TasLastModified(packet) => packet.kind(),
-            Self::DumpCreated(packet) => packet.kind(),
-            Self::DumpLastModified(packet) => packet.kind(),
-            Self::TotalFrames(packet) => packet.kind(),
-            Self::Rerecords(packet) => packet.kind(),
-            Self::SourceLink(packet) => packet.kind(),
-            Self::BlankFrames(packet) => packet.kind(),
-            Self::Verified(packet) => packet.kind(),
-            Self::MemoryInit(packet) => packet.kind(),
-            Self::GameIdentifier(packet) => packet.kind(),
-            Self::MovieLicense(packet) => packet.kind(),
-            Self::MovieFile(packet) => packet.kind(),
-            Self::PortController(packet) => packet.kind(),
-            Self::PortOverread(packet) => packet.kind(),
-            Self::NesLatchFilter(packet) => packet.kind(),
-            Self::NesClockFilter(packet) => packet.kind(),
-            Self::NesGameGenieCode(packet) => packet.kind(),
-            Self::SnesLatchFilter(packet) => packet.kind(),
-            Self::SnesClockFilter(packet) => packet.kind(),
-            Self::SnesGameGenieCode(packet) => packet.kind(),
-            Self::SnesLatchTrain(packet) => packet.kind(),
-            Self::GenesisGameGenieCode(packet) => packet.kind(),
-            Self::InputChunk(packet) => packet.kind(),
-            Self::InputMoment(packet) => packet.kind(),
-            Self::Transition(packet) => packet.kind(),
-            Self::LagFrameChunk(packet) => packet.kind(),
-            Self::MovieTransition(packet) => packet.kind(),
-            Self::Comment(packet) => packet.kind(),
-            Self::Experimental(packet) => packet.kind(),
-            Self::Unspecified(packet) => packet.kind(),
-            Self::Unsupported(packet) => packet.kind(),
-        }
+    /// Decodes one packet from the start of `data`, which doesn't need to be part of a file (e.g. a packet received
+    /// over the network on its own). [`Packet::encode`] does the reverse.
+    /// 
+    /// Returns the packet along with any bytes left over after it, which are empty if `data` held exactly one packet.
+    pub fn decode_single(data: &[u8], keylen: u8) -> Result<(Packet, &[u8]), PacketError> {
+        let mut r = Reader::new(data);
+        let packet = Self::with_reader(&mut r, keylen)?;
+        
+        Ok((packet, &data[r.pos()..]))
     }
 }
-impl Encode for Packet {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        match self {
-            Self::ConsoleType(packet) => packet.encode(keylen),
-            Self::ConsoleRegion(packet) => packet.encode(keylen),
-            Self::GameTitle(packet) => packet.encode(keylen),
-            Self::RomName(packet) => packet.encode(keylen),
-            Self::Attribution(packet) => packet.encode(keylen),
-            Self::Category(packet) => packet.encode(keylen),
-            Self::EmulatorName(packet) => packet.encode(keylen),
-            Self::EmulatorVersion(packet) => packet.encode(keylen),
-            Self::EmulatorCore(packet) => packet.encode(keylen),
-            Self::TasLastModified(packet) => packet.encode(keylen),
-            Self::DumpCreated(packet) => packet.encode(keylen),
-            Self::DumpLastModified(packet) => packet.encode(keylen),
-            Self::TotalFrames(packet) => packet.encode(keylen),
-            Self::Rerecords(packet) => packet.encode(keylen),
-            Self::SourceLink(packet) => packet.encode(keylen),
-            Self::BlankFrames(packet) => packet.encode(keylen),
-            Self::Verified(packet) => packet.encode(keylen),
-            Self::MemoryInit(packet) => packet.encode(keylen),
-            Self::GameIdentifier(packet) => packet.encode(keylen),
-            Self::MovieLicense(packet) => packet.encode(keylen),
-            Self::MovieFile(packet) => packet.encode(keylen),
-            Self::PortController(packet) => packet.encode(keylen),
-            Self::PortOverread(packet) => packet.encode(keylen),
-            Self::NesLatchFilter(packet) => packet.encode(keylen),
-            Self::NesClockFilter(packet) => packet.encode(keylen),
-            Self::NesGameGenieCode(packet) => packet.encode(keylen),
-            Self::SnesLatchFilter(packet) => packet.encode(keylen),
-            Self::SnesClockFilter(packet) => packet.encode(keylen),
-            Self::SnesGameGenieCode(packet) => packet.encode(keylen),
-            Self::SnesLatchTrain(packet) => packet.encode(keylen),
-            Self::GenesisGameGenieCode(packet) => packet.encode(keylen),
-            Self::InputChunk(packet) => packet.encode(keylen),
-            Self::InputMoment(packet) => packet.encode(keylen),
-            Self::Transition(packet) => packet.encode(keylen),
-            Self::LagFrameChunk(packet) => packet.encode(keylen),
-            Self::MovieTransition(packet) => packet.encode(keylen),
-            Self::Comment(packet) => packet.encode(keylen),
-            Self::Experimental(packet) => packet.encode(keylen),
-            Self::Unspecified(packet) => packet.encode(keylen),
-            Self::Unsupported(packet) => packet.encode(keylen),
-        }
+
+/// Reads the next packet's key (with any [padding][unpadded_key] removed) and payload, without decoding the payload.
+pub(crate) fn read_raw<'a>(r: &mut Reader<'a>, keylen: u8) -> Result<(&'a [u8], Reader<'a>), PacketError> {
+    if r.remaining() < keylen as usize {
+        return Err(PacketError::MissingKey);
+    }
+    let key = r.read_len(keylen as usize);
+    
+    if r.remaining() < 1 {
+        return Err(PacketError::MissingPayloadLength);
+    }
+    let exp = r.read_u8() as usize;
+    
+    if r.remaining() < exp {
+        return Err(PacketError::MissingPayloadLength);
+    }
+    if exp > 8 {
+        return Err(PacketError::UnsupportedExponent(exp as u8));
     }
+    
+    let mut plen = [0u8; 8];
+    for i in (0..exp).rev() {
+        plen[plen.len() - i - 1] = r.read_u8();
+    }
+    let plen = usize::try_from(u64::from_be_bytes(plen)).unwrap_or(usize::MAX);
+    let payload = r.sub_reader(plen)?;
+    
+    Ok((unpadded_key(key), payload))
+}
 
-    fn key(&self) -> Vec<u8> {
-        match self {
-            Self::ConsoleType(packet) => packet.key(),
-            Self::ConsoleRegion(packet) => packet.key(),
-            Self::GameTitle(packet) => packet.key(),
-            Self::RomName(packet) => packet.key(),
-            Self::Attribution(packet) => packet.key(),
-            Self::Category(packet) => packet.key(),
-            Self::EmulatorName(packet) => packet.key(),
-            Self::EmulatorVersion(packet) => packet.key(),
-            Self::EmulatorCore(packet) => packet.key(),
-            Self::TasLastModified(packet) => packet.key(),
-            Self::DumpCreated(packet) => packet.key(),
-            Self::DumpLastModified(packet) => packet.key(),
-            Self::TotalFrames(packet) => packet.key(),
-            Self::Rerecords(packet) => packet.key(),
-            Self::SourceLink(packet) => packet.key(),
-            Self::BlankFrames(packet) => packet.key(),
-            Self::Verified(packet) => packet.key(),
-            Self::MemoryInit(packet) => packet.key(),
-            Self::GameIdentifier(packet) => packet.key(),
-            Self::MovieLicense(packet) => packet.key(),
-            Self::MovieFile(packet) => packet.key(),
-            Self::PortController(packet) => packet.key(),
-            Self::PortOverread(packet) => packet.key(),
-            Self::NesLatchFilter(packet) => packet.key(),
-            Self::NesClockFilter(packet) => packet.key(),
-            Self::NesGameGenieCode(packet) => packet.key(),
-            Self::SnesLatchFilter(packet) => packet.key(),
-            Self::SnesClockFilter(packet) => packet.key(),
-            Self::SnesGameGenieCode(packet) => packet.key(),
-            Self::SnesLatchTrain(packet) => packet.key(),
-            Self::GenesisGameGenieCode(packet) => packet.key(),
-            Self::InputChunk(packet) => packet.key(),
-            Self::InputMoment(packet) => packet.key(),
-            Self::Transition(packet) => packet.key(),
-            Self::LagFrameChunk(packet) => packet.key(),
-            Self::MovieTransition(packet) => packet.key(),
-            Self::Comment(packet) => packet.key(),
-            Self::Experimental(packet) => packet.key(),
-            Self::Unspecified(packet) => packet.key(),
-            Self::Unsupported(packet) => packet.key(),
-        }
+/// Strips the leading zeros which pad keys longer than two bytes.
+pub(crate) fn unpadded_key(key: &[u8]) -> &[u8] {
+    let padding = key.iter().take(key.len().saturating_sub(2)).take_while(|byte| **byte == 0).count();
+    
+    &key[padding..]
+}
+
+define_packets! {
+    ConsoleType => KEY_CONSOLE_TYPE,
+    ConsoleRegion => KEY_CONSOLE_REGION,
+    GameTitle => KEY_GAME_TITLE,
+    RomName => KEY_ROM_NAME,
+    Attribution => KEY_ATTRIBUTION,
+    Category => KEY_CATEGORY,
+    EmulatorName => KEY_EMULATOR_NAME,
+    EmulatorVersion => KEY_EMULATOR_VERSION,
+    EmulatorCore => KEY_EMULATOR_CORE,
+    TasLastModified => KEY_TAS_LAST_MODIFIED,
+    DumpCreated => KEY_DUMP_CREATED,
+    DumpLastModified => KEY_DUMP_LAST_MODIFIED,
+    TotalFrames => KEY_TOTAL_FRAMES,
+    Rerecords => KEY_RERECORDS,
+    SourceLink => KEY_SOURCE_LINK,
+    BlankFrames => KEY_BLANK_FRAMES,
+    Verified => KEY_VERIFIED,
+    MemoryInit => KEY_MEMORY_INIT,
+    GameIdentifier => KEY_GAME_IDENTIFIER,
+    MovieLicense => KEY_MOVIE_LICENSE,
+    MovieFile => KEY_MOVIE_FILE,
+    PortController => KEY_PORT_CONTROLLER,
+    PortOverread => KEY_PORT_OVERREAD,
+    NesLatchFilter => KEY_NES_LATCH_FILTER,
+    NesClockFilter => KEY_NES_CLOCK_FILTER,
+    NesGameGenieCode => KEY_NES_GAME_GENIE_CODE,
+    SnesLatchFilter => KEY_SNES_LATCH_FILTER,
+    SnesClockFilter => KEY_SNES_CLOCK_FILTER,
+    SnesGameGenieCode => KEY_SNES_GAME_GENIE_CODE,
+    SnesLatchTrain => KEY_SNES_LATCH_TRAIN,
+    GenesisGameGenieCode => KEY_GENESIS_GAME_GENIE_CODE,
+    InputChunk => KEY_INPUT_CHUNK,
+    InputMoment => KEY_INPUT_MOMENT,
+    Transition => KEY_TRANSITION,
+    LagFrameChunk => KEY_LAG_FRAME_CHUNK,
+    MovieTransition => KEY_MOVIE_TRANSITION,
+    Comment => KEY_COMMENT,
+    Experimental => KEY_EXPERIMENTAL,
+    Unspecified => KEY_UNSPECIFIED,
+    PacketChecksum => KEY_PACKET_CHECKSUM,
+    SavestateAnchor => KEY_SAVESTATE_ANCHOR,
+    Bookmark => KEY_BOOKMARK,
+    LinkSession => KEY_LINK_SESSION,
+    RandomSeed => KEY_RANDOM_SEED,
+    InputDelta => KEY_INPUT_DELTA,
+    TableOfContents => KEY_TABLE_OF_CONTENTS,
+    Padding => KEY_PADDING,
+    Provenance => KEY_PROVENANCE,
+    EditHistory => KEY_EDIT_HISTORY,
+    ConsoleSetting => KEY_CONSOLE_SETTING,
+    LatchMap => KEY_LATCH_MAP,
+    AvSync => KEY_AV_SYNC,
+}
+
+/// Packet types from the draft of the next spec version ([DRAFT_VERSION][crate::spec::DRAFT_VERSION]), which may
+/// still change before it's finalized. They're only decoded with the `unstable-spec` feature, and are listed in
+/// `define_packets!` with `#[cfg(feature = "unstable-spec")]`.
+pub const DRAFT_KINDS: &[PacketKind] = &[];
+
+impl PacketKind {
+    /// Whether a movie may contain at most one packet of this kind.
+    /// 
+    /// Packets which are unique per port (such as PORT_CONTROLLER) are not included.
+    pub fn is_unique(&self) -> bool {
+        use PacketKind::*;
+        matches!(self,
+            ConsoleType | ConsoleRegion | GameTitle | RomName | Category | EmulatorName | EmulatorVersion | EmulatorCore
+            | TasLastModified | DumpCreated | DumpLastModified | TotalFrames | Rerecords | BlankFrames | Verified
+            | NesLatchFilter | NesClockFilter | SnesLatchFilter | SnesClockFilter | SnesLatchTrain
+            | Experimental | SavestateAnchor | LinkSession | TableOfContents | AvSync
+        )
+    }
+    
+    /// Whether packets of this kind configure the console or its ports, and so must appear before any input data
+    /// (INPUT_CHUNK or INPUT_MOMENT packets) of the movie.
+    pub fn is_configuration(&self) -> bool {
+        use PacketKind::*;
+        matches!(self,
+            ConsoleType | ConsoleRegion | MemoryInit | PortController | PortOverread | NesLatchFilter | NesClockFilter
+            | NesGameGenieCode | SnesLatchFilter | SnesClockFilter | SnesGameGenieCode | SnesLatchTrain
+            | GenesisGameGenieCode | SavestateAnchor | LinkSession | RandomSeed | ConsoleSetting
+        )
+    }
+    
+    /// Whether this is one of the extension packets specific to this crate, which aren't part of the TASD spec.
+    pub fn is_extension(&self) -> bool {
+        use PacketKind::*;
+        matches!(self, PacketChecksum | SavestateAnchor | Bookmark | LinkSession | RandomSeed | InputDelta | TableOfContents
+            | Padding | Provenance | EditHistory | ConsoleSetting | LatchMap | AvSync)
+    }
+    
+    /// Whether this packet type is from the draft of the next spec version. See [DRAFT_KINDS].
+    pub fn is_draft(&self) -> bool {
+        DRAFT_KINDS.contains(self)
+    }
+    
+    /// Whether packets of this kind contain input data.
+    pub fn is_input(&self) -> bool {
+        matches!(self, PacketKind::InputChunk | PacketKind::InputMoment | PacketKind::InputDelta)
     }
 }
-impl_from_packet!(
-    ConsoleType
-    ConsoleRegion
-    GameTitle
-    RomName
-    Attribution
-    Category
-    EmulatorName
-    EmulatorVersion
-    EmulatorCore
-    TasLastModified
-    DumpCreated
-    DumpLastModified
-    TotalFrames
-    Rerecords
-    SourceLink
-    BlankFrames
-    Verified
-    MemoryInit
-    GameIdentifier
-    MovieLicense
-    MovieFile
-    PortController
-    PortOverread
-    NesLatchFilter
-    NesClockFilter
-    NesGameGenieCode
-    SnesLatchFilter
-    SnesClockFilter
-    SnesGameGenieCode
-    SnesLatchTrain
-    GenesisGameGenieCode
-    InputChunk
-    InputMoment
-    Transition
-    LagFrameChunk
-    MovieTransition
-    Comment
-    Experimental
-    Unspecified
-    Unsupported
-);
-
-#[derive(Debug, Copy, Clone, PartialEq, strum_macros::Display, strum_macros::EnumString)]
-#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
-pub enum PacketKind {
-    ConsoleType,
-    ConsoleRegion,
-    GameTitle,
-    RomName,
-    Attribution,
-    Category,
-    EmulatorName,
-    EmulatorVersion,
-    EmulatorCore,
-    TasLastModified,
-    DumpCreated,
-    DumpLastModified,
-    TotalFrames,
-    Rerecords,
-    SourceLink,
-    BlankFrames,
-    Verified,
-    MemoryInit,
-    GameIdentifier,
-    MovieLicense,
-    MovieFile,
-    PortController,
-    PortOverread,
-    NesLatchFilter,
-    NesClockFilter,
-    NesGameGenieCode,
-    SnesLatchFilter,
-    SnesClockFilter,
-    SnesGameGenieCode,
-    SnesLatchTrain,
-    GenesisGameGenieCode,
-    InputChunk,
-    InputMoment,
-    Transition,
-    LagFrameChunk,
-    MovieTransition,
-    Comment,
-    Experimental,
+
+
+/// Why a packet's payload isn't interpreted by this crate, see [`Packet::opaque_kind`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpaqueKind {
+    /// The key isn't known, and isn't in the 0xFF group. Most likely a packet from a newer version of the spec.
+    UnknownKey,
+    /// UNSPECIFIED packet, whose contents are deliberately left up to the dumper.
     Unspecified,
-    Unsupported,
+    /// The key isn't known, and is in the 0xFF group which the spec leaves for non-standard packets (e.g. extensions of
+    /// other implementations).
+    VendorExperimental,
 }
 
+impl Packet {
+    /// Kind of opaque data this packet holds, or `None` if its payload was decoded into a specific type.
+    pub fn opaque_kind(&self) -> Option<OpaqueKind> {
+        match self {
+            Packet::Unsupported(unsupported) => Some(unsupported.opaque_kind()),
+            Packet::Unspecified(_) => Some(OpaqueKind::Unspecified),
+            _ => None,
+        }
+    }
+}
 
 
 ////////////////////////////////////// Unsupported //////////////////////////////////////
@@ -448,6 +488,15 @@ pub struct Unsupported {
     pub key: Vec<u8>,
     pub payload: Vec<u8>,
 }
+impl Unsupported {
+    /// Either [`OpaqueKind::VendorExperimental`] if the key is in the 0xFF group, or [`OpaqueKind::UnknownKey`].
+    pub fn opaque_kind(&self) -> OpaqueKind {
+        match unpadded_key(&self.key) {
+            [0xFF, _] => OpaqueKind::VendorExperimental,
+            _ => OpaqueKind::UnknownKey,
+        }
+    }
+}
 impl Decode for Unsupported {
     fn decode(key: &[u8], payload: Reader) -> Result<Self, PacketError> {
         Ok(Self {
@@ -455,10 +504,6 @@ impl Decode for Unsupported {
             payload: payload.to_vec(),
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Unsupported
-    }
 }
 impl Encode for Unsupported {
     fn encode(&self, keylen: u8) -> Vec<u8> {
@@ -472,6 +517,10 @@ impl Encode for Unsupported {
     fn key(&self) -> Vec<u8> {
         self.key.clone()
     }
+    
+    fn encoded_len(&self, keylen: u8) -> usize {
+        packet_header_len(&self.key, keylen, self.payload.len() as u64) + self.payload.len()
+    }
 }
 
 
@@ -500,563 +549,186 @@ impl Decode for ConsoleType {
             custom,
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::ConsoleType
-    }
 }
-impl Encode for ConsoleType {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for ConsoleType {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_u8(self.kind);
         w.write_option_string(&self.custom);
-        
-        w.into_packet(&self.key(), keylen)
     }
     
-    fn key(&self) -> Vec<u8> {
-        KEY_CONSOLE_TYPE.to_vec()
+    fn payload_len(&self, _keylen: u8) -> usize {
+        1 + self.custom.as_ref().map_or(0, String::len)
     }
 }
 
 
 ////////////////////////////////////// CONSOLE_REGION //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct ConsoleRegion {
     pub region: u8,
 }
-impl Decode for ConsoleRegion {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            region: payload.read_u8(),
-        })
+impl ConsoleRegion {
+    pub fn new(region: Region) -> Self {
+        Self { region: region as u8 }
     }
     
-    fn kind(&self) -> PacketKind {
-        PacketKind::ConsoleRegion
+    /// Region this packet declares, if it's one defined by the spec.
+    pub fn region(&self) -> Option<Region> {
+        match self.region {
+            0x01 => Some(Region::Ntsc),
+            0x02 => Some(Region::Pal),
+            _ => None,
+        }
     }
 }
-impl Encode for ConsoleRegion {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.region);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-    
-    fn key(&self) -> Vec<u8> {
-        KEY_CONSOLE_REGION.to_vec()
-    }
+
+/// CONSOLE_REGION values defined by the spec.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Region {
+    Ntsc = 0x01,
+    Pal = 0x02,
 }
 
 
 ////////////////////////////////////// GAME_TITLE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct GameTitle {
+    #[tasd(remaining)]
     pub title: String,
 }
-impl Decode for GameTitle {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            title: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::GameTitle
-    }
-}
-impl Encode for GameTitle {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.title);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_GAME_TITLE.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// ROM_NAME //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct RomName {
+    #[tasd(remaining)]
     pub name: String,
 }
-impl Decode for RomName {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            name: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::RomName
-    }
-}
-impl Encode for RomName {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.name);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_ROM_NAME.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// ATTRIBUTION //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct Attribution {
     pub kind: u8,
+    #[tasd(remaining)]
     pub name: String,
 }
-impl Decode for Attribution {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() < 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            kind: payload.read_u8(),
-            name: payload.read_string(payload.remaining()),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Attribution
-    }
-}
-impl Encode for Attribution {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.kind);
-        w.write_str(&self.name);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-    
-    fn key(&self) -> Vec<u8> {
-        KEY_ATTRIBUTION.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// CATEGORY //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct Category {
+    #[tasd(remaining)]
     pub category: String,
 }
-impl Decode for Category {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            category: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Category
-    }
-}
-impl Encode for Category {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.category);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_CATEGORY.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// EMULATOR_NAME //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct EmulatorName {
+    #[tasd(remaining)]
     pub name: String,
 }
-impl Decode for EmulatorName {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            name: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::EmulatorName
-    }
-}
-impl Encode for EmulatorName {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.name);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_EMULATOR_NAME.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// EMULATOR_VERSION //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct EmulatorVersion {
+    #[tasd(remaining)]
     pub version: String,
 }
-impl Decode for EmulatorVersion {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            version: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::EmulatorVersion
-    }
-}
-impl Encode for EmulatorVersion {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.version);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_EMULATOR_VERSION.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// EMULATOR_CORE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct EmulatorCore {
+    #[tasd(remaining)]
     pub core: String,
 }
-impl Decode for EmulatorCore {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            core: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::EmulatorCore
-    }
-}
-impl Encode for EmulatorCore {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.core);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_EMULATOR_CORE.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// TAS_LAST_MODIFIED //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+/// `epoch` is in seconds since the Unix epoch (UTC). See [crate::timestamp] for helpers.
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct TasLastModified {
     pub epoch: i64,
 }
-impl Decode for TasLastModified {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 8 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            epoch: payload.read_i64(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::TasLastModified
-    }
-}
-impl Encode for TasLastModified {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_i64(self.epoch);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_TAS_LAST_MODIFIED.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// DUMP_CREATED //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+/// `epoch` is in seconds since the Unix epoch (UTC). See [crate::timestamp] for helpers.
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct DumpCreated {
     pub epoch: i64,
 }
-impl Decode for DumpCreated {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 8 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            epoch: payload.read_i64(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::DumpCreated
-    }
-}
-impl Encode for DumpCreated {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_i64(self.epoch);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_DUMP_CREATED.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// DUMP_LAST_MODIFIED //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+/// `epoch` is in seconds since the Unix epoch (UTC). See [crate::timestamp] for helpers.
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct DumpLastModified {
     pub epoch: i64,
 }
-impl Decode for DumpLastModified {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 8 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            epoch: payload.read_i64(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::DumpLastModified
-    }
-}
-impl Encode for DumpLastModified {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_i64(self.epoch);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_DUMP_LAST_MODIFIED.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// TOTAL_FRAMES //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct TotalFrames {
     pub frames: u32,
 }
-impl Decode for TotalFrames {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 4 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            frames: payload.read_u32(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::TotalFrames
-    }
-}
-impl Encode for TotalFrames {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u32(self.frames);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_TOTAL_FRAMES.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// RERECORDS //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct Rerecords {
     pub rerecords: u32,
 }
-impl Decode for Rerecords {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 4 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            rerecords: payload.read_u32(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Rerecords
-    }
-}
-impl Encode for Rerecords {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u32(self.rerecords);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_RERECORDS.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// SOURCE_LINK //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct SourceLink {
+    #[tasd(remaining)]
     pub link: String,
 }
-impl Decode for SourceLink {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            link: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::SourceLink
-    }
-}
-impl Encode for SourceLink {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.link);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_SOURCE_LINK.to_vec()
+#[cfg(feature = "url")]
+impl SourceLink {
+    /// Parses the link as a URL.
+    pub fn source_url(&self) -> Result<url::Url, url::ParseError> {
+        url::Url::parse(&self.link)
     }
 }
 
 
 ////////////////////////////////////// BLANK_FRAMES //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct BlankFrames {
     pub frames: i16,
 }
-impl Decode for BlankFrames {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 2 {
-            return Err(PacketError::invalid(key, payload));
+impl BlankFrames {
+    /// Fails if `frames` is `0`.
+    pub fn new(frames: i16) -> Result<Self, ValueError> {
+        match frames {
+            0 => Err(ValueError::ZeroBlankFrames),
+            frames => Ok(Self { frames }),
         }
-        
-        Ok(Self {
-            frames: payload.read_i16(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::BlankFrames
-    }
-}
-impl Encode for BlankFrames {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_i16(self.frames);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_BLANK_FRAMES.to_vec()
     }
 }
 
 
 ////////////////////////////////////// VERIFIED //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct Verified {
     pub verified: bool,
 }
-impl Decode for Verified {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            verified: payload.read_bool(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Verified
-    }
-}
-impl Encode for Verified {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_bool(self.verified);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_VERIFIED.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// MEMORY_INIT //////////////////////////////////////
+/// Initial contents of a memory `device` (see [memory_init_device_lut][crate::lookup::memory_init_device_lut]), either
+/// a fill pattern given by `data_type`, or custom `data`.
+/// 
+/// Use [`TasdFile::memory_init_data`][crate::spec::TasdFile::memory_init_data] to materialize the contents, and
+/// [`TasdFile::attach_peripheral`][crate::spec::TasdFile::attach_peripheral] for save memory and Controller Paks.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MemoryInit {
     pub data_type: u8,
@@ -1088,25 +760,20 @@ impl Decode for MemoryInit {
             data: if data_type == 0xFF { Some(payload.read_remaining().to_vec()) } else { None },
         })
     }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::MemoryInit
-    }
 }
-impl Encode for MemoryInit {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for MemoryInit {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_u8(self.data_type);
         w.write_u16(self.device);
         w.write_bool(self.required);
         w.write_u8_str(&self.name);
-        
-        w.into_packet(&self.key(), keylen)
+        if let Some(data) = self.data.as_ref() {
+            w.write_slice(data);
+        }
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_MEMORY_INIT.to_vec()
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        5 + truncate_str(&self.name, u8::MAX as usize).len() + self.data.as_ref().map_or(0, Vec::len)
     }
 }
 
@@ -1143,379 +810,113 @@ impl Decode for GameIdentifier {
             identifier,
         })
     }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::GameIdentifier
-    }
 }
-impl Encode for GameIdentifier {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for GameIdentifier {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_u8(self.kind);
         w.write_u8(self.encoding);
         w.write_u8_str(&self.name);
         w.write_slice(&self.identifier);
-        
-        w.into_packet(&self.key(), keylen)
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_GAME_IDENTIFIER.to_vec()
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        3 + truncate_str(&self.name, u8::MAX as usize).len() + self.identifier.len()
     }
 }
 
 
 ////////////////////////////////////// MOVIE_LICENSE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct MovieLicense {
+    #[tasd(remaining)]
     pub license: String,
 }
-impl Decode for MovieLicense {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            license: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::MovieLicense
-    }
-}
-impl Encode for MovieLicense {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.license);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_MOVIE_LICENSE.to_vec()
+#[cfg(feature = "spdx")]
+impl MovieLicense {
+    /// Parses the license as an SPDX license expression (e.g. `CC-BY-4.0` or `MIT OR Apache-2.0`).
+    pub fn license_spdx(&self) -> Result<spdx::Expression, spdx::ParseError> {
+        spdx::Expression::parse(&self.license)
     }
 }
 
 
 ////////////////////////////////////// MOVIE_FILE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct MovieFile {
+    #[tasd(len_prefixed_str)]
     pub name: String,
+    #[tasd(remaining)]
     pub data: Vec<u8>,
 }
-impl Decode for MovieFile {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() < 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        let nlen = payload.read_u8();
-        if payload.remaining() < nlen as usize {
-            return Err(PacketError::invalid(key, payload));
-        }
-        let name = payload.read_string(nlen as usize);
-        
-        Ok(Self {
-            name,
-            data: payload.read_remaining().to_vec(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::MovieFile
-    }
-}
-impl Encode for MovieFile {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8_str(&self.name);
-        w.write_slice(&self.data);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_MOVIE_FILE.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// PORT_CONTROLLER //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct PortController {
     pub port: u8,
     pub kind: u16,
 }
-impl Decode for PortController {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 3 {
-            return Err(PacketError::invalid(key, payload));
+impl PortController {
+    /// Fails if `port` is `0`.
+    pub fn new(port: u8, kind: u16) -> Result<Self, ValueError> {
+        match port {
+            0 => Err(ValueError::ZeroPort),
+            port => Ok(Self { port, kind }),
         }
-        
-        Ok(Self {
-            port: payload.read_u8(),
-            kind: payload.read_u16(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::PortController
-    }
-}
-impl Encode for PortController {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.port);
-        w.write_u16(self.kind);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_PORT_CONTROLLER.to_vec()
     }
 }
 
 
 ////////////////////////////////////// PORT_OVERREAD //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct PortOverread {
     pub port: u8,
     pub overread: bool,
 }
-impl Decode for PortOverread {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 2 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            port: payload.read_u8(),
-            overread: payload.read_bool(),
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::PortOverread
-    }
-}
-impl Encode for PortOverread {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.port);
-        w.write_bool(self.overread);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_PORT_OVERREAD.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// NES_LATCH_FILTER //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct NesLatchFilter {
     pub time: u16,
 }
-impl Decode for NesLatchFilter {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 2 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            time: payload.read_u16(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::NesLatchFilter
-    }
-}
-impl Encode for NesLatchFilter {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u16(self.time);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_NES_LATCH_FILTER.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// NES_CLOCK_FILTER //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct NesClockFilter {
     pub time: u8,
 }
-impl Decode for NesClockFilter {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            time: payload.read_u8(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::NesClockFilter
-    }
-}
-impl Encode for NesClockFilter {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.time);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_NES_CLOCK_FILTER.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// NES_GAME_GENIE_CODE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct NesGameGenieCode {
+    #[tasd(remaining)]
     pub code: String,
 }
-impl Decode for NesGameGenieCode {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            code: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::NesGameGenieCode
-    }
-}
-impl Encode for NesGameGenieCode {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.code);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_NES_GAME_GENIE_CODE.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// SNES_LATCH_FILTER //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct SnesLatchFilter {
     pub time: u16,
 }
-impl Decode for SnesLatchFilter {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 2 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            time: payload.read_u16(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::SnesLatchFilter
-    }
-}
-impl Encode for SnesLatchFilter {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u16(self.time);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_SNES_LATCH_FILTER.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// SNES_CLOCK_FILTER //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct SnesClockFilter {
     pub time: u8,
 }
-impl Decode for SnesClockFilter {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 1 {
-            return Err(PacketError::invalid(key, payload));
-        }
-        
-        Ok(Self {
-            time: payload.read_u8(),
-        })
-    }
-
-    fn kind(&self) -> PacketKind {
-        PacketKind::SnesClockFilter
-    }
-}
-impl Encode for SnesClockFilter {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.time);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_SNES_CLOCK_FILTER.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// SNES_GAME_GENIE_CODE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct SnesGameGenieCode {
+    #[tasd(remaining)]
     pub code: String,
 }
-impl Decode for SnesGameGenieCode {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            code: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::SnesGameGenieCode
-    }
-}
-impl Encode for SnesGameGenieCode {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.code);
-        
-        w.into_packet(&self.key(), keylen)
-    }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_SNES_GAME_GENIE_CODE.to_vec()
-    }
-}
 
 
 ////////////////////////////////////// SNES_LATCH_TRAIN //////////////////////////////////////
@@ -1524,7 +925,11 @@ pub struct SnesLatchTrain {
     pub points: Vec<u64>,
 }
 impl Decode for SnesLatchTrain {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+        if !payload.remaining().is_multiple_of(8) {
+            return Err(PacketError::invalid(key, payload));
+        }
+        
         Ok(Self {
             points: payload.read_remaining()
                 .chunks_exact(8)
@@ -1532,138 +937,261 @@ impl Decode for SnesLatchTrain {
                 .collect()
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::SnesLatchTrain
-    }
 }
-impl Encode for SnesLatchTrain {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for SnesLatchTrain {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_slice(&self.points.iter()
-            .map(|point| point.to_be_bytes())
-            .flatten()
+            .flat_map(|point| point.to_be_bytes())
             .collect::<Vec<u8>>());
-        
-        w.into_packet(&self.key(), keylen)
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_SNES_LATCH_TRAIN.to_vec()
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        self.points.len() * 8
     }
 }
 
 
 ////////////////////////////////////// GENESIS_GAME_GENIE_CODE //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct GenesisGameGenieCode {
+    #[tasd(remaining)]
     pub code: String,
 }
-impl Decode for GenesisGameGenieCode {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            code: payload.read_string(payload.remaining())
-        })
-    }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::GenesisGameGenieCode
-    }
-}
-impl Encode for GenesisGameGenieCode {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.code);
-        
-        w.into_packet(&self.key(), keylen)
-    }
 
-    fn key(&self) -> Vec<u8> {
-        KEY_GENESIS_GAME_GENIE_CODE.to_vec()
-    }
+
+////////////////////////////////////// INPUT_CHUNK //////////////////////////////////////
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct InputChunk {
+    pub port: u8,
+    #[tasd(remaining)]
+    pub inputs: Vec<u8>,
 }
 
 
-////////////////////////////////////// INPUT_CHUNK //////////////////////////////////////
+////////////////////////////////////// INPUT_MOMENT //////////////////////////////////////
 #[derive(Debug, Clone, PartialEq)]
-pub struct InputChunk {
+pub struct InputMoment {
     pub port: u8,
+    pub index: Index,
     pub inputs: Vec<u8>,
 }
-impl Decode for InputChunk {
+impl Decode for InputMoment {
     fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() < 1 {
+        if payload.remaining() < 10 {
             return Err(PacketError::invalid(key, payload));
         }
         
         Ok(Self {
             port: payload.read_u8(),
+            index: Index::read(&mut payload),
             inputs: payload.read_remaining().to_vec(),
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::InputChunk
-    }
 }
-impl Encode for InputChunk {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for InputMoment {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_u8(self.port);
+        self.index.write(w);
         w.write_slice(&self.inputs);
-        
-        w.into_packet(&self.key(), keylen)
     }
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        1 + Index::LEN + self.inputs.len()
+    }
+}
+
+
+/// Point in a movie an INPUT_MOMENT or TRANSITION happens at, in the unit given by its index type.
+/// 
+/// Indexes are only ordered against indexes of the same unit. Comparing different units gives `None`, as converting
+/// between them depends on the console and region, which [`Index::to_seconds`] and [`Index::to_frame`] take.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Index {
+    Frame(u64),
+    CycleCount(u64),
+    Milliseconds(u64),
+    TenMicroseconds(u64),
+    /// Input of the port's INPUT_CHUNKs. Only defined for TRANSITION.
+    ChunkIndex(u64),
+    /// Index type not defined by the spec.
+    Other { kind: u8, value: u64 },
+}
+impl Index {
+    /// Encoded size of an index: its kind and value.
+    const LEN: usize = 9;
+    
+    /// Index of the given index type.
+    pub fn new(kind: u8, value: u64) -> Self {
+        match kind {
+            0x01 => Self::Frame(value),
+            0x02 => Self::CycleCount(value),
+            0x03 => Self::Milliseconds(value),
+            0x04 => Self::TenMicroseconds(value),
+            0x05 => Self::ChunkIndex(value),
+            _ => Self::Other { kind, value },
+        }
+    }
+    
+    /// Index type this is encoded with.
+    pub fn kind(&self) -> u8 {
+        match self {
+            Self::Frame(_) => 0x01,
+            Self::CycleCount(_) => 0x02,
+            Self::Milliseconds(_) => 0x03,
+            Self::TenMicroseconds(_) => 0x04,
+            Self::ChunkIndex(_) => 0x05,
+            Self::Other { kind, .. } => *kind,
+        }
+    }
+    
+    /// Raw index, in whatever unit the index type uses.
+    pub fn value(&self) -> u64 {
+        match self {
+            Self::Frame(value) | Self::CycleCount(value) | Self::Milliseconds(value) | Self::TenMicroseconds(value) | Self::ChunkIndex(value) => *value,
+            Self::Other { value, .. } => *value,
+        }
+    }
+    
+    /// Frame of a frame index, or `None` for other index types.
+    pub fn frame(&self) -> Option<u64> {
+        match self {
+            Self::Frame(frame) => Some(*frame),
+            _ => None,
+        }
+    }
+    
+    fn read(r: &mut Reader) -> Self {
+        let kind = r.read_u8();
+        Self::new(kind, r.read_u64())
+    }
+    
+    fn write(&self, w: &mut Writer) {
+        w.write_u8(self.kind());
+        w.write_u64(self.value());
+    }
+}
+impl PartialOrd for Index {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (self.kind() == other.kind()).then(|| self.value().cmp(&other.value()))
+    }
+}
 
-    fn key(&self) -> Vec<u8> {
-        KEY_INPUT_CHUNK.to_vec()
+/// Transition type not defined by the spec, for [`TransitionKind::Other`].
+/// 
+/// Types which the spec does define (including `0xFF`, which is [`TransitionKind::PacketDerived`]) can't be
+/// represented, so every transition has exactly one encoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OtherTransition(u8);
+impl OtherTransition {
+    /// Transition type `code`, or `None` if it's defined by the spec.
+    pub fn new(code: u8) -> Option<Self> {
+        match TransitionKind::from_code(code) {
+            Some(TransitionKind::Other(other)) => Some(other),
+            _ => None,
+        }
+    }
+    
+    pub fn code(&self) -> u8 {
+        self.0
     }
 }
 
+/// Maximum number of packet-derived transitions which are decoded inside each other, so that a crafted file of nested
+/// transitions can't overflow the stack.
+pub const MAX_TRANSITION_DEPTH: usize = 16;
 
-////////////////////////////////////// INPUT_MOMENT //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
-pub struct InputMoment {
-    pub port: u8,
-    pub index_type: u8,
-    pub index: u64,
-    pub inputs: Vec<u8>,
+thread_local! {
+    /// Number of packet-derived transitions currently being decoded on this thread.
+    static TRANSITION_DEPTH: Cell<usize> = const { Cell::new(0) };
 }
-impl Decode for InputMoment {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() < 10 {
-            return Err(PacketError::invalid(key, payload));
+
+/// What happens at a TRANSITION or MOVIE_TRANSITION.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionKind {
+    SoftReset,
+    PowerReset,
+    /// Ends the current movie and starts the next one of an [archive][crate::playlist].
+    RestartFile,
+    /// The contained packet takes effect, e.g. a PORT_CONTROLLER for swapping controllers.
+    PacketDerived(Box<Packet>),
+    /// Transition type not defined by the spec.
+    Other(OtherTransition),
+}
+impl TransitionKind {
+    /// Transition type this is encoded with.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::SoftReset => 0x01,
+            Self::PowerReset => 0x02,
+            Self::RestartFile => 0x03,
+            Self::PacketDerived(_) => 0xFF,
+            Self::Other(other) => other.code(),
         }
-        
-        Ok(Self {
-            port: payload.read_u8(),
-            index_type: payload.read_u8(),
-            index: payload.read_u64(),
-            inputs: payload.read_remaining().to_vec(),
+    }
+    
+    /// Whether this is a soft or power reset.
+    pub fn is_reset(&self) -> bool {
+        matches!(self, Self::SoftReset | Self::PowerReset)
+    }
+    
+    /// Packet of a packet-derived transition.
+    pub fn packet(&self) -> Option<&Packet> {
+        match self {
+            Self::PacketDerived(packet) => Some(packet),
+            _ => None,
+        }
+    }
+    
+    /// Transition of the given type, or `None` for `0xFF`, as packet-derived transitions need a packet.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0x01 => Self::SoftReset,
+            0x02 => Self::PowerReset,
+            0x03 => Self::RestartFile,
+            0xFF => return None,
+            code => Self::Other(OtherTransition(code)),
         })
     }
     
-    fn kind(&self) -> PacketKind {
-        PacketKind::InputMoment
+    /// Reads the transition type, and the packet following it for packet-derived transitions.
+    /// Reads the transition type and any packet after it from the payload of the packet with the given `key`.
+    fn read(key: &[u8], r: &mut Reader, keylen: u8) -> Result<Self, PacketError> {
+        let code = r.read_u8();
+        match Self::from_code(code) {
+            Some(kind) => Ok(kind),
+            None => {
+                let depth = TRANSITION_DEPTH.get();
+                if depth >= MAX_TRANSITION_DEPTH {
+                    return Err(PacketError::InvalidPayload { key: key.to_vec(), payload: r.to_vec() });
+                }
+                
+                let packet_data = r.read_remaining();
+                TRANSITION_DEPTH.set(depth + 1);
+                let packet = Packet::with_reader(&mut Reader::new(&packet_data), keylen);
+                TRANSITION_DEPTH.set(depth);
+                
+                Ok(Self::PacketDerived(Box::new(packet?)))
+            },
+        }
     }
-}
-impl Encode for InputMoment {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.port);
-        w.write_u8(self.index_type);
-        w.write_u64(self.index);
-        w.write_slice(&self.inputs);
-        
-        w.into_packet(&self.key(), keylen)
+    
+    fn write(&self, w: &mut Writer, keylen: u8) {
+        w.write_u8(self.code());
+        if let Self::PacketDerived(packet) = self {
+            w.write_slice(&packet.encode(keylen));
+        }
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_INPUT_MOMENT.to_vec()
+    
+    fn encoded_len(&self, keylen: u8) -> usize {
+        match self {
+            Self::PacketDerived(packet) => 1 + packet.encoded_len(keylen),
+            _ => 1,
+        }
+    }
+}
+impl From<Packet> for TransitionKind {
+    fn from(value: Packet) -> Self {
+        Self::PacketDerived(Box::new(value))
     }
 }
 
@@ -1671,54 +1199,42 @@ impl Encode for InputMoment {
 ////////////////////////////////////// TRANSITION //////////////////////////////////////
 #[derive(Debug, Clone, PartialEq)]
 pub struct Transition {
-    pub index_type: u8,
     pub port: u8,
-    pub index: u64,
-    pub transition_type: u8,
-    pub packet: Option<Box<Packet>>,
+    pub index: Index,
+    pub kind: TransitionKind,
 }
 impl Decode for Transition {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() < 10 {
+    /// Decodes the payload, assuming the embedded packet of a packet-derived transition uses keys as long as `key`. Use
+    /// [`Decode::decode_with_keylen`] for files with a keylen other than 2.
+    fn decode(key: &[u8], payload: Reader) -> Result<Self, PacketError> {
+        Self::decode_with_keylen(key, payload, key.len() as u8)
+    }
+    
+    fn decode_with_keylen(key: &[u8], mut payload: Reader, keylen: u8) -> Result<Self, PacketError> {
+        if payload.remaining() < 11 {
             return Err(PacketError::invalid(key, payload));
         }
         let index_type = payload.read_u8();
         let port = payload.read_u8();
-        let index = payload.read_u64();
-        let transition_type = payload.read_u8();
-        let packet_data = payload.read_remaining();
-        let mut packet_reader = Reader::new(&packet_data);
+        let index = Index::new(index_type, payload.read_u64());
         
         Ok(Self {
-            index_type,
             port,
             index,
-            transition_type,
-            packet: if transition_type == 0xFF { Some(Box::new(Packet::with_reader(&mut packet_reader, key.len() as u8)?)) } else { None }
+            kind: TransitionKind::read(key, &mut payload, keylen)?,
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::Transition
-    }
 }
-impl Encode for Transition {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_u8(self.index_type);
+impl EncodePayload for Transition {
+    fn encode_payload(&self, w: &mut Writer, keylen: u8) {
+        w.write_u8(self.index.kind());
         w.write_u8(self.port);
-        w.write_u64(self.index);
-        w.write_u8(self.transition_type);
-        if let Some(packet) = self.packet.as_ref() {
-            w.write_slice(&packet.encode(keylen));
-        }
-        
-        w.into_packet(&self.key(), keylen)
+        w.write_u64(self.index.value());
+        self.kind.write(w, keylen);
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_TRANSITION.to_vec()
+    
+    fn payload_len(&self, keylen: u8) -> usize {
+        1 + Index::LEN + self.kind.encoded_len(keylen)
     }
 }
 
@@ -1740,23 +1256,15 @@ impl Decode for LagFrameChunk {
             count: payload.read_u32(),
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::LagFrameChunk
-    }
 }
-impl Encode for LagFrameChunk {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for LagFrameChunk {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
         w.write_u32(self.movie_frame);
         w.write_u32(self.count);
-        
-        w.into_packet(&self.key(), keylen)
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_LAG_FRAME_CHUNK.to_vec()
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        8
     }
 }
 
@@ -1765,141 +1273,354 @@ impl Encode for LagFrameChunk {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MovieTransition {
     pub movie_frame: u32,
-    pub transition_type: u8,
-    pub packet: Option<Box<Packet>>,
+    pub kind: TransitionKind,
 }
 impl Decode for MovieTransition {
-    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+    /// Decodes the payload, assuming the embedded packet of a packet-derived transition uses keys as long as `key`. Use
+    /// [`Decode::decode_with_keylen`] for files with a keylen other than 2.
+    fn decode(key: &[u8], payload: Reader) -> Result<Self, PacketError> {
+        Self::decode_with_keylen(key, payload, key.len() as u8)
+    }
+    
+    fn decode_with_keylen(key: &[u8], mut payload: Reader, keylen: u8) -> Result<Self, PacketError> {
         if payload.remaining() < 5 {
             return Err(PacketError::invalid(key, payload));
         }
-        let movie_frame = payload.read_u32();
-        let transition_type = payload.read_u8();
-        let packet_data = payload.read_remaining();
-        let mut packet_reader = Reader::new(&packet_data);
-        
         Ok(Self {
-            movie_frame,
-            transition_type,
-            packet: if transition_type == 0xFF { Some(Box::new(Packet::with_reader(&mut packet_reader, key.len() as u8)?)) } else { None }
+            movie_frame: payload.read_u32(),
+            kind: TransitionKind::read(key, &mut payload, keylen)?,
         })
     }
-    
-    fn kind(&self) -> PacketKind {
-        PacketKind::MovieTransition
-    }
 }
-impl Encode for MovieTransition {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
+impl EncodePayload for MovieTransition {
+    fn encode_payload(&self, w: &mut Writer, keylen: u8) {
         w.write_u32(self.movie_frame);
-        w.write_u8(self.transition_type);
-        if let Some(packet) = self.packet.as_ref() {
-            w.write_slice(&packet.encode(keylen));
-        }
-        
-        w.into_packet(&self.key(), keylen)
+        self.kind.write(w, keylen);
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_MOVIE_TRANSITION.to_vec()
+    
+    fn payload_len(&self, keylen: u8) -> usize {
+        4 + self.kind.encoded_len(keylen)
     }
 }
 
 
 ////////////////////////////////////// COMMENT //////////////////////////////////////
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
 pub struct Comment {
+    #[tasd(remaining)]
     pub comment: String,
 }
-impl Decode for Comment {
-    fn decode(_key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+
+
+////////////////////////////////////// EXPERIMENTAL //////////////////////////////////////
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct Experimental {
+    pub experimental: bool,
+}
+
+
+////////////////////////////////////// UNSPECIFIED //////////////////////////////////////
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct Unspecified {
+    #[tasd(remaining)]
+    pub payload: Vec<u8>,
+}
+
+
+
+////////////////////////////////////// PACKET_CHECKSUM //////////////////////////////////////
+/// Extension packet containing the CRC-32 of the packet immediately before it.
+/// 
+/// The checksum covers the entire encoded packet (key, exponent, payload length, and payload).
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct PacketChecksum {
+    pub crc32: u32,
+}
+
+
+////////////////////////////////////// SAVESTATE_ANCHOR //////////////////////////////////////
+/// Extension packet marking the movie as starting from a savestate (or other saved state) rather than power-on.
+/// 
+/// `kind` describes the anchor (see [savestate_anchor_lut][crate::lookup::savestate_anchor_lut]), and `identifier`
+/// references the starting state, using the same hash kinds as GAME_IDENTIFIER. The state itself is expected to be
+/// embedded in MEMORY_INIT packets.
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct SavestateAnchor {
+    pub kind: u8,
+    pub identifier_kind: u8,
+    #[tasd(remaining)]
+    pub identifier: Vec<u8>,
+}
+
+
+////////////////////////////////////// BOOKMARK //////////////////////////////////////
+/// Extension packet marking a named position in the movie, for navigation in editors and players.
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct Bookmark {
+    pub frame: u64,
+    #[tasd(remaining)]
+    pub name: String,
+}
+
+
+////////////////////////////////////// LINK_SESSION //////////////////////////////////////
+/// Extension packet marking the dump as multiple linked consoles (e.g. two Game Boys connected by a link cable) played
+/// in lockstep.
+/// 
+/// `consoles` holds the CONSOLE_TYPE of each console. Ports are namespaced by console, see [crate::link] for details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkSession {
+    pub consoles: Vec<u8>,
+}
+impl Decode for LinkSession {
+    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+        if payload.remaining() < 1 {
+            return Err(PacketError::invalid(key, payload));
+        }
+        
         Ok(Self {
-            comment: payload.read_string(payload.remaining())
+            consoles: payload.read_remaining().to_vec(),
         })
     }
+}
+impl EncodePayload for LinkSession {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
+        w.write_slice(&self.consoles);
+    }
     
-    fn kind(&self) -> PacketKind {
-        PacketKind::Comment
+    fn payload_len(&self, _keylen: u8) -> usize {
+        self.consoles.len()
     }
 }
-impl Encode for Comment {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_str(&self.comment);
-        
-        w.into_packet(&self.key(), keylen)
-    }
 
-    fn key(&self) -> Vec<u8> {
-        KEY_COMMENT.to_vec()
-    }
+
+////////////////////////////////////// RANDOM_SEED //////////////////////////////////////
+/// Extension packet recording how the "Random" data of the MEMORY_INIT packet for `device` was generated, so it can be
+/// reproduced exactly.
+/// 
+/// `algorithm` is the pseudo-random number generator used (see [random_seed_algorithm_lut][crate::lookup::random_seed_algorithm_lut]).
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct RandomSeed {
+    pub device: u16,
+    pub algorithm: u8,
+    pub seed: u64,
 }
 
 
-////////////////////////////////////// EXPERIMENTAL //////////////////////////////////////
+////////////////////////////////////// INPUT_DELTA //////////////////////////////////////
+/// Extension packet holding the input data of `port` as the bytes where it differs from the input data of `base`, for
+/// ports whose input is mostly identical to another port's (e.g. unused multitap ports).
+/// 
+/// `len` is the number of bytes of input data, and `changes` lists the offset of each differing byte along with its
+/// XOR against the base port (which is treated as `0x00` past the end of its data). See [crate::input::delta].
 #[derive(Debug, Clone, PartialEq)]
-pub struct Experimental {
-    pub experimental: bool,
+pub struct InputDelta {
+    pub port: u8,
+    pub base: u8,
+    pub len: u32,
+    pub changes: Vec<(u32, u8)>,
 }
-impl Decode for Experimental {
+impl Decode for InputDelta {
     fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
-        if payload.remaining() != 1 {
+        if payload.remaining() < 6 || !(payload.remaining() - 6).is_multiple_of(5) {
             return Err(PacketError::invalid(key, payload));
         }
         
-        Ok(Self {
-            experimental: payload.read_bool(),
-        })
+        let port = payload.read_u8();
+        let base = payload.read_u8();
+        let len = payload.read_u32();
+        let mut changes = vec![];
+        while payload.remaining() > 0 {
+            changes.push((payload.read_u32(), payload.read_u8()));
+        }
+        
+        Ok(Self { port, base, len, changes })
+    }
+}
+impl EncodePayload for InputDelta {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
+        w.write_u8(self.port);
+        w.write_u8(self.base);
+        w.write_u32(self.len);
+        for (offset, xor) in &self.changes {
+            w.write_u32(*offset);
+            w.write_u8(*xor);
+        }
     }
     
-    fn kind(&self) -> PacketKind {
-        PacketKind::Experimental
+    fn payload_len(&self, _keylen: u8) -> usize {
+        6 + self.changes.len() * 5
     }
 }
-impl Encode for Experimental {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
+
+
+////////////////////////////////////// TABLE_OF_CONTENTS //////////////////////////////////////
+/// Extension packet listing the byte offsets (from the start of the file) of major sections, so readers can jump
+/// straight to them. Written as the first packet by [`TasdFile::encode_with_toc`][crate::spec::TasdFile::encode_with_toc],
+/// see [crate::spec::toc].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableOfContents {
+    pub entries: Vec<TocEntry>,
+}
+/// Entry of a [TableOfContents]: `kind` is `0x01` for the first INPUT_CHUNK of `port`, `0x02` for a TRANSITION, or `0x03`
+/// for a MOVIE_TRANSITION (whose `port` is `0`).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TocEntry {
+    pub kind: u8,
+    pub port: u8,
+    pub offset: u64,
+}
+impl Decode for TableOfContents {
+    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+        if !payload.remaining().is_multiple_of(10) {
+            return Err(PacketError::invalid(key, payload));
+        }
         
-        w.write_bool(self.experimental);
+        let mut entries = vec![];
+        while payload.remaining() > 0 {
+            entries.push(TocEntry { kind: payload.read_u8(), port: payload.read_u8(), offset: payload.read_u64() });
+        }
         
-        w.into_packet(&self.key(), keylen)
+        Ok(Self { entries })
     }
-
-    fn key(&self) -> Vec<u8> {
-        KEY_EXPERIMENTAL.to_vec()
+}
+impl EncodePayload for TableOfContents {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
+        for entry in &self.entries {
+            w.write_u8(entry.kind);
+            w.write_u8(entry.port);
+            w.write_u64(entry.offset);
+        }
+    }
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        self.entries.len() * 10
     }
 }
 
 
-////////////////////////////////////// UNSPECIFIED //////////////////////////////////////
+////////////////////////////////////// PADDING //////////////////////////////////////
+/// Extension packet whose payload is ignored, used to move the packets after it to a particular offset. The payload is
+/// `len` bytes of `0x00`.
+/// 
+/// Padding is skipped when parsing. To encode padding of an exact size, use [padding_packet][crate::spec::writer::padding_packet].
 #[derive(Debug, Clone, PartialEq)]
-pub struct Unspecified {
-    pub payload: Vec<u8>,
+pub struct Padding {
+    pub len: u64,
 }
-impl Decode for Unspecified {
+impl Decode for Padding {
     fn decode(_key: &[u8], payload: Reader) -> Result<Self, PacketError> {
-        Ok(Self {
-            payload: payload.to_vec(),
-        })
+        Ok(Self { len: payload.remaining() as u64 })
+    }
+}
+impl EncodePayload for Padding {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
+        w.write_slice(&vec![0x00; self.len as usize]);
     }
     
-    fn kind(&self) -> PacketKind {
-        PacketKind::Unspecified
+    fn payload_len(&self, _keylen: u8) -> usize {
+        self.len as usize
     }
 }
-impl Encode for Unspecified {
-    fn encode(&self, keylen: u8) -> Vec<u8> {
-        let mut w = Writer::new();
-        
-        w.write_slice(&self.payload);
-        
-        w.into_packet(&self.key(), keylen)
-    }
 
-    fn key(&self) -> Vec<u8> {
-        KEY_UNSPECIFIED.to_vec()
+
+////////////////////////////////////// PROVENANCE //////////////////////////////////////
+/// Extension packet recording the tool which added the packets following it, up to the next PROVENANCE. See
+/// [crate::provenance].
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct Provenance {
+    #[tasd(len_prefixed_str)]
+    pub tool: String,
+    #[tasd(remaining)]
+    pub version: String,
+}
+
+
+////////////////////////////////////// EDIT_HISTORY //////////////////////////////////////
+/// Extension packet recording one save of a modified file: who saved it, when, and which kinds of packets changed.
+/// Written by [`TasdFile::save_with_history`][crate::spec::TasdFile::save_with_history], see [crate::history].
+/// 
+/// The payload is `epoch` (i64), `author` (u8 length prefixed), and then each key of `changed`, prefixed with its
+/// length as a u8.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditHistory {
+    /// Seconds since the Unix epoch (UTC).
+    pub epoch: i64,
+    pub author: String,
+    /// Keys of the packet types which were added, removed, or changed, in the order they first appear in the file.
+    pub changed: Vec<Vec<u8>>,
+}
+impl Decode for EditHistory {
+    fn decode(key: &[u8], mut payload: Reader) -> Result<Self, PacketError> {
+        if payload.remaining() < 9 || payload.remaining() < 9 + payload.peek_len(9)[8] as usize {
+            return Err(PacketError::invalid(key, payload));
+        }
+        let epoch = payload.read_i64();
+        let len = payload.read_u8() as usize;
+        let author = payload.read_string(len);
+        
+        let mut changed = vec![];
+        while payload.remaining() > 0 {
+            let len = payload.read_u8() as usize;
+            if payload.remaining() < len {
+                return Err(PacketError::invalid(key, payload));
+            }
+            changed.push(payload.read_len(len).to_vec());
+        }
+        
+        Ok(Self { epoch, author, changed })
     }
 }
+impl EncodePayload for EditHistory {
+    fn encode_payload(&self, w: &mut Writer, _keylen: u8) {
+        w.write_i64(self.epoch);
+        w.write_u8_str(&self.author);
+        for key in &self.changed {
+            w.write_u8(key.len() as u8);
+            w.write_slice(key);
+        }
+    }
+    
+    fn payload_len(&self, _keylen: u8) -> usize {
+        9 + truncate_str(&self.author, u8::MAX as usize).len() + self.changed.iter().map(|key| 1 + key.len()).sum::<usize>()
+    }
+}
+
+////////////////////////////////////// CONSOLE_SETTING //////////////////////////////////////
+/// Extension packet recording a setting of the console hardware which must match for the movie to verify, such as a
+/// DIP switch, jumper, or revision (e.g. whether a Genesis has TMSS).
+/// 
+/// `setting` is a console-specific code whose high byte is the CONSOLE_TYPE it applies to, or `0x00` for any console.
+/// See [console_setting_lut][crate::lookup::console_setting_lut] and
+/// [console_setting_value_lut][crate::lookup::console_setting_value_lut].
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct ConsoleSetting {
+    pub setting: u16,
+    pub value: u8,
+}
+
+
+////////////////////////////////////// LATCH_MAP //////////////////////////////////////
+/// Extension packet recording how many times the game latches the controllers on each frame from `frame` onwards, for
+/// games which poll irregularly (zero or several times per frame). Frames which aren't covered latch once.
+/// 
+/// Frames are numbered after BLANK_FRAMES is applied. See [crate::input::latch].
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct LatchMap {
+    pub frame: u32,
+    #[tasd(remaining)]
+    pub latches: Vec<u8>,
+}
+
+
+////////////////////////////////////// AV_SYNC //////////////////////////////////////
+/// Extension packet with hints for keeping audio and video in sync when encoding the movie.
+/// 
+/// `first_visible_frame` is the first frame which shows the game's picture, after BLANK_FRAMES is applied. Earlier
+/// frames (e.g. a blank screen at power-on, or a reset sequence) are usually cut from encodes. `audio_offset` is how
+/// far the audio lags behind the video, in microseconds, and is negative if it runs ahead. See [crate::sync].
+#[derive(Debug, Clone, PartialEq, TasdPacket)]
+pub struct AvSync {
+    pub first_visible_frame: u32,
+    pub audio_offset: i32,
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+//! Restricting encoded files to what a given version of the spec allows.
+//! 
+//! Files produced for consumers with older or stricter parsers can be checked against a [SpecProfile] before being
+//! written, so anything those parsers wouldn't understand is caught up front instead of being silently misread.
+
+use std::fmt::{Display, Formatter};
+use crate::spec::packets::{Encode, Packet, PacketKind};
+use crate::spec::TasdFile;
+
+/// Set of restrictions on what may be encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecProfile {
+    /// Key length every file must use, if any.
+    pub keylen: Option<u8>,
+    /// Whether this crate's [extension packets][PacketKind::is_extension] are allowed.
+    pub allow_extensions: bool,
+    /// Whether packets with unknown keys (including [dynamic][crate::spec::dynamic] packets) are allowed.
+    pub allow_unknown: bool,
+}
+impl SpecProfile {
+    /// Exactly what spec version 0.001 defines: 2 byte keys, and only the packets listed in the spec.
+    pub const V0_001_STRICT: SpecProfile = SpecProfile { keylen: Some(2), allow_extensions: false, allow_unknown: false };
+    /// Anything this crate can encode.
+    pub const PERMISSIVE: SpecProfile = SpecProfile { keylen: None, allow_extensions: true, allow_unknown: true };
+    
+    /// Whether a packet may be encoded under this profile, including any packet embedded in a transition.
+    pub fn allows(&self, packet: &Packet) -> bool {
+        let kind = packet.kind();
+        let embedded = match packet {
+            Packet::Transition(transition) => transition.kind.packet(),
+            Packet::MovieTransition(transition) => transition.kind.packet(),
+            _ => None,
+        };
+        
+        (self.allow_extensions || !kind.is_extension())
+            && (self.allow_unknown || !matches!(kind, PacketKind::Unsupported | PacketKind::Dynamic))
+            && embedded.is_none_or(|packet| self.allows(packet))
+    }
+    
+    /// Checks a file against this profile, stopping at the first violation.
+    pub fn check(&self, file: &TasdFile) -> Result<(), ProfileViolation> {
+        if let Some(keylen) = self.keylen.filter(|keylen| *keylen != file.keylen) {
+            return Err(ProfileViolation::Keylen { expected: keylen, actual: file.keylen });
+        }
+        
+        match file.packets.iter().position(|packet| !self.allows(packet)) {
+            Some(index) => Err(ProfileViolation::Packet { index, kind: file.packets[index].kind(), key: file.packets[index].key() }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reason a file doesn't fit a [SpecProfile].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileViolation {
+    Keylen {
+        expected: u8,
+        actual: u8,
+    },
+    /// Packet which isn't allowed by the profile.
+    Packet {
+        /// Index into [`TasdFile::packets`].
+        index: usize,
+        kind: PacketKind,
+        key: Vec<u8>,
+    },
+}
+impl Display for ProfileViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Keylen { expected, actual } => write!(f, "keylen must be {expected}, but is {actual}"),
+            Self::Packet { index, kind, key } => write!(f, "packet #{index} ({kind}, key {key:02X?}) isn't allowed"),
+        }
+    }
+}
+
+impl TasdFile {
+    /// Encodes this file, after [checking][SpecProfile::check] that it fits `profile`.
+    pub fn encode_with_profile(&self, profile: &SpecProfile) -> Result<Vec<u8>, ProfileViolation> {
+        profile.check(self)?;
+        
+        Ok(self.encode())
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Bookmark, GameTitle, MovieTransition, PacketKind, TransitionKind, Unsupported};
+    use crate::spec::profile::{ProfileViolation, SpecProfile};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn profiles() {
+        let strict = SpecProfile::V0_001_STRICT;
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        assert_eq!(file.encode_with_profile(&strict), Ok(file.encode()));
+        
+        file.packets.push(MovieTransition { movie_frame: 1, kind: TransitionKind::PacketDerived(Box::new(Bookmark { frame: 1, name: "".into() }.into())) }.into());
+        assert_eq!(strict.check(&file), Err(ProfileViolation::Packet { index: 1, kind: PacketKind::MovieTransition, key: vec![0xFE, 0x05] }));
+        assert!(SpecProfile::PERMISSIVE.check(&file).is_ok());
+        
+        file.packets.pop();
+        file.packets.push(Unsupported { key: vec![0xAB, 0xCD], payload: vec![] }.into());
+        assert!(strict.check(&file).is_err());
+        assert!(SpecProfile { allow_unknown: true, ..strict.clone() }.check(&file).is_ok());
+        
+        file.keylen = 3;
+        assert_eq!(strict.check(&file), Err(ProfileViolation::Keylen { expected: 2, actual: 3 }));
+    }
+}
\ No newline at end of file
@@ -1,11 +1,12 @@
-
+use std::borrow::Cow;
+use crate::spec::packets::PacketError;
 
 pub struct Reader<'a> {
     inner: &'a [u8],
     pos: usize,
 }
 impl<'a> Reader<'a> {
-    pub fn new<T: AsRef<[u8]>>(inner: &'a T) -> Self {
+    pub fn new<T: AsRef<[u8]> + ?Sized>(inner: &'a T) -> Self {
         Self {
             inner: inner.as_ref(),
             pos: 0,
@@ -114,7 +115,7 @@ impl<'a> Reader<'a> {
         self.read_u8() > 0
     }
     
-    pub fn read_len(&mut self, len: usize) -> &[u8] {
+    pub fn read_len(&mut self, len: usize) -> &'a [u8] {
         let data = &self.inner[self.pos..(self.pos + len)];
         self.pos += len;
         
@@ -122,16 +123,45 @@ impl<'a> Reader<'a> {
     }
     
     pub fn read_string(&mut self, len: usize) -> String {
-        String::from_utf8_lossy(self.read_len(len)).to_string()
+        self.read_str(len).into_owned()
+    }
+    
+    /// Reads a string without copying it, unless it contains invalid UTF-8 which needs replacing.
+    pub fn read_str(&mut self, len: usize) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.read_len(len))
     }
     
-    pub fn read_remaining(&mut self) -> &[u8] {
+    pub fn read_remaining(&mut self) -> &'a [u8] {
         let data = &self.inner[self.pos..];
         self.pos += self.remaining();
         
         data
     }
     
+    /// Splits off the next `len` bytes as a separate reader and advances past them.
+    /// 
+    /// The new reader starts at position `0` and can't see anything beyond those `len` bytes, so a decoder given one
+    /// can't read into whatever follows, e.g. the next packet. Fails with [`PacketError::MissingPayload`] and leaves
+    /// this reader unchanged if fewer than `len` bytes remain.
+    /// 
+    /// # Example
+    /// ```
+    /// use tasd::spec::reader::Reader;
+    /// 
+    /// let mut r = Reader::new(&[0x11, 0x22, 0x33, 0x44]);
+    /// let mut sub = r.sub_reader(2).unwrap();
+    /// assert_eq!(sub.read_remaining(), &[0x11, 0x22]);
+    /// assert_eq!(r.read_remaining(), &[0x33, 0x44]);
+    /// assert!(r.sub_reader(1).is_err());
+    /// ```
+    pub fn sub_reader(&mut self, len: usize) -> Result<Reader<'a>, PacketError> {
+        if self.remaining() < len {
+            return Err(PacketError::MissingPayload);
+        }
+        
+        Ok(Reader::new(self.read_len(len)))
+    }
+    
     /// Copies entire buffer into a Vec regardless of current position.
     /// 
     /// Use [`Self::read_remaining`] if only the remaining data is needed.
@@ -335,7 +365,8 @@ mod tests {
             for i in 0..data.len() {
                 r.set_pos(i);
                 assert_eq!(r.read_remaining(), &data[i..]);
-                assert_eq!(r.read_remaining(), &[]);
+                // typed, as serde_json's `PartialEq<Value> for u8` makes `&[]` ambiguous when it's enabled
+                assert_eq!(r.read_remaining(), &[0u8; 0]);
                 
                 assert_eq!(r.to_vec(), &data);
             }
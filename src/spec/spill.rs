@@ -0,0 +1,212 @@
+//! Spilling of large payloads to disk, for processing dumps larger than the available memory.
+//! 
+//! [`StreamParser::next_packet_limited`] keeps payloads within the [ResourceLimits] in memory as normal, and copies
+//! larger ones to temporary files. [LimitedFile] collects a whole file this way, and re-streams the spilled payloads
+//! from disk when it's written back out.
+
+use std::fs::{File, OpenOptions, remove_file};
+use std::io::{copy, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::spec::packets::{Encode, Packet, PacketKind};
+use crate::spec::reader::Reader;
+use crate::spec::stream::StreamParser;
+use crate::spec::writer::packet_header;
+use crate::spec::{LATEST_VERSION, MAGIC_NUMBER, TasdError};
+use crate::util::HashingWriter;
+
+/// Limits on the memory used for packet payloads while parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    /// Payloads larger than this many bytes are spilled to a temporary file.
+    pub max_payload_in_memory: u64,
+    /// Directory to create temporary files in.
+    pub spill_dir: PathBuf,
+}
+impl Default for ResourceLimits {
+    /// 64 MiB per payload, spilling to the system's temporary directory.
+    fn default() -> Self {
+        Self {
+            max_payload_in_memory: 64 * 1024 * 1024,
+            spill_dir: std::env::temp_dir(),
+        }
+    }
+}
+
+/// Payload of a packet stored in a temporary file. The file is removed when the handle is dropped.
+#[derive(Debug)]
+pub struct PayloadHandle {
+    key: Vec<u8>,
+    keylen: u8,
+    len: u64,
+    path: PathBuf,
+}
+impl PayloadHandle {
+    /// Creates a new, uniquely named temporary file in `dir`, returning its handle and a writer for the payload.
+    /// 
+    /// `keylen` is that of the file the packet is from, which is needed to decode embedded packets.
+    pub(crate) fn create(dir: &Path, key: Vec<u8>, keylen: u8, len: u64) -> std::io::Result<(Self, BufWriter<File>)> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let path = dir.join(format!("tasd-{}-{}.payload", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((Self { key, keylen, len, path }, BufWriter::new(file))),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+    
+    pub fn kind(&self) -> PacketKind {
+        PacketKind::from_key(&self.key)
+    }
+    
+    /// Length of the payload in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Opens the payload for reading.
+    pub fn open(&self) -> std::io::Result<File> {
+        File::open(&self.path)
+    }
+    
+    /// Reads the payload into memory and decodes it.
+    pub fn load(&self) -> Result<Packet, TasdError> {
+        let mut payload = vec![];
+        self.open()?.read_to_end(&mut payload)?;
+        
+        Ok(Packet::decode_keyed(&self.key, Reader::new(&payload), self.keylen)?)
+    }
+}
+impl Drop for PayloadHandle {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
+}
+
+/// Packet read by [`StreamParser::next_packet_limited`].
+#[derive(Debug)]
+pub enum LimitedPacket {
+    Packet(Packet),
+    /// Packet whose payload exceeded [`ResourceLimits::max_payload_in_memory`].
+    Spilled(PayloadHandle),
+}
+impl LimitedPacket {
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Self::Packet(packet) => packet.kind(),
+            Self::Spilled(handle) => handle.kind(),
+        }
+    }
+    
+    /// Encodes this packet to `out`, copying spilled payloads from disk.
+    pub fn write_to<W: Write>(&self, out: &mut W, keylen: u8) -> std::io::Result<()> {
+        match self {
+            Self::Packet(packet) => out.write_all(&packet.encode(keylen)),
+            Self::Spilled(handle) => {
+                out.write_all(&packet_header(&handle.key, keylen, handle.len))?;
+                copy(&mut handle.open()?, out)?;
+                Ok(())
+            },
+        }
+    }
+}
+
+/// TASD file whose large payloads may be spilled to disk, see [the module docs][self].
+#[derive(Debug)]
+pub struct LimitedFile {
+    pub version: u16,
+    pub keylen: u8,
+    pub packets: Vec<LimitedPacket>,
+}
+impl LimitedFile {
+    /// Parses a whole file from `inner`, spilling payloads which exceed `limits`.
+    pub fn parse<R: Read>(inner: R, limits: &ResourceLimits) -> Result<Self, TasdError> {
+        let mut parser = StreamParser::new(inner)?;
+        let mut packets = vec![];
+        while let Some(packet) = parser.next_packet_limited(limits)? {
+            packets.push(packet);
+        }
+        
+        Ok(Self {
+            version: parser.version(),
+            keylen: parser.keylen(),
+            packets,
+        })
+    }
+    
+    /// Encodes the file to `out`, re-streaming spilled payloads from disk.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(&MAGIC_NUMBER)?;
+        out.write_all(&LATEST_VERSION)?;
+        out.write_all(&[self.keylen])?;
+        for packet in &self.packets {
+            packet.write_to(out, self.keylen)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Same as [`LimitedFile::write_to`], also returning the SHA-256 hash of the encoded file, computed as it's written.
+    pub fn write_to_hashed<W: Write>(&self, out: W) -> std::io::Result<(W, [u8; 32])> {
+        let mut out = HashingWriter::new(out);
+        self.write_to(&mut out)?;
+        
+        Ok(out.finish())
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Comment, MovieFile, Packet};
+    use crate::spec::spill::{LimitedFile, LimitedPacket, ResourceLimits};
+    use crate::spec::TasdFile;
+    use crate::util::sha256;
+    
+    #[test]
+    fn spills() {
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "small".into() }.into());
+        file.packets.push(MovieFile { name: "movie.bk2".into(), data: vec![0x5A; 5000] }.into());
+        let data = file.encode();
+        
+        let limits = ResourceLimits { max_payload_in_memory: 1024, ..Default::default() };
+        let limited = LimitedFile::parse(data.as_slice(), &limits).unwrap();
+        assert!(matches!(limited.packets[0], LimitedPacket::Packet(Packet::Comment(_))));
+        let LimitedPacket::Spilled(handle) = &limited.packets[1] else { panic!("payload wasn't spilled") };
+        assert_eq!(handle.len(), 1 + 9 + 5000);
+        assert_eq!(handle.load().unwrap(), file.packets[1]);
+        let path = handle.path().to_path_buf();
+        assert!(path.exists());
+        
+        let mut encoded = vec![];
+        limited.write_to(&mut encoded).unwrap();
+        assert_eq!(encoded, data);
+        
+        // hashed on the fly, in pieces
+        let (encoded, hash) = limited.write_to_hashed(vec![]).unwrap();
+        assert_eq!(encoded, data);
+        assert_eq!(hash, sha256(&data));
+        assert_eq!(file.write_to_hashed(vec![]).unwrap(), (data.clone(), sha256(&data)));
+        
+        drop(limited);
+        assert!(!path.exists());
+    }
+}
\ No newline at end of file
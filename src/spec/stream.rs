@@ -0,0 +1,449 @@
+use std::io::{copy, sink, ErrorKind, Read, Write};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use crate::metrics::{Counter, ParseMetrics};
+use crate::spec::packets::{KEY_MOVIE_FILE, KEY_PADDING, Packet, PacketError, PacketKind, unpadded_key};
+use crate::spec::reader::Reader;
+use crate::spec::spill::{LimitedPacket, PayloadHandle, ResourceLimits};
+use crate::spec::writer::packet_header;
+use crate::spec::{MAGIC_NUMBER, TasdError};
+use crate::util::truncate_str;
+
+/// Packet read by [`StreamParser::next_packet_streaming`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamedPacket {
+    Packet(Packet),
+    /// MOVIE_FILE whose data was copied to a writer instead of being kept in memory.
+    MovieFile {
+        name: String,
+        len: u64,
+    },
+}
+
+/// Progress of a [StreamParser], passed to [`ParseObserver::on_packet`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParseProgress {
+    /// Total number of bytes consumed from the source, including the header.
+    pub bytes_read: u64,
+    /// Number of packets parsed so far, including the one just parsed.
+    pub packets: u64,
+    /// Kind of the packet just parsed.
+    pub kind: PacketKind,
+}
+
+/// Receives progress updates from a [StreamParser], for rendering progress bars or cancelling a long parse.
+/// 
+/// Implemented for closures taking a [ParseProgress] and returning a [ControlFlow].
+pub trait ParseObserver {
+    /// Called after each packet is parsed successfully. Returning [`ControlFlow::Break`] stops the parser, which then
+    /// returns [`TasdError::Cancelled`] instead of the packet.
+    fn on_packet(&mut self, progress: &ParseProgress) -> ControlFlow<()>;
+}
+impl<F: FnMut(&ParseProgress) -> ControlFlow<()>> ParseObserver for F {
+    fn on_packet(&mut self, progress: &ParseProgress) -> ControlFlow<()> {
+        self(progress)
+    }
+}
+
+/// Incrementally parses packets from any [Read] source, without buffering the entire file in memory.
+/// 
+/// PADDING packets are skipped.
+/// 
+/// Unlike [`TasdFile::parse_slice`][crate::spec::TasdFile::parse_slice], packets which fail to decode are returned as
+/// errors instead of being skipped. The stream is left positioned after the offending packet, so parsing may continue.
+pub struct StreamParser<R: Read> {
+    inner: R,
+    version: u16,
+    keylen: u8,
+    bytes_read: u64,
+    packets: u64,
+    observer: Option<Box<dyn ParseObserver>>,
+    metrics: Option<Arc<dyn ParseMetrics>>,
+    /// Value of `bytes_read` when bytes were last reported to `metrics`.
+    bytes_counted: u64,
+}
+impl<R: Read> StreamParser<R> {
+    /// Reads and verifies the TASD header from `inner`.
+    pub fn new(mut inner: R) -> Result<Self, TasdError> {
+        let mut header = [0u8; 7];
+        inner.read_exact(&mut header).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => TasdError::MissingHeader,
+            _ => err.into(),
+        })?;
+        if header[..4] != MAGIC_NUMBER {
+            return Err(TasdError::MagicNumberMismatch(header[..4].to_vec()));
+        }
+        
+        Ok(Self {
+            inner,
+            version: u16::from_be_bytes([header[4], header[5]]),
+            keylen: header[6],
+            bytes_read: header.len() as u64,
+            packets: 0,
+            observer: None,
+            metrics: None,
+            bytes_counted: 0,
+        })
+    }
+    
+    /// Creates a parser for a source whose header has already been read by other means.
+    pub fn with_header(inner: R, version: u16, keylen: u8) -> Self {
+        Self {
+            inner,
+            version,
+            keylen,
+            bytes_read: 0,
+            packets: 0,
+            observer: None,
+            metrics: None,
+            bytes_counted: 0,
+        }
+    }
+    
+    /// Reports progress to `observer` after each packet.
+    pub fn with_observer<O: ParseObserver + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+    
+    /// Reports each packet parsed to `metrics`, which may be shared with other parsers. See [crate::metrics].
+    /// 
+    /// Packets which fail to decode are counted as [warnings][Counter::Warnings].
+    pub fn with_metrics(mut self, metrics: Arc<dyn ParseMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+    
+    pub fn keylen(&self) -> u8 {
+        self.keylen
+    }
+    
+    /// Total number of bytes consumed from the source so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+    
+    /// Number of packets parsed so far.
+    pub fn packets_parsed(&self) -> u64 {
+        self.packets
+    }
+    
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+    
+    /// Reads the next packet from the source.
+    /// 
+    /// Returns `Ok(None)` once the source is cleanly exhausted between packets.
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, TasdError> {
+        let Some((header, plen)) = self.read_header()? else { return Ok(None) };
+        let packet = self.read_payload(header, plen)?;
+        self.observe(packet.kind())?;
+        
+        Ok(Some(packet))
+    }
+    
+    /// Reads the next packet from the source, like [`Self::next_packet`], except that the data of MOVIE_FILE packets is
+    /// copied to the writer returned by `open` (called with the file's name) instead of being buffered in memory.
+    /// 
+    /// This allows embedded files of any size (up to the 2^64-1 byte limit of the payload length) to be extracted.
+    pub fn next_packet_streaming<W: Write, F: FnOnce(&str) -> std::io::Result<W>>(&mut self, open: F) -> Result<Option<StreamedPacket>, TasdError> {
+        let Some((header, plen)) = self.read_header()? else { return Ok(None) };
+        if unpadded_key(&header[..(self.keylen as usize)]) != KEY_MOVIE_FILE || plen < 1 {
+            let packet = self.read_payload(header, plen)?;
+            self.observe(packet.kind())?;
+            return Ok(Some(StreamedPacket::Packet(packet)));
+        }
+        
+        let mut nlen = [0u8; 1];
+        self.read_exact(&mut nlen)?;
+        let nlen = nlen[0] as u64;
+        if plen < 1 + nlen {
+            let mut payload = vec![nlen as u8];
+            payload.resize(plen as usize, 0);
+            self.read_exact(&mut payload[1..])?;
+            return Err(PacketError::InvalidPayload { key: KEY_MOVIE_FILE.to_vec(), payload }.into());
+        }
+        let mut name = vec![0u8; nlen as usize];
+        self.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).to_string();
+        
+        let len = plen - 1 - nlen;
+        let mut out = open(&name)?;
+        self.copy_payload(len, &mut out)?;
+        self.observe(PacketKind::MovieFile)?;
+        
+        Ok(Some(StreamedPacket::MovieFile { name, len }))
+    }
+    
+    /// Reads the next packet from the source, like [`Self::next_packet`], except that payloads larger than the limit are
+    /// [spilled][crate::spec::spill] to a temporary file instead of being held in memory.
+    pub fn next_packet_limited(&mut self, limits: &ResourceLimits) -> Result<Option<LimitedPacket>, TasdError> {
+        let Some((header, plen)) = self.read_header()? else { return Ok(None) };
+        if plen <= limits.max_payload_in_memory {
+            let packet = self.read_payload(header, plen)?;
+            self.observe(packet.kind())?;
+            return Ok(Some(LimitedPacket::Packet(packet)));
+        }
+        
+        let key = unpadded_key(&header[..(self.keylen as usize)]).to_vec();
+        let (handle, mut out) = PayloadHandle::create(&limits.spill_dir, key, self.keylen, plen)?;
+        self.copy_payload(plen, &mut out)?;
+        out.flush()?;
+        self.observe(handle.kind())?;
+        
+        Ok(Some(LimitedPacket::Spilled(handle)))
+    }
+    
+    /// Copies the next `len` bytes of the source to `out`.
+    fn copy_payload<W: Write>(&mut self, len: u64, out: &mut W) -> Result<(), TasdError> {
+        let copied = copy(&mut (&mut self.inner).take(len), out)?;
+        self.bytes_read += copied;
+        if copied < len {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        
+        Ok(())
+    }
+    
+    /// Counts a parsed packet, and reports it to the metrics and observer (if any).
+    fn observe(&mut self, kind: PacketKind) -> Result<(), TasdError> {
+        self.packets += 1;
+        if let Some(metrics) = &self.metrics {
+            metrics.increment(Counter::PacketsParsed, 1);
+            metrics.increment(Counter::BytesDecoded, self.bytes_read - self.bytes_counted);
+            if kind == PacketKind::Unsupported {
+                metrics.increment(Counter::UnknownKeys, 1);
+            }
+            self.bytes_counted = self.bytes_read;
+        }
+        let progress = ParseProgress { bytes_read: self.bytes_read, packets: self.packets, kind };
+        match self.observer.as_mut().map(|observer| observer.on_packet(&progress)) {
+            Some(ControlFlow::Break(())) => Err(TasdError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Reads the key, exponent, and payload length of the next packet which isn't PADDING, returning them along with the
+    /// payload length. The payloads of any PADDING packets in between are skipped without being buffered.
+    fn read_header(&mut self) -> Result<Option<(Vec<u8>, u64)>, TasdError> {
+        loop {
+            let Some((header, plen)) = self.read_any_header()? else { return Ok(None) };
+            if unpadded_key(&header[..(self.keylen as usize)]) != KEY_PADDING {
+                return Ok(Some((header, plen)));
+            }
+            self.copy_payload(plen, &mut sink())?;
+        }
+    }
+    
+    /// Reads the key, exponent, and payload length of the next packet, returning them along with the payload length.
+    fn read_any_header(&mut self) -> Result<Option<(Vec<u8>, u64)>, TasdError> {
+        let keylen = self.keylen as usize;
+        let mut data = vec![0u8; keylen + 1];
+        
+        let read = self.read_fully(&mut data)?;
+        if read == 0 {
+            return Ok(None);
+        } else if read < keylen {
+            return Err(PacketError::MissingKey.into());
+        } else if read < data.len() {
+            return Err(PacketError::MissingPayloadLength.into());
+        }
+        
+        let exp = data[keylen] as usize;
+        if exp > 8 {
+            return Err(PacketError::UnsupportedExponent(exp as u8).into());
+        }
+        let mut plen = [0u8; 8];
+        if self.read_fully(&mut plen[(8 - exp)..])? < exp {
+            return Err(PacketError::MissingPayloadLength.into());
+        }
+        data.extend_from_slice(&plen[(8 - exp)..]);
+        
+        Ok(Some((data, u64::from_be_bytes(plen))))
+    }
+    
+    /// Reads a payload of `plen` bytes and decodes it along with the already read `header`.
+    /// 
+    /// The buffer only grows as data actually arrives, so a header claiming a huge length can't exhaust memory on its
+    /// own.
+    fn read_payload(&mut self, mut data: Vec<u8>, plen: u64) -> Result<Packet, TasdError> {
+        let read = (&mut self.inner).take(plen).read_to_end(&mut data)?;
+        self.bytes_read += read as u64;
+        if (read as u64) < plen {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        
+        let packet = Packet::with_reader(&mut Reader::new(&data), self.keylen);
+        if let (Err(_), Some(metrics)) = (&packet, &self.metrics) {
+            metrics.increment(Counter::Warnings, 1);
+        }
+        
+        packet.map_err(|err| err.into())
+    }
+    
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), TasdError> {
+        if self.read_fully(buf)? < buf.len() {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        
+        Ok(())
+    }
+    
+    /// Fills as much of `buf` as possible, returning the number of bytes read. Only returns less than `buf.len()` at EOF.
+    fn read_fully(&mut self, buf: &mut [u8]) -> Result<usize, TasdError> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.bytes_read += read as u64;
+        
+        Ok(read)
+    }
+}
+impl<R: Read> Iterator for StreamParser<R> {
+    type Item = Result<Packet, TasdError>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+
+/// Writes a MOVIE_FILE packet whose data is copied from `data`, without buffering it in memory.
+/// 
+/// Exactly `len` bytes are read from `data`, and the payload length is encoded with however large an exponent it needs
+/// (up to 8). Names longer than 255 bytes are truncated, as with [`Writer::write_u8_str`][crate::spec::writer::Writer::write_u8_str].
+pub fn write_movie_file<W: Write, R: Read>(out: &mut W, keylen: u8, name: &str, data: R, len: u64) -> std::io::Result<()> {
+    let name = truncate_str(name, u8::MAX as usize);
+    
+    out.write_all(&packet_header(KEY_MOVIE_FILE, keylen, 1 + name.len() as u64 + len))?;
+    out.write_all(&[name.len() as u8])?;
+    out.write_all(name.as_bytes())?;
+    if copy(&mut data.take(len), out)? < len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    
+    Ok(())
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ops::ControlFlow;
+    use std::rc::Rc;
+    use crate::spec::packets::{Comment, Encode, GameTitle, KEY_MOVIE_FILE, MovieFile, Packet, PacketError, PacketKind};
+    use crate::spec::reader::Reader;
+    use crate::spec::TasdFile;
+    use crate::spec::TasdError;
+    use crate::spec::stream::{ParseProgress, StreamParser, StreamedPacket, write_movie_file};
+    use crate::spec::writer::{packet_header, padding_packet};
+    
+    #[test]
+    fn parses() {
+        let mut file = TasdFile::new();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(Comment { comment: "c".repeat(300) }.into());
+        let data = file.encode();
+        
+        let parser = StreamParser::new(data.as_slice()).unwrap();
+        assert_eq!(parser.keylen(), 2);
+        let packets = parser.collect::<Result<Vec<Packet>, TasdError>>().unwrap();
+        assert_eq!(packets, file.packets);
+        
+        let mut parser = StreamParser::new(&data[..(data.len() - 1)]).unwrap();
+        assert!(parser.next_packet().unwrap().is_some());
+        assert!(parser.next_packet().unwrap().is_some());
+        assert!(matches!(parser.next_packet(), Err(TasdError::Io(_))));
+        
+        assert!(matches!(StreamParser::new(&data[..5]), Err(TasdError::MissingHeader)));
+        assert!(matches!(StreamParser::new(&[0u8; 7][..]), Err(TasdError::MagicNumberMismatch(_))));
+        
+        let mut padded = data[..7].to_vec();
+        padded.extend(padding_packet(300, 2).unwrap());
+        padded.extend_from_slice(&data[7..]);
+        assert_eq!(StreamParser::new(padded.as_slice()).unwrap().collect::<Result<Vec<Packet>, TasdError>>().unwrap(), file.packets);
+        
+        let mut parser = StreamParser::new(&data[..8]).unwrap();
+        assert!(matches!(parser.next_packet(), Err(TasdError::Packet(PacketError::MissingKey))));
+    }
+    
+    #[test]
+    fn large_payloads() {
+        assert_eq!(packet_header(KEY_MOVIE_FILE, 2, 0), vec![0x00, 0x15, 0x00]);
+        assert_eq!(packet_header(KEY_MOVIE_FILE, 2, 5 << 30), vec![0x00, 0x15, 0x05, 0x01, 0x40, 0x00, 0x00, 0x00]);
+        assert_eq!(packet_header(KEY_MOVIE_FILE, 2, u64::MAX), [&[0x00, 0x15, 0x08][..], &[0xFF; 8]].concat());
+        
+        // an oversized exponent is still valid
+        let mut data = vec![0x00, 0x15, 0x08, 0, 0, 0, 0, 0, 0, 0, 0x03, 0x01, b'm', 0xAB];
+        let packet = Packet::MovieFile(MovieFile { name: "m".into(), data: vec![0xAB] });
+        assert_eq!(Packet::with_reader(&mut Reader::new(&data), 2), Ok(packet.clone()));
+        assert_eq!(StreamParser::with_header(data.as_slice(), 1, 2).next_packet().unwrap(), Some(packet));
+        
+        data[10] = 0x04;
+        assert_eq!(Packet::with_reader(&mut Reader::new(&data), 2), Err(PacketError::MissingPayload));
+        
+        // a header claiming far more data than there is fails without allocating it
+        let data = [&packet_header(KEY_MOVIE_FILE, 2, u64::MAX)[..], &[0x01, b'm']].concat();
+        assert!(matches!(StreamParser::with_header(data.as_slice(), 1, 2).next_packet(), Err(TasdError::Io(_))));
+    }
+    
+    #[test]
+    fn streams_movie_files() {
+        let mut data = TasdFile::default().encode();
+        write_movie_file(&mut data, 2, "movie.bk2", &[0x5A; 1000][..], 1000).unwrap();
+        data.extend_from_slice(&Packet::from(Comment { comment: "c".into() }).encode(2));
+        assert!(write_movie_file(&mut vec![], 2, "short", &[0; 10][..], 11).is_err());
+        
+        let mut parser = StreamParser::new(data.as_slice()).unwrap();
+        let mut extracted = vec![];
+        let packet = parser.next_packet_streaming(|name| {
+            assert_eq!(name, "movie.bk2");
+            Ok(&mut extracted)
+        }).unwrap();
+        assert_eq!(packet, Some(StreamedPacket::MovieFile { name: "movie.bk2".into(), len: 1000 }));
+        assert_eq!(extracted, vec![0x5A; 1000]);
+        
+        let packet = parser.next_packet_streaming(|_| Ok(vec![])).unwrap();
+        assert_eq!(packet, Some(StreamedPacket::Packet(Comment { comment: "c".into() }.into())));
+        assert_eq!(parser.bytes_read(), data.len() as u64);
+        
+        let file = TasdFile::parse_slice(&data).unwrap();
+        assert_eq!(file.first::<MovieFile>().unwrap().data.len(), 1000);
+    }
+    
+    #[test]
+    fn observes() {
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(Comment { comment: "c".into() }.into());
+        file.packets.push(Comment { comment: "c".into() }.into());
+        let data = file.encode();
+        
+        let progress = Rc::new(RefCell::new(vec![]));
+        let seen = progress.clone();
+        let mut parser = StreamParser::new(data.as_slice()).unwrap().with_observer(move |progress: &ParseProgress| {
+            seen.borrow_mut().push(*progress);
+            if progress.packets < 2 { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+        });
+        assert!(parser.next_packet().unwrap().is_some());
+        assert!(matches!(parser.next_packet(), Err(TasdError::Cancelled)));
+        assert_eq!(parser.packets_parsed(), 2);
+        
+        let progress = progress.borrow();
+        assert_eq!(progress[0], ParseProgress { bytes_read: 7 + 4 + 5, packets: 1, kind: PacketKind::GameTitle });
+        assert_eq!(progress[1].kind, PacketKind::Comment);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,123 @@
+//! Table of contents for jumping straight to sections of a file.
+//! 
+//! [`TasdFile::encode_with_toc`] writes a [TableOfContents] as the first packet, listing where each port's input data
+//! starts and where each transition is. Readers (e.g. replay devices reading from slow SD cards) can then [read the
+//! table][read_toc] and [start parsing][parser_at] at any of those offsets, instead of scanning the whole file.
+//! 
+//! The offsets are only valid for the exact bytes they were written with. After editing a file, encode it with
+//! [`TasdFile::encode_with_toc`] again (which replaces the old table) rather than [`TasdFile::encode`].
+
+use std::io::{Read, Seek, SeekFrom};
+use crate::spec::packets::{Encode, Packet, TableOfContents, TocEntry};
+use crate::spec::stream::StreamParser;
+use crate::spec::{LATEST_VERSION, MAGIC_NUMBER, TasdError, TasdFile};
+
+impl TableOfContents {
+    /// Offset of the first INPUT_CHUNK of a port.
+    pub fn first_input_chunk(&self, port: u8) -> Option<u64> {
+        self.entries.iter().find(|entry| entry.kind == 0x01 && entry.port == port).map(|entry| entry.offset)
+    }
+}
+
+impl TasdFile {
+    /// Encodes this file with a freshly generated TABLE_OF_CONTENTS as the first packet, in place of any existing one.
+    pub fn encode_with_toc(&self) -> Vec<u8> {
+        let packets: Vec<(&Packet, Vec<u8>)> = self.packets.iter()
+            .filter(|packet| !matches!(packet, Packet::TableOfContents(_)))
+            .map(|packet| (packet, packet.encode(self.keylen)))
+            .collect();
+        
+        // offsets are first relative to the end of the table, which has a fixed size per entry
+        let mut toc = TableOfContents { entries: vec![] };
+        let mut offset = 0;
+        for (packet, encoded) in &packets {
+            let entry = match packet {
+                Packet::InputChunk(chunk) if toc.first_input_chunk(chunk.port).is_none() => Some((0x01, chunk.port)),
+                Packet::Transition(transition) => Some((0x02, transition.port)),
+                Packet::MovieTransition(_) => Some((0x03, 0)),
+                _ => None,
+            };
+            if let Some((kind, port)) = entry {
+                toc.entries.push(TocEntry { kind, port, offset });
+            }
+            offset += encoded.len() as u64;
+        }
+        let start = (MAGIC_NUMBER.len() + LATEST_VERSION.len() + 1 + toc.encode(self.keylen).len()) as u64;
+        for entry in &mut toc.entries {
+            entry.offset += start;
+        }
+        
+        let mut data = vec![];
+        data.extend_from_slice(&MAGIC_NUMBER);
+        data.extend_from_slice(&LATEST_VERSION);
+        data.push(self.keylen);
+        data.extend_from_slice(&toc.encode(self.keylen));
+        for (_, encoded) in packets {
+            data.extend_from_slice(&encoded);
+        }
+        
+        data
+    }
+}
+
+/// Reads the TABLE_OF_CONTENTS at the start of a file, without reading any further.
+/// 
+/// Returns `None` if the first packet isn't a table of contents.
+pub fn read_toc<R: Read>(inner: R) -> Result<Option<TableOfContents>, TasdError> {
+    match StreamParser::new(inner)?.next_packet()? {
+        Some(Packet::TableOfContents(toc)) => Ok(Some(toc)),
+        _ => Ok(None),
+    }
+}
+
+/// Reads the header of a file, then creates a parser starting at `offset` (e.g. from a [TableOfContents]).
+pub fn parser_at<R: Read + Seek>(inner: R, offset: u64) -> Result<StreamParser<R>, TasdError> {
+    let parser = StreamParser::new(inner)?;
+    let (version, keylen) = (parser.version(), parser.keylen());
+    let mut inner = parser.into_inner();
+    inner.seek(SeekFrom::Start(offset))?;
+    
+    Ok(StreamParser::with_header(inner, version, keylen))
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::spec::packets::{Comment, InputChunk, MovieTransition, Packet, TableOfContents, TransitionKind};
+    use crate::spec::toc::{parser_at, read_toc};
+    use crate::spec::TasdFile;
+    
+    #[test]
+    fn table_of_contents() {
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![1, 2] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![3] }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![4] }.into());
+        file.packets.push(MovieTransition { movie_frame: 1, kind: TransitionKind::SoftReset }.into());
+        
+        let data = file.encode_with_toc();
+        let toc = read_toc(data.as_slice()).unwrap().unwrap();
+        assert_eq!(toc.entries.len(), 3);
+        
+        let mut parser = parser_at(Cursor::new(&data), toc.first_input_chunk(2).unwrap()).unwrap();
+        assert_eq!(parser.next_packet().unwrap(), Some(file.packets[2].clone()));
+        let mut parser = parser_at(Cursor::new(&data), toc.entries[2].offset).unwrap();
+        assert_eq!(parser.next_packet().unwrap(), Some(file.packets[4].clone()));
+        
+        // re-encoding replaces the table instead of adding another
+        let mut parsed = TasdFile::parse_slice(&data).unwrap();
+        assert!(matches!(parsed.packets[0], Packet::TableOfContents(_)));
+        parsed.packets.remove(1);
+        let data = parsed.encode_with_toc();
+        let reparsed = TasdFile::parse_slice(&data).unwrap();
+        assert_eq!(reparsed.all::<TableOfContents>().count(), 1);
+        assert_eq!(reparsed.packets.len(), 5);
+        
+        assert_eq!(read_toc(file.encode().as_slice()).unwrap(), None);
+    }
+}
\ No newline at end of file
@@ -1,9 +1,15 @@
-use std::cmp::{max, min};
-use crate::util::to_bytes;
+use std::cmp::max;
+use crate::spec::packets::KEY_PADDING;
+use crate::util::truncate_str;
 
 pub struct Writer {
     inner: Vec<u8>,
 }
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Writer {
     pub fn new() -> Self {
         Self {
@@ -51,12 +57,14 @@ impl Writer {
         self.inner.extend_from_slice(data.as_bytes());
     }
     
+    /// Writes a string prefixed with a u8 length byte.
+    /// 
+    /// Strings longer than 255 bytes are truncated to the last full character that fits.
     pub fn write_u8_str(&mut self, data: &str) {
-        let data = data.as_bytes();
-        let len = min(data.len(), 255);
+        let data = truncate_str(data, u8::MAX as usize);
         
-        self.write_u8(len as u8);
-        self.inner.extend_from_slice(&data[..len]);
+        self.write_u8(data.len() as u8);
+        self.inner.extend_from_slice(data.as_bytes());
     }
     
     pub fn write_option_string(&mut self, data: &Option<String>) {
@@ -72,27 +80,7 @@ impl Writer {
     }
     
     pub fn into_packet(self, key: &[u8], keylen: u8) -> Vec<u8> {
-        let key = {
-            let mut resized_key = vec![0u8; max(key.len(), keylen as usize) - key.len()];
-            resized_key.extend_from_slice(key);
-            resized_key
-        };
-        
-        let exp = {
-            let mut tmp = self.inner.len();
-            let mut exp = 0u8;
-            while tmp > 0 {
-                tmp >>= 8;
-                exp += 1;
-            }
-            exp
-        };
-        let plen = to_bytes(self.inner.len(), exp);
-        
-        let mut data = Vec::with_capacity(self.inner.len() + key.len() + 1 + exp as usize);
-        data.extend_from_slice(&key);
-        data.push(exp);
-        data.extend_from_slice(&plen);
+        let mut data = packet_header(key, keylen, self.inner.len() as u64);
         data.extend_from_slice(&self.inner);
         
         data
@@ -102,6 +90,64 @@ impl Writer {
     pub fn to_vec(&self) -> Vec<u8> {
         self.inner.clone()
     }
+    
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+/// Encodes a PADDING packet which is exactly `size` bytes long, for filling a gap left by a shrunken packet or moving the
+/// following packets to an aligned offset.
+/// 
+/// Any size of at least `keylen + 1` bytes is possible, since the payload length may be encoded with a larger exponent
+/// than it needs. Returns `None` for anything smaller.
+pub fn padding_packet(size: u64, keylen: u8) -> Option<Vec<u8>> {
+    let mut data = vec![0u8; max(KEY_PADDING.len(), keylen as usize) - KEY_PADDING.len()];
+    data.extend_from_slice(KEY_PADDING);
+    let remaining = size.checked_sub(data.len() as u64 + 1)?;
+    
+    // an exponent of 0 only fits an empty payload, otherwise use the smallest exponent whose length bytes fit the rest
+    let exp = (0..=8u64).find(|exp| match exp {
+        0 => remaining == 0,
+        8 => true,
+        _ => remaining.checked_sub(*exp).is_some_and(|len| len < 1 << (exp * 8)),
+    })?;
+    let len = remaining.checked_sub(exp)?;
+    data.push(exp as u8);
+    data.extend_from_slice(&len.to_be_bytes()[(8 - exp as usize)..]);
+    data.resize(data.len() + len as usize, 0x00);
+    
+    Some(data)
+}
+
+/// Encodes the key, exponent, and payload length which come before a payload of `len` bytes.
+/// 
+/// Keys shorter than `keylen` are padded with leading zeros. The smallest exponent which fits the length is used, up to
+/// 8 for payloads of 2^56 bytes or more.
+pub fn packet_header(key: &[u8], keylen: u8, len: u64) -> Vec<u8> {
+    let exp = length_exponent(len);
+    
+    let mut data = vec![0u8; max(key.len(), keylen as usize) - key.len()];
+    data.extend_from_slice(key);
+    data.push(exp as u8);
+    data.extend_from_slice(&len.to_be_bytes()[(8 - exp)..]);
+    
+    data
+}
+
+/// Length of the [packet_header] for a payload of `len` bytes.
+pub fn packet_header_len(key: &[u8], keylen: u8, len: u64) -> usize {
+    max(key.len(), keylen as usize) + 1 + length_exponent(len)
+}
+
+/// Smallest exponent (number of payload length bytes) which fits `len`.
+fn length_exponent(len: u64) -> usize {
+    (u64::BITS - len.leading_zeros()).div_ceil(8) as usize
 }
 
 
@@ -112,7 +158,19 @@ impl Writer {
 mod tests {
     use std::array::from_fn;
     use std::cmp::min;
-    use crate::spec::writer::Writer;
+    use crate::spec::writer::{padding_packet, Writer};
+    
+    #[test]
+    fn padding() {
+        assert_eq!(padding_packet(2, 2), None);
+        assert_eq!(padding_packet(3, 2), Some(vec![0xFF, 0x87, 0x00]));
+        assert_eq!(padding_packet(4, 2), Some(vec![0xFF, 0x87, 0x01, 0x00]));
+        assert_eq!(padding_packet(5, 2), Some(vec![0xFF, 0x87, 0x01, 0x01, 0x00]));
+        for size in 5..70_000 {
+            assert_eq!(padding_packet(size, 2).unwrap().len() as u64, size);
+            assert_eq!(padding_packet(size, 4).unwrap().len() as u64, size);
+        }
+    }
     
     #[test]
     fn writes() {
@@ -197,6 +255,15 @@ mod tests {
             expected.extend_from_slice(&s.as_bytes()[..min(len, 255)]);
             assert_eq!(w.inner, expected);
         }
+        for len in 250..260 {
+            let mut w = Writer::new();
+            let s = "é".repeat(len / 2);
+            w.write_u8_str(&s);
+            
+            let written = w.inner[0] as usize;
+            assert!(written <= 255 && written.is_multiple_of(2));
+            assert_eq!(&w.inner[1..], &s.as_bytes()[..written]);
+        }
         
         let mut w = Writer::new();
         w.write_iter(0..=255);
@@ -0,0 +1,115 @@
+//! How a movie starts: from a clean power-on, or from saved data which has to be restored first.
+//! 
+//! The spec has no packet for this, so [`TasdFile::start_condition`] derives it from the packets present: a
+//! SAVESTATE_ANCHOR, or MEMORY_INIT packets embedding cartridge save data. [`TasdFile::set_start_condition`] declares
+//! it explicitly with a SAVESTATE_ANCHOR, and validation warns when the data a start condition needs is missing.
+
+use crate::memory::Peripheral;
+use crate::spec::packets::{MemoryInit, Packet, SavestateAnchor};
+use crate::spec::TasdFile;
+
+/// How a movie starts. See the [module docs][self].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StartCondition {
+    /// From power-on, with nothing restored beforehand.
+    #[default]
+    PowerOn,
+    /// From power-on, with existing cartridge save data (SRAM, EEPROM, or Flash).
+    SramAnchored,
+    /// From a savestate or snapshot of the console's memory.
+    SavestateAnchored,
+}
+
+/// Whether a MEMORY_INIT packet initializes cartridge save memory, either as a console's "Cartridge Save Data" device
+/// or as one of the [peripherals][Peripheral] without a device code.
+pub fn is_cartridge_save(init: &MemoryInit) -> bool {
+    match init.device {
+        0xFFFF => [Peripheral::Eeprom, Peripheral::Sram, Peripheral::Flash].iter().any(|peripheral| peripheral.name() == init.name),
+        device => device & 0xFF == 0x02 && matches!(device >> 8, 0x01 | 0x02 | 0x05..=0x09),
+    }
+}
+
+impl TasdFile {
+    /// How this movie starts, from its SAVESTATE_ANCHOR if it has one, or otherwise from whether it embeds cartridge
+    /// save data.
+    pub fn start_condition(&self) -> StartCondition {
+        match self.first::<SavestateAnchor>() {
+            Some(anchor) if anchor.kind == 0x02 => StartCondition::SramAnchored,
+            Some(_) => StartCondition::SavestateAnchored,
+            None if self.all::<MemoryInit>().any(|init| init.data_type == 0xFF && is_cartridge_save(init)) => StartCondition::SramAnchored,
+            None => StartCondition::PowerOn,
+        }
+    }
+    
+    /// Declares how this movie starts. A SAVESTATE_ANCHOR of the matching kind is added before the input data if there
+    /// isn't one already, and any others are removed.
+    /// 
+    /// [`StartCondition::PowerOn`] removes every SAVESTATE_ANCHOR, along with any MEMORY_INIT packets embedding
+    /// cartridge save data, as the movie would otherwise still be [SRAM anchored][StartCondition::SramAnchored].
+    /// 
+    /// Added anchors don't identify the state (they use an identifier kind of `0xFF` with no data), so callers which
+    /// know its hash should set it afterwards.
+    pub fn set_start_condition(&mut self, condition: StartCondition) {
+        let fits = |anchor: &SavestateAnchor| match condition {
+            StartCondition::PowerOn => false,
+            StartCondition::SramAnchored => anchor.kind == 0x02,
+            StartCondition::SavestateAnchored => anchor.kind != 0x02,
+        };
+        let mut kept = false;
+        self.packets.retain(|packet| match packet {
+            Packet::SavestateAnchor(anchor) if !kept && fits(anchor) => {
+                kept = true;
+                true
+            },
+            Packet::SavestateAnchor(_) => false,
+            Packet::MemoryInit(init) if condition == StartCondition::PowerOn => init.data_type != 0xFF || !is_cartridge_save(init),
+            _ => true,
+        });
+        
+        let kind = match condition {
+            _ if kept => return,
+            StartCondition::PowerOn => return,
+            StartCondition::SramAnchored => 0x02,
+            StartCondition::SavestateAnchored => 0x01,
+        };
+        let index = self.packets.iter().position(|packet| packet.kind().is_input()).unwrap_or(self.packets.len());
+        self.packets.insert(index, SavestateAnchor { kind, identifier_kind: 0xFF, identifier: vec![] }.into());
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::Peripheral;
+    use crate::spec::packets::{ConsoleType, InputChunk, MemoryInit, SavestateAnchor};
+    use crate::spec::TasdFile;
+    use crate::start::StartCondition;
+    
+    #[test]
+    fn start_condition() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x02, custom: None }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 2] }.into());
+        assert_eq!(file.start_condition(), StartCondition::PowerOn);
+        
+        file.attach_peripheral(Peripheral::Sram, vec![0; 8]);
+        assert_eq!(file.start_condition(), StartCondition::SramAnchored);
+        
+        file.set_start_condition(StartCondition::SavestateAnchored);
+        assert_eq!(file.start_condition(), StartCondition::SavestateAnchored);
+        assert_eq!(file.packets[2], SavestateAnchor { kind: 0x01, identifier_kind: 0xFF, identifier: vec![] }.into());
+        
+        file.set_start_condition(StartCondition::SramAnchored);
+        assert_eq!(file.all::<SavestateAnchor>().map(|anchor| anchor.kind).collect::<Vec<_>>(), [0x02]);
+        
+        // the save data is removed too, so the movie really does start from power-on
+        file.packets.push(MemoryInit { data_type: 0x02, device: 0x0201, required: true, name: "WRAM".into(), data: None }.into());
+        file.set_start_condition(StartCondition::PowerOn);
+        assert!(file.first::<SavestateAnchor>().is_none());
+        assert_eq!(file.start_condition(), StartCondition::PowerOn);
+        assert_eq!(file.all::<MemoryInit>().count(), 1);
+    }
+}
\ No newline at end of file
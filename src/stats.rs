@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+use crate::spec::packets::{Encode, PacketKind};
+use crate::spec::TasdFile;
+
+/// Size and redundancy statistics for all packets of one kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketStats {
+    pub kind: PacketKind,
+    pub count: usize,
+    /// Total size of the payloads, excluding keys and payload lengths.
+    pub payload_bytes: usize,
+    /// Shannon entropy of the payload bytes, in bits per byte (`0.0` to `8.0`).
+    pub entropy: f64,
+    /// Number of packets whose payload is identical to an earlier packet of the same kind.
+    pub duplicates: usize,
+}
+impl PacketStats {
+    /// Lower bound on the size the payloads could be compressed to by an order-0 entropy coder.
+    pub fn estimated_compressed_bytes(&self) -> usize {
+        (self.payload_bytes as f64 * self.entropy / 8.0).ceil() as usize
+    }
+    
+    /// Estimated fraction of the payload bytes which compression could save (`0.0` to `1.0`).
+    pub fn compressibility(&self) -> f64 {
+        if self.payload_bytes == 0 {
+            0.0
+        } else {
+            1.0 - (self.estimated_compressed_bytes() as f64 / self.payload_bytes as f64)
+        }
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    data.iter().for_each(|byte| counts[*byte as usize] += 1);
+    
+    let len = data.len() as f64;
+    counts.iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum::<f64>()
+        .max(0.0)
+}
+
+impl TasdFile {
+    /// Statistics for each kind of packet in this file, in order of first appearance.
+    /// 
+    /// Useful for deciding whether consolidating or compressing packets (e.g. INPUT_CHUNKs) is worthwhile.
+    pub fn packet_stats(&self) -> Vec<PacketStats> {
+        let mut groups: Vec<(PacketKind, Vec<Vec<u8>>)> = vec![];
+        for packet in &self.packets {
+            let payload = payload(&packet.encode(self.keylen), self.keylen);
+            match groups.iter_mut().find(|(kind, _)| *kind == packet.kind()) {
+                Some((_, payloads)) => payloads.push(payload),
+                None => groups.push((packet.kind(), vec![payload])),
+            }
+        }
+        
+        groups.into_iter().map(|(kind, payloads)| {
+            let mut seen = HashSet::new();
+            let duplicates = payloads.iter().filter(|payload| !seen.insert(payload.as_slice())).count();
+            let data = payloads.concat();
+            
+            PacketStats {
+                kind,
+                count: payloads.len(),
+                payload_bytes: data.len(),
+                entropy: entropy(&data),
+                duplicates,
+            }
+        }).collect()
+    }
+}
+
+/// Strips the key and payload length from an encoded packet.
+fn payload(encoded: &[u8], keylen: u8) -> Vec<u8> {
+    let exp = encoded.get(keylen as usize).copied().unwrap_or(0) as usize;
+    
+    encoded.get((keylen as usize + 1 + exp)..).unwrap_or_default().to_vec()
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Comment, InputChunk, PacketKind};
+    use crate::spec::TasdFile;
+    use crate::stats::entropy;
+    
+    #[test]
+    fn stats() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert_eq!(entropy(&[0, 1, 2, 3]), 2.0);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 255] }.into());
+        file.packets.push(Comment { comment: "abcd".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 255] }.into());
+        
+        let stats = file.packet_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].kind, PacketKind::InputChunk);
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(stats[0].payload_bytes, 512);
+        assert_eq!(stats[0].duplicates, 1);
+        assert!(stats[0].compressibility() > 0.9);
+        assert_eq!(stats[1].payload_bytes, 4);
+        assert_eq!(stats[1].entropy, 2.0);
+        assert_eq!(stats[1].estimated_compressed_bytes(), 1);
+    }
+}
\ No newline at end of file
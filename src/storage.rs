@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+#[cfg(feature = "upload")]
+use std::io::Read;
+#[cfg(feature = "upload")]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Backing store used to load and save [TasdFile][crate::spec::TasdFile]s.
+/// 
+/// [FsStorage] is used by default, [MemoryStorage] keeps files in memory, and with the `upload` feature,
+/// [ObjectStorage] keeps them in an object store over HTTP. Implementations for other backends (e.g. browser storage
+/// when targeting WASM) only need to read and write whole files by path.
+pub trait TasdStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, std::io::Error>;
+    
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), std::io::Error>;
+}
+
+/// Storage backed by the real filesystem.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct FsStorage;
+impl TasdStorage for FsStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, std::io::Error> {
+        std::fs::read(path)
+    }
+    
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+        std::fs::write(path, data)
+    }
+}
+
+/// Storage which keeps every file in memory. Useful for tests and sandboxed environments.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: RwLock<HashMap<PathBuf, Vec<u8>>>,
+}
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.read().unwrap().contains_key(path)
+    }
+    
+    pub fn remove(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.write().unwrap().remove(path)
+    }
+    
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.read().unwrap().keys().cloned().collect()
+    }
+}
+impl TasdStorage for MemoryStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, std::io::Error> {
+        self.files.read().unwrap()
+            .get(path)
+            .cloned()
+            .ok_or(ErrorKind::NotFound.into())
+    }
+    
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+        self.files.write().unwrap().insert(path.to_path_buf(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// Storage backed by an object store reached over HTTP, such as an S3, GCS, or MinIO bucket.
+/// 
+/// Each path is an object under the base URL, e.g. `runs/movie.tasd` is `{base_url}/runs/movie.tasd`. Files are read
+/// with `GET` and written with `PUT`, along with [`Self::headers`] (e.g. for authentication). A `404` response is
+/// reported as [NotFound][ErrorKind::NotFound], and paths which aren't relative or contain `..` are rejected as
+/// [InvalidInput][ErrorKind::InvalidInput].
+#[cfg(feature = "upload")]
+#[derive(Debug, Clone)]
+pub struct ObjectStorage {
+    agent: ureq::Agent,
+    base_url: String,
+    /// Additional headers sent with every request.
+    pub headers: Vec<(String, String)>,
+}
+#[cfg(feature = "upload")]
+impl ObjectStorage {
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            headers: vec![],
+        }
+    }
+    
+    /// URL of the object storing `path`, with each path component percent-encoded.
+    pub fn object_url(&self, path: &Path) -> Result<String, std::io::Error> {
+        let mut url = self.base_url.clone();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => {
+                    let name = name.to_str().ok_or(ErrorKind::InvalidInput)?;
+                    url.push('/');
+                    for byte in name.bytes() {
+                        match byte {
+                            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => url.push(byte as char),
+                            byte => url.push_str(&format!("%{byte:02X}")),
+                        }
+                    }
+                },
+                Component::CurDir => (),
+                _ => return Err(ErrorKind::InvalidInput.into()),
+            }
+        }
+        
+        Ok(url)
+    }
+    
+    fn request(&self, method: &str, path: &Path) -> Result<ureq::Request, std::io::Error> {
+        let mut request = self.agent.request(method, &self.object_url(path)?);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        
+        Ok(request)
+    }
+}
+#[cfg(feature = "upload")]
+impl TasdStorage for ObjectStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, std::io::Error> {
+        let response = self.request("GET", path)?.call().map_err(http_error)?;
+        let mut data = vec![];
+        response.into_reader().read_to_end(&mut data)?;
+        
+        Ok(data)
+    }
+    
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+        self.request("PUT", path)?.send_bytes(data).map_err(http_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "upload")]
+fn http_error(err: ureq::Error) -> std::io::Error {
+    match err {
+        ureq::Error::Status(404, _) => ErrorKind::NotFound.into(),
+        ureq::Error::Status(code, _) => std::io::Error::other(format!("object store responded with status {code}")),
+        ureq::Error::Transport(transport) => std::io::Error::other(transport),
+    }
+}
+
+impl<T: TasdStorage + ?Sized> TasdStorage for &T {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, std::io::Error> {
+        (**self).read(path)
+    }
+    
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), std::io::Error> {
+        (**self).write(path, data)
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::spec::packets::GameTitle;
+    use crate::spec::{TasdError, TasdFile};
+    use crate::storage::MemoryStorage;
+    
+    #[test]
+    fn memory() {
+        let storage = MemoryStorage::new();
+        let mut file = TasdFile::new();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        
+        assert!(matches!(file.save_with(&storage), Err(TasdError::MissingPath)));
+        file.path = Some("runs/movie.tasd".into());
+        file.save_with(&storage).unwrap();
+        assert!(storage.contains(Path::new("runs/movie.tasd")));
+        
+        let parsed = TasdFile::parse_file_with(&storage, "runs/movie.tasd").unwrap();
+        assert_eq!(parsed, file);
+        
+        assert!(matches!(TasdFile::parse_file_with(&storage, "missing.tasd"), Err(TasdError::Io(_))));
+    }
+    
+    /// Minimal object store which keeps `PUT` objects in memory, and serves them to `GET` requests until `requests`
+    /// have been handled.
+    #[cfg(feature = "upload")]
+    fn serve(listener: std::net::TcpListener, requests: usize) -> Vec<String> {
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader, Read, Write};
+        
+        let mut objects = HashMap::new();
+        let mut paths = vec![];
+        for stream in listener.incoming().take(requests) {
+            let mut stream = BufReader::new(stream.unwrap());
+            let mut request = String::new();
+            stream.read_line(&mut request).unwrap();
+            let mut parts = request.split_whitespace();
+            let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+            
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                match line.trim().split_once(':') {
+                    Some((name, value)) if name.eq_ignore_ascii_case("content-length") => length = value.trim().parse().unwrap(),
+                    Some(_) => (),
+                    None => break,
+                }
+            }
+            let mut body = vec![0u8; length];
+            stream.read_exact(&mut body).unwrap();
+            
+            let mut stream = stream.into_inner();
+            match (method.as_str(), objects.get(&path)) {
+                ("PUT", _) => {
+                    objects.insert(path.clone(), body);
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+                },
+                ("GET", Some(object)) => {
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", object.len()).unwrap();
+                    stream.write_all(object).unwrap();
+                },
+                _ => write!(stream, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap(),
+            }
+            paths.push(path);
+        }
+        
+        paths
+    }
+    
+    #[cfg(feature = "upload")]
+    #[test]
+    fn object() {
+        use std::io::ErrorKind;
+        use crate::storage::{ObjectStorage, TasdStorage};
+        
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let storage = ObjectStorage::new(format!("http://{}/bucket/", listener.local_addr().unwrap()));
+        let server = std::thread::spawn(move || serve(listener, 3));
+        
+        let mut file = TasdFile::new();
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.path = Some("runs/my movie.tasd".into());
+        file.save_with(&storage).unwrap();
+        assert_eq!(TasdFile::parse_file_with(&storage, "runs/my movie.tasd").unwrap(), file);
+        assert_eq!(storage.read(Path::new("missing.tasd")).unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(server.join().unwrap(), ["/bucket/runs/my%20movie.tasd", "/bucket/runs/my%20movie.tasd", "/bucket/missing.tasd"]);
+        
+        assert_eq!(storage.read(Path::new("../movie.tasd")).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(storage.read(Path::new("/movie.tasd")).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,390 @@
+//! In-place editing of TASD files on disk.
+//!
+//! Changing one metadata packet normally means parsing and re-encoding the entire file, which is slow for dumps that
+//! are gigabytes of input data. [TasdSurgeon] instead overwrites the packet where it is, filling any space left over
+//! with PADDING, and only moves the rest of the file when the new encoding doesn't fit.
+//!
+//! Moving the rest of the file keeps a [TABLE_OF_CONTENTS][crate::spec::toc] up to date, but would break
+//! [block alignment][crate::spec::align], so files with PADDING after the edited packet are only edited in place.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::spec::packets::{Encode, KEY_PACKET_CHECKSUM, KEY_PADDING, KeyedPacket, Packet, PacketChecksum, PacketError, PacketKind, TableOfContents, unpadded_key};
+use crate::spec::stream::StreamParser;
+use crate::spec::writer::padding_packet;
+use crate::spec::{eof_as, TasdError};
+use crate::util::crc32;
+
+const SHIFT_BUFFER_SIZE: u64 = 1 << 16;
+
+/// Location of an encoded packet within a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketSpan {
+    /// Key of the packet, with any padding removed.
+    pub key: Vec<u8>,
+    /// Offset of the start of the packet's key from the start of the file.
+    pub offset: u64,
+    /// Encoded size of the packet, including its key and payload length.
+    pub len: u64,
+}
+impl PacketSpan {
+    pub fn kind(&self) -> PacketKind {
+        PacketKind::from_key(&self.key)
+    }
+}
+
+/// How [`TasdSurgeon::replace`] applied an edit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Surgery {
+    /// The new packet was written over the old one (and any PADDING directly after it), followed by `padding` bytes of
+    /// new PADDING. Nothing else in the file was touched.
+    InPlace {
+        padding: u64,
+    },
+    /// The new packet didn't fit, so everything after it was moved by `shift` bytes. PADDING directly after the old
+    /// packet is removed.
+    Rewritten {
+        shift: i64,
+    },
+}
+
+/// Modifies individual packets of a TASD file on disk, without rewriting the whole file when possible.
+/// 
+/// Opening a file only reads the key and length of each packet, skipping over payloads.
+/// 
+/// ```no_run
+/// # use tasd::surgery::TasdSurgeon;
+/// use tasd::spec::packets::GameTitle;
+/// 
+/// let mut surgeon = TasdSurgeon::open("dump.tasd").unwrap();
+/// if let Some(index) = surgeon.position::<GameTitle>() {
+///     surgeon.replace(index, GameTitle { title: "Super Mario Bros.".into() }).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TasdSurgeon {
+    file: File,
+    keylen: u8,
+    spans: Vec<PacketSpan>,
+}
+impl TasdSurgeon {
+    /// Opens the file at `path` for reading and writing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, TasdError> {
+        Self::new(OpenOptions::new().read(true).write(true).open(path)?)
+    }
+    
+    /// Reads the header and locates every packet in `file`, which must be open for reading and writing.
+    pub fn new(mut file: File) -> Result<Self, TasdError> {
+        file.seek(SeekFrom::Start(0))?;
+        let keylen = StreamParser::new(&mut file)?.keylen();
+        let end = file.seek(SeekFrom::End(0))?;
+        
+        let mut spans = vec![];
+        let mut offset = file.seek(SeekFrom::Start(7))?;
+        while offset < end {
+            let mut key = vec![0u8; keylen as usize];
+            let mut exp = [0u8];
+            file.read_exact(&mut key).map_err(|err| eof_as(err, PacketError::MissingKey))?;
+            file.read_exact(&mut exp).map_err(|err| eof_as(err, PacketError::MissingPayloadLength))?;
+            if exp[0] > 8 {
+                return Err(PacketError::UnsupportedExponent(exp[0]).into());
+            }
+            let mut plen = [0u8; 8];
+            file.read_exact(&mut plen[(8 - exp[0] as usize)..]).map_err(|err| eof_as(err, PacketError::MissingPayloadLength))?;
+            
+            let len = u64::from_be_bytes(plen).checked_add(keylen as u64 + 1 + exp[0] as u64);
+            let Some(len) = len.filter(|len| end - offset >= *len) else {
+                return Err(PacketError::MissingPayload.into());
+            };
+            spans.push(PacketSpan { key: unpadded_key(&key).to_vec(), offset, len });
+            offset = file.seek(SeekFrom::Start(offset + len))?;
+        }
+        
+        Ok(Self {
+            file,
+            keylen,
+            spans,
+        })
+    }
+    
+    pub fn keylen(&self) -> u8 {
+        self.keylen
+    }
+    
+    /// Every packet in the file, in order. Unlike [`TasdFile::parse_file`][crate::spec::TasdFile::parse_file], this
+    /// includes PADDING.
+    pub fn packets(&self) -> &[PacketSpan] {
+        &self.spans
+    }
+    
+    /// Index of the first packet of type `T`, if any.
+    pub fn position<T: KeyedPacket>(&self) -> Option<usize> {
+        self.spans.iter().position(|span| span.kind() == T::KIND)
+    }
+    
+    /// Reads and decodes the packet at `index`.
+    /// 
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn read(&mut self, index: usize) -> Result<Packet, TasdError> {
+        let span = &self.spans[index];
+        let mut data = vec![0u8; span.len as usize];
+        self.file.seek(SeekFrom::Start(span.offset))?;
+        self.file.read_exact(&mut data)?;
+        
+        Ok(Packet::decode_single(&data, self.keylen)?.0)
+    }
+    
+    /// Replaces the packet at `index` with `packet`.
+    /// 
+    /// If the new encoding fits in the space of the old packet plus any PADDING directly after it, it's written in
+    /// place and the remainder is filled with new PADDING. Otherwise, the rest of the file is moved to make room.
+    /// 
+    /// Moving the rest of the file isn't crash-safe: if the process is interrupted part way through, the file is left
+    /// corrupted. Copy the file first, or edit it with [`TasdFile`][crate::spec::TasdFile] and write it out in full,
+    /// if that matters.
+    /// 
+    /// A PACKET_CHECKSUM directly after the old packet is rewritten to match the new one. When the rest of the file is
+    /// moved, the offsets in a TABLE_OF_CONTENTS are updated to match (unless it's the packet being replaced).
+    /// 
+    /// Returns [`TasdError::AlignmentPadding`], without changing the file, if the rest of the file would need to be moved
+    /// but contains PADDING, as it may be aligning packets (e.g. from
+    /// [`TasdFile::encode_block_aligned`][crate::spec::TasdFile::encode_block_aligned]) which moving would misalign.
+    /// 
+    /// # Panics
+    /// If `index` is out of bounds.
+    pub fn replace<P: Into<Packet>>(&mut self, index: usize, packet: P) -> Result<Surgery, TasdError> {
+        let packet = packet.into();
+        let offset = self.spans[index].offset;
+        let mut encoded = packet.encode(self.keylen);
+        let mut new_spans = vec![PacketSpan { key: unpadded_key(&packet.key()).to_vec(), offset, len: encoded.len() as u64 }];
+        
+        let checksummed = packet.kind() != PacketKind::PacketChecksum
+            && self.spans.get(index + 1).is_some_and(|span| span.kind() == PacketKind::PacketChecksum);
+        if checksummed {
+            let checksum = PacketChecksum { crc32: crc32(&encoded) }.encode(self.keylen);
+            new_spans.push(PacketSpan { key: KEY_PACKET_CHECKSUM.to_vec(), offset: offset + encoded.len() as u64, len: checksum.len() as u64 });
+            encoded.extend_from_slice(&checksum);
+        }
+        let last = index + checksummed as usize;
+        
+        let following = self.spans[(last + 1)..].iter().take_while(|span| span.key == KEY_PADDING).count();
+        let available: u64 = self.spans[index..=(last + following)].iter().map(|span| span.len).sum();
+        let padding = match available.checked_sub(encoded.len() as u64) {
+            Some(0) => Some(vec![]),
+            Some(rest) => padding_packet(rest, self.keylen),
+            None => None,
+        };
+        
+        if let Some(padding) = padding {
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(&encoded)?;
+            self.file.write_all(&padding)?;
+            
+            if !padding.is_empty() {
+                new_spans.push(PacketSpan { key: KEY_PADDING.to_vec(), offset: offset + encoded.len() as u64, len: padding.len() as u64 });
+            }
+            self.spans.splice(index..=(last + following), new_spans);
+            
+            return Ok(Surgery::InPlace { padding: padding.len() as u64 });
+        }
+        
+        if self.spans[(last + following + 1)..].iter().any(|span| span.key == KEY_PADDING) {
+            return Err(TasdError::AlignmentPadding);
+        }
+        
+        // the PADDING isn't needed anymore, so it's replaced too
+        let shift = encoded.len() as i64 - available as i64;
+        let tail = offset + available;
+        self.shift_tail(tail, shift)?;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&encoded)?;
+        
+        let replaced = new_spans.len();
+        self.spans.splice(index..=(last + following), new_spans);
+        for span in &mut self.spans[(index + replaced)..] {
+            span.offset = span.offset.wrapping_add_signed(shift);
+        }
+        if packet.kind() != PacketKind::TableOfContents {
+            self.shift_toc(tail, shift)?;
+        }
+        
+        Ok(Surgery::Rewritten { shift })
+    }
+    
+    /// Moves the offsets in the TABLE_OF_CONTENTS (if any) which were at or after `from` by `shift` bytes, rewriting it
+    /// in place along with a PACKET_CHECKSUM directly after it.
+    fn shift_toc(&mut self, from: u64, shift: i64) -> Result<(), TasdError> {
+        let Some(index) = self.position::<TableOfContents>() else { return Ok(()) };
+        let Packet::TableOfContents(mut toc) = self.read(index)? else { return Ok(()) };
+        for entry in &mut toc.entries {
+            if entry.offset >= from {
+                entry.offset = entry.offset.wrapping_add_signed(shift);
+            }
+        }
+        
+        // the entries are a fixed size, so the table is the same length as before
+        let mut encoded = toc.encode(self.keylen);
+        if self.spans.get(index + 1).is_some_and(|span| span.kind() == PacketKind::PacketChecksum) {
+            encoded.extend_from_slice(&PacketChecksum { crc32: crc32(&encoded) }.encode(self.keylen));
+        }
+        self.file.seek(SeekFrom::Start(self.spans[index].offset))?;
+        self.file.write_all(&encoded)?;
+        
+        Ok(())
+    }
+    
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+    
+    /// Moves everything from `from` to the end of the file by `shift` bytes, in chunks so the file is never read into
+    /// memory all at once.
+    /// 
+    /// The data is moved in place, so this isn't crash-safe: if it's interrupted, part of the tail has been moved and
+    /// the chunks it overwrote are lost.
+    fn shift_tail(&mut self, from: u64, shift: i64) -> Result<(), TasdError> {
+        let end = self.file.seek(SeekFrom::End(0))?;
+        let mut buf = vec![0u8; SHIFT_BUFFER_SIZE as usize];
+        let mut move_chunk = |file: &mut File, pos: u64, len: u64| -> std::io::Result<()> {
+            let buf = &mut buf[..(len as usize)];
+            file.seek(SeekFrom::Start(pos))?;
+            file.read_exact(buf)?;
+            file.seek(SeekFrom::Start(pos.wrapping_add_signed(shift)))?;
+            file.write_all(buf)
+        };
+        
+        if shift > 0 {
+            // growing, so start from the end to avoid overwriting data which hasn't been moved yet
+            let mut pos = end;
+            while pos > from {
+                let len = (pos - from).min(SHIFT_BUFFER_SIZE);
+                pos -= len;
+                move_chunk(&mut self.file, pos, len)?;
+            }
+        } else if shift < 0 {
+            let mut pos = from;
+            while pos < end {
+                let len = (end - pos).min(SHIFT_BUFFER_SIZE);
+                move_chunk(&mut self.file, pos, len)?;
+                pos += len;
+            }
+            self.file.set_len(end.wrapping_add_signed(shift))?;
+        }
+        
+        Ok(())
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use crate::spec::align::SD_BLOCK_SIZE;
+    use crate::spec::packets::{Comment, GameTitle, InputChunk, KEY_PADDING, Packet, PacketError};
+    use crate::spec::toc::{parser_at, read_toc};
+    use crate::spec::{TasdError, TasdFile};
+    use crate::surgery::{Surgery, TasdSurgeon};
+    
+    #[test]
+    fn replace() {
+        let path = std::env::temp_dir().join(format!("tasd-surgery-{}.tasd", std::process::id()));
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: (0..=255).collect() }.into());
+        std::fs::write(&path, file.encode()).unwrap();
+        
+        let mut surgeon = TasdSurgeon::open(&path).unwrap();
+        assert_eq!(surgeon.packets().len(), 3);
+        assert_eq!(surgeon.read(2).unwrap(), file.packets[2]);
+        
+        // shorter, so the leftover space becomes padding
+        let index = surgeon.position::<GameTitle>().unwrap();
+        assert_eq!(surgeon.replace(index, GameTitle { title: "SMB".into() }).unwrap(), Surgery::InPlace { padding: 14 });
+        assert_eq!(surgeon.packets()[1].key, KEY_PADDING);
+        
+        // longer, but the padding from before makes room
+        assert_eq!(surgeon.replace(index, GameTitle { title: "Super Mario".into() }).unwrap(), Surgery::InPlace { padding: 6 });
+        
+        // too long for the available space
+        assert_eq!(surgeon.replace(2, Comment { comment: "a longer comment".into() }).unwrap(), Surgery::Rewritten { shift: 9 });
+        assert_eq!(surgeon.replace(2, Comment { comment: "c".into() }).unwrap(), Surgery::InPlace { padding: 15 });
+        // fits, but only leaves 1 byte which is too small for padding
+        assert_eq!(surgeon.replace(2, Comment { comment: "fifteen chars!!".into() }).unwrap(), Surgery::Rewritten { shift: -1 });
+        
+        let spans = surgeon.packets().to_vec();
+        drop(surgeon);
+        assert_eq!(TasdSurgeon::open(&path).unwrap().packets(), spans);
+        
+        // a payload length which overflows the packet's size
+        let mut data = file.encode();
+        data.extend_from_slice(&[0x00, 0xCC, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let overflowing = path.with_extension("overflow");
+        std::fs::write(&overflowing, data).unwrap();
+        assert!(matches!(TasdSurgeon::open(&overflowing), Err(TasdError::Packet(PacketError::MissingPayload))));
+        std::fs::remove_file(&overflowing).unwrap();
+        
+        file.packets[0] = GameTitle { title: "Super Mario".into() }.into();
+        file.packets[1] = Comment { comment: "fifteen chars!!".into() }.into();
+        assert_eq!(TasdFile::parse_file(&path).unwrap().packets, file.packets);
+        
+        std::fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn replace_checksummed() {
+        let path = std::env::temp_dir().join(format!("tasd-surgery-checksum-{}.tasd", std::process::id()));
+        let mut file = TasdFile::default();
+        file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 4] }.into());
+        file.add_checksums();
+        std::fs::write(&path, file.encode()).unwrap();
+        
+        let mut surgeon = TasdSurgeon::open(&path).unwrap();
+        assert!(matches!(surgeon.replace(0, GameTitle { title: "SMB".into() }).unwrap(), Surgery::InPlace { .. }));
+        assert!(matches!(surgeon.replace(0, GameTitle { title: "Super Mario Bros. 3".into() }).unwrap(), Surgery::Rewritten { .. }));
+        drop(surgeon);
+        
+        let parsed = TasdFile::parse_file(&path).unwrap();
+        assert_eq!(parsed.packets[0], GameTitle { title: "Super Mario Bros. 3".into() }.into());
+        assert_eq!(parsed.packets.len(), 4);
+        assert!(parsed.verify_checksums().is_empty());
+        
+        std::fs::remove_file(&path).unwrap();
+    }
+    
+    #[test]
+    fn toc_and_alignment() {
+        let path = std::env::temp_dir().join(format!("tasd-surgery-toc-{}.tasd", std::process::id()));
+        let mut file = TasdFile::default();
+        file.packets.push(Comment { comment: "comment".into() }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0x01; 100] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0x02; 100] }.into());
+        std::fs::write(&path, file.encode_with_toc()).unwrap();
+        
+        // the table is updated to point at where the input chunks were moved to
+        let mut surgeon = TasdSurgeon::open(&path).unwrap();
+        assert_eq!(surgeon.replace(1, Comment { comment: "a longer comment".into() }).unwrap(), Surgery::Rewritten { shift: 9 });
+        drop(surgeon);
+        let toc = read_toc(File::open(&path).unwrap()).unwrap().unwrap();
+        for port in [1, 2] {
+            let mut parser = parser_at(File::open(&path).unwrap(), toc.first_input_chunk(port).unwrap()).unwrap();
+            assert!(matches!(parser.next_packet().unwrap(), Some(Packet::InputChunk(chunk)) if chunk.port == port));
+        }
+        
+        // moving the input chunks would misalign them, so the file is left alone
+        let data = file.encode_block_aligned(SD_BLOCK_SIZE);
+        std::fs::write(&path, &data).unwrap();
+        let mut surgeon = TasdSurgeon::open(&path).unwrap();
+        assert!(matches!(surgeon.replace(0, Comment { comment: "c".repeat(1000) }), Err(TasdError::AlignmentPadding)));
+        drop(surgeon);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        
+        std::fs::remove_file(&path).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,92 @@
+//! Hints for encoders to keep audio and video in sync, from the AV_SYNC extension packet.
+//!
+//! Encodes usually cut the frames before the game shows a picture (a blank screen at power-on, or a reset sequence),
+//! so anything timed against the movie, like [subtitles][crate::export::subtitles] and
+//! [input timelines][crate::export::timeline], has to be shifted by the same amount to line up with the encode.
+
+use std::time::Duration;
+use crate::spec::packets::AvSync;
+use crate::spec::TasdFile;
+use crate::timing::{DEFAULT_FRAME_RATE, frame_to_duration};
+
+/// Offsets for lining up an encode with the movie, from [`TasdFile::sync_offsets`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct SyncOffsets {
+    /// First frame which shows the game's picture, after BLANK_FRAMES is applied.
+    pub first_visible_frame: u64,
+    /// Time from the start of the movie to the first visible frame, which is cut from the video.
+    pub video: Duration,
+    /// How far the audio lags behind the video, in microseconds. Negative if it runs ahead.
+    pub audio_micros: i64,
+}
+impl SyncOffsets {
+    /// Time at which the audio should start, relative to the first visible frame of the encode. Audio which runs ahead
+    /// of the video starts at zero, and is trimmed by [`Self::audio_trim`] instead.
+    pub fn audio_delay(&self) -> Duration {
+        Duration::from_micros(self.audio_micros.max(0) as u64)
+    }
+    
+    /// Length of audio to cut from the start, in addition to [`Self::video`], for audio which runs ahead of the video.
+    pub fn audio_trim(&self) -> Duration {
+        Duration::from_micros(self.audio_micros.min(0).unsigned_abs())
+    }
+}
+
+impl TasdFile {
+    /// First frame which shows the game's picture, after BLANK_FRAMES is applied, from the AV_SYNC packet. Returns `0`
+    /// if there's none.
+    pub fn first_visible_frame(&self) -> u64 {
+        self.first::<AvSync>().map(|sync| sync.first_visible_frame as u64).unwrap_or(0)
+    }
+    
+    /// Offsets from the AV_SYNC packet, with frames converted to time using the file's
+    /// [frame rate][TasdFile::frame_rate] (or [DEFAULT_FRAME_RATE] if it isn't known).
+    /// 
+    /// Without an AV_SYNC packet, every offset is zero.
+    pub fn sync_offsets(&self) -> SyncOffsets {
+        let Some(sync) = self.first::<AvSync>() else { return SyncOffsets::default() };
+        let fps = self.frame_rate().unwrap_or(DEFAULT_FRAME_RATE);
+        
+        SyncOffsets {
+            first_visible_frame: sync.first_visible_frame as u64,
+            video: frame_to_duration(sync.first_visible_frame as u64, fps),
+            audio_micros: sync.audio_offset as i64,
+        }
+    }
+    
+    /// Sets the AV_SYNC packet, replacing any existing one, or adding it to the end of the file.
+    pub fn set_av_sync(&mut self, first_visible_frame: u32, audio_offset: i32) {
+        self.set(AvSync { first_visible_frame, audio_offset });
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::spec::packets::{AvSync, ConsoleType};
+    use crate::spec::TasdFile;
+    use crate::sync::SyncOffsets;
+    
+    #[test]
+    fn sync_offsets() {
+        let mut file = TasdFile::default();
+        assert_eq!(file.first_visible_frame(), 0);
+        assert_eq!(file.sync_offsets(), SyncOffsets::default());
+        
+        file.packets.push(ConsoleType { kind: 0x03, custom: None }.into());
+        file.packets.push(AvSync { first_visible_frame: 1, audio_offset: 0 }.into());
+        file.packets.push(AvSync { first_visible_frame: 2, audio_offset: 0 }.into());
+        file.set_av_sync(90, -2_500);
+        assert_eq!(file.all::<AvSync>().count(), 1);
+        assert_eq!(file.first_visible_frame(), 90);
+        
+        let offsets = file.sync_offsets();
+        assert_eq!(offsets.video, Duration::from_millis(1500));
+        assert_eq!(offsets.audio_delay(), Duration::ZERO);
+        assert_eq!(offsets.audio_trim(), Duration::from_micros(2_500));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+//! Applying the same publication metadata to many files at once, e.g. for dumpers producing a batch of files for one
+//! publication.
+//!
+//! With the `serde` feature, a [MetadataTemplate] can be loaded from JSON or TOML:
+//!
+//! ```toml
+//! license = "CC-BY-4.0"
+//! category = "any%"
+//! emulator_name = "BizHawk"
+//! source_links = ["https://tasvideos.org/1234M"]
+//!
+//! [[attributions]]
+//! kind = 1
+//! name = "author"
+//! ```
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use crate::spec::packets::{Attribution, Category, EmulatorCore, EmulatorName, EmulatorVersion, MovieLicense, SourceLink};
+use crate::spec::TasdFile;
+
+/// Attribution added by a [MetadataTemplate]. `kind` uses the same values as ATTRIBUTION.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TemplateAttribution {
+    pub kind: u8,
+    pub name: String,
+}
+
+/// Metadata set by [`TasdFile::apply_template`]. Fields left empty aren't changed.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MetadataTemplate {
+    /// Added to the file's ATTRIBUTIONs, unless it already has an identical one.
+    pub attributions: Vec<TemplateAttribution>,
+    /// Replaces every MOVIE_LICENSE.
+    pub license: Option<String>,
+    /// Added to the file's SOURCE_LINKs, unless it already has the same link.
+    pub source_links: Vec<String>,
+    /// Replaces CATEGORY.
+    pub category: Option<String>,
+    /// Replaces EMULATOR_NAME.
+    pub emulator_name: Option<String>,
+    /// Replaces EMULATOR_VERSION.
+    pub emulator_version: Option<String>,
+    /// Replaces EMULATOR_CORE.
+    pub emulator_core: Option<String>,
+}
+
+/// Error loading a [MetadataTemplate].
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum TemplateError {
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+}
+#[cfg(feature = "serde")]
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Json(err) => write!(f, "invalid JSON template: {err}"),
+            TemplateError::Toml(err) => write!(f, "invalid TOML template: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl MetadataTemplate {
+    pub fn from_json(json: &str) -> Result<Self, TemplateError> {
+        serde_json::from_str(json).map_err(TemplateError::Json)
+    }
+    
+    pub fn from_toml(toml: &str) -> Result<Self, TemplateError> {
+        toml::from_str(toml).map_err(TemplateError::Toml)
+    }
+}
+
+impl TasdFile {
+    /// Sets the metadata in `template`. Replaced packets keep the position of the first packet of their kind, and new
+    /// packets are added to the end of the file.
+    pub fn apply_template(&mut self, template: &MetadataTemplate) {
+        if let Some(license) = &template.license {
+            self.set(MovieLicense { license: license.clone() });
+        }
+        if let Some(category) = &template.category {
+            self.set(Category { category: category.clone() });
+        }
+        if let Some(name) = &template.emulator_name {
+            self.set(EmulatorName { name: name.clone() });
+        }
+        if let Some(version) = &template.emulator_version {
+            self.set(EmulatorVersion { version: version.clone() });
+        }
+        if let Some(core) = &template.emulator_core {
+            self.set(EmulatorCore { core: core.clone() });
+        }
+        
+        for attribution in &template.attributions {
+            let attribution = Attribution { kind: attribution.kind, name: attribution.name.clone() };
+            if !self.all::<Attribution>().any(|existing| *existing == attribution) {
+                self.packets.push(attribution.into());
+            }
+        }
+        for link in &template.source_links {
+            if !self.all::<SourceLink>().any(|existing| existing.link == *link) {
+                self.packets.push(SourceLink { link: link.clone() }.into());
+            }
+        }
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{Attribution, Category, GameTitle, MovieLicense, Packet, SourceLink};
+    use crate::spec::TasdFile;
+    use crate::template::{MetadataTemplate, TemplateAttribution};
+    
+    #[test]
+    fn apply_template() {
+        let template = MetadataTemplate {
+            attributions: vec![TemplateAttribution { kind: 0x01, name: "author".into() }],
+            license: Some("CC0-1.0".into()),
+            source_links: vec!["https://tasvideos.org/1234M".into()],
+            category: Some("any%".into()),
+            ..Default::default()
+        };
+        
+        let mut file = TasdFile::default();
+        file.packets.push(MovieLicense { license: "MIT".into() }.into());
+        file.packets.push(GameTitle { title: "title".into() }.into());
+        file.packets.push(MovieLicense { license: "CC-BY-4.0".into() }.into());
+        file.packets.push(Attribution { kind: 0x01, name: "author".into() }.into());
+        file.apply_template(&template);
+        file.apply_template(&template);
+        
+        assert_eq!(file.packets, vec![
+            Packet::from(MovieLicense { license: "CC0-1.0".into() }),
+            GameTitle { title: "title".into() }.into(),
+            Attribution { kind: 0x01, name: "author".into() }.into(),
+            Category { category: "any%".into() }.into(),
+            SourceLink { link: "https://tasvideos.org/1234M".into() }.into(),
+        ]);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn load_template() {
+        let toml = "license = \"CC0-1.0\"\nemulator_name = \"BizHawk\"\n\n[[attributions]]\nkind = 1\nname = \"author\"\n";
+        let template = MetadataTemplate::from_toml(toml).unwrap();
+        assert_eq!(template.license.as_deref(), Some("CC0-1.0"));
+        assert_eq!(template.attributions, [TemplateAttribution { kind: 0x01, name: "author".into() }]);
+        
+        let json = r#"{ "license": "CC0-1.0", "emulator_name": "BizHawk", "attributions": [{ "kind": 1, "name": "author" }] }"#;
+        assert_eq!(MetadataTemplate::from_json(json).unwrap(), template);
+        assert!(MetadataTemplate::from_json("{ \"license\": 1 }").is_err());
+    }
+}
\ No newline at end of file
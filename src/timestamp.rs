@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::spec::packets::{DumpCreated, DumpLastModified, Packet};
+use crate::spec::TasdFile;
+
+/// Largest timestamp treated as seconds since the Unix epoch (the start of the year 3000).
+/// 
+/// Millisecond timestamps passed this value in January 1971, so anything larger was almost certainly written in
+/// milliseconds by mistake.
+pub const MAX_SECONDS_EPOCH: i64 = 32_503_680_000;
+
+/// Current time in seconds since the Unix epoch, as stored in timestamp packets.
+pub fn now_epoch() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() as i64).unwrap_or(0)
+}
+
+/// Whether a timestamp is too far from the Unix epoch to be in seconds, and is most likely in milliseconds instead.
+pub fn is_millisecond_epoch(epoch: i64) -> bool {
+    epoch.unsigned_abs() > MAX_SECONDS_EPOCH as u64
+}
+
+/// Converts a millisecond timestamp to seconds, rounding towards negative infinity.
+pub fn millis_to_epoch(millis: i64) -> i64 {
+    millis.div_euclid(1000)
+}
+
+/// Timestamp of a TAS_LAST_MODIFIED, DUMP_CREATED, or DUMP_LAST_MODIFIED packet.
+pub(crate) fn epoch(packet: &Packet) -> Option<i64> {
+    match packet {
+        Packet::TasLastModified(packet) => Some(packet.epoch),
+        Packet::DumpCreated(packet) => Some(packet.epoch),
+        Packet::DumpLastModified(packet) => Some(packet.epoch),
+        _ => None,
+    }
+}
+
+pub(crate) fn epoch_mut(packet: &mut Packet) -> Option<&mut i64> {
+    match packet {
+        Packet::TasLastModified(packet) => Some(&mut packet.epoch),
+        Packet::DumpCreated(packet) => Some(&mut packet.epoch),
+        Packet::DumpLastModified(packet) => Some(&mut packet.epoch),
+        _ => None,
+    }
+}
+
+impl TasdFile {
+    /// Sets DUMP_CREATED to the current time, adding the packet to the end of the file if there isn't one.
+    pub fn set_dump_created_now(&mut self) {
+        let epoch = now_epoch();
+        match self.first_mut::<DumpCreated>() {
+            Some(created) => created.epoch = epoch,
+            None => self.packets.push(DumpCreated { epoch }.into()),
+        }
+    }
+    
+    /// Sets DUMP_LAST_MODIFIED to the current time, adding the packet to the end of the file if there isn't one.
+    pub fn set_dump_last_modified_now(&mut self) {
+        let epoch = now_epoch();
+        match self.first_mut::<DumpLastModified>() {
+            Some(modified) => modified.epoch = epoch,
+            None => self.packets.push(DumpLastModified { epoch }.into()),
+        }
+    }
+    
+    /// Converts every timestamp which [looks like milliseconds][is_millisecond_epoch] to seconds, for repairing files
+    /// written by tools with that mistake.
+    /// 
+    /// Returns the number of packets changed.
+    pub fn fix_millisecond_timestamps(&mut self) -> usize {
+        self.packets.iter_mut()
+            .filter_map(epoch_mut)
+            .filter(|epoch| is_millisecond_epoch(**epoch))
+            .map(|epoch| *epoch = millis_to_epoch(*epoch))
+            .count()
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{DumpCreated, DumpLastModified, TasLastModified};
+    use crate::spec::TasdFile;
+    use crate::timestamp::{is_millisecond_epoch, millis_to_epoch, now_epoch};
+    
+    #[test]
+    fn timestamps() {
+        assert!(!is_millisecond_epoch(1_700_000_000));
+        assert!(is_millisecond_epoch(1_700_000_000_123));
+        assert!(is_millisecond_epoch(-1_700_000_000_123));
+        assert_eq!(millis_to_epoch(1_700_000_000_999), 1_700_000_000);
+        assert_eq!(millis_to_epoch(-1), -1);
+        
+        let mut file = TasdFile::default();
+        file.packets.push(TasLastModified { epoch: 1_700_000_000_500 }.into());
+        file.packets.push(DumpLastModified { epoch: 1_700_000_000 }.into());
+        assert_eq!(file.fix_millisecond_timestamps(), 1);
+        assert_eq!(file.first::<TasLastModified>().unwrap().epoch, 1_700_000_000);
+        assert_eq!(file.fix_millisecond_timestamps(), 0);
+        
+        let before = now_epoch();
+        file.set_dump_created_now();
+        file.set_dump_last_modified_now();
+        assert!(file.first::<DumpCreated>().unwrap().epoch >= before);
+        assert!(file.first::<DumpLastModified>().unwrap().epoch >= before);
+        assert_eq!(file.packets.len(), 3);
+    }
+}
\ No newline at end of file
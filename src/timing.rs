@@ -0,0 +1,197 @@
+use std::time::Duration;
+use crate::spec::packets::{ConsoleRegion, ConsoleType, Index, Packet, TotalFrames};
+use crate::spec::TasdFile;
+
+/// Frame rate used when a file doesn't declare a console with a known frame rate.
+pub const DEFAULT_FRAME_RATE: f64 = 60.0;
+
+/// Native frame rate, in frames per second, of the given CONSOLE_TYPE and CONSOLE_REGION.
+/// 
+/// Returns `None` for custom or unknown consoles and regions.
+pub fn frame_rate(console: u8, region: u8) -> Option<f64> {
+    Some(match (console, region) {
+        (0x01 | 0x02, 0x01) => 60.098813897440515,
+        (0x01 | 0x02, 0x02) => 50.00697796826829,
+        (0x03, 0x01) => 60.0,
+        (0x03, 0x02) => 50.0,
+        (0x04, 0x01) => 59.94,
+        (0x04, 0x02) => 50.0,
+        (0x05..=0x07, 0x01 | 0x02) => 59.727500569606,
+        (0x08, 0x01) => 59.922743404312,
+        (0x08, 0x02) => 49.701460119948,
+        (0x09, 0x01) => 59.922743404312,
+        (0x09, 0x02) => 49.860759671614,
+        _ => return None,
+    })
+}
+
+/// Clock rate, in Hz, which cycle count indices (index type `0x02`) of the given CONSOLE_TYPE and CONSOLE_REGION are
+/// counted in. This is the CPU clock, except for the SNES which uses its master clock.
+/// 
+/// Returns `None` for consoles where it isn't known, or doesn't depend on the region.
+pub fn clock_rate(console: u8, region: u8) -> Option<f64> {
+    Some(match (console, region) {
+        (0x01, 0x01) => 1_789_772.727_272_7,
+        (0x01, 0x02) => 1_662_607.031_25,
+        (0x02, 0x01) => 21_477_272.727_272,
+        (0x02, 0x02) => 21_281_370.0,
+        (0x08, 0x01) => 7_670_453.0,
+        (0x08, 0x02) => 7_600_489.0,
+        (0x09, 0x01) => 1_193_181.666_667,
+        (0x09, 0x02) => 1_182_298.0,
+        _ => return None,
+    })
+}
+
+/// Time elapsed at the start of `frame` (0-indexed), at the given frame rate.
+/// 
+/// ```
+/// use std::time::Duration;
+/// use tasd::timing::frame_to_duration;
+/// 
+/// assert_eq!(frame_to_duration(90, 60.0), Duration::from_millis(1500));
+/// ```
+pub fn frame_to_duration(frame: u64, fps: f64) -> Duration {
+    Duration::from_secs_f64(frame as f64 / fps)
+}
+
+/// Frame being displayed after `duration` has elapsed, at the given frame rate.
+pub fn duration_to_frame(duration: Duration, fps: f64) -> u64 {
+    (duration.as_secs_f64() * fps).floor() as u64
+}
+
+impl Index {
+    /// Time from the start of the movie, in seconds, on the given CONSOLE_TYPE and CONSOLE_REGION.
+    /// 
+    /// Returns `None` for INPUT_CHUNK indexes and unknown index types, which can't be converted on their own, and when
+    /// the frame or clock rate the index is counted in isn't known.
+    pub fn to_seconds(&self, console: u8, region: u8) -> Option<f64> {
+        Some(match *self {
+            Index::Frame(frame) => frame as f64 / frame_rate(console, region)?,
+            Index::CycleCount(cycles) => cycles as f64 / clock_rate(console, region)?,
+            Index::Milliseconds(ms) => ms as f64 / 1_000.0,
+            Index::TenMicroseconds(us) => us as f64 / 100_000.0,
+            Index::ChunkIndex(_) | Index::Other { .. } => return None,
+        })
+    }
+    
+    /// Frame being displayed at this index, on the given CONSOLE_TYPE and CONSOLE_REGION. Frame indexes are returned
+    /// as they are, even if the frame rate isn't known.
+    /// 
+    /// ```
+    /// use tasd::spec::packets::Index;
+    /// 
+    /// assert_eq!(Index::Milliseconds(1000).to_frame(0x03, 0x02), Some(50));
+    /// assert_eq!(Index::Frame(7).to_frame(0xFF, 0x01), Some(7));
+    /// ```
+    pub fn to_frame(&self, console: u8, region: u8) -> Option<u64> {
+        match *self {
+            Index::Frame(frame) => Some(frame),
+            _ => Some((self.to_seconds(console, region)? * frame_rate(console, region)?).floor() as u64),
+        }
+    }
+}
+
+impl TasdFile {
+    /// Frame rate of this file, based on its CONSOLE_TYPE and CONSOLE_REGION packets.
+    /// 
+    /// NTSC is assumed if there's no CONSOLE_REGION. Returns `None` if the console or region isn't known.
+    pub fn frame_rate(&self) -> Option<f64> {
+        let console = self.first::<ConsoleType>()?.kind;
+        let region = self.first::<ConsoleRegion>().map(|region| region.region).unwrap_or(0x01);
+        
+        frame_rate(console, region)
+    }
+}
+
+/// Evidence that a file's CONSOLE_REGION is wrong, from [`TasdFile::region_conflict`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RegionConflict {
+    /// Index into [`TasdFile::packets`] of the INPUT_MOMENT or TRANSITION which doesn't fit the declared region.
+    pub index: usize,
+    /// Declared CONSOLE_REGION (NTSC if there's no CONSOLE_REGION packet).
+    pub declared: u8,
+    /// CONSOLE_REGION whose timing does fit.
+    pub implied: u8,
+    /// Frame the packet falls on with the declared region's timing.
+    pub declared_frame: u64,
+    /// Frame the packet falls on with the implied region's timing.
+    pub implied_frame: u64,
+    /// Length of the movie in frames.
+    pub frames: u64,
+}
+
+impl TasdFile {
+    /// Checks whether the cycle count and real-time indices of INPUT_MOMENT and TRANSITION packets fit the declared
+    /// region's clock and frame rate.
+    /// 
+    /// Timing recorded on the other region's console runs past the end of the movie when converted to frames with the
+    /// wrong region, so a conflict is reported when the latest timed packet does that, but would fit with the other
+    /// region. Mislabeled regions are a common cause of desyncs on real hardware.
+    /// 
+    /// Returns `None` if there's no conflict, or not enough information to tell (no NTSC/PAL console with a known
+    /// frame rate, no movie length, or no timed packets).
+    pub fn region_conflict(&self) -> Option<RegionConflict> {
+        let console = self.first::<ConsoleType>()?.kind;
+        let declared = self.first::<ConsoleRegion>().map(|region| region.region).unwrap_or(0x01);
+        let implied = match declared {
+            0x01 => 0x02,
+            0x02 => 0x01,
+            _ => return None,
+        };
+        let frames = self.computed_total_frames().or_else(|| self.first::<TotalFrames>().map(|total| total.frames))? as u64;
+        
+        // frame indexes are the same in either region, so only timed indexes can tell them apart
+        let (index, declared_frame, implied_frame) = self.packets.iter().enumerate()
+            .filter_map(|(i, packet)| match packet {
+                Packet::InputMoment(moment) => Some((i, moment.index)),
+                Packet::Transition(transition) => Some((i, transition.index)),
+                _ => None,
+            })
+            .filter(|(_, index)| !matches!(index, Index::Frame(_)))
+            .filter_map(|(i, index)| Some((i, index.to_frame(console, declared)?, index.to_frame(console, implied)?)))
+            .max_by_key(|(_, declared_frame, _)| *declared_frame)?;
+        
+        (declared_frame > frames && implied_frame <= frames).then_some(RegionConflict {
+            index,
+            declared,
+            implied,
+            declared_frame,
+            implied_frame,
+            frames,
+        })
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{ConsoleRegion, ConsoleType, Index, InputChunk, InputMoment, PortController};
+    use crate::spec::TasdFile;
+    use crate::timing::{clock_rate, frame_rate};
+    
+    #[test]
+    fn region_conflict() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 100] }.into());
+        assert_eq!(file.region_conflict(), None);
+        
+        // 95 frames into a PAL movie
+        let cycles = 95.0 * clock_rate(0x01, 0x02).unwrap() / frame_rate(0x01, 0x02).unwrap();
+        file.packets.push(InputMoment { port: 1, index: Index::CycleCount(cycles.ceil() as u64), inputs: vec![0xFF] }.into());
+        file.packets.push(InputMoment { port: 1, index: Index::Milliseconds(1000), inputs: vec![0xFF] }.into());
+        let conflict = file.region_conflict().unwrap();
+        assert_eq!(conflict.index, 3);
+        assert_eq!((conflict.declared, conflict.implied), (0x01, 0x02));
+        assert_eq!((conflict.declared_frame, conflict.implied_frame, conflict.frames), (106, 95, 100));
+        assert_eq!(file.validate().len(), 1);
+        
+        file.packets.insert(1, ConsoleRegion { region: 0x02 }.into());
+        assert_eq!(file.region_conflict(), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,333 @@
+//! Helpers for uploading dumps to an HTTP endpoint, such as a community dump archive.
+//!
+//! Data is sent as a series of `PUT` requests to the configured endpoint, each containing one chunk and a
+//! `Content-Range` header (e.g. `bytes 0-4194303/*`). The total size is only included once it's known, which is always
+//! the case for the final chunk. Any 2xx response is treated as the chunk being accepted.
+//!
+//! If a chunk fails to send, the uploader issues a `HEAD` request to the endpoint, and expects the server to report
+//! how many bytes it has received in an `Upload-Offset` header. The upload then resumes from that offset. Only the
+//! current chunk is kept in memory, so the offset must be within it. Servers which don't report an offset have the
+//! rest of the chunk sent again.
+
+use std::io::{copy, Read, Write};
+use crate::spec::TasdFile;
+
+#[derive(Debug)]
+pub enum UploadError {
+    Io(std::io::Error),
+    /// The server rejected a request with a non-retryable status code.
+    Status(u16),
+    /// A chunk still failed after [`UploadConfig::max_retries`] attempts. Contains the last error encountered.
+    RetriesExhausted {
+        offset: u64,
+        error: String,
+    },
+    /// The server reported an `Upload-Offset` outside of the chunk being sent. Earlier chunks aren't kept, so the
+    /// upload can't be resumed from there.
+    UnexpectedOffset(u64),
+}
+impl From<std::io::Error> for UploadError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadConfig {
+    pub endpoint: String,
+    /// Maximum number of bytes sent per request.
+    pub chunk_size: usize,
+    /// Number of times a failed chunk is retried before giving up.
+    pub max_retries: u32,
+    /// Additional headers sent with every request (e.g. for authentication).
+    pub headers: Vec<(String, String)>,
+}
+impl UploadConfig {
+    pub fn new<S: Into<String>>(endpoint: S) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            chunk_size: 4 * 1024 * 1024,
+            max_retries: 5,
+            headers: vec![],
+        }
+    }
+}
+
+/// Result of a successful upload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadReceipt {
+    pub bytes_sent: u64,
+    /// Status code of the response to the final chunk.
+    pub status: u16,
+    /// Body of the response to the final chunk.
+    pub body: String,
+}
+
+pub struct Uploader {
+    agent: ureq::Agent,
+    config: UploadConfig,
+}
+impl Uploader {
+    pub fn new(config: UploadConfig) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            config,
+        }
+    }
+    
+    /// Encodes and uploads the file. The file is encoded as it's sent, so only one chunk is held in memory at a time.
+    pub fn upload_file(&self, file: &TasdFile) -> Result<UploadReceipt, UploadError> {
+        let mut writer = ChunkWriter::new(self, Some(file.estimated_encoded_size() as u64));
+        if let Err(err) = file.write_to(&mut writer) {
+            return Err(writer.error.take().unwrap_or(UploadError::Io(err)));
+        }
+        
+        writer.finish()
+    }
+    
+    /// Uploads everything read from `reader`, one chunk at a time.
+    /// 
+    /// `total` is the total number of bytes that will be read, if known ahead of time. Live dumps can pass `None`.
+    pub fn upload_reader<R: Read>(&self, mut reader: R, total: Option<u64>) -> Result<UploadReceipt, UploadError> {
+        let mut writer = ChunkWriter::new(self, total);
+        if let Err(err) = copy(&mut reader, &mut writer) {
+            return Err(writer.error.take().unwrap_or(UploadError::Io(err)));
+        }
+        
+        writer.finish()
+    }
+    
+    /// Sends a chunk starting at `base`, resuming it from the server's offset if a request fails.
+    /// 
+    /// Returns the response to the last request, or `None` if the server already had the whole chunk (which is only
+    /// possible for chunks which aren't `last`).
+    fn send_chunk(&self, chunk: &[u8], base: u64, total: Option<u64>, last: bool) -> Result<Option<ureq::Response>, UploadError> {
+        let total = total.map(|total| total.to_string()).unwrap_or("*".into());
+        let mut start = 0usize;
+        let mut attempts = 0;
+        loop {
+            let body = &chunk[start..];
+            let first = base + start as u64;
+            let range = match (body.is_empty(), last) {
+                (true, false) => return Ok(None),
+                // the total is always known for the last chunk, so this is never `bytes */*`
+                (true, true) => format!("bytes */{total}"),
+                _ => format!("bytes {first}-{}/{total}", first + body.len() as u64 - 1),
+            };
+            
+            let mut request = self.agent.put(&self.config.endpoint).set("Content-Range", &range);
+            for (name, value) in &self.config.headers {
+                request = request.set(name, value);
+            }
+            
+            let error = match request.send_bytes(body) {
+                Ok(response) => return Ok(Some(response)),
+                Err(ureq::Error::Status(code, _)) if !is_retryable(code) => return Err(UploadError::Status(code)),
+                Err(err) => err.to_string(),
+            };
+            
+            attempts += 1;
+            if attempts > self.config.max_retries {
+                return Err(UploadError::RetriesExhausted { offset: first, error });
+            }
+            
+            match self.query_offset() {
+                Some(offset) if (base..=(base + chunk.len() as u64)).contains(&offset) => start = (offset - base) as usize,
+                Some(offset) => return Err(UploadError::UnexpectedOffset(offset)),
+                // the server can't say how much it has, so the rest of the chunk is sent again
+                None => (),
+            }
+        }
+    }
+    
+    /// Asks the server how many bytes of the upload it has received.
+    /// 
+    /// Returns `None` if the request fails, or the response has no valid `Upload-Offset` header.
+    fn query_offset(&self) -> Option<u64> {
+        let mut request = self.agent.head(&self.config.endpoint);
+        for (name, value) in &self.config.headers {
+            request = request.set(name, value);
+        }
+        
+        request.call().ok()?
+            .header("Upload-Offset")?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+/// Convenience function for uploading a file with a one-off [Uploader].
+pub fn upload_file(file: &TasdFile, config: UploadConfig) -> Result<UploadReceipt, UploadError> {
+    Uploader::new(config).upload_file(file)
+}
+
+fn is_retryable(code: u16) -> bool {
+    code >= 500 || code == 408 || code == 429
+}
+
+/// Sends everything written to it as chunks of [`UploadConfig::chunk_size`] bytes.
+/// 
+/// A full chunk is held back until more data is written, so that the final chunk can be sent along with the total size
+/// in [`Self::finish`].
+struct ChunkWriter<'a> {
+    uploader: &'a Uploader,
+    total: Option<u64>,
+    chunk: Vec<u8>,
+    sent: u64,
+    /// Error from sending a chunk, which [Write] can only return as an [std::io::Error].
+    error: Option<UploadError>,
+}
+impl<'a> ChunkWriter<'a> {
+    fn new(uploader: &'a Uploader, total: Option<u64>) -> Self {
+        Self {
+            uploader,
+            total,
+            chunk: Vec::with_capacity(uploader.config.chunk_size.max(1)),
+            sent: 0,
+            error: None,
+        }
+    }
+    
+    fn chunk_size(&self) -> usize {
+        self.uploader.config.chunk_size.max(1)
+    }
+    
+    /// Sends the final chunk.
+    fn finish(self) -> Result<UploadReceipt, UploadError> {
+        let total = self.sent + self.chunk.len() as u64;
+        let Some(response) = self.uploader.send_chunk(&self.chunk, self.sent, Some(total), true)? else {
+            unreachable!("the last chunk is always sent")
+        };
+        
+        Ok(UploadReceipt {
+            bytes_sent: total,
+            status: response.status(),
+            body: response.into_string().unwrap_or_default(),
+        })
+    }
+}
+impl Write for ChunkWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if self.chunk.len() == self.chunk_size() {
+            if let Err(err) = self.uploader.send_chunk(&self.chunk, self.sent, self.total, false) {
+                let message = format!("{err:?}");
+                self.error = Some(err);
+                return Err(std::io::Error::other(message));
+            }
+            self.sent += self.chunk.len() as u64;
+            self.chunk.clear();
+        }
+        
+        let len = data.len().min(self.chunk_size() - self.chunk.len());
+        self.chunk.extend_from_slice(&data[..len]);
+        
+        Ok(len)
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use crate::spec::packets::Comment;
+    use crate::spec::TasdFile;
+    use crate::upload::{UploadConfig, Uploader};
+    
+    /// Minimal server which accepts chunked uploads, and drops the connection of the second chunk request it receives.
+    fn serve(listener: TcpListener) -> Vec<u8> {
+        let mut received = vec![];
+        let mut puts = 0;
+        for stream in listener.incoming() {
+            let mut stream = BufReader::new(stream.unwrap());
+            let mut request = String::new();
+            stream.read_line(&mut request).unwrap();
+            
+            let mut length = 0;
+            let mut range = String::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                let line = line.trim();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap();
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => length = value.trim().parse().unwrap(),
+                    "content-range" => range = value.trim().to_string(),
+                    _ => (),
+                }
+            }
+            let mut body = vec![0u8; length];
+            stream.read_exact(&mut body).unwrap();
+            
+            let mut stream = stream.into_inner();
+            if request.starts_with("HEAD") {
+                write!(stream, "HTTP/1.1 200 OK\r\nUpload-Offset: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", received.len()).unwrap();
+                continue;
+            }
+            
+            puts += 1;
+            if puts == 2 {
+                // accept part of the chunk, then fail
+                received.extend_from_slice(&body[..(body.len() / 2)]);
+                continue;
+            }
+            
+            let start: usize = range.trim_start_matches("bytes ").split('-').next().unwrap().parse().unwrap();
+            assert_eq!(start, received.len());
+            received.extend_from_slice(&body);
+            
+            let (end, total) = range.split_once('-').unwrap().1.split_once('/').unwrap();
+            let last = total.parse().ok() == Some(end.parse::<usize>().unwrap() + 1);
+            if last {
+                write!(stream, "HTTP/1.1 201 Created\r\nContent-Length: 4\r\nConnection: close\r\n\r\ndone").unwrap();
+                break;
+            }
+            write!(stream, "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").unwrap();
+        }
+        
+        received
+    }
+    
+    #[test]
+    fn resumes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || serve(listener));
+        
+        let mut file = TasdFile::new();
+        file.packets.push(Comment { comment: "c".repeat(1000) }.into());
+        let data = file.encode();
+        
+        let mut config = UploadConfig::new(endpoint);
+        config.chunk_size = 300;
+        let receipt = Uploader::new(config).upload_reader(data.as_slice(), None).unwrap();
+        assert_eq!(receipt.bytes_sent, data.len() as u64);
+        assert_eq!(receipt.status, 201);
+        
+        assert_eq!(server.join().unwrap(), data);
+        
+        // encoded as it's sent
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = UploadConfig::new(format!("http://{}/upload", listener.local_addr().unwrap()));
+        config.chunk_size = 300;
+        let server = thread::spawn(move || serve(listener));
+        let receipt = Uploader::new(config).upload_file(&file).unwrap();
+        assert_eq!(receipt.bytes_sent, data.len() as u64);
+        assert_eq!(server.join().unwrap(), data);
+    }
+}
\ No newline at end of file
@@ -1,3 +1,6 @@
+use std::borrow::Cow;
+use std::io::Write;
+
 pub fn to_bytes(mut number: usize, length: u8) -> Vec<u8> {
     let mut out = Vec::new();
     
@@ -9,11 +12,306 @@ pub fn to_bytes(mut number: usize, length: u8) -> Vec<u8> {
     out
 }
 
+/// Truncates a string to at most `max` bytes, without splitting a multi-byte character.
+/// 
+/// # Example
+/// ```
+/// use tasd::util::truncate_str;
+/// 
+/// assert_eq!(truncate_str("hello", 3), "hel");
+/// assert_eq!(truncate_str("héllo", 2), "h");
+/// assert_eq!(truncate_str("hi", 10), "hi");
+/// ```
+pub fn truncate_str(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    
+    &s[..end]
+}
+
+/// Truncates a string to at most `max` bytes, without splitting a grapheme cluster (a character along with any accents
+/// or joiners attached to it). Use this when enforcing length limits on text that will be displayed.
+/// 
+/// Without the `unicode` feature, this is the same as [truncate_str].
+/// 
+/// # Example
+/// ```
+/// use tasd::util::truncate_graphemes;
+/// 
+/// # #[cfg(feature = "unicode")]
+/// assert_eq!(truncate_graphemes("Poke\u{0301}mon", 5), "Pok");
+/// assert_eq!(truncate_graphemes("Pokemon", 5), "Pokem");
+/// ```
+pub fn truncate_graphemes(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    
+    let end = graphemes(s).scan(0, |end, grapheme| {
+        *end += grapheme.len();
+        Some(*end)
+    }).take_while(|end| *end <= max).last().unwrap_or(0);
+    
+    &s[..end]
+}
+
+/// Number of terminal columns a string takes up when displayed.
+/// 
+/// With the `unicode` feature, wide characters (e.g. CJK) count as 2 columns and combining characters as 0. Otherwise,
+/// every character counts as 1.
+pub fn display_width(s: &str) -> usize {
+    graphemes(s).map(grapheme_width).sum()
+}
+
+/// Shortens a string to fit in `max` columns for display, ending it with "…" if anything was removed. Only whole
+/// grapheme clusters are removed.
+/// 
+/// # Example
+/// ```
+/// use tasd::util::elide;
+/// 
+/// assert_eq!(elide("Super Mario Bros.", 10), "Super Mar…");
+/// assert_eq!(elide("Tetris", 10), "Tetris");
+/// ```
+pub fn elide(s: &str, max: usize) -> Cow<'_, str> {
+    if display_width(s) <= max {
+        return Cow::Borrowed(s);
+    }
+    
+    let mut width = 0;
+    let mut elided: String = graphemes(s)
+        .take_while(|grapheme| {
+            width += grapheme_width(grapheme);
+            width < max
+        })
+        .collect();
+    if max > 0 {
+        elided.push('…');
+    }
+    
+    Cow::Owned(elided)
+}
+
+#[cfg(feature = "unicode")]
+fn graphemes(s: &str) -> impl Iterator<Item = &str> {
+    unicode_segmentation::UnicodeSegmentation::graphemes(s, true)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn graphemes(s: &str) -> impl Iterator<Item = &str> {
+    s.split_inclusive(|_| true)
+}
+
+#[cfg(feature = "unicode")]
+fn grapheme_width(grapheme: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(grapheme)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.chars().count()
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    
+    table
+};
+
+/// Calculates the CRC-32 (IEEE 802.3) checksum of the provided data.
+/// 
+/// # Example
+/// ```
+/// use tasd::util::crc32;
+/// 
+/// assert_eq!(crc32(b"123456789"), 0xCBF43926);
+/// ```
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428A2F98, 0x71374491, 0xB5C0FBCF, 0xE9B5DBA5, 0x3956C25B, 0x59F111F1, 0x923F82A4, 0xAB1C5ED5,
+    0xD807AA98, 0x12835B01, 0x243185BE, 0x550C7DC3, 0x72BE5D74, 0x80DEB1FE, 0x9BDC06A7, 0xC19BF174,
+    0xE49B69C1, 0xEFBE4786, 0x0FC19DC6, 0x240CA1CC, 0x2DE92C6F, 0x4A7484AA, 0x5CB0A9DC, 0x76F988DA,
+    0x983E5152, 0xA831C66D, 0xB00327C8, 0xBF597FC7, 0xC6E00BF3, 0xD5A79147, 0x06CA6351, 0x14292967,
+    0x27B70A85, 0x2E1B2138, 0x4D2C6DFC, 0x53380D13, 0x650A7354, 0x766A0ABB, 0x81C2C92E, 0x92722C85,
+    0xA2BFE8A1, 0xA81A664B, 0xC24B8B70, 0xC76C51A3, 0xD192E819, 0xD6990624, 0xF40E3585, 0x106AA070,
+    0x19A4C116, 0x1E376C08, 0x2748774C, 0x34B0BCB5, 0x391C0CB3, 0x4ED8AA4A, 0x5B9CCA4F, 0x682E6FF3,
+    0x748F82EE, 0x78A5636F, 0x84C87814, 0x8CC70208, 0x90BEFFFA, 0xA4506CEB, 0xBEF9A3F7, 0xC67178F2,
+];
+
+/// Calculates the SHA-256 hash of the provided data.
+/// 
+/// # Example
+/// ```
+/// use tasd::util::{format_slice_hex, sha256};
+/// 
+/// assert_eq!(format_slice_hex(&sha256(b"abc")).replace(' ', ""), "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD");
+/// assert_eq!(format_slice_hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")).replace(' ', ""), "248D6A61D20638B8E5C026930C3E6039A33CE45964FF2167F6ECEDD419DB06C1");
+/// ```
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    
+    hasher.finish()
+}
+
+/// Incremental SHA-256 hasher, for data which arrives in pieces. [sha256] hashes a single slice.
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    h: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    len: u64,
+}
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            h: [0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19],
+            block: [0; 64],
+            block_len: 0,
+            len: 0,
+        }
+    }
+    
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let n = data.len().min(64 - self.block_len);
+            self.block[self.block_len..(self.block_len + n)].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+    
+    pub fn finish(mut self) -> [u8; 32] {
+        let bits = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0x00]);
+        }
+        self.update(&bits.to_be_bytes());
+        
+        let mut out = [0u8; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.h) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        
+        out
+    }
+    
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, word) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = self.h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        
+        for (h, value) in self.h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+}
+
+/// [Write] wrapper which hashes everything written through it with SHA-256, so the output of a streaming encode can be
+/// hashed without reading it back.
+#[derive(Debug)]
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            written: 0,
+        }
+    }
+    
+    /// Number of bytes written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+    
+    /// Returns the inner writer, and the SHA-256 hash of everything written to it.
+    pub fn finish(self) -> (W, [u8; 32]) {
+        (self.inner, self.hasher.finish())
+    }
+}
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        
+        Ok(n)
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 pub fn print_slice(slice: &[u8]) {
     for byte in slice {
         print!("{:02X} ", byte);
     }
-    println!("")
+    println!()
 }
 
 pub fn format_slice_hex(slice: &[u8]) -> String {
@@ -30,4 +328,30 @@ pub fn format_slice_bin(slice: &[u8]) -> String {
         s.push_str(&format!("{:08b} ", byte));
     }
     s
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{format_slice_hex, sha256, Sha256};
+    
+    #[test]
+    fn sha256_padding() {
+        // 55 bytes is the most which fits the length in the same block, 56 needs another block, and 64 fills one exactly
+        for (len, digest) in [
+            (55, "9F4390F8D30C2DD92EC9F095B65E2B9AE9B0A925A5258E241C9F1E910F734318"),
+            (56, "B35439A4AC6F0948B6D6F9E3C6AF0F5F590CE20F1BDE7090EF7970686EC6738A"),
+            (64, "FFE054FE7AE0CB6DC65C3AF9B61D5209F439851DB43D0BA5997337DF154668EB"),
+        ] {
+            let data = vec![b'a'; len];
+            assert_eq!(format_slice_hex(&sha256(&data)).replace(' ', ""), digest, "{len} bytes");
+            
+            let mut hasher = Sha256::new();
+            hasher.update(&data[..7]);
+            hasher.update(&data[7..]);
+            assert_eq!(hasher.finish(), sha256(&data));
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,569 @@
+use std::fmt::{Display, Formatter};
+use crate::emulator::{normalize_core, normalize_version, split_name};
+use crate::input::computed_total_frames;
+use crate::link;
+use crate::lookup::{console_region_lut, console_setting_lut, console_setting_value_lut, random_seed_algorithm_lut};
+use crate::playlist::movie_start;
+use crate::spec::packets::{ConsoleType, Decode, DRAFT_KINDS, EmulatorName, LinkSession, MemoryInit, OpaqueKind, Packet, PacketKind, RandomSeed};
+use crate::spec::{DRAFT_VERSION, TasdFile};
+use crate::start::{is_cartridge_save, StartCondition};
+use crate::timestamp::{self, is_millisecond_epoch};
+
+/// Maximum length, in bytes, of a string encoded with a u8 length prefix.
+pub const MAX_PREFIXED_STR_LEN: usize = u8::MAX as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while validating a [TasdFile].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Index into [`TasdFile::packets`] of the packet this issue refers to, if any.
+    pub index: Option<usize>,
+    pub kind: Option<PacketKind>,
+    pub message: String,
+}
+impl Issue {
+    pub fn error<S: Into<String>>(index: usize, kind: PacketKind, message: S) -> Self {
+        Self {
+            severity: Severity::Error,
+            index: Some(index),
+            kind: Some(kind),
+            message: message.into(),
+        }
+    }
+    
+    pub fn warning<S: Into<String>>(index: usize, kind: PacketKind, message: S) -> Self {
+        Self {
+            severity: Severity::Warning,
+            index: Some(index),
+            kind: Some(kind),
+            message: message.into(),
+        }
+    }
+}
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        
+        match (self.index, self.kind) {
+            (Some(index), Some(kind)) => write!(f, "{severity}: packet #{index} ({kind}): {}", self.message),
+            (Some(index), None) => write!(f, "{severity}: packet #{index}: {}", self.message),
+            (None, Some(kind)) => write!(f, "{severity}: {kind}: {}", self.message),
+            (None, None) => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+impl TasdFile {
+    /// Checks this file for data which is either invalid per the spec, or would not survive encoding unchanged.
+    /// 
+    /// An empty list means no problems were found.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        
+        for (i, packet) in self.packets.iter().enumerate() {
+            prefixed_names(&mut issues, i, packet);
+            timestamp(&mut issues, i, packet);
+            unknown_key(&mut issues, i, packet);
+            draft_packet(&mut issues, i, packet, self.version, DRAFT_KINDS);
+            #[cfg(feature = "url")]
+            source_link(&mut issues, i, packet);
+            #[cfg(feature = "spdx")]
+            movie_license(&mut issues, i, packet);
+        }
+        packet_order(&mut issues, self);
+        total_frames(&mut issues, self);
+        input_alignment(&mut issues, self);
+        blank_frames(&mut issues, self);
+        savestate_anchor(&mut issues, self);
+        random_seed(&mut issues, self);
+        console_settings(&mut issues, self);
+        av_sync(&mut issues, self);
+        link_session(&mut issues, self);
+        emulator(&mut issues, self);
+        region(&mut issues, self);
+        
+        issues
+    }
+}
+
+fn prefixed_names(issues: &mut Vec<Issue>, index: usize, packet: &Packet) {
+    let (kind, name) = match packet {
+        Packet::MemoryInit(packet) => (packet.kind(), &packet.name),
+        Packet::GameIdentifier(packet) => (packet.kind(), &packet.name),
+        Packet::MovieFile(packet) => (packet.kind(), &packet.name),
+        _ => return,
+    };
+    
+    if name.len() > MAX_PREFIXED_STR_LEN {
+        issues.push(Issue::error(index, kind, format!("name is {} bytes long, but may be at most {MAX_PREFIXED_STR_LEN} bytes", name.len())));
+    }
+}
+
+fn timestamp(issues: &mut Vec<Issue>, index: usize, packet: &Packet) {
+    if let Some(epoch) = timestamp::epoch(packet) {
+        if is_millisecond_epoch(epoch) {
+            issues.push(Issue::error(index, packet.kind(), format!("timestamp {epoch} is too large for seconds since the Unix epoch, and is likely in milliseconds")));
+        }
+    }
+}
+
+#[cfg(feature = "url")]
+fn source_link(issues: &mut Vec<Issue>, index: usize, packet: &Packet) {
+    if let Packet::SourceLink(link) = packet {
+        if let Err(err) = link.source_url() {
+            issues.push(Issue::warning(index, link.kind(), format!("link is not a valid URL ({err})")));
+        }
+    }
+}
+
+#[cfg(feature = "spdx")]
+fn movie_license(issues: &mut Vec<Issue>, index: usize, packet: &Packet) {
+    if let Packet::MovieLicense(license) = packet {
+        if let Err(err) = license.license_spdx() {
+            issues.push(Issue::warning(index, license.kind(), format!("license is not a valid SPDX expression ({})", err.reason)));
+        }
+    }
+}
+
+fn packet_order(issues: &mut Vec<Issue>, file: &TasdFile) {
+    for range in file.movie_ranges() {
+        let mut first: Vec<(PacketKind, usize)> = vec![];
+        let mut ports: Vec<(PacketKind, u8, usize)> = vec![];
+        let mut first_input: Option<usize> = None;
+        
+        for i in range {
+            let packet = &file.packets[i];
+            let kind = packet.kind();
+            
+            if kind.is_unique() {
+                match first.iter().find(|(k, _)| *k == kind) {
+                    Some((_, index)) => issues.push(Issue::warning(i, kind, format!("may only appear once per movie, but already appears at packet #{index}"))),
+                    None => first.push((kind, i)),
+                }
+            }
+            let port = match packet {
+                Packet::PortController(controller) => Some(controller.port),
+                Packet::PortOverread(overread) => Some(overread.port),
+                _ => None,
+            };
+            if let Some(port) = port {
+                match ports.iter().find(|(k, p, _)| *k == kind && *p == port) {
+                    Some((_, _, index)) => issues.push(Issue::warning(i, kind, format!("may only appear once per port, but port {port} already has one at packet #{index}"))),
+                    None => ports.push((kind, port, i)),
+                }
+            }
+            
+            match first_input {
+                Some(input) if kind.is_configuration() => issues.push(Issue::error(i, kind, format!("must appear before any input data, but packet #{input} is {}", file.packets[input].kind()))),
+                None if kind.is_input() => first_input = Some(i),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn total_frames(issues: &mut Vec<Issue>, file: &TasdFile) {
+    // each movie of an archive declares its own frame count
+    for range in file.movie_ranges() {
+        let movie = &file.packets[range.clone()];
+        let Some(computed) = computed_total_frames(movie, movie_start(movie)) else { continue };
+        
+        for (i, packet) in movie.iter().enumerate() {
+            if let Packet::TotalFrames(total) = packet {
+                if total.frames != computed {
+                    issues.push(Issue::warning(range.start + i, total.kind(), format!("declares {} frames, but the input data contains {computed} frames", total.frames)));
+                }
+            }
+        }
+    }
+}
+
+fn input_alignment(issues: &mut Vec<Issue>, file: &TasdFile) {
+    for chunk in file.misaligned_chunks() {
+        issues.push(Issue::error(chunk.index, PacketKind::InputChunk, format!("contains {} bytes of input data for port {}, which isn't a multiple of the controller's {} bytes per frame", chunk.len, chunk.port, chunk.stride)));
+    }
+}
+
+fn unknown_key(issues: &mut Vec<Issue>, index: usize, packet: &Packet) {
+    if let Packet::Unsupported(unsupported) = packet {
+        if unsupported.opaque_kind() == OpaqueKind::UnknownKey {
+            issues.push(Issue::warning(index, packet.kind(), format!("key {:02X?} isn't part of the TASD spec, the file may be from a newer version", unsupported.key)));
+        }
+    }
+}
+
+/// `drafts` is [DRAFT_KINDS] outside of tests, as the draft doesn't add any packets yet.
+fn draft_packet(issues: &mut Vec<Issue>, index: usize, packet: &Packet, version: u16, drafts: &[PacketKind]) {
+    let kind = packet.kind();
+    let draft = u16::from_be_bytes(DRAFT_VERSION);
+    if drafts.contains(&kind) && version < draft {
+        issues.push(Issue::warning(index, kind, format!("is a draft packet of spec version 0x{draft:04X}, but the file claims version 0x{version:04X}")));
+    }
+}
+
+fn blank_frames(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let Some(computed) = file.computed_total_frames() else { return };
+    
+    for (i, packet) in file.packets.iter().enumerate() {
+        if let Packet::BlankFrames(blank) = packet {
+            if blank.frames < 0 && blank.frames.unsigned_abs() as u32 > computed {
+                issues.push(Issue::error(i, blank.kind(), format!("removes {} frames, but the input data only contains {computed} frames", blank.frames.unsigned_abs())));
+            }
+        }
+    }
+}
+
+fn savestate_anchor(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let Some(index) = file.packets.iter().position(|packet| matches!(packet, Packet::SavestateAnchor(_))) else { return };
+    
+    let inits: Vec<_> = file.all::<MemoryInit>().collect();
+    if file.start_condition() == StartCondition::SramAnchored {
+        if !inits.iter().any(|init| init.data.is_some() && is_cartridge_save(init)) {
+            issues.push(Issue::warning(index, PacketKind::SavestateAnchor, "movie is SRAM-anchored, but no MEMORY_INIT packet embeds cartridge save data"));
+        }
+    } else if inits.is_empty() {
+        issues.push(Issue::warning(index, PacketKind::SavestateAnchor, "movie is savestate-anchored, but has no MEMORY_INIT packets"));
+    } else if inits.iter().all(|init| init.data.is_none()) {
+        issues.push(Issue::warning(index, PacketKind::SavestateAnchor, "movie is savestate-anchored, but no MEMORY_INIT packet embeds its state data"));
+    }
+}
+
+fn random_seed(issues: &mut Vec<Issue>, file: &TasdFile) {
+    for (i, packet) in file.packets.iter().enumerate() {
+        match packet {
+            Packet::MemoryInit(init) if init.data_type == 0x05 && !file.all::<RandomSeed>().any(|seed| seed.device == init.device) => {
+                issues.push(Issue::warning(i, init.kind(), format!("random data for device {:#06X} can't be reproduced without a RANDOM_SEED packet", init.device)));
+            },
+            Packet::RandomSeed(seed) if random_seed_algorithm_lut(seed.algorithm).is_none() => {
+                issues.push(Issue::warning(i, seed.kind(), format!("unknown algorithm {:#04X}", seed.algorithm)));
+            },
+            _ => (),
+        }
+    }
+}
+
+fn console_settings(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let console = file.first::<ConsoleType>().map(|console| console.kind);
+    let mut seen: Vec<(u16, usize)> = vec![];
+    
+    for (i, packet) in file.packets.iter().enumerate() {
+        let Packet::ConsoleSetting(setting) = packet else { continue };
+        let (code, kind) = (setting.setting, setting.kind());
+        
+        if let Some((_, index)) = seen.iter().find(|(seen, _)| *seen == code) {
+            issues.push(Issue::warning(i, kind, format!("setting {code:#06X} is already set at packet #{index}")));
+        } else {
+            seen.push((code, i));
+        }
+        
+        let Some(name) = console_setting_lut(code) else {
+            issues.push(Issue::warning(i, kind, format!("unknown setting {code:#06X}")));
+            continue;
+        };
+        if console_setting_value_lut(code, setting.value).is_none() {
+            issues.push(Issue::warning(i, kind, format!("unknown value {:#04X} for {name}", setting.value)));
+        }
+        let target = (code >> 8) as u8;
+        if let Some(console) = console.filter(|console| target != 0x00 && *console != target) {
+            issues.push(Issue::warning(i, kind, format!("{name} applies to console type {target:#04X}, but the file is for {console:#04X}")));
+        }
+    }
+}
+
+fn av_sync(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let Some(computed) = file.computed_total_frames() else { return };
+    let frames = (computed as u64).saturating_add_signed(file.blank_frames());
+    
+    for (i, packet) in file.packets.iter().enumerate() {
+        if let Packet::AvSync(sync) = packet {
+            if frames > 0 && sync.first_visible_frame as u64 >= frames {
+                issues.push(Issue::warning(i, sync.kind(), format!("first visible frame {} is past the end of the movie's {frames} frames", sync.first_visible_frame)));
+            }
+        }
+    }
+}
+
+fn link_session(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let Some(index) = file.packets.iter().position(|packet| matches!(packet, Packet::LinkSession(_))) else { return };
+    let consoles = file.first::<LinkSession>().map(|session| session.consoles.len()).unwrap_or(0);
+    
+    for (i, packet) in file.packets.iter().enumerate() {
+        if let Some(port) = link::port(packet) {
+            let (console, _) = link::split_port(port);
+            if console as usize >= consoles {
+                issues.push(Issue::error(i, packet.kind(), format!("port {port:#04X} belongs to console {console}, but the session only has {consoles} consoles")));
+            }
+        }
+    }
+    
+    let mut frames = vec![0u64; consoles];
+    for (port, count) in file.input_frames() {
+        if let Some(frames) = frames.get_mut(link::split_port(port).0 as usize) {
+            *frames = (*frames).max(count);
+        }
+    }
+    if frames.iter().any(|count| *count != frames[0]) {
+        issues.push(Issue::warning(index, PacketKind::LinkSession, format!("linked consoles must run in lockstep, but have differing frame counts {frames:?}")));
+    }
+}
+
+fn emulator(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let emulator = file.first::<EmulatorName>().and_then(|name| split_name(&name.name)).map(|(emulator, _)| emulator);
+    
+    for (i, packet) in file.packets.iter().enumerate() {
+        match packet {
+            Packet::EmulatorName(name) if split_name(&name.name).is_none() => {
+                issues.push(Issue::warning(i, name.kind(), format!("emulator \"{}\" isn't recognized", name.name)));
+            },
+            Packet::EmulatorVersion(version) if normalize_version(&version.version).is_none() => {
+                issues.push(Issue::warning(i, version.kind(), format!("\"{}\" isn't a version number", version.version)));
+            },
+            Packet::EmulatorCore(core) => if let Some(emulator) = emulator {
+                if !emulator.cores().is_empty() && normalize_core(emulator, &core.core).is_none() {
+                    issues.push(Issue::warning(i, core.kind(), format!("\"{}\" isn't a known {emulator} core", core.core)));
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+fn region(issues: &mut Vec<Issue>, file: &TasdFile) {
+    let Some(conflict) = file.region_conflict() else { return };
+    let declared = console_region_lut(conflict.declared).unwrap_or_default();
+    let implied = console_region_lut(conflict.implied).unwrap_or_default();
+    
+    issues.push(Issue::warning(conflict.index, file.packets[conflict.index].kind(), format!("falls on frame {} with {declared} timing, past the end of the {} frame movie, but fits {implied} timing (frame {}); the region may be mislabeled", conflict.declared_frame, conflict.frames, conflict.implied_frame)));
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::spec::packets::{AvSync, BlankFrames, ConsoleSetting, ConsoleType, DRAFT_KINDS, DumpCreated, EmulatorCore, EmulatorName, EmulatorVersion, Experimental, InputChunk, MemoryInit, MovieFile, Packet, PacketKind, PortController, RandomSeed, SavestateAnchor, TotalFrames};
+    use crate::playback::Playback;
+    use crate::spec::TasdFile;
+    use crate::validate::{draft_packet, Severity};
+    
+    #[test]
+    fn prefixed_names() {
+        let mut file = TasdFile::default();
+        file.packets.push(MovieFile { name: "a".repeat(255), data: vec![] }.into());
+        file.packets.push(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "b".repeat(255), data: None }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(MovieFile { name: "a".repeat(256), data: vec![] }.into());
+        file.packets.push(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "b".repeat(256), data: None }.into());
+        let issues = file.validate();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].index, Some(2));
+        assert_eq!(issues[0].kind, Some(PacketKind::MovieFile));
+        assert_eq!(issues[1].index, Some(3));
+        assert_eq!(issues[1].kind, Some(PacketKind::MemoryInit));
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Error));
+    }
+    
+    #[test]
+    fn total_frames() {
+        let mut file = TasdFile::default();
+        file.packets.push(TotalFrames { frames: 3 }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 6] }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 2] }.into());
+        let issues = file.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].index, Some(0));
+        assert_eq!(issues[0].severity, Severity::Warning);
+    }
+    
+    #[test]
+    fn packet_order() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 2] }.into());
+        file.packets.push(PortController { port: 2, kind: 0x0101 }.into());
+        file.packets.push(ConsoleType { kind: 0x01, custom: None }.into());
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        
+        let issues = file.validate();
+        assert_eq!(issues.len(), 5);
+        assert_eq!(issues[0].index, Some(3));
+        assert_eq!(issues[0].message, "must appear before any input data, but packet #2 is INPUT_CHUNK");
+        assert_eq!(issues[1].message, "may only appear once per movie, but already appears at packet #0");
+        assert_eq!(issues[3].message, "may only appear once per port, but port 1 already has one at packet #1");
+        
+        assert_eq!(file.order_configuration(), 3);
+        // CONSOLE_TYPE is configuration rather than metadata, so the repeated one isn't removed
+        assert_eq!(file.dedup_metadata(), 0);
+        assert_eq!(file.validate().len(), 2);
+    }
+    
+    #[test]
+    fn input_alignment() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 4] }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 3] }.into());
+        file.packets.push(InputChunk { port: 2, inputs: vec![0; 3] }.into());
+        
+        let issues = file.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].index, Some(2));
+        assert_eq!(issues[0].message, "contains 3 bytes of input data for port 1, which isn't a multiple of the controller's 2 bytes per frame");
+        assert!(Playback::try_new(&file).is_err());
+        
+        file.packets.remove(2);
+        assert!(file.validate().is_empty());
+        assert!(Playback::try_new(&file).is_ok());
+    }
+    
+    #[test]
+    fn blank_frames() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0; 2] }.into());
+        file.packets.push(BlankFrames { frames: -2 }.into());
+        assert!(file.validate().is_empty());
+        
+        file.first_mut::<BlankFrames>().unwrap().frames = -3;
+        let issues = file.validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].index, Some(2));
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+    
+    #[test]
+    fn timestamps() {
+        let mut file = TasdFile::default();
+        file.packets.push(DumpCreated { epoch: 1_700_000_000 }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(DumpCreated { epoch: 1_700_000_000_000 }.into());
+        let issues = file.validate();
+        assert!(issues.iter().any(|issue| issue.index == Some(1) && issue.severity == Severity::Error));
+    }
+    
+    #[test]
+    fn random_seed() {
+        let mut file = TasdFile::default();
+        file.packets.push(MemoryInit { data_type: 0x05, device: 0x0101, required: true, name: "".into(), data: None }.into());
+        assert_eq!(file.validate().len(), 1);
+        
+        file.packets.push(RandomSeed { device: 0x0101, algorithm: 0x01, seed: 1 }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets[1] = RandomSeed { device: 0x0101, algorithm: 0x7F, seed: 1 }.into();
+        assert_eq!(file.validate()[0].index, Some(1));
+    }
+    
+    #[test]
+    fn console_settings() {
+        let mut file = TasdFile::default();
+        file.packets.push(ConsoleType { kind: 0x08, custom: None }.into());
+        file.packets.push(ConsoleSetting { setting: 0x0801, value: 0x01 }.into());
+        file.packets.push(ConsoleSetting { setting: 0x0001, value: 0x04 }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(ConsoleSetting { setting: 0x0801, value: 0x00 }.into());
+        file.packets.push(ConsoleSetting { setting: 0x0201, value: 0x01 }.into());
+        file.packets.push(ConsoleSetting { setting: 0x0802, value: 0x7F }.into());
+        file.packets.push(ConsoleSetting { setting: 0x08FF, value: 0x00 }.into());
+        let issues: Vec<_> = file.validate().into_iter().filter_map(|issue| issue.index).collect();
+        assert_eq!(issues, [3, 4, 5, 6]);
+    }
+    
+    #[test]
+    fn draft_packets() {
+        // the draft doesn't add any packets yet, so EXPERIMENTAL stands in for one
+        let packet: Packet = Experimental { experimental: true }.into();
+        let mut issues = vec![];
+        draft_packet(&mut issues, 0, &packet, 0x0001, &[PacketKind::Experimental]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert_eq!(issues[0].message, "is a draft packet of spec version 0x0002, but the file claims version 0x0001");
+        
+        issues.clear();
+        draft_packet(&mut issues, 0, &packet, 0x0002, &[PacketKind::Experimental]);
+        draft_packet(&mut issues, 0, &packet, 0x0001, DRAFT_KINDS);
+        assert!(issues.is_empty());
+    }
+    
+    #[test]
+    fn av_sync() {
+        let mut file = TasdFile::default();
+        file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+        file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 10] }.into());
+        file.packets.push(AvSync { first_visible_frame: 9, audio_offset: 0 }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(BlankFrames { frames: -2 }.into());
+        assert_eq!(file.validate()[0].message, "first visible frame 9 is past the end of the movie's 8 frames");
+    }
+    
+    #[test]
+    fn savestate_anchor() {
+        let mut file = TasdFile::default();
+        file.packets.push(SavestateAnchor { kind: 0x01, identifier_kind: 0x04, identifier: vec![0xAB; 32] }.into());
+        assert_eq!(file.validate()[0].message, "movie is savestate-anchored, but has no MEMORY_INIT packets");
+        
+        file.packets.push(MemoryInit { data_type: 0xFF, device: 0x0101, required: true, name: "state".into(), data: None }.into());
+        assert_eq!(file.validate()[0].message, "movie is savestate-anchored, but no MEMORY_INIT packet embeds its state data");
+        
+        file.packets[1] = MemoryInit { data_type: 0xFF, device: 0x0101, required: true, name: "state".into(), data: Some(vec![0; 2048]) }.into();
+        assert!(file.validate().is_empty());
+        
+        file.packets[0] = SavestateAnchor { kind: 0x02, identifier_kind: 0xFF, identifier: vec![] }.into();
+        assert_eq!(file.validate()[0].message, "movie is SRAM-anchored, but no MEMORY_INIT packet embeds cartridge save data");
+        file.packets[1] = MemoryInit { data_type: 0xFF, device: 0x0102, required: true, name: "".into(), data: Some(vec![0; 8192]) }.into();
+        assert!(file.validate().is_empty());
+    }
+    
+    #[cfg(all(feature = "url", feature = "spdx"))]
+    #[test]
+    fn links_and_licenses() {
+        use crate::spec::packets::{MovieLicense, SourceLink};
+        
+        let mut file = TasdFile::default();
+        file.packets.push(SourceLink { link: "https://tasvideos.org/1234M".into() }.into());
+        file.packets.push(MovieLicense { license: "CC-BY-4.0".into() }.into());
+        file.packets.push(MovieLicense { license: "MIT OR Apache-2.0".into() }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets.push(SourceLink { link: "tasvideos.org/1234M".into() }.into());
+        file.packets.push(MovieLicense { license: "do whatever you want".into() }.into());
+        let issues = file.validate();
+        assert_eq!(issues.iter().map(|issue| issue.kind.unwrap()).collect::<Vec<_>>(), vec![PacketKind::SourceLink, PacketKind::MovieLicense]);
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Warning));
+    }
+    
+    #[test]
+    fn emulator() {
+        let mut file = TasdFile::default();
+        file.packets.push(EmulatorName { name: "bizhawk".into() }.into());
+        file.packets.push(EmulatorVersion { version: "2.9.1".into() }.into());
+        file.packets.push(EmulatorCore { core: "QuickNES".into() }.into());
+        assert!(file.validate().is_empty());
+        
+        file.packets[1] = EmulatorVersion { version: "latest".into() }.into();
+        file.packets[2] = EmulatorCore { core: "NotACore".into() }.into();
+        let issues = file.validate();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[1].message, "\"NotACore\" isn't a known BizHawk core");
+        
+        file.packets[0] = EmulatorName { name: "Unknown Emulator".into() }.into();
+        assert_eq!(file.validate().len(), 2);
+    }
+}
\ No newline at end of file
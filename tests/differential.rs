@@ -0,0 +1,160 @@
+//! Differential test against another TASD implementation.
+//!
+//! Set `TASD_REFERENCE` to a command which parses the file given as its first argument and writes it back out, re-encoded,
+//! to its second argument (exiting unsuccessfully if it rejects the file), then run:
+//! ```text
+//! TASD_REFERENCE="java -jar TASD-Edit.jar roundtrip" cargo test --test differential -- --ignored
+//! ```
+//! Files are generated with varying key lengths, needlessly large payload length exponents, and console-specific
+//! packets, which are where implementations are most likely to disagree. Both the input and the reference's output are
+//! parsed with this crate and compared, so differences in how the reference chooses to encode are tolerated.
+//!
+//! `TASD_REFERENCE_CASES` sets the number of files to generate (default 200), and `TASD_REFERENCE_SEED` the seed to start
+//! from, for reproducing a failure.
+//!
+//! With the `compat` feature, the generated files are also compared against version 0.6 of the original `tasd` crate,
+//! without needing a reference command.
+
+use std::process::Command;
+use tasd::spec::packets::{Attribution, ConsoleRegion, ConsoleType, GameTitle, InputChunk, NesClockFilter, NesLatchFilter, Packet, PortController, Rerecords, SnesClockFilter, SnesLatchFilter, TotalFrames};
+use tasd::spec::TasdFile;
+
+/// Xorshift generator, so failures can be reproduced from their seed without any extra dependencies.
+struct Rng(u64);
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+    
+    fn string(&mut self) -> String {
+        (0..self.below(24)).map(|_| (b'a' + self.below(26) as u8) as char).collect()
+    }
+}
+
+fn generate(rng: &mut Rng) -> TasdFile {
+    let mut file = TasdFile::new();
+    file.keylen = 2 + rng.below(3) as u8;
+    file.packets.push(ConsoleType { kind: 1 + rng.below(2) as u8, custom: None }.into());
+    
+    for _ in 0..rng.below(16) {
+        let packet: Packet = match rng.below(11) {
+            0 => ConsoleRegion { region: rng.below(4) as u8 }.into(),
+            1 => GameTitle { title: rng.string() }.into(),
+            2 => Attribution { kind: 1 + rng.below(5) as u8, name: rng.string() }.into(),
+            3 => TotalFrames { frames: rng.next() as u32 }.into(),
+            4 => Rerecords { rerecords: rng.next() as u32 }.into(),
+            5 => PortController { port: 1 + rng.below(4) as u8, kind: 0x0101 + rng.below(3) as u16 }.into(),
+            6 => NesLatchFilter { time: rng.next() as u16 }.into(),
+            7 => NesClockFilter { time: rng.next() as u8 }.into(),
+            8 => SnesLatchFilter { time: rng.next() as u16 }.into(),
+            9 => SnesClockFilter { time: rng.next() as u8 }.into(),
+            _ => InputChunk { port: 1 + rng.below(4) as u8, inputs: (0..rng.below(600)).map(|_| rng.next() as u8).collect() }.into(),
+        };
+        file.packets.push(packet);
+    }
+    
+    file
+}
+
+/// Encodes `file`, with some payload lengths written using a larger exponent than needed.
+fn encode_loosely(file: &TasdFile, rng: &mut Rng) -> Vec<u8> {
+    let keylen = file.keylen as usize;
+    let encoded = file.encode();
+    let mut data = encoded[..7].to_vec();
+    
+    let mut pos = 7;
+    while pos < encoded.len() {
+        let exp = encoded[pos + keylen] as usize;
+        let mut plen = [0u8; 8];
+        plen[(8 - exp)..].copy_from_slice(&encoded[(pos + keylen + 1)..(pos + keylen + 1 + exp)]);
+        let plen = u64::from_be_bytes(plen);
+        
+        let new_exp = exp.max(1) + rng.below((9 - exp.max(1)) as u64) as usize;
+        data.extend_from_slice(&encoded[pos..(pos + keylen)]);
+        data.push(new_exp as u8);
+        data.extend_from_slice(&plen.to_be_bytes()[(8 - new_exp)..]);
+        
+        let payload = pos + keylen + 1 + exp;
+        data.extend_from_slice(&encoded[payload..(payload + plen as usize)]);
+        pos = payload + plen as usize;
+    }
+    
+    data
+}
+
+#[test]
+#[ignore = "requires a reference implementation, see the module docs"]
+fn matches_reference() {
+    let reference = std::env::var("TASD_REFERENCE").expect("TASD_REFERENCE isn't set");
+    let mut reference = reference.split_whitespace();
+    let program = reference.next().expect("TASD_REFERENCE is empty");
+    let args: Vec<&str> = reference.collect();
+    let cases: u64 = std::env::var("TASD_REFERENCE_CASES").map_or(200, |cases| cases.parse().unwrap());
+    let seed: u64 = std::env::var("TASD_REFERENCE_SEED").map_or(1, |seed| seed.parse().unwrap());
+    
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential");
+    std::fs::create_dir_all(&dir).unwrap();
+    for seed in seed..(seed + cases) {
+        let mut rng = Rng(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1);
+        let file = generate(&mut rng);
+        let input = dir.join(format!("{seed}.tasd"));
+        let output = dir.join(format!("{seed}.out.tasd"));
+        std::fs::write(&input, encode_loosely(&file, &mut rng)).unwrap();
+        
+        let status = Command::new(program).args(&args).arg(&input).arg(&output).status().unwrap();
+        assert!(status.success(), "reference rejected {} (seed {seed})", input.display());
+        
+        let ours = TasdFile::parse_file(&input).unwrap();
+        let theirs = TasdFile::parse_file(&output).unwrap_or_else(|err| panic!("failed to parse {} (seed {seed}): {err:?}", output.display()));
+        assert_eq!(ours.packets, file.packets, "seed {seed}");
+        assert_eq!(theirs.packets, ours.packets, "reference disagrees on {} (seed {seed})", input.display());
+        
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+}
+
+/// Runs the harness against this crate itself, so it's known to work before a reference is plugged in.
+#[test]
+fn generated_files_round_trip() {
+    for seed in 1..50 {
+        let mut rng = Rng(seed);
+        let file = generate(&mut rng);
+        let parsed = TasdFile::parse_slice(&encode_loosely(&file, &mut rng)).unwrap();
+        
+        assert_eq!(parsed.keylen, file.keylen);
+        assert_eq!(parsed.packets, file.packets);
+    }
+}
+
+/// Compares against version 0.6 of the original `tasd` crate, which is built in with the `compat` feature.
+#[cfg(feature = "compat")]
+#[test]
+fn matches_tasd_0_6() {
+    use tasd::spec::packets::Encode;
+    
+    for seed in 1..200 {
+        let mut rng = Rng(seed);
+        let mut file = generate(&mut rng);
+        file.keylen = 2; // the only key length the other crate supports
+        let data = encode_loosely(&file, &mut rng);
+        
+        let theirs = tasd06::TasdFile::parse_slice(&data).unwrap_or_else(|err| panic!("tasd 0.6 rejected seed {seed}: {err:?}"));
+        assert_eq!(TasdFile::from(theirs.clone()).packets, file.packets, "seed {seed}");
+        assert_eq!(theirs.encode().unwrap(), file.encode(), "seed {seed}");
+        
+        // the other crate can't decode empty payloads, which are written with an exponent of 0
+        let empty = file.packets.iter().any(|packet| packet.encode(2)[2] == 0);
+        match tasd06::TasdFile::try_from(file) {
+            Ok(converted) => assert_eq!(converted, theirs, "seed {seed}"),
+            Err(err) => assert!(empty, "seed {seed}: {err:?}"),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+use std::process::Command;
+
+/// The core spec has to build without any optional dependencies, for embedded users who only need the parser.
+#[test]
+fn builds_without_default_features() {
+    let status = Command::new(env!("CARGO"))
+        .args(["check", "--lib", "--no-default-features", "--manifest-path", concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")])
+        .env("CARGO_TARGET_DIR", concat!(env!("CARGO_TARGET_TMPDIR"), "/no-default-features"))
+        .status()
+        .unwrap();
+    
+    assert!(status.success());
+}
\ No newline at end of file
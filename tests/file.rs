@@ -0,0 +1,232 @@
+use tasd::lookup::controller_input_size;
+use tasd::spec::packets::{Bookmark, Comment, ConsoleType, Decode, EditHistory, Encode, GameIdentifier, GameTitle, Index, InputChunk, InputDelta, InputMoment, KeyedPacket, LagFrameChunk, LinkSession, MemoryInit, MovieFile, MovieTransition, Packet, PacketKind, Padding, PortController, SnesLatchTrain, TableOfContents, TocEntry, TotalFrames, Transition, TransitionKind, Unsupported, KEY_GAME_TITLE};
+use tasd::input::RechunkError;
+use tasd::spec::borrowed::PacketRefs;
+use tasd::spec::stream::StreamParser;
+use tasd::spec::{TasdError, TasdFile};
+
+fn sample() -> TasdFile {
+    let mut file = TasdFile::default();
+    file.packets.push(GameTitle { title: "Super Mario Bros.".into() }.into());
+    file.packets.push(Comment { comment: "first".into() }.into());
+    file.packets.push(Comment { comment: "second".into() }.into());
+    
+    file
+}
+
+#[test]
+fn checksums() {
+    let mut file = sample();
+    file.add_checksums();
+    assert_eq!(file.packets.len(), 6);
+    assert!(matches!(file.packets[1], Packet::PacketChecksum(_)));
+    assert!(matches!(file.packets[5], Packet::PacketChecksum(_)));
+    
+    // regenerating shouldn't duplicate checksums
+    file.add_checksums();
+    assert_eq!(file.packets.len(), 6);
+    
+    let data = file.encode();
+    let (parsed, report) = TasdFile::parse_slice_with_report(&data).unwrap();
+    assert!(report.is_clean());
+    assert_eq!(parsed.packets, file.packets);
+    
+    // corrupt the last byte of the "first" comment
+    let mut corrupt = data.clone();
+    let pos = corrupt.windows(5).position(|w| w == b"first").unwrap() + 4;
+    corrupt[pos] = b'X';
+    let (parsed, report) = TasdFile::parse_slice_with_report(&corrupt).unwrap();
+    assert_eq!(parsed.packets.len(), 6);
+    assert_eq!(report.corrupted.len(), 1);
+    assert_eq!(report.corrupted[0].index, 2);
+    assert_eq!(parsed.packets[2], Comment { comment: "firsX".into() }.into());
+    
+    file.remove_checksums();
+    assert_eq!(file.packets, sample().packets);
+    assert!(!file.packets.iter().any(|packet| matches!(packet, Packet::PacketChecksum(_))));
+}
+
+#[test]
+fn total_frames() {
+    let mut file = TasdFile::default();
+    file.packets.push(PortController { port: 1, kind: 0x0101 }.into());
+    file.packets.push(PortController { port: 2, kind: 0x0201 }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 10] }.into());
+    file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 16] }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 2] }.into());
+    
+    assert_eq!(file.input_frames().into_iter().collect::<Vec<_>>(), vec![(1, 12), (2, 8)]);
+    assert_eq!(file.recompute_total_frames(), Some(12));
+    assert_eq!(file.packets[2], TotalFrames { frames: 12 }.into());
+    
+    file.packets.push(TotalFrames { frames: 1 }.into());
+    file.packets.push(InputChunk { port: 2, inputs: vec![0xFF; 16] }.into());
+    assert_eq!(file.recompute_total_frames(), Some(16));
+    assert_eq!(file.packets.iter().filter(|packet| matches!(packet, Packet::TotalFrames(_))).count(), 1);
+    assert_eq!(file.packets[2], TotalFrames { frames: 16 }.into());
+    
+    assert_eq!(TasdFile::default().recompute_total_frames(), None);
+}
+
+#[test]
+fn typed_lookup() {
+    let mut file = sample();
+    assert_eq!(file.first::<GameTitle>().unwrap().title, "Super Mario Bros.");
+    assert_eq!(file.all::<Comment>().map(|c| c.comment.as_str()).collect::<Vec<_>>(), vec!["first", "second"]);
+    assert!(file.first::<TotalFrames>().is_none());
+    
+    file.first_mut::<Comment>().unwrap().comment = "changed".into();
+    assert_eq!(file.packets[1], Comment { comment: "changed".into() }.into());
+    
+    assert_eq!(GameTitle::KEY, KEY_GAME_TITLE);
+    assert_eq!(GameTitle::KIND, PacketKind::GameTitle);
+    let title = file.first::<GameTitle>().unwrap();
+    assert_eq!(title.key(), KEY_GAME_TITLE);
+    assert_eq!(title.kind(), PacketKind::GameTitle);
+}
+
+#[test]
+fn bookmarks() {
+    let mut file = sample();
+    file.add_bookmark(3900, "warp");
+    file.add_bookmark(120, "start");
+    file.add_bookmark(5000, "warp");
+    assert_eq!(file.packets[3], Bookmark { frame: 3900, name: "warp".into() }.into());
+    
+    let parsed = TasdFile::parse_slice(&file.encode()).unwrap();
+    assert_eq!(parsed.packets, file.packets);
+    assert_eq!(parsed.bookmark("start"), Some(&Bookmark { frame: 120, name: "start".into() }));
+    assert_eq!(parsed.bookmark("warp").unwrap().frame, 3900);
+    assert_eq!(parsed.bookmark("missing"), None);
+    assert_eq!(parsed.bookmarks().iter().map(|bookmark| bookmark.frame).collect::<Vec<_>>(), vec![120, 3900, 5000]);
+    
+    assert_eq!(file.remove_bookmark("warp"), 2);
+    assert_eq!(file.bookmarks().len(), 1);
+}
+
+#[test]
+fn controller_input_sizes() {
+    // N64 Densha de Go controllers have the same 4 bytes per frame as the standard controller
+    assert_eq!(controller_input_size(0x0308), Some(4));
+    assert_eq!(controller_input_size(0x0308), controller_input_size(0x0301));
+    assert_eq!(controller_input_size(0x0306), None);
+}
+
+#[test]
+fn rechunk_inputs_for_device() {
+    let mut file = TasdFile::default();
+    file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0x01; 6] }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0x02; 8] }.into());
+    file.packets.push(Comment { comment: "split".into() }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0x03; 2] }.into());
+    
+    let mut rechunked = file.clone();
+    assert_eq!(rechunked.rechunk_inputs_for_device(10, 1), Ok(5));
+    assert!(rechunked.packets.iter().all(|packet| packet.encode(rechunked.keylen).len() <= 10));
+    let lens: Vec<_> = rechunked.all::<InputChunk>().map(|chunk| chunk.inputs.len()).collect();
+    assert_eq!(lens, vec![4, 4, 4, 2, 2]);
+    assert_eq!(rechunked.packets[5], Comment { comment: "split".into() }.into());
+    assert_eq!(rechunked.input_bytes(), file.input_bytes());
+    
+    let mut rechunked = file.clone();
+    assert_eq!(rechunked.rechunk_inputs_for_device(300, 1), Ok(2));
+    assert_eq!(rechunked.first::<InputChunk>().unwrap().inputs.len(), 14);
+    
+    assert_eq!(file.clone().rechunk_inputs_for_device(6, 4), Err(RechunkError::BufferTooSmall { port: 1, required: 9 }));
+    
+    // port 1 fits, but port 2's 8 byte frames don't, so nothing is changed
+    file.packets.push(PortController { port: 2, kind: 0x0401 }.into());
+    file.packets.push(InputChunk { port: 2, inputs: vec![0x04; 16] }.into());
+    let mut failed = file.clone();
+    assert_eq!(failed.rechunk_inputs_for_device(10, 1), Err(RechunkError::BufferTooSmall { port: 2, required: 13 }));
+    assert_eq!(failed, file);
+    
+    // chunks are split where the frame size changes, rather than cutting a frame in half
+    let mut file = TasdFile::default();
+    file.packets.push(PortController { port: 1, kind: 0x0201 }.into());
+    file.packets.push(Transition { port: 1, index: Index::Frame(3), kind: TransitionKind::PacketDerived(Box::new(PortController { port: 1, kind: 0x0203 }.into())) }.into());
+    file.packets.push(InputChunk { port: 1, inputs: vec![0x01; 18] }.into());
+    let mut rechunked = file.clone();
+    assert_eq!(rechunked.rechunk_inputs_for_device(13, 1), Ok(3));
+    let lens: Vec<_> = rechunked.all::<InputChunk>().map(|chunk| chunk.inputs.len()).collect();
+    assert_eq!(lens, vec![6, 8, 4]);
+    
+    // frames can't be located without knowing the controller's input size
+    file.packets.push(InputChunk { port: 2, inputs: vec![0x02; 4] }.into());
+    assert_eq!(file.clone().rechunk_inputs_for_device(13, 1), Err(RechunkError::UnknownInputSize(2)));
+    file.packets.insert(0, PortController { port: 2, kind: 0xFFFF }.into());
+    assert_eq!(file.clone().rechunk_inputs_for_device(13, 1), Err(RechunkError::UnknownInputSize(2)));
+}
+
+#[test]
+fn newer_spec_packets() {
+    let mut file = sample();
+    let newer = Unsupported { key: vec![0x00, 0x7F], payload: vec![1, 2, 3] };
+    file.packets.insert(1, newer.clone().into());
+    file.packets.push(Unsupported { key: vec![0xFF, 0xC0], payload: vec![] }.into());
+    
+    let mut data = file.encode();
+    data[4..6].copy_from_slice(&[0x00, 0x02]);
+    let mut parsed = TasdFile::parse_slice(&data).unwrap();
+    assert!(parsed.is_newer_version());
+    let packets = parsed.newer_spec_packets();
+    assert_eq!(packets.len(), 1);
+    assert_eq!((packets[0].index, packets[0].key.clone()), (1, newer.key.clone()));
+    
+    // edits keep the packet unchanged
+    parsed.canonicalize();
+    parsed.trim(0..1).unwrap();
+    parsed.set_port_inputs(1, vec![0x00]);
+    let reparsed = TasdFile::parse_slice(&parsed.encode()).unwrap();
+    assert_eq!(reparsed.all::<Unsupported>().next(), Some(&newer));
+    assert!(!reparsed.is_newer_version());
+}
+
+#[test]
+fn nested_packets_with_keylen_3() {
+    let mut file = sample();
+    file.keylen = 3;
+    file.packets.push(Transition { port: 1, index: Index::Frame(2), kind: TransitionKind::PacketDerived(Box::new(PortController { port: 1, kind: 0x0102 }.into())) }.into());
+    file.packets.push(MovieTransition { movie_frame: 3, kind: TransitionKind::PacketDerived(Box::new(Comment { comment: "nested".into() }.into())) }.into());
+    
+    let data = file.encode();
+    let parsed = TasdFile::parse_slice(&data).unwrap();
+    assert_eq!(parsed.keylen, 3);
+    assert_eq!(parsed.packets, file.packets);
+    
+    let streamed = StreamParser::new(data.as_slice()).unwrap().collect::<Result<Vec<Packet>, TasdError>>().unwrap();
+    assert_eq!(streamed, file.packets);
+    let borrowed = PacketRefs::new(&data).unwrap().map(|packet| packet.unwrap().into_owned()).collect::<Vec<_>>();
+    assert_eq!(borrowed, file.packets);
+}
+
+#[test]
+fn estimated_encoded_size() {
+    let mut file = sample();
+    file.packets.push(InputChunk { port: 1, inputs: vec![0xFF; 70_000] }.into());
+    file.packets.push(MovieFile { name: "é".repeat(200), data: vec![1, 2, 3] }.into());
+    file.packets.push(Transition { port: 1, index: Index::Frame(2), kind: TransitionKind::SoftReset }.into());
+    file.packets.push(Unsupported { key: vec![0x7F, 0x01], payload: vec![0; 300] }.into());
+    file.packets.push(ConsoleType { kind: 0xFF, custom: Some("custom".into()) }.into());
+    file.packets.push(MemoryInit { data_type: 0xFF, device: 0x0101, required: true, name: "é".repeat(200), data: Some(vec![0; 5]) }.into());
+    file.packets.push(GameIdentifier { kind: 0x01, encoding: 0x01, name: "md5".into(), identifier: vec![0; 16] }.into());
+    file.packets.push(SnesLatchTrain { points: vec![1, 2, 3] }.into());
+    file.packets.push(InputMoment { port: 1, index: Index::Frame(3), inputs: vec![0xFF; 2] }.into());
+    file.packets.push(Transition { port: 1, index: Index::Frame(4), kind: Packet::from(PortController { port: 1, kind: 0x0101 }).into() }.into());
+    file.packets.push(LagFrameChunk { movie_frame: 1, count: 2 }.into());
+    file.packets.push(MovieTransition { movie_frame: 5, kind: TransitionKind::PowerReset }.into());
+    file.packets.push(LinkSession { consoles: vec![0x05, 0x05] }.into());
+    file.packets.push(InputDelta { port: 1, base: 0, len: 10, changes: vec![(1, 0xFF), (4, 0x01)] }.into());
+    file.packets.push(TableOfContents { entries: vec![TocEntry { kind: 0x01, port: 1, offset: 7 }] }.into());
+    file.packets.push(Padding { len: 300 }.into());
+    file.packets.push(EditHistory { epoch: 0, author: "author".into(), changed: vec![KEY_GAME_TITLE.to_vec()] }.into());
+    for packet in &file.packets {
+        assert_eq!(packet.encoded_len(2), packet.encode(2).len(), "{packet:?}");
+    }
+    assert_eq!(file.estimated_encoded_size(), file.encode().len());
+    
+    file.keylen = 3;
+    assert_eq!(file.estimated_encoded_size(), file.encode().len());
+    assert_eq!(TasdFile::default().estimated_encoded_size(), 7);
+}
\ No newline at end of file
@@ -1,4 +1,5 @@
-use tasd::spec::packets::{Attribution, Category, ConsoleRegion, ConsoleType, Encode, GameTitle, Packet, RomName};
+use tasd::spec::packets::{Attribution, BlankFrames, Bookmark, Category, Comment, ConsoleRegion, ConsoleType, DumpCreated, DumpLastModified, EmulatorCore, EmulatorName, EmulatorVersion, Encode, Experimental, GameIdentifier, GameTitle, GenesisGameGenieCode, Index, InputChunk, InputMoment, LagFrameChunk, MAX_TRANSITION_DEPTH, MemoryInit, MovieFile, MovieLicense, MovieTransition, NesClockFilter, NesGameGenieCode, NesLatchFilter, OpaqueKind, OtherTransition, Packet, PACKET_KEYS, PacketError, PortController, PortOverread, Region, Rerecords, RomName, SnesClockFilter, SnesGameGenieCode, SnesLatchFilter, SnesLatchTrain, SourceLink, TasLastModified, TotalFrames, Transition, TransitionKind, Unspecified, Unsupported, ValueError, Verified};
+use tasd::spec::reader::Reader;
 use tasd::spec::writer::Writer;
 
 /// Small wrapper around [`Writer`] for creating a packet using a key and some data.
@@ -9,6 +10,11 @@ fn packet<D: AsRef<[u8]>>(key: &[u8], data: D) -> Vec<u8> {
     w.into_packet(key, key.len() as u8)
 }
 
+/// Decodes a single packet from the provided bytes.
+fn decode(data: &[u8], keylen: u8) -> Packet {
+    Packet::with_reader(&mut Reader::new(&data), keylen).unwrap()
+}
+
 macro_rules! assert_packet {
     ($packet:expr, $key:expr, $data:expr) => {
         assert_eq!(
@@ -18,6 +24,66 @@ macro_rules! assert_packet {
     };
 }
 
+/// Asserts that decoding a packet with `data` as its payload fails with [`PacketError::InvalidPayload`].
+macro_rules! assert_invalid {
+    ($key:expr, $data:expr) => {
+        let data: Vec<u8> = $data.into();
+        assert_eq!(
+            Packet::with_reader(&mut Reader::new(&packet(&$key, &data)), $key.len() as u8),
+            Err(PacketError::InvalidPayload { key: $key.to_vec(), payload: data }),
+        );
+    };
+}
+
+/// Generates tests for packets made of a single string taking up the whole payload: empty, non-ASCII, and longer than
+/// any length prefix could hold. Invalid UTF-8 is replaced rather than rejected.
+macro_rules! string_packet_tests {
+    ($($test:ident: $packet:ident { $field:ident } => $key:expr;)*) => {$(
+        #[test]
+        fn $test() {
+            for text in ["", "a", "スーパーマリオ", &"s".repeat(300)] {
+                assert_packet!($packet { $field: text.into() }, $key, text.as_bytes());
+                assert_eq!(decode(&packet(&$key, text), 2), $packet { $field: text.into() }.into());
+            }
+            assert_eq!(decode(&packet(&$key, [b'a', 0xFF, b'b']), 2), $packet { $field: "a\u{FFFD}b".into() }.into());
+        }
+    )*};
+}
+
+/// Generates tests for packets made of a single integer: its minimum and maximum values, and payloads one byte too
+/// short or too long.
+macro_rules! int_packet_tests {
+    ($($test:ident: $packet:ident { $field:ident: $ty:ty } => $key:expr;)*) => {$(
+        #[test]
+        fn $test() {
+            for value in [<$ty>::MIN, 0, 1, <$ty>::MAX] {
+                assert_packet!($packet { $field: value }, $key, value.to_be_bytes());
+                assert_eq!(decode(&packet(&$key, value.to_be_bytes()), 2), $packet { $field: value }.into());
+            }
+            
+            let size = std::mem::size_of::<$ty>();
+            for len in [0, size - 1, size + 1] {
+                assert_invalid!($key, vec![0xFF; len]);
+            }
+        }
+    )*};
+}
+
+/// Generates tests for packets made of a single boolean, where any non-zero byte is true.
+macro_rules! bool_packet_tests {
+    ($($test:ident: $packet:ident { $field:ident } => $key:expr;)*) => {$(
+        #[test]
+        fn $test() {
+            assert_packet!($packet { $field: false }, $key, [0x00]);
+            assert_packet!($packet { $field: true }, $key, [0x01]);
+            assert_eq!(decode(&packet(&$key, [0x80]), 2), $packet { $field: true }.into());
+            
+            assert_invalid!($key, []);
+            assert_invalid!($key, [0x01, 0x00]);
+        }
+    )*};
+}
+
 #[test]
 fn console_type() {
     assert_packet!(ConsoleType { kind: 0x01, custom: None }, [0x00, 0x01], [0x01]);
@@ -26,11 +92,21 @@ fn console_type() {
     assert_packet!(ConsoleType { kind: 0xFF, custom: Some("something".into()) }, [0x00, 0x01], [&[0xFF], "something".as_bytes()].concat());
 }
 
+#[test]
+fn padded_key() {
+    let packet = Packet::from(ConsoleType { kind: 0x01, custom: None });
+    assert_eq!(decode(&packet.encode(4), 4), packet);
+}
+
 #[test]
 fn console_region() {
     assert_packet!(ConsoleRegion { region: 0x01 }, [0x00, 0x02], [0x01]);
     assert_packet!(ConsoleRegion { region: 0x02 }, [0x00, 0x02], [0x02]);
     assert_packet!(ConsoleRegion { region: 0xFF }, [0x00, 0x02], [0xFF]);
+    
+    assert_eq!(ConsoleRegion::new(Region::Pal), ConsoleRegion { region: 0x02 });
+    assert_eq!(ConsoleRegion { region: 0x01 }.region(), Some(Region::Ntsc));
+    assert_eq!(ConsoleRegion { region: 0xFF }.region(), None);
 }
 
 #[test]
@@ -58,172 +134,295 @@ fn category() {
     assert_packet!(Category { category: "any%, no jumps, invisible hud, 2 players".into() }, [0x00, 0x06], "any%, no jumps, invisible hud, 2 players".as_bytes());
 }
 
-#[test]
-fn emulator_name() {
-    
+string_packet_tests! {
+    emulator_name: EmulatorName { name } => [0x00, 0x07];
+    emulator_version: EmulatorVersion { version } => [0x00, 0x08];
+    emulator_core: EmulatorCore { core } => [0x00, 0x09];
 }
 
-#[test]
-fn emulator_version() {
-    
+int_packet_tests! {
+    tas_last_modified: TasLastModified { epoch: i64 } => [0x00, 0x0A];
+    dump_created: DumpCreated { epoch: i64 } => [0x00, 0x0B];
+    dump_last_modified: DumpLastModified { epoch: i64 } => [0x00, 0x0C];
+    total_frames: TotalFrames { frames: u32 } => [0x00, 0x0D];
+    rerecords: Rerecords { rerecords: u32 } => [0x00, 0x0E];
 }
 
-#[test]
-fn emulator_core() {
-    
+string_packet_tests! {
+    source_link: SourceLink { link } => [0x00, 0x0F];
 }
 
 #[test]
-fn tas_last_modified() {
+fn blank_frames() {
+    assert_packet!(BlankFrames { frames: -2 }, [0x00, 0x10], [0xFF, 0xFE]);
     
+    assert_eq!(BlankFrames::new(-2), Ok(BlankFrames { frames: -2 }));
+    assert_eq!(BlankFrames::new(0), Err(ValueError::ZeroBlankFrames));
 }
 
-#[test]
-fn dump_created() {
-    
+bool_packet_tests! {
+    verified: Verified { verified } => [0x00, 0x11];
 }
 
 #[test]
-fn dump_last_modified() {
+fn memory_init() {
+    assert_packet!(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "RAM".into(), data: None }, [0x00, 0x12], [&[0x02, 0x01, 0x01, 0x01, 0x03], "RAM".as_bytes()].concat());
+    assert_packet!(MemoryInit { data_type: 0xFF, device: 0x0102, required: false, name: "".into(), data: Some(vec![0xAA, 0x55]) }, [0x00, 0x12], [0xFF, 0x01, 0x02, 0x00, 0x00, 0xAA, 0x55]);
     
-}
-
-#[test]
-fn total_frames() {
+    let name = "n".repeat(255);
+    assert_packet!(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: name.clone(), data: None }, [0x00, 0x12], [&[0x02, 0x01, 0x01, 0x01, 0xFF], name.as_bytes()].concat());
+    assert_packet!(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: "n".repeat(256), data: None }, [0x00, 0x12], [&[0x02, 0x01, 0x01, 0x01, 0xFF], name.as_bytes()].concat());
     
-}
-
-#[test]
-fn rerecords() {
+    // 128 two-byte characters; the last one must be dropped entirely rather than split
+    let name = "é".repeat(128);
+    assert_packet!(MemoryInit { data_type: 0x02, device: 0x0101, required: true, name: name.clone(), data: None }, [0x00, 0x12], [&[0x02, 0x01, 0x01, 0x01, 0xFE], &name.as_bytes()[..254]].concat());
     
+    for packet in [
+        MemoryInit { data_type: 0x05, device: 0x0201, required: false, name: "n".repeat(255), data: None },
+        MemoryInit { data_type: 0xFF, device: 0xFFFF, required: true, name: "custom".into(), data: Some(vec![0x00, 0x11, 0x22]) },
+    ] {
+        assert_eq!(decode(&Packet::from(packet.clone()).encode(2), 2), Packet::MemoryInit(packet));
+    }
 }
 
 #[test]
-fn source_link() {
+fn game_identifier() {
+    assert_packet!(GameIdentifier { kind: 0x01, encoding: 0x02, name: "".into(), identifier: vec![] }, [0x00, 0x13], [0x01, 0x02, 0x00]);
+    assert_packet!(GameIdentifier { kind: 0x01, encoding: 0x02, name: "md5".into(), identifier: vec![0xAB, 0xCD] }, [0x00, 0x13], [&[0x01, 0x02, 0x03], "md5".as_bytes(), &[0xAB, 0xCD]].concat());
     
-}
-
-#[test]
-fn blank_frames() {
+    let name = "n".repeat(255);
+    assert_packet!(GameIdentifier { kind: 0xFF, encoding: 0xFF, name: "n".repeat(256), identifier: vec![0x01] }, [0x00, 0x13], [&[0xFF, 0xFF, 0xFF], name.as_bytes(), &[0x01]].concat());
+    let packet = GameIdentifier { kind: 0xFF, encoding: 0xFF, name, identifier: vec![0x01] };
+    assert_eq!(decode(&Packet::from(packet.clone()).encode(2), 2), Packet::GameIdentifier(packet));
     
+    assert_invalid!([0x00, 0x13], [0x01, 0x02]);
+    // name length past the end of the payload
+    assert_invalid!([0x00, 0x13], [0x01, 0x02, 0x02, b'n']);
 }
 
-#[test]
-fn verified() {
-    
+string_packet_tests! {
+    movie_license: MovieLicense { license } => [0x00, 0x14];
 }
 
 #[test]
-fn memory_init() {
+fn movie_file() {
+    assert_packet!(MovieFile { name: "movie.bk2".into(), data: vec![0x01, 0x02] }, [0x00, 0x15], [&[0x09], "movie.bk2".as_bytes(), &[0x01, 0x02]].concat());
     
-}
-
-#[test]
-fn game_identifier() {
+    let name = "m".repeat(255);
+    assert_packet!(MovieFile { name: name.clone(), data: vec![0x5A] }, [0x00, 0x15], [&[0xFF], name.as_bytes(), &[0x5A]].concat());
+    assert_packet!(MovieFile { name: "m".repeat(256), data: vec![0x5A] }, [0x00, 0x15], [&[0xFF], name.as_bytes(), &[0x5A]].concat());
     
-}
-
-#[test]
-fn movie_license() {
+    let name = "é".repeat(128);
+    assert_packet!(MovieFile { name: name.clone(), data: vec![0x5A] }, [0x00, 0x15], [&[0xFE], &name.as_bytes()[..254], &[0x5A]].concat());
     
-}
-
-#[test]
-fn movie_file() {
+    let packet = MovieFile { name: "m".repeat(255), data: vec![0x01, 0x02, 0x03] };
+    assert_eq!(decode(&Packet::from(packet.clone()).encode(2), 2), Packet::MovieFile(packet));
     
+    // name length past the end of the payload
+    let result = Packet::with_reader(&mut Reader::new(&self::packet(&[0x00, 0x15], [0x05, b'm'])), 2);
+    assert!(matches!(result, Err(PacketError::InvalidPayload { .. })));
 }
 
 #[test]
 fn port_controller() {
+    assert_packet!(PortController { port: 1, kind: 0x0101 }, [0x00, 0xF0], [0x01, 0x01, 0x01]);
+    assert_eq!(PortController::new(1, 0x0101), Ok(PortController { port: 1, kind: 0x0101 }));
+    assert_eq!(PortController::new(0, 0x0101), Err(ValueError::ZeroPort));
     
+    // fixed size payloads must be exactly the right length
+    for data in [&[0x01, 0x01][..], &[0x01, 0x01, 0x01, 0x00]] {
+        let result = Packet::with_reader(&mut Reader::new(&packet(&[0x00, 0xF0], data)), 2);
+        assert_eq!(result, Err(PacketError::InvalidPayload { key: vec![0x00, 0xF0], payload: data.to_vec() }));
+    }
 }
 
-#[test]
-fn nes_latch_filter() {
-    
-}
-
-#[test]
-fn nes_clock_filter() {
-    
+int_packet_tests! {
+    nes_latch_filter: NesLatchFilter { time: u16 } => [0x01, 0x01];
+    nes_clock_filter: NesClockFilter { time: u8 } => [0x01, 0x02];
+    snes_latch_filter: SnesLatchFilter { time: u16 } => [0x02, 0x01];
+    snes_clock_filter: SnesClockFilter { time: u8 } => [0x02, 0x02];
 }
 
 #[test]
 fn nes_overread() {
+    // overread is per port since PORT_OVERREAD replaced the console specific packets
+    assert_packet!(PortOverread { port: 1, overread: true }, [0x00, 0xF1], [0x01, 0x01]);
+    assert_packet!(PortOverread { port: 0xFF, overread: false }, [0x00, 0xF1], [0xFF, 0x00]);
+    assert_invalid!([0x00, 0xF1], [0x01]);
+    assert_invalid!([0x00, 0xF1], [0x01, 0x01, 0x00]);
     
+    assert_eq!(decode(&packet(&[0x01, 0x03], [0x01]), 2), Packet::Unsupported(Unsupported { key: vec![0x01, 0x03], payload: vec![0x01] }));
 }
 
-#[test]
-fn nes_game_genie_code() {
-    
-}
-
-#[test]
-fn snes_clock_filter() {
-    
+string_packet_tests! {
+    nes_game_genie_code: NesGameGenieCode { code } => [0x01, 0x04];
 }
 
 #[test]
 fn snes_overread() {
-    
+    assert_eq!(decode(&packet(&[0x02, 0x03], [0x01]), 2), Packet::Unsupported(Unsupported { key: vec![0x02, 0x03], payload: vec![0x01] }));
 }
 
-#[test]
-fn snes_game_genie_code() {
-    
+string_packet_tests! {
+    snes_game_genie_code: SnesGameGenieCode { code } => [0x02, 0x04];
 }
 
 #[test]
 fn snes_latch_train() {
+    assert_packet!(SnesLatchTrain { points: vec![] }, [0x02, 0x05], []);
+    assert_packet!(SnesLatchTrain { points: vec![0, u64::MAX] }, [0x02, 0x05], [[0x00; 8], [0xFF; 8]].concat());
     
+    // every point is 8 bytes, so partial points are rejected rather than dropped
+    assert_invalid!([0x02, 0x05], [0x00; 7]);
+    assert_invalid!([0x02, 0x05], [0x00; 9]);
 }
 
-#[test]
-fn genesis_game_genie_code() {
-    
+string_packet_tests! {
+    genesis_game_genie_code: GenesisGameGenieCode { code } => [0x08, 0x04];
 }
 
 #[test]
 fn input_chunk() {
-    
+    assert_packet!(InputChunk { port: 1, inputs: vec![] }, [0xFE, 0x01], [0x01]);
+    assert_packet!(InputChunk { port: 0xFF, inputs: vec![0x00, 0xFF, 0x7F] }, [0xFE, 0x01], [0xFF, 0x00, 0xFF, 0x7F]);
+    assert_invalid!([0xFE, 0x01], []);
 }
 
 #[test]
 fn input_moment() {
+    assert_packet!(InputMoment { port: 1, index: Index::Frame(0), inputs: vec![] }, [0xFE, 0x02], [&[0x01, 0x01], &[0x00; 8][..]].concat());
+    assert_packet!(InputMoment { port: 2, index: Index::CycleCount(u64::MAX), inputs: vec![0xAA] }, [0xFE, 0x02], [&[0x02, 0x02], &[0xFF; 8][..], &[0xAA]].concat());
+    assert_packet!(InputMoment { port: 1, index: Index::Other { kind: 0x20, value: 1 }, inputs: vec![] }, [0xFE, 0x02], [&[0x01, 0x20], &1u64.to_be_bytes()[..]].concat());
+    assert_invalid!([0xFE, 0x02], [0x01; 9]);
     
+    // indexes are only ordered within the same unit
+    assert!(Index::Frame(1) < Index::Frame(2));
+    assert!(Index::Milliseconds(1) > Index::Milliseconds(0));
+    assert_eq!(Index::Frame(1).partial_cmp(&Index::Milliseconds(2)), None);
+    assert_eq!(Index::new(0x05, 3), Index::ChunkIndex(3));
 }
 
 #[test]
 fn transition() {
+    assert_packet!(Transition { port: 1, index: Index::Frame(u64::MAX), kind: TransitionKind::SoftReset }, [0xFE, 0x03], [&[0x01, 0x01], &[0xFF; 8][..], &[0x01]].concat());
+    
+    let title = GameTitle { title: "title".into() };
+    let nested = Packet::from(title.clone()).encode(2);
+    let transition = Transition { port: 2, index: Index::CycleCount(0), kind: TransitionKind::PacketDerived(Box::new(title.into())) };
+    assert_packet!(transition.clone(), [0xFE, 0x03], [&[0x02, 0x02], &[0x00; 8][..], &[0xFF], &nested].concat());
+    assert_eq!(decode(&Packet::from(transition.clone()).encode(2), 2), Packet::Transition(transition));
     
+    // trailing data is only meaningful for packet transitions, and is ignored otherwise
+    let decoded = decode(&packet(&[0xFE, 0x03], [&[0x01, 0x01], &[0x00; 8][..], &[0x01, 0xAA]].concat()), 2);
+    assert_eq!(decoded, Transition { port: 1, index: Index::Frame(0), kind: TransitionKind::SoftReset }.into());
+    
+    assert_invalid!([0xFE, 0x03], [0x01; 10]);
+    assert!(Packet::with_reader(&mut Reader::new(&packet(&[0xFE, 0x03], [&[0x01, 0x01], &[0x00; 8][..], &[0xFF]].concat())), 2).is_err());
 }
 
 #[test]
 fn lag_frame_chunk() {
-    
+    assert_packet!(LagFrameChunk { movie_frame: 0, count: u32::MAX }, [0xFE, 0x04], [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_invalid!([0xFE, 0x04], [0x00; 7]);
+    assert_invalid!([0xFE, 0x04], [0x00; 9]);
 }
 
 #[test]
 fn movie_transition() {
+    assert_packet!(MovieTransition { movie_frame: u32::MAX, kind: TransitionKind::SoftReset }, [0xFE, 0x05], [0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+    assert_packet!(MovieTransition { movie_frame: 1, kind: TransitionKind::RestartFile }, [0xFE, 0x05], [0x00, 0x00, 0x00, 0x01, 0x03]);
+    assert_packet!(MovieTransition { movie_frame: 1, kind: TransitionKind::Other(OtherTransition::new(0x10).unwrap()) }, [0xFE, 0x05], [0x00, 0x00, 0x00, 0x01, 0x10]);
+    assert_eq!(TransitionKind::from_code(0xFF), None);
+    assert_eq!(OtherTransition::new(0xFF), None);
+    assert_eq!(OtherTransition::new(0x01), None);
     
+    let comment = Comment { comment: "".into() };
+    let transition = MovieTransition { movie_frame: 0, kind: TransitionKind::PacketDerived(Box::new(comment.clone().into())) };
+    assert_packet!(transition.clone(), [0xFE, 0x05], [&[0x00, 0x00, 0x00, 0x00, 0xFF], &Packet::from(comment).encode(2)[..]].concat());
+    assert_eq!(decode(&Packet::from(transition.clone()).encode(2), 2), Packet::MovieTransition(transition));
+    
+    assert_invalid!([0xFE, 0x05], [0x00; 4]);
+    
+    // deeply nested transitions are refused rather than overflowing the stack
+    let nest = |depth: usize| (0..depth).fold(Packet::from(Comment { comment: "".into() }).encode(2), |inner, _| packet(&[0xFE, 0x05], [&[0x00, 0x00, 0x00, 0x00, 0xFF], &inner[..]].concat()));
+    assert!(matches!(decode(&nest(MAX_TRANSITION_DEPTH), 2), Packet::MovieTransition(_)));
+    assert!(matches!(Packet::with_reader(&mut Reader::new(&nest(MAX_TRANSITION_DEPTH + 1)), 2), Err(PacketError::InvalidPayload { .. })));
+    assert!(matches!(Packet::with_reader(&mut Reader::new(&nest(5_000)), 2), Err(PacketError::InvalidPayload { .. })));
 }
 
 #[test]
-fn comment() {
+fn bookmark() {
+    assert_packet!(Bookmark { frame: 0, name: "".into() }, [0xFF, 0x82], [0x00; 8]);
+    assert_packet!(Bookmark { frame: u64::MAX, name: "boss".into() }, [0xFF, 0x82], [&[0xFF; 8][..], "boss".as_bytes()].concat());
+    
+    for bookmark in [Bookmark { frame: 1, name: "".into() }, Bookmark { frame: 3900, name: "ワープ".into() }] {
+        assert_eq!(decode(&Packet::from(bookmark.clone()).encode(2), 2), Packet::Bookmark(bookmark.clone()));
+        assert_eq!(decode(&Packet::from(bookmark.clone()).encode(3), 3), Packet::Bookmark(bookmark));
+    }
     
+    assert_invalid!([0xFF, 0x82], [0x00; 7]);
 }
 
-#[test]
-fn experimental() {
-    
+string_packet_tests! {
+    comment: Comment { comment } => [0xFF, 0x01];
+}
+
+bool_packet_tests! {
+    experimental: Experimental { experimental } => [0xFF, 0xFE];
 }
 
 #[test]
 fn unspecified() {
-    
+    assert_packet!(Unspecified { payload: vec![] }, [0xFF, 0xFF], []);
+    assert_packet!(Unspecified { payload: (0..=255).collect() }, [0xFF, 0xFF], (0..=255).collect::<Vec<u8>>());
 }
 
 #[test]
 fn unsupported() {
-    
-}
+    // packets from newer or third-party implementations must survive a round trip unchanged
+    let data = packet(&[0xAB, 0xCD], [0x01, 0x02, 0x03]);
+    let decoded = decode(&data, 2);
+    assert_eq!(decoded, Packet::Unsupported(Unsupported { key: vec![0xAB, 0xCD], payload: vec![0x01, 0x02, 0x03] }));
+    assert_eq!(decoded.encode(2), data);
+    assert_eq!(decoded.opaque_kind(), Some(OpaqueKind::UnknownKey));
+    
+    let vendor = decode(&packet(&[0xFF, 0xC0], [0x01]), 2);
+    assert_eq!(vendor.opaque_kind(), Some(OpaqueKind::VendorExperimental));
+    assert_eq!(decode(&packet(&[0xFF, 0xFF], [0x01]), 2).opaque_kind(), Some(OpaqueKind::Unspecified));
+    assert_eq!(decode(&packet(&[0x00, 0x03], "title"), 2).opaque_kind(), None);
+}
+
+#[test]
+fn decode_single() {
+    let title = packet(&[0x00, 0x03], "title");
+    let mut data = title.clone();
+    data.extend(packet(&[0x00, 0x04], "rom"));
+    
+    let (decoded, leftover) = Packet::decode_single(&data, 2).unwrap();
+    assert_eq!(decoded, GameTitle { title: "title".into() }.into());
+    assert_eq!(leftover, &data[title.len()..]);
+    assert_eq!(decoded.encode(2), title);
+    
+    let (decoded, leftover) = Packet::decode_single(leftover, 2).unwrap();
+    assert_eq!(decoded, RomName { name: "rom".into() }.into());
+    assert!(leftover.is_empty());
+    
+    assert_eq!(Packet::decode_single(&title[..title.len() - 1], 2), Err(PacketError::MissingPayload));
+}
+
+#[test]
+fn every_payload_length() {
+    // no payload makes decoding panic, and anything that decodes survives a round trip
+    for key in PACKET_KEYS {
+        for len in 0..48 {
+            for fill in [0x00, 0x01, 0xFF] {
+                let data = packet(key, vec![fill; len]);
+                match Packet::with_reader(&mut Reader::new(&data), 2) {
+                    Ok(decoded) => assert_eq!(decode(&decoded.encode(2), 2), decoded, "key {key:02X?}, {len} bytes of {fill:#04X}"),
+                    // a TRANSITION's nested packet can fail in any of the ways a packet can
+                    Err(err) => assert!(matches!(err, PacketError::InvalidPayload { .. }) || (fill == 0xFF && key[0] == 0xFE), "key {key:02X?}, {len} bytes of {fill:#04X}: {err:?}"),
+                }
+            }
+        }
+    }
+}
\ No newline at end of file